@@ -1,5 +1,59 @@
+use std::path::PathBuf;
+
+use crate::extensions::GitError;
+
+/// The errors from the `blame` module.
+///
+/// The errors from `GitTools` are `BlameError::Git`. The errors other than
+/// the variants to match are `BlameError::Other`; their types and messages
+/// are subject to change.
 #[derive(thiserror::Error, Debug)]
 pub enum BlameError {
     #[error("The file was deleted at {0:?}")]
     FileDeleted(git2::Oid),
+    #[error("{} doesn't exist in {commit_id}", path.display())]
+    FileNotFound { path: PathBuf, commit_id: git2::Oid },
+    /// The commit is not in the history read.
+    #[error("Commit {0:?} not found")]
+    CommitNotFound(git2::Oid),
+    #[error("No commits before {0}")]
+    NoCommitsBefore(git2::Oid),
+    #[error("Not a valid line number: {0}")]
+    InvalidLineNumber(usize),
+    /// The line was added before the oldest commit read;
+    /// e.g., when `BlameOptions::set_max_commits()` is set.
+    #[error("Line {0} is older than the commits read")]
+    NotAttributed(usize),
+    /// `FileHistory::read_all_with()` didn't complete in the time.
+    #[error("Reading the history timed out after {0:?}")]
+    Timeout(std::time::Duration),
+    #[error(transparent)]
+    Git(#[from] GitError),
+    /// Other errors, for messages.
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+/// Recovers the `BlameError` or the `GitError` if the `anyhow::Error` has it,
+/// even with context added; the context is dropped in that case.
+impl From<anyhow::Error> for BlameError {
+    fn from(error: anyhow::Error) -> Self {
+        error.downcast::<Self>().unwrap_or_else(|error| {
+            error
+                .downcast::<GitError>()
+                .map_or_else(Self::Other, Self::Git)
+        })
+    }
+}
+
+impl From<git2::Error> for BlameError {
+    fn from(error: git2::Error) -> Self {
+        Self::Git(error.into())
+    }
+}
+
+impl From<std::io::Error> for BlameError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Other(error.into())
+    }
 }
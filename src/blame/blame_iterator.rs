@@ -0,0 +1,133 @@
+use std::{collections::VecDeque, path::Path};
+
+use log::*;
+
+use super::{BlameError, FileContent, FileHistory};
+
+/// An `Iterator` of the blame results of a file,
+/// without the need to poll `FileHistory::read_poll()`.
+///
+/// Each item is a tuple of the line number, the commit ID,
+/// and the content of the line.
+/// Items are yielded as the history is loaded in background,
+/// so the order of the items isn't the order of the line numbers.
+/// # Examples
+/// ```no_run
+/// use git_iblame::blame::BlameIterator;
+///
+/// # use std::path::Path;
+/// fn main() -> anyhow::Result<()> {
+///   for item in BlameIterator::new(Path::new("path/to/file"))? {
+///     let (line_number, commit_id, content) = item?;
+///     println!("{line_number} {commit_id} {content}");
+///   }
+///   Ok(())
+/// }
+/// ```
+pub struct BlameIterator {
+    history: FileHistory,
+    content: FileContent,
+    is_yielded: Vec<bool>,
+    pending: VecDeque<(usize, git2::Oid, String)>,
+    is_done: bool,
+}
+
+impl BlameIterator {
+    /// Start reading the history of the file at `path`,
+    /// and annotate the `HEAD` of the file.
+    pub fn new(path: &Path) -> Result<Self, BlameError> {
+        let mut history = FileHistory::new(path);
+        history.read_start()?;
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let is_yielded = vec![false; content.lines_len()];
+        Ok(Self {
+            history,
+            content,
+            is_yielded,
+            pending: VecDeque::new(),
+            is_done: false,
+        })
+    }
+
+    /// The underlying `FileHistory`.
+    pub fn history(&self) -> &FileHistory {
+        &self.history
+    }
+
+    fn read_next(&mut self) -> anyhow::Result<bool> {
+        if !self.history.read_wait()? {
+            return Ok(false);
+        }
+        self.content.update_commits(&self.history)?;
+        for line in self.content.lines() {
+            if line.is_deleted() {
+                continue;
+            }
+            let Some(commit_id) = line.commit_id() else {
+                continue;
+            };
+            let line_index = line.line_number() - 1;
+            if self.is_yielded[line_index] {
+                continue;
+            }
+            self.is_yielded[line_index] = true;
+            self.pending
+                .push_back((line.line_number(), commit_id, line.content().to_string()));
+        }
+        trace!("read_next: {} pending", self.pending.len());
+        Ok(true)
+    }
+}
+
+impl Iterator for BlameIterator {
+    type Item = anyhow::Result<(usize, git2::Oid, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Some(Ok(item));
+            }
+            if self.is_done {
+                return None;
+            }
+            match self.read_next() {
+                Ok(true) => {}
+                Ok(false) => self.is_done = true,
+                Err(error) => {
+                    self.is_done = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extensions::tests::TempRepository;
+
+    use super::*;
+
+    #[test]
+    fn blame_iterator() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n3\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\nX\n3\n")?;
+        let commit_id2 = git.commit(commit_id1, "Change line 2")?;
+
+        let mut items =
+            BlameIterator::new(&git.to_file_path(path))?.collect::<anyhow::Result<Vec<_>>>()?;
+        items.sort();
+        assert_eq!(
+            items,
+            [
+                (1, commit_id1, "1".to_string()),
+                (2, commit_id2, "X".to_string()),
+                (3, commit_id1, "3".to_string()),
+            ]
+        );
+        Ok(())
+    }
+}
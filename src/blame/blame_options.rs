@@ -0,0 +1,256 @@
+use std::{cmp, collections::HashSet, num::NonZero, thread};
+
+use crate::extensions::GitTools;
+
+/// Options to compute the blame of a file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlameOptions {
+    ignore_revs: Vec<String>,
+    follow: bool,
+    rename_threshold: Option<u16>,
+    detect_moves: bool,
+    ignore_cr_at_eol: bool,
+    at: Option<String>,
+    rev: Option<String>,
+    max_commits: Option<usize>,
+    since: Option<String>,
+    exclude_commit_id: Option<git2::Oid>,
+    jobs: Option<usize>,
+    memory_budget: Option<usize>,
+    nice: Option<i32>,
+}
+
+impl Default for BlameOptions {
+    fn default() -> Self {
+        Self {
+            ignore_revs: vec![],
+            follow: true,
+            rename_threshold: None,
+            detect_moves: false,
+            ignore_cr_at_eol: false,
+            at: None,
+            rev: None,
+            max_commits: None,
+            since: None,
+            exclude_commit_id: None,
+            jobs: None,
+            memory_budget: None,
+            nice: None,
+        }
+    }
+}
+
+impl BlameOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// True if renames of the file are followed. The default is `true`.
+    pub fn follow(&self) -> bool {
+        self.follow
+    }
+
+    pub fn set_follow(&mut self, follow: bool) {
+        self.follow = follow;
+    }
+
+    /// The similarity threshold in percent to detect renames,
+    /// or `None` to use the default of `git` (50%).
+    pub fn rename_threshold(&self) -> Option<u16> {
+        self.rename_threshold
+    }
+
+    pub fn set_rename_threshold(&mut self, percent: Option<u16>) {
+        self.rename_threshold = percent;
+    }
+
+    /// The `-M` argument of `git` for the `rename_threshold()`.
+    pub(crate) fn rename_threshold_arg(&self) -> Option<String> {
+        self.rename_threshold.map(|percent| format!("-M{percent}%"))
+    }
+
+    /// True if blocks of lines moved within the file are detected,
+    /// so that they are attributed to the commits that originally added them,
+    /// similar to the `-M` option of `git blame`. The default is `false`.
+    pub fn detect_moves(&self) -> bool {
+        self.detect_moves
+    }
+
+    pub fn set_detect_moves(&mut self, detect_moves: bool) {
+        self.detect_moves = detect_moves;
+    }
+
+    /// True if changes of only the line endings, such as from LF to CRLF,
+    /// are ignored when assigning blame. The default is `false`.
+    ///
+    /// This applies to `GitEngine::Git`.
+    /// `GitEngine::Git2` always ignores changes of whitespaces.
+    /// `git blame` doesn't support this, so `GitEngine::GitBlame` doesn't.
+    pub fn ignore_cr_at_eol(&self) -> bool {
+        self.ignore_cr_at_eol
+    }
+
+    pub fn set_ignore_cr_at_eol(&mut self, ignore_cr_at_eol: bool) {
+        self.ignore_cr_at_eol = ignore_cr_at_eol;
+    }
+
+    /// The argument of `git` for the `ignore_cr_at_eol()`.
+    pub(crate) fn ignore_cr_at_eol_arg(&self) -> Option<&'static str> {
+        self.ignore_cr_at_eol.then_some("--ignore-cr-at-eol")
+    }
+
+    /// The date to compute the blame at, or `None` to compute at `HEAD`.
+    pub fn at(&self) -> Option<&str> {
+        self.at.as_deref()
+    }
+
+    /// Compute the blame at the latest commit before the `date`,
+    /// instead of at `HEAD`. See `GitTools::commit_id_at()` for the formats.
+    pub fn set_at(&mut self, date: Option<&str>) {
+        self.at = date.map(|date| date.to_string());
+    }
+
+    /// The revision to compute the blame at, or `None` to compute at `HEAD`.
+    pub fn rev(&self) -> Option<&str> {
+        self.rev.as_deref()
+    }
+
+    /// Compute the blame at the revision such as a commit ID, a branch, or a
+    /// tag, instead of at `HEAD`. If `set_at()` is also set, the blame is
+    /// computed at the latest commit before the date from the revision.
+    pub fn set_rev(&mut self, rev: Option<&str>) {
+        self.rev = rev.map(|rev| rev.to_string());
+    }
+
+    /// The maximum number of the commits to read, or `None` to read all.
+    pub fn max_commits(&self) -> Option<usize> {
+        self.max_commits
+    }
+
+    /// Read only the newest `max_commits` commits that changed the file,
+    /// similar to the `--max-count` option of `git log`.
+    /// Lines older than them are attributed to the oldest commit read, as
+    /// the boundary; see `FileCommit::is_boundary()`.
+    pub fn set_max_commits(&mut self, max_commits: Option<usize>) {
+        self.max_commits = max_commits;
+    }
+
+    /// The date to read commits since, or `None` to read all.
+    pub fn since(&self) -> Option<&str> {
+        self.since.as_deref()
+    }
+
+    /// Read only the commits more recent than the `date`,
+    /// similar to the `--since` option of `git log`.
+    /// Lines older than them are attributed to the oldest commit read, as
+    /// the boundary; see `FileCommit::is_boundary()`.
+    pub fn set_since(&mut self, date: Option<&str>) {
+        self.since = date.map(|date| date.to_string());
+    }
+
+    /// The commit whose ancestors, including itself, are not read, similar to
+    /// `git log <commit>..`. See `FileHistory::read_start_after()`.
+    pub(crate) fn exclude_commit_id(&self) -> Option<git2::Oid> {
+        self.exclude_commit_id
+    }
+
+    pub(crate) fn set_exclude_commit_id(&mut self, commit_id: Option<git2::Oid>) {
+        self.exclude_commit_id = commit_id;
+    }
+
+    /// The number of threads to compute the diffs of commits.
+    /// The default is the available parallelism, up to 8.
+    pub fn jobs(&self) -> usize {
+        const MAX_DEFAULT_JOBS: usize = 8;
+        let jobs = self.jobs.unwrap_or_else(|| {
+            let parallelism = thread::available_parallelism().map_or(1, NonZero::get);
+            cmp::min(parallelism, MAX_DEFAULT_JOBS)
+        });
+        cmp::max(jobs, 1)
+    }
+
+    /// Whether `set_jobs()` is set, instead of the default.
+    pub fn has_jobs(&self) -> bool {
+        self.jobs.is_some()
+    }
+
+    /// Compute the diffs of commits in the `jobs` threads,
+    /// or `None` to use the default. The commits are still delivered
+    /// in the order of the history.
+    pub fn set_jobs(&mut self, jobs: Option<usize>) {
+        self.jobs = jobs;
+    }
+
+    /// The niceness to add to the threads reading the history,
+    /// or `None` to run them at the same priority as the caller.
+    pub fn nice(&self) -> Option<i32> {
+        self.nice
+    }
+
+    /// Run the threads reading the history, and the `git` processes they
+    /// start, at the lower priority by the `nice` increment, like the `nice`
+    /// command, so that they don't slow other processes.
+    /// This is supported only on Linux.
+    pub fn set_nice(&mut self, nice: Option<i32>) {
+        self.nice = nice;
+    }
+
+    /// The bytes of the diffs of commits to keep in memory,
+    /// or `None` to keep all.
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.memory_budget
+    }
+
+    /// Keep the diffs of commits in memory up to the `bytes`, from the newest
+    /// commit. The diffs of older commits are spilled to a temporary file,
+    /// and are read back when they're needed. A quarter of the `bytes` is
+    /// used to cache the diffs read back.
+    pub fn set_memory_budget(&mut self, bytes: Option<usize>) {
+        self.memory_budget = bytes;
+    }
+
+    /// Resolve the commit to compute the blame at.
+    pub(crate) fn start_commit_id(&self, git: &GitTools) -> anyhow::Result<git2::Oid> {
+        let commit_id = match &self.rev {
+            Some(rev) => git.commit_id_from_rev(rev)?,
+            None => git.head_commit_id()?,
+        };
+        match &self.at {
+            Some(date) => Ok(git.commit_id_at(date, commit_id)?),
+            None => Ok(commit_id),
+        }
+    }
+
+    /// Ignore changes made by the revision when assigning blame,
+    /// as if the change never happened,
+    /// similar to the `--ignore-rev` option of `git blame`.
+    pub fn add_ignore_rev(&mut self, rev: &str) {
+        self.ignore_revs.push(rev.to_string());
+    }
+
+    pub fn ignore_revs(&self) -> &[String] {
+        &self.ignore_revs
+    }
+
+    /// The arguments of `git blame` for the `ignore_revs()` and the
+    /// `detect_moves()`.
+    pub(crate) fn blame_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = self
+            .ignore_revs
+            .iter()
+            .map(|rev| format!("--ignore-rev={rev}"))
+            .collect();
+        if self.detect_moves {
+            args.push("-M".to_string());
+        }
+        args
+    }
+
+    /// Resolve the revisions added by `add_ignore_rev()` to commit IDs.
+    pub(crate) fn ignore_commit_ids(&self, git: &GitTools) -> anyhow::Result<HashSet<git2::Oid>> {
+        self.ignore_revs
+            .iter()
+            .map(|rev| Ok(git.commit_id_from_rev(rev)?))
+            .collect()
+    }
+}
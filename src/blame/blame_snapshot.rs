@@ -0,0 +1,157 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use super::{FileCommit, FileContent, FileHistory, Line};
+
+/// The annotated lines of a file at a commit, and the commits they're
+/// attributed to. Unlike `FileContent`, this owns everything, so that it can
+/// be kept or serialized after the `FileHistory` is dropped.
+///
+/// The lines are shared with the `FileContent` if it has no deleted lines, and
+/// with the clones of this snapshot.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BlameSnapshot {
+    path: PathBuf,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize::oid"))]
+    commit_id: git2::Oid,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize::arc"))]
+    lines: Arc<Vec<Line>>,
+    commits: Vec<FileCommit>,
+}
+
+impl BlameSnapshot {
+    /// The snapshot of the `content` read from the `history`.
+    /// Deleted lines are not included.
+    /// The commits are the ones the lines are attributed to, newest first.
+    pub fn new(history: &FileHistory, content: &FileContent) -> Self {
+        let is_excluded = |line: &Line| line.is_deleted() || line.is_log();
+        let lines = if content.lines().iter().any(is_excluded) {
+            Arc::new(
+                content
+                    .lines()
+                    .iter()
+                    .filter(|line| !is_excluded(line))
+                    .cloned()
+                    .collect(),
+            )
+        } else {
+            content.shared_lines()
+        };
+        let commit_ids: HashSet<git2::Oid> =
+            lines.iter().filter_map(|line| line.commit_id()).collect();
+        let commits = history
+            .commits()
+            .iter()
+            .filter(|commit| commit_ids.contains(&commit.commit_id()))
+            .cloned()
+            .collect();
+        Self {
+            path: content.path().to_path_buf(),
+            commit_id: content.commit_id(),
+            lines,
+            commits,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The commit of the content.
+    pub fn commit_id(&self) -> git2::Oid {
+        self.commit_id
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    pub fn commits(&self) -> &[FileCommit] {
+        &self.commits
+    }
+
+    /// The commit the `line` is attributed to.
+    pub fn commit_of(&self, line: &Line) -> Option<&FileCommit> {
+        let commit_id = line.commit_id()?;
+        self.commits
+            .iter()
+            .find(|commit| commit.commit_id() == commit_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extensions::tests::TempRepository;
+
+    use super::*;
+
+    #[test]
+    fn new() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n3\n")?;
+        let commit_id2 = git.commit(commit_id1, "Modify 2")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        history.read_join()?;
+        history.read_poll()?;
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let snapshot = BlameSnapshot::new(&history, &content);
+        assert_eq!(snapshot.commit_id(), commit_id2);
+        let lines: Vec<(&str, Option<git2::Oid>)> = snapshot
+            .lines()
+            .iter()
+            .map(|line| (line.content(), line.commit_id()))
+            .collect();
+        assert_eq!(lines, [("1", Some(commit_id1)), ("3", Some(commit_id2))]);
+        let commit_ids: Vec<git2::Oid> = snapshot
+            .commits()
+            .iter()
+            .map(|commit| commit.commit_id())
+            .collect();
+        assert_eq!(commit_ids, [commit_id2, commit_id1]);
+        let commit = snapshot.commit_of(&snapshot.lines()[1]).unwrap();
+        assert_eq!(commit.summary().map(String::as_str), Some("Modify 2"));
+
+        // The lines are shared if the content has no deleted lines.
+        assert!(Arc::ptr_eq(&snapshot.lines, &content.shared_lines()));
+        assert!(Arc::ptr_eq(&snapshot.clone().lines, &snapshot.lines));
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        history.read_join()?;
+        history.read_poll()?;
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let snapshot = BlameSnapshot::new(&history, &content);
+        let json = serde_json::to_value(&snapshot)?;
+        assert_eq!(json["commit_id"], commit_id.to_string());
+        assert_eq!(json["lines"][1]["content"], "2");
+        assert_eq!(json["lines"][1]["line_type"], "line");
+        assert_eq!(json["lines"][1]["commit_id"], commit_id.to_string());
+        assert_eq!(json["commits"][0]["commit_id"], commit_id.to_string());
+        assert_eq!(json["commits"][0]["summary"], "Add file");
+        assert!(json["commits"][0]["time"]["seconds"].is_i64());
+        assert_eq!(
+            json["commits"][0]["diff_parts"][0]["old"],
+            serde_json::json!({"start": 0, "end": 0})
+        );
+        Ok(())
+    }
+}
@@ -1,20 +1,37 @@
 use std::{
+    collections::{BinaryHeap, HashSet},
     io::{BufRead, BufReader},
     path::{Path, PathBuf},
     process,
-    sync::mpsc,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc,
+    },
     thread,
+    time::Duration,
 };
 
 use log::*;
 
+use crate::extensions::{BloomKey, CommitGraph, GitTools};
+
+use super::{BlameError, BlameOptions, CancellationToken, FileCommit};
+
 #[derive(Debug, Default)]
 pub struct CommitIterator {
     path: PathBuf,
     repository_path: PathBuf,
+    options: BlameOptions,
+    start_commit_id: Option<git2::Oid>,
     log_child: Option<process::Child>,
     receive_thread: Option<thread::JoinHandle<anyhow::Result<()>>>,
     rx: Option<mpsc::Receiver<git2::Oid>>,
+    discovered: Arc<AtomicUsize>,
+    /// The number of the commits returned by the iterator.
+    returned: usize,
+    is_killed: Arc<AtomicBool>,
+    cancellation_token: Option<CancellationToken>,
 }
 
 impl CommitIterator {
@@ -23,19 +40,82 @@ impl CommitIterator {
         Self {
             path: path.to_path_buf(),
             repository_path: repository_path.to_path_buf(),
+            options: BlameOptions::default(),
+            start_commit_id: None,
             log_child: None,
             receive_thread: None,
             rx: None,
+            discovered: Arc::default(),
+            returned: 0,
+            is_killed: Arc::default(),
+            cancellation_token: None,
+        }
+    }
+
+    /// The interval to check the `set_cancellation_token()` while waiting
+    /// for the next commit.
+    pub(crate) const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn repository_path(&self) -> &Path {
+        &self.repository_path
+    }
+
+    /// Set the `BlameOptions`. This should be called before `start()`.
+    pub fn set_options(&mut self, options: BlameOptions) {
+        self.options = options;
+    }
+
+    /// Stop iterating when the `cancellation_token` is cancelled, even while
+    /// waiting for a slow `git log`. Call `kill()` to stop the `git log`.
+    pub fn set_cancellation_token(&mut self, cancellation_token: CancellationToken) {
+        self.cancellation_token = Some(cancellation_token);
+    }
+
+    /// Start from the `commit_id` instead of `HEAD`.
+    pub fn set_start_commit_id(&mut self, commit_id: Option<git2::Oid>) {
+        self.start_commit_id = commit_id;
+    }
+
+    /// The number of the commits discovered so far, including the ones not
+    /// yet returned by the iterator. The counter is shared with the thread
+    /// receiving the commits, so that it can be read while iterating.
+    pub fn discovered(&self) -> Arc<AtomicUsize> {
+        self.discovered.clone()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation_token
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Convert to the iterator of `HistoryProvider::commits()`; i.e., the
+    /// `FileCommit`s of the commit IDs, and then the error of `join()` if it
+    /// fails. This should be called after `start()`.
+    pub fn into_file_commits(self) -> impl Iterator<Item = Result<FileCommit, BlameError>> + Send {
+        IntoFileCommits {
+            commits: self,
+            is_done: false,
         }
     }
 
-    pub fn start(&mut self) -> anyhow::Result<()> {
-        // self.commits_by_thread()?;
-        self.commits_by_process()?;
+    /// Start listing the commits by `git log`, or by the `git2` crate if the
+    /// `git` command is not available; see `GitTools::check_git_command()`.
+    pub fn start(&mut self) -> Result<(), BlameError> {
+        if let Err(error) = GitTools::check_git_command() {
+            debug!("CommitIterator: {error}");
+            self.commits_by_thread()?;
+        } else {
+            self.commits_by_process()?;
+        }
         Ok(())
     }
 
-    pub fn join(&mut self) -> anyhow::Result<()> {
+    pub fn join(&mut self) -> Result<(), BlameError> {
         if let Some(mut child) = self.log_child.take() {
             let status = child.wait()?;
             debug!("Child process exited with: {status}");
@@ -46,9 +126,42 @@ impl CommitIterator {
         Ok(())
     }
 
+    /// Kill the `git log` process and wait for the threads to finish.
+    /// The commits already received are still returned by the iterator.
+    pub fn kill(&mut self) -> Result<(), BlameError> {
+        if let Some(child) = self.log_child.as_mut() {
+            debug!("Killing the child process {}", child.id());
+            child.kill()?;
+        }
+        self.is_killed.store(true, Ordering::Relaxed);
+        self.join()
+    }
+
     fn commits_by_process(&mut self) -> anyhow::Result<()> {
-        let mut child = process::Command::new("git")
-            .args(["log", "--format=%H", "--follow", "--"])
+        let mut command = process::Command::new("git");
+        GitTools::set_command_env(&mut command, &self.repository_path, None);
+        command.args(["log", "--format=%H"]);
+        if self.options.follow() {
+            command.arg("--follow");
+        }
+        if let Some(arg) = self.options.rename_threshold_arg() {
+            command.arg(arg);
+        }
+        if let Some(max_commits) = self.options.max_commits() {
+            command.arg(format!("--max-count={max_commits}"));
+        }
+        if let Some(date) = self.options.since() {
+            command.arg(format!("--since={date}"));
+        }
+        match self.start_commit_id {
+            Some(commit_id) => command.arg(commit_id.to_string()),
+            None => command.arg("HEAD"),
+        };
+        if let Some(commit_id) = self.options.exclude_commit_id() {
+            command.arg(format!("^{commit_id}"));
+        }
+        let mut child = command
+            .arg("--")
             .arg(&self.path)
             .current_dir(&self.repository_path)
             .stdout(process::Stdio::piped())
@@ -57,6 +170,7 @@ impl CommitIterator {
         self.log_child = Some(child);
 
         let (tx, rx) = mpsc::channel::<git2::Oid>();
+        let discovered = self.discovered.clone();
         let receiver = thread::spawn(move || -> anyhow::Result<()> {
             trace!("receiver thread start");
             let reader = BufReader::new(stdout);
@@ -65,6 +179,7 @@ impl CommitIterator {
                     Ok(line) => {
                         let commit_id = git2::Oid::from_str(&line).unwrap();
                         trace!("THREAD: {commit_id}");
+                        discovered.fetch_add(1, Ordering::Relaxed);
                         tx.send(commit_id)?;
                     }
                     Err(error) => {
@@ -82,14 +197,434 @@ impl CommitIterator {
     }
 }
 
+impl CommitIterator {
+    /// List the commits by the `git2` crate in a thread, in the same order as
+    /// `git log`. This is slower than `git log`, though both skip the commits
+    /// by the changed-path Bloom filters if the commit-graph has them.
+    /// The `BlameOptions::since()` is limited to the formats of
+    /// `GitTools::timestamp_from_date()` without the `git` command.
+    fn commits_by_thread(&mut self) -> anyhow::Result<()> {
+        let since = self
+            .options
+            .since()
+            .map(GitTools::timestamp_from_date_without_git)
+            .transpose()?;
+        let repository_path = self.repository_path.clone();
+        let path = self.path.clone();
+        let options = self.options.clone();
+        let start_commit_id = self.start_commit_id;
+        let discovered = self.discovered.clone();
+        let is_killed = self.is_killed.clone();
+        let (tx, rx) = mpsc::channel::<git2::Oid>();
+        let walker = thread::spawn(move || -> anyhow::Result<()> {
+            trace!("walker thread start");
+            let repository = git2::Repository::open(&repository_path)?;
+            let mut walk = CommitWalk::new(&repository, path, &options, start_commit_id)?;
+            walk.since = since;
+            let mut count = 0;
+            while !is_killed.load(Ordering::Relaxed)
+                && options.max_commits().is_none_or(|max| count < max)
+                && let Some(commit_id) = walk.next_commit()?
+            {
+                trace!("THREAD: {commit_id}");
+                discovered.fetch_add(1, Ordering::Relaxed);
+                if tx.send(commit_id).is_err() {
+                    break;
+                }
+                count += 1;
+            }
+            trace!("walker thread end");
+            Ok(())
+        });
+        self.receive_thread = Some(walker);
+        self.rx = Some(rx);
+        Ok(())
+    }
+}
+
+/// Walks the commits that changed a file by the `git2` crate, newest first.
+///
+/// Merges are simplified in the same way as `git log -- <path>`; i.e., if the
+/// file is the same as in one of the parents, only the parent is followed.
+/// If `BlameOptions::follow()`, the file is followed across renames, using
+/// the old path for older commits as `git log --follow` does.
+///
+/// If the commit-graph has the changed-path Bloom filters, the commits not
+/// changing the file are skipped by them without reading their trees;
+/// otherwise, or if a filter doesn't tell, the trees are compared.
+///
+/// The `BlameOptions::exclude_commit_id()` and its ancestors are not walked.
+struct CommitWalk<'a> {
+    repository: &'a git2::Repository,
+    path: PathBuf,
+    graph: Option<CommitGraph>,
+    path_key: BloomKey,
+    options: &'a BlameOptions,
+    queue: BinaryHeap<(i64, git2::Oid)>,
+    visited: HashSet<git2::Oid>,
+    /// Stop at the commits older than this, as `git log --since` does.
+    since: Option<i64>,
+}
+
+impl<'a> CommitWalk<'a> {
+    fn new(
+        repository: &'a git2::Repository,
+        path: PathBuf,
+        options: &'a BlameOptions,
+        start_commit_id: Option<git2::Oid>,
+    ) -> anyhow::Result<Self> {
+        let start_commit = match start_commit_id {
+            Some(commit_id) => repository.find_commit(commit_id)?,
+            None => repository.head()?.peel_to_commit()?,
+        };
+        let graph = CommitGraph::open(repository).filter(CommitGraph::has_changed_path_filters);
+        debug!("CommitWalk: changed-path filters: {}", graph.is_some());
+        let mut walk = Self {
+            repository,
+            path_key: BloomKey::new(&path),
+            path,
+            graph,
+            options,
+            queue: BinaryHeap::new(),
+            visited: HashSet::new(),
+            since: None,
+        };
+        walk.push(&start_commit);
+        Ok(walk)
+    }
+
+    fn push(&mut self, commit: &git2::Commit) {
+        self.push_commit_id(commit.time().seconds(), commit.id());
+    }
+
+    fn push_commit_id(&mut self, time: i64, commit_id: git2::Oid) {
+        if self.visited.insert(commit_id) {
+            self.queue.push((time, commit_id));
+        }
+    }
+
+    /// The ID and the mode of the file at the `path` in the `commit`.
+    fn entry(commit: &git2::Commit, path: &Path) -> anyhow::Result<Option<(git2::Oid, i32)>> {
+        Ok(commit
+            .tree()?
+            .get_path(path)
+            .ok()
+            .map(|entry| (entry.id(), entry.filemode())))
+    }
+
+    fn next_commit(&mut self) -> anyhow::Result<Option<git2::Oid>> {
+        while let Some((time, commit_id)) = self.queue.pop() {
+            if self.since.is_some_and(|since| time < since) {
+                // The rest in the `queue` are older.
+                self.queue.clear();
+                break;
+            }
+            if let Some(exclude_commit_id) = self.options.exclude_commit_id()
+                && (commit_id == exclude_commit_id
+                    || self
+                        .repository
+                        .graph_descendant_of(exclude_commit_id, commit_id)?)
+            {
+                continue;
+            }
+            if let Some(graph) = &self.graph
+                && let Some((time, parent_id)) =
+                    graph.unchanged_first_parent(commit_id, &self.path_key)
+            {
+                self.push_commit_id(time, parent_id);
+                continue;
+            }
+            let commit = self.repository.find_commit(commit_id)?;
+            let entry = Self::entry(&commit, &self.path)?;
+            let parents: Vec<git2::Commit> = commit.parents().collect();
+            let mut parent_entries = Vec::with_capacity(parents.len());
+            for parent in &parents {
+                parent_entries.push(Self::entry(parent, &self.path)?);
+            }
+            if let Some(index) = parent_entries.iter().position(|parent| *parent == entry) {
+                self.push(&parents[index]);
+                continue;
+            }
+            if entry.is_none() && parents.is_empty() {
+                continue;
+            }
+            if self.options.follow()
+                && entry.is_some()
+                && parent_entries.iter().all(Option::is_none)
+                && let Some(parent) = parents.first()
+                && let Some(old_path) = self.rename_source(parent, &commit)?
+            {
+                debug!("CommitWalk: {commit_id} renamed from {old_path:?}");
+                self.path_key = BloomKey::new(&old_path);
+                self.path = old_path;
+            }
+            for parent in &parents {
+                self.push(parent);
+            }
+            return Ok(Some(commit_id));
+        }
+        Ok(None)
+    }
+
+    /// The old path if the file at the `path` was renamed from the `parent`.
+    fn rename_source(
+        &self,
+        parent: &git2::Commit,
+        commit: &git2::Commit,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        let mut diff = self.repository.diff_tree_to_tree(
+            Some(&parent.tree()?),
+            Some(&commit.tree()?),
+            None,
+        )?;
+        let mut diff_find_options = git2::DiffFindOptions::new();
+        diff_find_options.renames(true);
+        if let Some(percent) = self.options.rename_threshold() {
+            diff_find_options.rename_threshold(percent);
+        }
+        diff.find_similar(Some(&mut diff_find_options))?;
+        Ok(diff
+            .deltas()
+            .find(|delta| {
+                delta.status() == git2::Delta::Renamed
+                    && delta.new_file().path() == Some(self.path.as_path())
+            })
+            .and_then(|delta| delta.old_file().path().map(Path::to_path_buf)))
+    }
+}
+
+impl CommitIterator {
+    fn recv(&self) -> Option<git2::Oid> {
+        let rx = self.rx.as_ref()?;
+        let Some(cancellation_token) = &self.cancellation_token else {
+            return rx.recv().ok();
+        };
+        while !cancellation_token.is_cancelled() {
+            match rx.recv_timeout(Self::CANCEL_CHECK_INTERVAL) {
+                Ok(commit_id) => return Some(commit_id),
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+        None
+    }
+}
+
 impl Iterator for CommitIterator {
     type Item = git2::Oid;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(rx) = self.rx.as_mut() {
-            rx.recv().ok()
+        let commit_id = self.recv()?;
+        self.returned += 1;
+        Some(commit_id)
+    }
+
+    /// The lower bound is the number of the commits discovered but not
+    /// returned yet.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let discovered = self.discovered.load(Ordering::Relaxed);
+        (discovered.saturating_sub(self.returned), None)
+    }
+}
+
+impl Drop for CommitIterator {
+    /// Kill the `git log` if it's still running.
+    fn drop(&mut self) {
+        if (self.log_child.is_some() || self.receive_thread.is_some())
+            && let Err(error) = self.kill()
+        {
+            warn!("CommitIterator: {error}");
+        }
+    }
+}
+
+/// The iterator of `CommitIterator::into_file_commits()`.
+struct IntoFileCommits {
+    commits: CommitIterator,
+    is_done: bool,
+}
+
+impl Iterator for IntoFileCommits {
+    type Item = Result<FileCommit, BlameError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_done {
+            return None;
+        }
+        if let Some(commit_id) = self.commits.next() {
+            return Some(Ok(FileCommit::new(commit_id, &self.commits.path)));
+        }
+        self.is_done = true;
+        // If cancelled, the `git log` may still be running.
+        let result = if self.commits.is_cancelled() {
+            self.commits.kill()
         } else {
-            None
+            self.commits.join()
+        };
+        result.err().map(Err)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.commits.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::extensions::tests::TempRepository;
+
+    #[test]
+    fn commit_graph() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = PathBuf::from("test.txt");
+        let other_path = PathBuf::from("other.txt");
+        git.add_file_content(&path, "1\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(&other_path, "1\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add other file")?;
+        git.add_file_content(&path, "1\n2\n")?;
+        let commit_id3 = git.commit(commit_id2, "Add line")?;
+        let repository_path = git.git.repository_path();
+        assert!(!git.git.has_commit_graph());
+
+        let mut commits = CommitIterator::new(&path, repository_path);
+        commits.start()?;
+        let commit_ids: Vec<_> = commits.by_ref().collect();
+        commits.join()?;
+        assert_eq!(commit_ids, [commit_id3, commit_id1]);
+        assert_eq!(commits.discovered().load(Ordering::Relaxed), 2);
+
+        git.write_commit_graph(&[])?;
+        assert!(git.git.has_commit_graph());
+        assert!(!git.git.has_changed_path_filters());
+        git.write_commit_graph(&["--changed-paths"])?;
+        assert!(git.git.has_changed_path_filters());
+
+        let mut commits = CommitIterator::new(&path, repository_path);
+        commits.start()?;
+        let commit_ids_by_graph: Vec<_> = commits.by_ref().collect();
+        commits.join()?;
+        assert_eq!(commit_ids_by_graph, commit_ids);
+
+        let mut commits = CommitIterator::new(&path, repository_path);
+        commits.commits_by_thread()?;
+        let commit_ids_by_graph: Vec<_> = commits.by_ref().collect();
+        commits.join()?;
+        assert_eq!(commit_ids_by_graph, commit_ids);
+        Ok(())
+    }
+
+    #[test]
+    fn commits_by_thread() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let old_path = PathBuf::from("old.txt");
+        let path = PathBuf::from("test.txt");
+        let other_path = PathBuf::from("other.txt");
+        git.add_file_content(&old_path, "1\n2\n3\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(&other_path, "1\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add other file")?;
+        git.add_file_content(&old_path, "1\n2\n3\n4\n")?;
+        let commit_id3 = git.commit(commit_id2, "Add line")?;
+        git.rename_file(&old_path, &path)?;
+        let commit_id4 = git.commit(commit_id3, "Rename file")?;
+        git.add_file_content(&other_path, "2\n")?;
+        let commit_id5 = git.commit_detached(&[commit_id4], "Change other file")?;
+        let commit_id6 = git.commit_merge(&[commit_id4, commit_id5], "Merge")?;
+        git.add_file_content(&path, "1\n2\n3\n4\n5\n")?;
+        let commit_id7 = git.commit(commit_id6, "Add line after merge")?;
+
+        // Without and with the changed-path Bloom filters.
+        for has_filters in [false, true] {
+            if has_filters {
+                git.write_commit_graph(&["--changed-paths"])?;
+            }
+            for follow in [true, false] {
+                let mut options = BlameOptions::default();
+                options.set_follow(follow);
+                let mut commits = CommitIterator::new(&path, git.git.repository_path());
+                commits.set_options(options.clone());
+                commits.commits_by_thread()?;
+                let commit_ids: Vec<_> = commits.by_ref().collect();
+                commits.join()?;
+                if follow {
+                    assert_eq!(commit_ids, [commit_id7, commit_id4, commit_id3, commit_id1]);
+                } else {
+                    assert_eq!(commit_ids, [commit_id7, commit_id4]);
+                }
+                assert_eq!(
+                    commits.discovered().load(Ordering::Relaxed),
+                    commit_ids.len()
+                );
+
+                // Same as `git log`.
+                commits = CommitIterator::new(&path, git.git.repository_path());
+                commits.set_options(options);
+                commits.commits_by_process()?;
+                let commit_ids_by_git: Vec<_> = commits.by_ref().collect();
+                commits.join()?;
+                assert_eq!(commit_ids_by_git, commit_ids);
+            }
+        }
+
+        // Exclude the commits reachable from the `commit_id4`.
+        let mut options = BlameOptions::default();
+        options.set_exclude_commit_id(Some(commit_id4));
+        let mut commits = CommitIterator::new(&path, git.git.repository_path());
+        commits.set_options(options.clone());
+        commits.commits_by_thread()?;
+        assert_eq!(commits.by_ref().collect::<Vec<_>>(), [commit_id7]);
+        commits.join()?;
+        commits = CommitIterator::new(&path, git.git.repository_path());
+        commits.set_options(options);
+        commits.commits_by_process()?;
+        assert_eq!(commits.by_ref().collect::<Vec<_>>(), [commit_id7]);
+        commits.join()?;
+
+        for (since, len) in [("2000-01-01", 4), ("2099-01-01", 0)] {
+            let mut options = BlameOptions::default();
+            options.set_since(Some(since));
+            let mut commits = CommitIterator::new(&path, git.git.repository_path());
+            commits.set_options(options);
+            commits.commits_by_thread()?;
+            assert_eq!(commits.by_ref().count(), len);
+            commits.join()?;
         }
+        Ok(())
+    }
+
+    #[test]
+    fn kill() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = PathBuf::from("test.txt");
+        git.add_file_content(&path, "1\n")?;
+        git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+
+        let mut commits = CommitIterator::new(&path, git.git.repository_path());
+        commits.start()?;
+        commits.kill()?;
+        assert!(commits.log_child.is_none());
+        assert!(commits.receive_thread.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn cancellation_token() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = PathBuf::from("test.txt");
+        git.add_file_content(&path, "1\n")?;
+        git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+
+        let cancellation_token = CancellationToken::new();
+        let mut commits = CommitIterator::new(&path, git.git.repository_path());
+        commits.set_cancellation_token(cancellation_token.clone());
+        commits.start()?;
+        cancellation_token.cancel();
+        assert_eq!(commits.next(), None);
+        commits.kill()?;
+        Ok(())
     }
 }
@@ -0,0 +1,141 @@
+use std::fmt;
+
+use regex::Regex;
+
+use crate::extensions::GitTools;
+
+use super::{BlameError, FileCommit};
+
+/// Extra information of a commit, such as the pull request that merged it,
+/// attached by `MetadataProvider`s.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CommitMetadata {
+    name: String,
+    value: String,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    url: Option<String>,
+}
+
+impl CommitMetadata {
+    pub fn new(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            value: value.to_string(),
+            url: None,
+        }
+    }
+
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    /// The short name of the information, such as `PR`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// The URL to see the details, if any.
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+}
+
+/// Attaches `CommitMetadata` to the commits of a `FileHistory`.
+///
+/// The providers run in a thread separate from the reading thread, so that
+/// slow providers don't slow the reading. The metadata is attached to the
+/// `FileCommit` by `FileHistory::read_poll()` when it's ready.
+/// Use `FileHistoryBuilder::metadata_provider()` to add providers.
+pub trait MetadataProvider: fmt::Debug + Send + Sync {
+    /// The metadata of the `commit`, or an empty `Vec` if none.
+    /// Errors are logged, and don't stop reading the history.
+    fn metadata(
+        &self,
+        git: &GitTools,
+        commit: &FileCommit,
+    ) -> Result<Vec<CommitMetadata>, BlameError>;
+}
+
+/// The `MetadataProvider` of the pull request numbers in the summaries,
+/// such as `Fix a bug (#123)` or `Merge pull request #123 from ...`.
+#[derive(Debug, Default)]
+pub struct PullRequestMetadataProvider;
+
+impl PullRequestMetadataProvider {
+    fn pull_request_number(summary: &str) -> anyhow::Result<Option<&str>> {
+        let re = Regex::new(r"^Merge pull request #(\d+) |\(#(\d+)\)$")?;
+        Ok(re.captures(summary).and_then(|captures| {
+            captures
+                .get(1)
+                .or_else(|| captures.get(2))
+                .map(|number| number.as_str())
+        }))
+    }
+}
+
+impl MetadataProvider for PullRequestMetadataProvider {
+    fn metadata(
+        &self,
+        _: &GitTools,
+        commit: &FileCommit,
+    ) -> Result<Vec<CommitMetadata>, BlameError> {
+        let Some(summary) = commit.summary() else {
+            return Ok(vec![]);
+        };
+        Ok(Self::pull_request_number(summary)?
+            .map(|number| CommitMetadata::new("PR", &format!("#{number}")))
+            .into_iter()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, sync::Arc};
+
+    use crate::{blame::FileHistoryBuilder, extensions::tests::TempRepository};
+
+    use super::*;
+
+    #[test]
+    fn pull_request_number() -> anyhow::Result<()> {
+        let number = PullRequestMetadataProvider::pull_request_number;
+        assert_eq!(number("Fix a bug (#123)")?, Some("123"));
+        assert_eq!(
+            number("Merge pull request #45 from user/branch")?,
+            Some("45")
+        );
+        assert_eq!(number("Fix #123 in the middle")?, None);
+        assert_eq!(number("Fix a bug")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn read_poll() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file (#12)")?;
+        git.add_file_content(path, "1\n3\n")?;
+        let commit_id2 = git.commit(commit_id1, "Modify 2")?;
+
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(path))
+            .metadata_provider(Arc::new(PullRequestMetadataProvider))
+            .build();
+        history.read_all()?;
+        assert!(!history.is_reading());
+        let commits = history.commits();
+        assert_eq!(
+            commits.get_by_commit_id(commit_id1)?.metadata(),
+            [CommitMetadata::new("PR", "#12")]
+        );
+        assert_eq!(commits.get_by_commit_id(commit_id2)?.metadata(), []);
+        Ok(())
+    }
+}
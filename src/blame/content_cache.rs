@@ -0,0 +1,72 @@
+use std::collections::VecDeque;
+
+use super::FileContent;
+
+/// The recently used `FileContent`s, keyed by the commit IDs requested to
+/// `FileHistory::content()`, so that
+/// `FileHistory::content()` doesn't read the blobs and apply the commits
+/// again when going back and forth between commits.
+pub(crate) struct ContentCache {
+    capacity: usize,
+    /// The most recently used first.
+    items: VecDeque<(git2::Oid, FileContent)>,
+}
+
+impl ContentCache {
+    pub const DEFAULT_CAPACITY: usize = 8;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            items: VecDeque::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// A copy of the content of the `commit_id`, and mark it as the most
+    /// recently used.
+    pub fn get(&mut self, commit_id: git2::Oid) -> Option<FileContent> {
+        let index = self.items.iter().position(|(key, _)| *key == commit_id)?;
+        let item = self.items.remove(index)?;
+        self.items.push_front(item);
+        self.items.front().map(|(_, content)| content.clone())
+    }
+
+    /// Add the `content` of the `commit_id` as the most recently used,
+    /// replacing the existing one, and evicting the least recently used if
+    /// it's full.
+    pub fn insert(&mut self, commit_id: git2::Oid, content: FileContent) {
+        self.items.retain(|(key, _)| *key != commit_id);
+        self.items.push_front((commit_id, content));
+        self.items.truncate(self.capacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn lru() {
+        let commit_id = |i: u8| git2::Oid::from_bytes(&[i; 20]).unwrap();
+        let mut cache = ContentCache::new(2);
+        cache.insert(commit_id(1), FileContent::new(commit_id(1), Path::new("a")));
+        cache.insert(commit_id(2), FileContent::new(commit_id(2), Path::new("a")));
+        assert!(cache.get(commit_id(1)).is_some());
+        cache.insert(commit_id(3), FileContent::new(commit_id(3), Path::new("a")));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(commit_id(2)).is_none());
+        assert!(cache.get(commit_id(1)).is_some());
+        assert!(cache.get(commit_id(3)).is_some());
+
+        cache.insert(commit_id(3), FileContent::new(commit_id(3), Path::new("b")));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(commit_id(3)).unwrap().path(), Path::new("b"));
+    }
+}
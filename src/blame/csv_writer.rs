@@ -0,0 +1,123 @@
+use std::io::Write;
+
+use git2_time_chrono_ext::Git2TimeChronoExt;
+
+use super::{BlameError, FileContent, FileHistory};
+
+/// Writes lines as CSV, or TSV if the `delimiter` is a tab,
+/// with the line number, the commit ID, the author, the email, the date,
+/// and the content of each line, for spreadsheets or data analysis tools.
+#[derive(Debug)]
+pub struct CsvWriter {
+    delimiter: char,
+}
+
+impl CsvWriter {
+    pub fn new(delimiter: char) -> Self {
+        Self { delimiter }
+    }
+
+    /// Write the header and the lines of the `content`,
+    /// except the deleted lines. The commit ID, the author, the email,
+    /// and the date are empty for lines not committed yet.
+    pub fn write(
+        &self,
+        out: &mut impl Write,
+        history: &FileHistory,
+        content: &FileContent,
+    ) -> Result<(), BlameError> {
+        self.write_record(
+            out,
+            &["line", "commit", "author", "email", "date", "content"],
+        )?;
+        for line in content.lines().iter().filter(|line| !line.is_deleted()) {
+            let line_number = line.line_number().to_string();
+            let commit = match line.commit_id() {
+                Some(commit_id) => Some(history.commits().get_by_commit_id(commit_id)?),
+                None => None,
+            };
+            let commit_id = commit.map_or_else(String::new, |c| c.commit_id().to_string());
+            let date = commit
+                .and_then(|commit| commit.time().to_local_date_time().ok())
+                .map_or_else(String::new, |datetime| {
+                    datetime.format("%Y-%m-%dT%H:%M:%S%:z").to_string()
+                });
+            self.write_record(
+                out,
+                &[
+                    &line_number,
+                    &commit_id,
+                    commit.map_or("", |commit| commit.author_name()),
+                    commit.map_or("", |commit| commit.author_email()),
+                    &date,
+                    line.content(),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_record(&self, out: &mut impl Write, fields: &[&str]) -> anyhow::Result<()> {
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                write!(out, "{}", self.delimiter)?;
+            }
+            write!(out, "{}", self.quote(field))?;
+        }
+        writeln!(out)?;
+        Ok(())
+    }
+
+    /// Quote the `field` if needed, as in RFC 4180.
+    fn quote(&self, field: &str) -> String {
+        if field.contains([self.delimiter, '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::extensions::tests::TempRepository;
+
+    use super::*;
+
+    #[test]
+    fn quote() {
+        let writer = CsvWriter::new(',');
+        assert_eq!(writer.quote("a b"), "a b");
+        assert_eq!(writer.quote("a,b"), "\"a,b\"");
+        assert_eq!(writer.quote("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(writer.quote("a\tb"), "a\tb");
+        assert_eq!(CsvWriter::new('\t').quote("a\tb"), "\"a\tb\"");
+    }
+
+    #[test]
+    fn write() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "a, b\n\"c\"\n")?;
+        let commit_id = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        history.read_join()?;
+        history.read_poll()?;
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let mut out = vec![];
+        CsvWriter::new(',').write(&mut out, &history, &content)?;
+        let out = String::from_utf8(out)?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "line,commit,author,email,date,content");
+        assert!(lines[1].starts_with(&format!("1,{commit_id},Test User,test@test.com,")));
+        assert!(lines[1].ends_with(",\"a, b\""));
+        assert!(lines[2].starts_with(&format!("2,{commit_id},")));
+        assert!(lines[2].ends_with(",\"\"\"c\"\"\""));
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@ use std::{
 use anyhow::bail;
 
 #[derive(Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct DiffPart {
     pub old: DiffRange,
     pub new: DiffRange,
@@ -36,15 +37,15 @@ impl DiffPart {
             if part.old.line_numbers.start < last_old {
                 bail!("old start isn't ascending: {:?}", part);
             }
-            part.old
-                .validate_ascending()
-                .unwrap_or_else(|_| panic!("old start and end aren't ascending: {part:?}"));
+            if !part.old.is_ascending() {
+                bail!("old start and end aren't ascending: {:?}", part);
+            }
             if part.new.line_numbers.start < last_new {
                 bail!("new start isn't ascending: {:?}", part);
             }
-            part.new
-                .validate_ascending()
-                .unwrap_or_else(|_| panic!("new start and end aren't ascending: {part:?}"));
+            if !part.new.is_ascending() {
+                bail!("new start and end aren't ascending: {:?}", part);
+            }
             last_old = part.old.line_numbers.end;
             last_new = part.new.line_numbers.end;
         }
@@ -58,7 +59,9 @@ impl fmt::Debug for DiffPart {
     }
 }
 
+/// Serialized as the `line_numbers`; e.g., `{"start": 1, "end": 3}`.
 #[derive(Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
 pub struct DiffRange {
     pub line_numbers: Range<usize>,
 }
@@ -106,10 +109,10 @@ impl DiffRange {
         if self.line_numbers.is_empty() {
             self.line_numbers = line_number..line_number + 1;
         } else {
-            assert_eq!(self.line_numbers.end, line_number);
+            debug_assert_eq!(self.line_numbers.end, line_number);
             self.line_numbers.end += 1;
         }
-        self.validate_ascending().unwrap();
+        debug_assert!(self.is_ascending(), "{self:?}");
     }
 
     pub fn set_line_number_if_empty(&mut self, line_number: usize) {
@@ -0,0 +1,272 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    process,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use log::*;
+
+use super::{DiffPart, DiffRange};
+
+/// A temporary file to spill the `DiffPart`s of old commits to, so that very
+/// long histories don't keep all of them in memory.
+/// See `BlameOptions::set_memory_budget()`.
+///
+/// The parts read back are cached up to the `cache_size` bytes, dropping the
+/// least recently used first.
+///
+/// The file is removed when this is dropped.
+#[derive(Debug)]
+pub(crate) struct DiffPartStore {
+    path: PathBuf,
+    file: Mutex<File>,
+    cache: Mutex<DiffPartCache>,
+}
+
+#[derive(Debug, Default)]
+struct DiffPartCache {
+    /// The parts and their offsets, the most recently used last.
+    entries: VecDeque<(u64, Arc<Vec<DiffPart>>)>,
+    size: usize,
+    capacity: usize,
+}
+
+impl DiffPartStore {
+    /// The bytes of a `DiffPart` in the file; the 4 line numbers in `u64`.
+    const PART_SIZE: usize = 4 * size_of::<u64>();
+
+    pub fn new(cache_size: usize) -> anyhow::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "git-iblame-{}-{}.diffs",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        debug!("DiffPartStore: {path:?}");
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+            cache: Mutex::new(DiffPartCache {
+                capacity: cache_size,
+                ..Default::default()
+            }),
+        })
+    }
+
+    /// The `len` parts written at the `offset`, from the cache if they're
+    /// read recently, or by `read()` otherwise.
+    fn load(&self, offset: u64, len: usize) -> anyhow::Result<Arc<Vec<DiffPart>>> {
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(index) = cache.entries.iter().position(|(o, _)| *o == offset) {
+            let entry = cache.entries.remove(index).unwrap();
+            let parts = entry.1.clone();
+            cache.entries.push_back(entry);
+            return Ok(parts);
+        }
+        trace!("DiffPartStore: read {len} parts");
+        let parts = Arc::new(self.read(offset, len)?);
+        let size = len * size_of::<DiffPart>();
+        while cache.size + size > cache.capacity
+            && let Some((_, evicted)) = cache.entries.pop_front()
+        {
+            cache.size -= evicted.len() * size_of::<DiffPart>();
+        }
+        if size <= cache.capacity {
+            cache.entries.push_back((offset, parts.clone()));
+            cache.size += size;
+        }
+        Ok(parts)
+    }
+
+    /// Append the `parts` to the file, and return the offset to `read()` them.
+    fn write(&self, parts: &[DiffPart]) -> anyhow::Result<u64> {
+        let mut bytes = Vec::with_capacity(parts.len() * Self::PART_SIZE);
+        for part in parts {
+            for line_number in [
+                part.old.line_numbers.start,
+                part.old.line_numbers.end,
+                part.new.line_numbers.start,
+                part.new.line_numbers.end,
+            ] {
+                bytes.extend_from_slice(&(line_number as u64).to_le_bytes());
+            }
+        }
+        let mut file = self.file.lock().unwrap();
+        let offset = file.seek(SeekFrom::End(0))?;
+        file.write_all(&bytes)?;
+        Ok(offset)
+    }
+
+    /// Read the `len` parts written at the `offset` by `write()`.
+    fn read(&self, offset: u64, len: usize) -> anyhow::Result<Vec<DiffPart>> {
+        let mut bytes = vec![0; len * Self::PART_SIZE];
+        {
+            let mut file = self.file.lock().unwrap();
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut bytes)?;
+        }
+        let line_numbers: Vec<usize> = bytes
+            .chunks_exact(size_of::<u64>())
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()) as usize)
+            .collect();
+        Ok(line_numbers
+            .chunks_exact(4)
+            .map(|line_numbers| DiffPart {
+                old: DiffRange {
+                    line_numbers: line_numbers[0]..line_numbers[1],
+                },
+                new: DiffRange {
+                    line_numbers: line_numbers[2]..line_numbers[3],
+                },
+            })
+            .collect())
+    }
+}
+
+impl Drop for DiffPartStore {
+    fn drop(&mut self) {
+        if let Err(error) = fs::remove_file(&self.path) {
+            warn!("Failed to remove {:?}: {error}", self.path);
+        }
+    }
+}
+
+/// The `DiffPart`s of a `FileCommit`, which may have been spilled to a
+/// `DiffPartStore`. Spilled parts are read back by `load()` on demand.
+#[derive(Clone, Default)]
+pub(crate) struct DiffParts {
+    parts: Arc<Vec<DiffPart>>,
+    spilled: Option<SpilledDiffParts>,
+}
+
+#[derive(Clone)]
+struct SpilledDiffParts {
+    store: Arc<DiffPartStore>,
+    offset: u64,
+    len: usize,
+}
+
+impl DiffParts {
+    pub fn len(&self) -> usize {
+        match &self.spilled {
+            Some(spilled) => spilled.len,
+            None => self.parts.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The bytes of the parts in memory, excluding the ones spilled.
+    pub fn memory_size(&self) -> usize {
+        match &self.spilled {
+            Some(_) => 0,
+            None => self.parts.len() * size_of::<DiffPart>(),
+        }
+    }
+
+    /// The parts, read back from the `DiffPartStore` if they were spilled.
+    pub fn load(&self) -> anyhow::Result<Arc<Vec<DiffPart>>> {
+        match &self.spilled {
+            Some(spilled) => spilled.store.load(spilled.offset, spilled.len),
+            None => Ok(self.parts.clone()),
+        }
+    }
+
+    /// Move the parts to the `store`. This is no-op if they're already
+    /// spilled.
+    pub fn spill(&mut self, store: &Arc<DiffPartStore>) -> anyhow::Result<()> {
+        if self.spilled.is_some() || self.parts.is_empty() {
+            return Ok(());
+        }
+        let offset = store.write(&self.parts)?;
+        self.spilled = Some(SpilledDiffParts {
+            store: store.clone(),
+            offset,
+            len: self.parts.len(),
+        });
+        self.parts = Arc::default();
+        Ok(())
+    }
+}
+
+impl From<Vec<DiffPart>> for DiffParts {
+    fn from(parts: Vec<DiffPart>) -> Self {
+        Self {
+            parts: Arc::new(parts),
+            spilled: None,
+        }
+    }
+}
+
+impl<const N: usize> PartialEq<[DiffPart; N]> for DiffParts {
+    fn eq(&self, other: &[DiffPart; N]) -> bool {
+        self.load().is_ok_and(|parts| *parts == *other)
+    }
+}
+
+impl fmt::Debug for DiffParts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.spilled {
+            Some(spilled) => write!(f, "<{} parts spilled>", spilled.len),
+            None => self.parts.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for DiffParts {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.load()
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spill() -> anyhow::Result<()> {
+        // Cache up to 2 parts.
+        let store = Arc::new(DiffPartStore::new(2 * size_of::<DiffPart>())?);
+        let path = store.path.clone();
+        let parts = vec![
+            DiffPart::from_ranges(1..2, 1..3),
+            DiffPart::from_ranges(5..5, 6..usize::MAX),
+        ];
+        let mut diff_parts = DiffParts::from(parts.clone());
+        let mut other = DiffParts::from(vec![DiffPart::from_ranges(0..0, 1..4)]);
+        assert_eq!(diff_parts.memory_size(), 2 * size_of::<DiffPart>());
+        other.spill(&store)?;
+        diff_parts.spill(&store)?;
+        assert_eq!(diff_parts.memory_size(), 0);
+        assert_eq!(diff_parts.len(), 2);
+
+        assert_eq!(*diff_parts.load()?, parts);
+        assert_eq!(store.cache.lock().unwrap().size, 2 * size_of::<DiffPart>());
+        // Loading `other` evicts `diff_parts` from the cache.
+        assert_eq!(other, [DiffPart::from_ranges(0..0, 1..4)]);
+        assert_eq!(store.cache.lock().unwrap().entries.len(), 1);
+        assert_eq!(*diff_parts.load()?, parts);
+        assert_eq!(diff_parts.memory_size(), 0);
+
+        drop((diff_parts, other, store));
+        assert!(!path.exists());
+        Ok(())
+    }
+}
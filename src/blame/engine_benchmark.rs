@@ -0,0 +1,120 @@
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use log::*;
+
+use crate::extensions::GitTools;
+
+use super::{BlameError, BlameOptions, CommitIterator, FileCommit, GitEngine};
+
+/// The time to read the diffs of the newest commits of a file by each
+/// `GitEngine`, to choose the faster engine for the repository and the file.
+#[derive(Debug)]
+pub(crate) struct EngineBenchmark {
+    commits_len: usize,
+    elapsed: Vec<(GitEngine, Duration)>,
+}
+
+impl EngineBenchmark {
+    /// The engines to compare. `GitEngine::GitBlame` and
+    /// `GitEngine::Git2Blame` are not candidates, because they read diffs in
+    /// the same way as `GitEngine::Git` and `GitEngine::Git2`.
+    const ENGINES: [GitEngine; 2] = [GitEngine::Git, GitEngine::Git2];
+
+    /// The default number of the commits to read.
+    pub const DEFAULT_COMMITS_LEN: usize = 10;
+
+    /// Read the newest `commits_len` commits of the file at the `path` by each
+    /// engine. The engines read each commit in turn, in the reversed order for
+    /// every other commit, so that caches warmed by the first engine don't
+    /// favor the others.
+    pub fn new(path: &Path, options: &BlameOptions, commits_len: usize) -> anyhow::Result<Self> {
+        let (git, path) = GitTools::from_file_or_relative_path(path)?;
+        let mut commits = CommitIterator::new(&path, git.repository_path());
+        commits.set_options(options.clone());
+        commits.set_start_commit_id(Some(options.start_commit_id(&git)?));
+        commits.start()?;
+        // Dropping the `commits` stops the `git log` for the rest.
+        let commit_ids: Vec<git2::Oid> = commits
+            .into_file_commits()
+            .take(commits_len)
+            .map(|commit| commit.map(|commit| commit.commit_id()))
+            .collect::<Result<_, BlameError>>()?;
+
+        let mut elapsed = Self::ENGINES.map(|engine| (engine, Duration::ZERO));
+        let mut path = path;
+        for (index, commit_id) in commit_ids.iter().enumerate() {
+            let mut old_path = None;
+            let engines: Box<dyn Iterator<Item = _>> = if index % 2 == 0 {
+                Box::new(elapsed.iter_mut())
+            } else {
+                Box::new(elapsed.iter_mut().rev())
+            };
+            for (engine, elapsed) in engines {
+                let start_time = Instant::now();
+                let mut commit = FileCommit::new(*commit_id, &path);
+                commit.read_with_engine(&git, options, *engine)?;
+                *elapsed += start_time.elapsed();
+                old_path = commit.old_path_if_rename().map(Path::to_path_buf);
+            }
+            if let Some(old_path) = old_path {
+                path = old_path;
+            }
+        }
+        debug!("EngineBenchmark: {} commits, {elapsed:?}", commit_ids.len());
+        Ok(Self {
+            commits_len: commit_ids.len(),
+            elapsed: elapsed.to_vec(),
+        })
+    }
+
+    /// The number of the commits read by each engine.
+    pub fn commits_len(&self) -> usize {
+        self.commits_len
+    }
+
+    /// The engines and the time they took.
+    pub fn elapsed(&self) -> &[(GitEngine, Duration)] {
+        &self.elapsed
+    }
+
+    /// The engine that took the shortest time.
+    pub fn fastest(&self) -> GitEngine {
+        self.elapsed
+            .iter()
+            .min_by_key(|(_, elapsed)| *elapsed)
+            .map_or_else(FileCommit::git_engine, |(engine, _)| *engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extensions::tests::TempRepository;
+
+    use super::*;
+
+    #[test]
+    fn new() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add 2")?;
+        git.add_file_content(path, "1\n2\n3\n")?;
+        git.commit(commit_id2, "Add 3")?;
+
+        let benchmark = EngineBenchmark::new(&git.to_file_path(path), &BlameOptions::new(), 2)?;
+        assert_eq!(benchmark.commits_len(), 2);
+        let engines: Vec<GitEngine> = benchmark
+            .elapsed()
+            .iter()
+            .map(|(engine, _)| *engine)
+            .collect();
+        assert_eq!(engines, EngineBenchmark::ENGINES);
+        assert!(EngineBenchmark::ENGINES.contains(&benchmark.fastest()));
+        Ok(())
+    }
+}
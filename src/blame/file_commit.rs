@@ -1,7 +1,10 @@
 use std::{
     io::BufReader,
     path::{Path, PathBuf},
-    sync::atomic::{self, AtomicBool},
+    sync::{
+        Arc,
+        atomic::{self, AtomicBool},
+    },
 };
 
 use log::*;
@@ -9,12 +12,67 @@ use regex::Regex;
 
 use crate::extensions::{GitTools, LineReadBuffer};
 
-use super::DiffPart;
+use super::{
+    BlameError, BlameOptions, CommitMetadata, DiffPart, DiffPartStore, DiffParts,
+    Git2BlameProvider, Git2Provider, GitBlameProvider, GitDiffHeader, GitProvider, HistoryProvider,
+    LineNumberMap, MoveDetector,
+};
 
-#[derive(Copy, Clone, Debug)]
-enum GitEngine {
+/// How to compute the diffs of commits and attribute lines.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GitEngine {
+    /// Compute the diffs by the `git` command.
     Git,
+    /// Compute the diffs by the `git2` crate.
     Git2,
+    /// Attribute lines by `git blame`. Diffs are computed by `git`.
+    GitBlame,
+    /// Attribute lines by `git2::Blame`, the visible lines first if
+    /// `FileContent::set_visible_line_range()` is set.
+    /// Diffs are computed by the `git2` crate.
+    Git2Blame,
+}
+
+impl GitEngine {
+    /// The name in the `--engine` option.
+    pub fn name(&self) -> &'static str {
+        match self {
+            GitEngine::Git => "git",
+            GitEngine::Git2 => "git2",
+            GitEngine::GitBlame => "blame",
+            GitEngine::Git2Blame => "git2-blame",
+        }
+    }
+
+    /// The `HistoryProvider` to read the history by this engine.
+    /// `GitEngine::GitBlame` reads diffs in the same way as `GitEngine::Git`,
+    /// and `GitEngine::Git2Blame` as `GitEngine::Git2`.
+    pub fn provider(&self) -> Arc<dyn HistoryProvider> {
+        match self {
+            GitEngine::Git => Arc::new(GitProvider),
+            GitEngine::Git2 => Arc::new(Git2Provider),
+            GitEngine::GitBlame => Arc::new(GitBlameProvider),
+            GitEngine::Git2Blame => Arc::new(Git2BlameProvider),
+        }
+    }
+
+    /// True if lines are attributed by blame instead of applying the diffs.
+    pub fn is_blame(&self) -> bool {
+        matches!(self, GitEngine::GitBlame | GitEngine::Git2Blame)
+    }
+
+    /// The engine to use instead of this engine, if this engine doesn't
+    /// support the `options`. `GitEngine::Git2Blame` falls back to
+    /// `GitEngine::Git2`, because `git2::Blame` can't ignore revisions nor
+    /// detect moves.
+    pub fn fallback_engine(&self, options: &BlameOptions) -> Option<GitEngine> {
+        match self {
+            GitEngine::Git2Blame if !options.ignore_revs().is_empty() || options.detect_moves() => {
+                Some(GitEngine::Git2)
+            }
+            _ => None,
+        }
+    }
 }
 
 #[cfg(feature = "git2")]
@@ -23,18 +81,58 @@ static mut DIFF_ENGINE: GitEngine = GitEngine::Git2;
 static mut DIFF_ENGINE: GitEngine = GitEngine::Git;
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct FileCommit {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize::oid"))]
     commit_id: git2::Oid,
     path: PathBuf,
     index: usize,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "super::serialize::time"))]
     time: git2::Time,
     summary: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    change_id: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    metadata: Vec<CommitMetadata>,
+    author_name: String,
     author_email: String,
     old_path: Option<PathBuf>,
-    diff_parts: Vec<DiffPart>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "super::serialize::option_oid")
+    )]
+    parent_commit_id: Option<git2::Oid>,
+    diff_parts: DiffParts,
+    moved_parts: Vec<DiffPart>,
+    is_boundary: bool,
+    is_ignored: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
     is_apply_failed: AtomicBool,
 }
 
+impl Clone for FileCommit {
+    fn clone(&self) -> Self {
+        Self {
+            commit_id: self.commit_id,
+            path: self.path.clone(),
+            index: self.index,
+            time: self.time,
+            summary: self.summary.clone(),
+            change_id: self.change_id.clone(),
+            metadata: self.metadata.clone(),
+            author_name: self.author_name.clone(),
+            author_email: self.author_email.clone(),
+            old_path: self.old_path.clone(),
+            parent_commit_id: self.parent_commit_id,
+            diff_parts: self.diff_parts.clone(),
+            moved_parts: self.moved_parts.clone(),
+            is_boundary: self.is_boundary,
+            is_ignored: self.is_ignored,
+            is_apply_failed: AtomicBool::new(self.is_apply_failed()),
+        }
+    }
+}
+
 impl FileCommit {
     pub fn new(commit_id: git2::Oid, path: &Path) -> Self {
         Self {
@@ -43,14 +141,21 @@ impl FileCommit {
             index: 0,
             time: git2::Time::new(0, 0),
             summary: None,
+            change_id: None,
+            metadata: Vec::new(),
+            author_name: String::default(),
             author_email: String::default(),
             old_path: None,
-            diff_parts: Vec::new(),
+            parent_commit_id: None,
+            diff_parts: DiffParts::default(),
+            moved_parts: Vec::new(),
+            is_boundary: false,
+            is_ignored: false,
             is_apply_failed: AtomicBool::new(false),
         }
     }
 
-    fn git_engine() -> GitEngine {
+    pub(crate) fn git_engine() -> GitEngine {
         unsafe { DIFF_ENGINE }
     }
 
@@ -62,6 +167,13 @@ impl FileCommit {
         unsafe { DIFF_ENGINE = GitEngine::Git2 }
     }
 
+    /// Use `git blame` to attribute lines, instead of the diffs of commits.
+    /// This is slower, but is useful when the diffs of commits can't be
+    /// applied correctly.
+    pub fn use_git_blame() {
+        unsafe { DIFF_ENGINE = GitEngine::GitBlame }
+    }
+
     pub fn commit_id(&self) -> git2::Oid {
         self.commit_id
     }
@@ -78,14 +190,50 @@ impl FileCommit {
         self.time
     }
 
+    #[cfg(test)]
+    pub(crate) fn set_time_for_test(&mut self, time: git2::Time) {
+        self.time = time;
+    }
+
     pub fn summary(&self) -> Option<&String> {
         self.summary.as_ref()
     }
 
+    /// The change ID of the commit, such as in `jj`, set by the
+    /// `HistoryProvider`. `None` if the version control system doesn't have
+    /// change IDs.
+    pub fn change_id(&self) -> Option<&str> {
+        self.change_id.as_deref()
+    }
+
+    pub fn set_change_id(&mut self, change_id: Option<String>) {
+        self.change_id = change_id;
+    }
+
+    /// The `CommitMetadata` attached by the `MetadataProvider`s.
+    /// Empty until `FileHistory::read_poll()` attaches them.
+    pub fn metadata(&self) -> &[CommitMetadata] {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: Vec<CommitMetadata>) {
+        self.metadata = metadata;
+    }
+
+    pub fn author_name(&self) -> &str {
+        self.author_name.as_ref()
+    }
+
     pub fn author_email(&self) -> &str {
         self.author_email.as_ref()
     }
 
+    #[cfg(test)]
+    pub(crate) fn set_metadata_for_test(&mut self, author_email: &str, summary: &str) {
+        self.author_email = author_email.to_string();
+        self.summary = Some(summary.to_string());
+    }
+
     pub fn path(&self) -> &Path {
         &self.path
     }
@@ -95,8 +243,106 @@ impl FileCommit {
         self.old_path.as_deref()
     }
 
-    pub fn diff_parts(&self) -> &Vec<DiffPart> {
-        &self.diff_parts
+    /// The parent commit that the `diff_parts()` are computed against,
+    /// or `None` if this commit has no parents.
+    ///
+    /// For merge commits, this is the parent closest to this commit;
+    /// i.e., the parent with the fewest changed lines of the file.
+    pub fn parent_commit_id(&self) -> Option<git2::Oid> {
+        self.parent_commit_id
+    }
+
+    /// The diffs from the `parent_commit_id()`. They're read back from a
+    /// temporary file if they were spilled; see
+    /// `BlameOptions::set_memory_budget()`.
+    pub fn diff_parts(&self) -> Result<Arc<Vec<DiffPart>>, BlameError> {
+        Ok(self.diff_parts.load()?)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_diff_parts_for_test(&mut self, diff_parts: Vec<DiffPart>) {
+        self.diff_parts = diff_parts.into();
+    }
+
+    /// The bytes of the `diff_parts()` in memory.
+    pub(crate) fn diff_parts_memory_size(&self) -> usize {
+        self.diff_parts.memory_size()
+    }
+
+    /// Move the `diff_parts()` to the `store`, to read them back when
+    /// they're needed. See `BlameOptions::set_memory_budget()`.
+    pub(crate) fn spill_diff_parts(&mut self, store: &Arc<DiffPartStore>) -> anyhow::Result<()> {
+        self.diff_parts.spill(store)
+    }
+
+    /// The blocks of lines moved within the file by this commit.
+    /// The `old` of each part is the block before the move,
+    /// and the `new` is the block after the move.
+    /// This is empty unless `BlameOptions::detect_moves()` is set.
+    pub fn moved_parts(&self) -> &[DiffPart] {
+        &self.moved_parts
+    }
+
+    /// The line number before the move if the `line_number` is in
+    /// `moved_parts()`.
+    pub fn moved_from(&self, line_number: usize) -> Option<usize> {
+        self.moved_parts
+            .iter()
+            .find(|part| part.new.line_numbers.contains(&line_number))
+            .map(|part| part.old.line_numbers.start + line_number - part.new.line_numbers.start)
+    }
+
+    /// The line number before this commit of the line at the `line_number`
+    /// after it, or `None` if this commit added the line.
+    ///
+    /// The `parts` are the `diff_parts()`, and the `map` is created from them
+    /// by `LineNumberMap::new_old_from_new()`, so that callers mapping many
+    /// lines can create them once. The lines in `moved_parts()` are mapped to
+    /// where they were moved from. If this commit `is_ignored()`, the changed
+    /// lines are mapped to their corresponding old lines.
+    pub(super) fn old_line_number(
+        &self,
+        line_number: usize,
+        parts: &[DiffPart],
+        map: &LineNumberMap,
+    ) -> Option<usize> {
+        if let Some(moved_from) = self.moved_from(line_number) {
+            return Some(moved_from);
+        }
+        if let Some(part) = parts
+            .iter()
+            .find(|part| part.new.line_numbers.contains(&line_number))
+        {
+            let index_in_part = line_number - part.new.line_numbers.start;
+            if !self.is_ignored() || index_in_part >= part.old.len() {
+                return None;
+            }
+            return Some(part.old.line_numbers.start + index_in_part);
+        }
+        Some(map.map(line_number))
+    }
+
+    /// True if this commit is a boundary of the history; i.e., it has no
+    /// parents, or it's the oldest commit read when the history was cut off
+    /// by `BlameOptions::max_commits()` or `since()`. The lines older than
+    /// the limits are attributed to it. This is what `git blame` marks with
+    /// the `^` prefix.
+    pub fn is_boundary(&self) -> bool {
+        self.is_boundary
+    }
+
+    pub(super) fn set_boundary(&mut self) {
+        self.is_boundary = true;
+    }
+
+    /// True if changes by this commit are ignored when assigning blame.
+    /// See `BlameOptions::add_ignore_rev()`.
+    pub fn is_ignored(&self) -> bool {
+        self.is_ignored
+    }
+
+    pub fn set_ignored(&mut self, is_ignored: bool) {
+        self.is_ignored = is_ignored;
     }
 
     pub fn is_apply_failed(&self) -> bool {
@@ -108,45 +354,83 @@ impl FileCommit {
         self.is_apply_failed.store(true, atomic::Ordering::Relaxed);
     }
 
-    pub fn read(&mut self, git: &GitTools) -> anyhow::Result<()> {
+    pub fn read(&mut self, git: &GitTools) -> Result<(), BlameError> {
+        self.read_with_options(git, &BlameOptions::default())
+    }
+
+    /// Same as `read()`, with the rename detection options in the `options`.
+    pub fn read_with_options(
+        &mut self,
+        git: &GitTools,
+        options: &BlameOptions,
+    ) -> Result<(), BlameError> {
+        Ok(self.read_with_engine(git, options, Self::git_engine())?)
+    }
+
+    /// Same as `read_with_options()`, by the `engine`
+    /// instead of the engine set by `use_git()` or `use_git2()`.
+    pub(crate) fn read_with_engine(
+        &mut self,
+        git: &GitTools,
+        options: &BlameOptions,
+        engine: GitEngine,
+    ) -> anyhow::Result<()> {
+        self.read_with_provider(git, options, engine.provider().as_ref())
+    }
+
+    /// Same as `read_with_options()`, by the `provider`.
+    pub(crate) fn read_with_provider(
+        &mut self,
+        git: &GitTools,
+        options: &BlameOptions,
+        provider: &dyn HistoryProvider,
+    ) -> anyhow::Result<()> {
         assert!(self.path.is_relative());
         assert!(self.diff_parts.is_empty());
-        match Self::git_engine() {
-            GitEngine::Git => self.read_by_git(git),
-            GitEngine::Git2 => self.read_by_git2(git),
-        }?;
-        DiffPart::validate_ascending_parts(&self.diff_parts)
+        provider.read_commit(git, self, options)?;
+        let parts = self.diff_parts.load()?;
+        DiffPart::validate_ascending_parts(&parts)
     }
 
-    fn read_by_git(&mut self, git: &GitTools) -> anyhow::Result<()> {
-        self.read_by_git_paths(git, false)
+    pub(super) fn read_by_git(
+        &mut self,
+        git: &GitTools,
+        options: &BlameOptions,
+    ) -> anyhow::Result<()> {
+        self.read_by_git_paths(git, options, false)
     }
 
-    fn read_by_git_paths(&mut self, git: &GitTools, check_rename: bool) -> anyhow::Result<()> {
+    fn read_by_git_paths(
+        &mut self,
+        git: &GitTools,
+        options: &BlameOptions,
+        check_rename: bool,
+    ) -> anyhow::Result<()> {
         let start_time = std::time::Instant::now();
         let commit_id = self.commit_id;
         debug!("read_by_git.start: {commit_id:?} {:?}", self.path);
         let commit = git.repository().find_commit(commit_id)?;
         self.set_commit(&commit)?;
+        self.set_parent(git, &commit)?;
 
         let mut paths: Vec<&Path> = vec![];
         if !check_rename {
             paths.push(&self.path);
         }
-        let mut command = git.create_show(commit_id, &paths);
+        let mut command = self.create_diff_command(git, &commit, options, &paths);
         let mut child = command.stdout(std::process::Stdio::piped()).spawn()?;
         let stdout = child.stdout.take().unwrap();
         let mut reader = BufReader::new(stdout);
         let mut buffer = LineReadBuffer::new();
-        let re_file = Regex::new(r#"^diff --git "?a/(.+?)"? "?b/(.+?)"?$"#)?;
-        let re_new_file = Regex::new(r"^--- /dev/null$")?;
-        let re_hunk = Regex::new(r"^@@ -(\d+),(\d+) \+(\d+),(\d+) @@")?;
+        // The line counts are omitted when they're 1.
+        let re_hunk = Regex::new(r"^@@ -(\d+)(?:,\d+)? \+(\d+)(?:,\d+)? @@")?;
 
         let path = self.path.to_str().unwrap();
+        let mut header: Option<GitDiffHeader> = None;
         let mut is_in_hunk = false;
         let mut is_path_found = false;
         let mut old_path: Option<PathBuf> = None;
-        let mut context = DiffReadContext::default();
+        let mut context = DiffReadContext::new(options);
         while buffer.read_line_from(&mut reader)? {
             let line = buffer.as_ref();
             trace!("read_by_git: line={line}");
@@ -155,7 +439,10 @@ impl FileCommit {
             {
                 return Err(buffer.error());
             }
-            if let Some(captures) = re_file.captures(line) {
+            if let Some(next_header) = GitDiffHeader::new(line) {
+                if let Some(header) = header.take() {
+                    is_path_found = Self::match_git_diff_header(&header, path, &mut old_path);
+                }
                 if is_path_found {
                     if let Err(error) = child.kill() {
                         warn!("Stopping child: {error}");
@@ -163,32 +450,31 @@ impl FileCommit {
                     break;
                 }
                 is_in_hunk = false;
-                let new_path = captures.get(2).unwrap().as_str();
-                is_path_found = new_path == path;
-                if !is_path_found {
-                    // trace!("file: {new_path:?}, not interesting");
+                header = Some(next_header);
+                continue;
+            }
+            if let Some(current_header) = &mut header {
+                if !line.starts_with("@@ ") {
+                    current_header.read_line(line);
                     continue;
                 }
-                let old_path_from_git = captures.get(1).unwrap().as_str();
-                if old_path_from_git != path {
-                    old_path = Some(old_path_from_git.into());
+                let is_new_file = current_header.is_new_file();
+                let current_header = header.take().unwrap();
+                is_path_found = Self::match_git_diff_header(&current_header, path, &mut old_path);
+                if is_path_found && is_new_file && !check_rename && options.follow() {
+                    // If we're using paths and the target file is a new file, it's
+                    // possible that this is a rename. Show all files so that `git`
+                    // can detect the rename.
+                    trace!("read_by_git.file: possible rename, show all files");
+                    return self.read_by_git_paths(git, options, true);
                 }
-                trace!("read_by_git.file: {new_path:?} old={old_path:?} {old_path_from_git}");
-                continue;
             }
             if !is_path_found {
                 continue;
             }
-            if !is_in_hunk && !check_rename && re_new_file.is_match(line) {
-                // If we're using paths and the target file is a new file, it's
-                // possible that this is a rename. Show all files so that `git`
-                // can detect the rename.
-                trace!("read_by_git.file: possible rename, show all files");
-                return self.read_by_git_paths(git, true);
-            }
             if let Some(captures) = re_hunk.captures(line) {
                 let old_line_number = captures.get(1).unwrap().as_str().parse::<usize>()?;
-                let new_line_number = captures.get(3).unwrap().as_str().parse::<usize>()?;
+                let new_line_number = captures.get(2).unwrap().as_str().parse::<usize>()?;
                 context.on_git_hunk(old_line_number, new_line_number);
                 is_in_hunk = true;
                 continue;
@@ -197,10 +483,15 @@ impl FileCommit {
                 context.on_git_line(line);
             }
         }
+        // The last file may have no hunks, such as renames without changes.
+        if let Some(header) = header.take() {
+            Self::match_git_diff_header(&header, path, &mut old_path);
+        }
         context.flush_part();
 
         self.old_path = old_path;
-        self.diff_parts = context.parts;
+        self.moved_parts = context.moved_parts();
+        self.diff_parts = context.parts.into();
         debug!("read_by_git.done: elapsed {:?}", start_time.elapsed());
         trace!("read_by_git.result={self:#?}");
 
@@ -212,27 +503,82 @@ impl FileCommit {
         Ok(())
     }
 
-    fn read_by_git2(&mut self, git: &GitTools) -> anyhow::Result<()> {
-        self.read_by_git2_rename(git, false)
+    /// Create the `git` command to read the diffs of the `commit`.
+    fn create_diff_command(
+        &self,
+        git: &GitTools,
+        commit: &git2::Commit,
+        options: &BlameOptions,
+        paths: &[&Path],
+    ) -> std::process::Command {
+        // The options must be before the `--` and the `paths`.
+        let rename_threshold_arg = options.rename_threshold_arg();
+        let args: Vec<&str> = [
+            rename_threshold_arg.as_deref(),
+            options.ignore_cr_at_eol_arg(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        match self.parent_commit_id {
+            // `git show` shows combined diffs for merges. Diff against the
+            // closest parent instead.
+            Some(parent_commit_id) if commit.parent_count() > 1 => {
+                git.create_diff(parent_commit_id, commit.id(), &args, paths)
+            }
+            _ => git.create_show_with_args(commit.id(), &args, paths),
+        }
     }
 
-    fn read_by_git2_rename(&mut self, git: &GitTools, check_rename: bool) -> anyhow::Result<()> {
+    /// Whether the `header` is of the `path`.
+    /// If so, and if it's renamed from another path, set it to the `old_path`.
+    fn match_git_diff_header(
+        header: &GitDiffHeader,
+        path: &str,
+        old_path: &mut Option<PathBuf>,
+    ) -> bool {
+        if header.new_path() != Some(path) {
+            return false;
+        }
+        if let Some(old_path_from_git) = header.old_path()
+            && old_path_from_git != path
+        {
+            *old_path = Some(old_path_from_git.into());
+        }
+        trace!("read_by_git.file: {path:?} old={old_path:?}");
+        true
+    }
+
+    pub(super) fn read_by_git2(
+        &mut self,
+        git: &GitTools,
+        options: &BlameOptions,
+    ) -> anyhow::Result<()> {
+        self.read_by_git2_rename(git, options, false)
+    }
+
+    fn read_by_git2_rename(
+        &mut self,
+        git: &GitTools,
+        options: &BlameOptions,
+        check_rename: bool,
+    ) -> anyhow::Result<()> {
         assert!(self.diff_parts.is_empty());
         let start_time = std::time::Instant::now();
         let commit_id = self.commit_id;
         debug!("read_by_git2.start: {commit_id:?} {:?}", self.path);
         let commit = git.repository().find_commit(commit_id)?;
         self.set_commit(&commit)?;
+        self.set_parent(git, &commit)?;
 
-        let parent = commit.parent(0);
-        if parent.is_err() {
+        let Some(parent_commit_id) = self.parent_commit_id else {
             trace!("no parent");
             let mut diff_hunk = DiffPart::default();
             diff_hunk.new.line_numbers = 1..usize::MAX;
-            self.diff_parts.push(diff_hunk);
+            self.diff_parts = vec![diff_hunk].into();
             return Ok(());
-        }
-        let parent = parent.unwrap();
+        };
+        let parent = git.repository().find_commit(parent_commit_id)?;
 
         let tree = commit.tree()?;
         let parent_tree = parent.tree()?;
@@ -259,20 +605,23 @@ impl FileCommit {
         if check_rename {
             let mut diff_find_options = git2::DiffFindOptions::new();
             diff_find_options.renames(true);
+            if let Some(percent) = options.rename_threshold() {
+                diff_find_options.rename_threshold(percent);
+            }
             diff.find_similar(Some(&mut diff_find_options))?;
             trace!("find_similar: elapsed {:?}", start_time.elapsed());
         }
 
         let path = self.path.as_path();
         let mut old_path: Option<PathBuf> = None;
-        let mut context = DiffReadContext::default();
+        let mut context = DiffReadContext::new(options);
         let foreach_result = diff.foreach(
             &mut |delta, _| {
                 trace!("read_by_git2.file: {delta:?}");
                 if !DiffReadContext::is_path(&delta, path) {
                     return true;
                 }
-                if !check_rename && delta.status() == git2::Delta::Added {
+                if !check_rename && options.follow() && delta.status() == git2::Delta::Added {
                     // If we don't call `find_similar` and the target file is a
                     // new file, it's possible that this is a rename. Get all
                     // files and call `find_similar` to detect renames.
@@ -312,6 +661,7 @@ impl FileCommit {
                     line.num_lines(),
                     String::from_utf8(line.content().to_vec())
                 );
+                context.on_line_content(line.origin(), line.old_lineno(), line.new_lineno(), line.content());
                 context.on_line_callback(line.origin(), line.old_lineno(), line.new_lineno(), line.num_lines());
                 true
             }),
@@ -319,7 +669,7 @@ impl FileCommit {
         if let Err(error) = foreach_result {
             if error.code() == git2::ErrorCode::User && !check_rename {
                 trace!("read_by_git2: foreach aborted");
-                return self.read_by_git2_rename(git, true);
+                return self.read_by_git2_rename(git, options, true);
             }
             trace!("read_by_git2: foreach failed: {error:?}");
             anyhow::bail!(error);
@@ -331,7 +681,8 @@ impl FileCommit {
         {
             self.old_path = Some(old_path);
         }
-        self.diff_parts = context.parts;
+        self.moved_parts = context.moved_parts();
+        self.diff_parts = context.parts.into();
         debug!("read_by_git2.done: elapsed {:?}", start_time.elapsed());
         trace!("read_by_git2.result={self:#?}");
         Ok(())
@@ -340,9 +691,48 @@ impl FileCommit {
     fn set_commit(&mut self, commit: &git2::Commit) -> Result<(), git2::Error> {
         self.time = commit.time();
         self.summary = commit.summary()?.map(|s| s.to_string());
-        self.author_email = commit.author().email()?.to_string();
+        let author = commit.author();
+        self.author_name = author.name()?.to_string();
+        self.author_email = author.email()?.to_string();
+        self.is_boundary = commit.parent_count() == 0;
+        Ok(())
+    }
+
+    fn set_parent(&mut self, git: &GitTools, commit: &git2::Commit) -> anyhow::Result<()> {
+        self.parent_commit_id = match commit.parent_count() {
+            0 => None,
+            1 => Some(commit.parent_id(0)?),
+            _ => Some(self.closest_parent_commit_id(git, commit)?),
+        };
         Ok(())
     }
+
+    /// Find the parent with the fewest changed lines of the file.
+    /// When there are ties, the earlier parent wins.
+    fn closest_parent_commit_id(
+        &self,
+        git: &GitTools,
+        commit: &git2::Commit,
+    ) -> anyhow::Result<git2::Oid> {
+        let tree = commit.tree()?;
+        let mut closest: Option<(usize, git2::Oid)> = None;
+        for parent in commit.parents() {
+            let mut diff_options = git2::DiffOptions::new();
+            diff_options.pathspec(self.path.clone());
+            let diff = git.repository().diff_tree_to_tree(
+                Some(&parent.tree()?),
+                Some(&tree),
+                Some(&mut diff_options),
+            )?;
+            let stats = diff.stats()?;
+            let changes = stats.insertions() + stats.deletions() + stats.files_changed();
+            trace!("closest_parent: {} changes={changes}", parent.id());
+            if closest.is_none_or(|(min_changes, _)| changes < min_changes) {
+                closest = Some((changes, parent.id()));
+            }
+        }
+        Ok(closest.unwrap().1)
+    }
 }
 
 #[derive(Debug, Default)]
@@ -351,9 +741,23 @@ struct DiffReadContext {
     new_line_number: usize,
     part: DiffPart,
     parts: Vec<DiffPart>,
+    move_detector: Option<MoveDetector>,
 }
 
 impl DiffReadContext {
+    fn new(options: &BlameOptions) -> Self {
+        Self {
+            move_detector: options.detect_moves().then(MoveDetector::default),
+            ..Default::default()
+        }
+    }
+
+    fn moved_parts(&self) -> Vec<DiffPart> {
+        self.move_detector
+            .as_ref()
+            .map_or_else(Vec::new, |move_detector| move_detector.moved_parts())
+    }
+
     fn is_path(delta: &git2::DiffDelta, target_path: &Path) -> bool {
         let new_path = delta.new_file().path();
         if new_path.is_none() || new_path.unwrap() != target_path {
@@ -400,6 +804,27 @@ impl DiffReadContext {
         }
     }
 
+    fn on_line_content(
+        &mut self,
+        origin: char,
+        old_line_number: Option<u32>,
+        new_line_number: Option<u32>,
+        content: &[u8],
+    ) {
+        let Some(move_detector) = self.move_detector.as_mut() else {
+            return;
+        };
+        match (origin, old_line_number, new_line_number) {
+            ('+', _, Some(new_line_number)) => {
+                move_detector.add_new_line(new_line_number as usize, content)
+            }
+            ('-', Some(old_line_number), _) => {
+                move_detector.add_old_line(old_line_number as usize, content)
+            }
+            _ => {}
+        }
+    }
+
     fn on_git_hunk(&mut self, old_line_number: usize, new_line_number: usize) {
         trace!("on_git_hunk: {old_line_number},{new_line_number}");
         self.old_line_number = old_line_number;
@@ -413,6 +838,13 @@ impl DiffReadContext {
             "on_git_line: {origin} {},{}",
             self.old_line_number, self.new_line_number
         );
+        if let Some(move_detector) = self.move_detector.as_mut() {
+            match origin {
+                '+' => move_detector.add_new_line(self.new_line_number, &line.as_bytes()[1..]),
+                '-' => move_detector.add_old_line(self.old_line_number, &line.as_bytes()[1..]),
+                _ => {}
+            }
+        }
         match origin {
             ' ' => {
                 self.flush_part();
@@ -467,6 +899,11 @@ mod tests {
         file_commit.read(&git.git)?;
         assert_eq!(file_commit.diff_parts, [DiffPart::from_ranges(3..4, 3..6)]);
         assert_eq!(file_commit.old_path_if_rename(), None);
+        assert!(!file_commit.is_boundary());
+
+        let mut file_commit = FileCommit::new(commit_id1, path);
+        file_commit.read(&git.git)?;
+        assert!(file_commit.is_boundary());
         Ok(())
     }
 
@@ -494,11 +931,11 @@ mod tests {
         let mut file_commit = FileCommit::new(commit_id3, path);
         file_commit.read(&git.git)?;
         match FileCommit::git_engine() {
-            GitEngine::Git => {
+            GitEngine::Git | GitEngine::GitBlame => {
                 assert_eq!(file_commit.diff_parts, [DiffPart::from_ranges(5..6, 5..6)])
             }
             // `git2` can't report changes in the newline at end.
-            GitEngine::Git2 => assert_eq!(file_commit.diff_parts, []),
+            GitEngine::Git2 | GitEngine::Git2Blame => assert_eq!(file_commit.diff_parts, []),
         }
         assert_eq!(file_commit.old_path_if_rename(), None);
         Ok(())
@@ -519,6 +956,123 @@ mod tests {
         file_commit.read(&git.git)?;
         assert_eq!(file_commit.old_path_if_rename(), Some(old_path));
         assert!(file_commit.diff_parts.is_empty());
+
+        let mut options = BlameOptions::new();
+        options.set_follow(false);
+        let mut file_commit = FileCommit::new(commit_id2, new_path);
+        file_commit.read_with_options(&git.git, &options)?;
+        assert_eq!(file_commit.old_path_if_rename(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn git_rename_threshold() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let old_path = Path::new("old.txt");
+        let new_path = Path::new("new.txt");
+        git.add_file_content(old_path, "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+
+        // Rename with 2 lines out of 10 changed.
+        git.rename_file(old_path, new_path)?;
+        git.add_file_content(new_path, "1\n2\n3\n4\n5\n6\n7\n8\nX\nY\n")?;
+        let commit_id2 = git.commit(commit_id1, "Rename and change file")?;
+
+        let mut options = BlameOptions::new();
+        options.set_rename_threshold(Some(50));
+        let mut file_commit = FileCommit::new(commit_id2, new_path);
+        file_commit.read_with_options(&git.git, &options)?;
+        assert_eq!(file_commit.old_path_if_rename(), Some(old_path));
+        assert_eq!(
+            file_commit.diff_parts,
+            [DiffPart::from_ranges(9..11, 9..11)]
+        );
+
+        options.set_rename_threshold(Some(95));
+        let mut file_commit = FileCommit::new(commit_id2, new_path);
+        file_commit.read_with_options(&git.git, &options)?;
+        assert_eq!(file_commit.old_path_if_rename(), None);
+        assert_eq!(file_commit.diff_parts, [DiffPart::from_ranges(0..0, 1..11)]);
+        let commit = git.repository().find_commit(commit_id2)?;
+        let command = file_commit.create_diff_command(&git.git, &commit, &options, &[new_path]);
+        let args: Vec<_> = command.get_args().collect();
+        let position = |arg: &str| args.iter().position(|a| *a == arg).unwrap();
+        assert!(position("-M95%") < position("--"));
+        Ok(())
+    }
+
+    #[test]
+    fn git_rename_unusual_paths() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let old_path = Path::new("x b/old name.txt");
+        let new_path = Path::new("dir/\u{e9} \"new\".txt");
+        git.add_file_content(old_path, "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+
+        git.rename_file(old_path, new_path)?;
+        git.add_file_content(new_path, "1\n2\n3\n4\n5\nX\n7\n8\n9\n10\n")?;
+        let commit_id2 = git.commit(commit_id1, "Rename and change file")?;
+
+        let options = BlameOptions::default();
+        for engine in [GitEngine::Git, GitEngine::Git2] {
+            let mut file_commit = FileCommit::new(commit_id2, new_path);
+            file_commit.read_with_engine(&git.git, &options, engine)?;
+            assert_eq!(
+                file_commit.old_path_if_rename(),
+                Some(old_path),
+                "{engine:?}"
+            );
+            assert_eq!(
+                file_commit.diff_parts,
+                [DiffPart::from_ranges(6..7, 6..7)],
+                "{engine:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn git_ignore_cr_at_eol() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n3\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\r\n2\r\nX\r\n")?;
+        let commit_id2 = git.commit(commit_id1, "Change to CRLF")?;
+
+        let mut options = BlameOptions::default();
+        let mut file_commit = FileCommit::new(commit_id2, path);
+        file_commit.read_with_engine(&git.git, &options, GitEngine::Git)?;
+        assert_eq!(file_commit.diff_parts, [DiffPart::from_ranges(1..4, 1..4)]);
+
+        options.set_ignore_cr_at_eol(true);
+        let mut file_commit = FileCommit::new(commit_id2, path);
+        file_commit.read_with_engine(&git.git, &options, GitEngine::Git)?;
+        assert_eq!(file_commit.diff_parts, [DiffPart::from_ranges(3..4, 3..4)]);
+        Ok(())
+    }
+
+    #[test]
+    fn octopus_merge() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n3\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\nB\n3\n")?;
+        let commit_id2 = git.commit_detached(&[commit_id1], "Change 2")?;
+        git.add_file_content(path, "A\nB\n3\n")?;
+        let commit_id3 = git.commit_detached(&[commit_id1], "Change 1 and 2")?;
+        git.add_file_content(path, "A\n2\n3\n")?;
+        let commit_id4 = git.commit(commit_id1, "Change 1")?;
+
+        git.add_file_content(path, "A\nB\nC\n")?;
+        let commit_id5 = git.commit_merge(&[commit_id4, commit_id2, commit_id3], "Merge")?;
+
+        let mut file_commit = FileCommit::new(commit_id5, path);
+        file_commit.read(&git.git)?;
+        assert_eq!(file_commit.parent_commit_id(), Some(commit_id3));
+        assert_eq!(file_commit.diff_parts, [DiffPart::from_ranges(3..4, 3..4)]);
+        assert!(!file_commit.is_boundary());
         Ok(())
     }
 
@@ -563,4 +1117,50 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn old_line_number() {
+        let mut commit = FileCommit::new(git2::Oid::ZERO_SHA1, Path::new(""));
+        // Line 2 is changed, and lines 5-6 are added.
+        let parts = vec![
+            DiffPart::from_ranges(2..3, 2..3),
+            DiffPart::from_ranges(4..4, 5..7),
+        ];
+        commit.moved_parts = vec![DiffPart::from_ranges(9..10, 6..7)];
+        let map = LineNumberMap::new_old_from_new(&parts);
+        let old_line_numbers = |commit: &FileCommit| -> Vec<Option<usize>> {
+            (1..=8)
+                .map(|line_number| commit.old_line_number(line_number, &parts, &map))
+                .collect()
+        };
+        assert_eq!(
+            old_line_numbers(&commit),
+            [
+                Some(1),
+                None,
+                Some(3),
+                Some(4),
+                None,
+                Some(9),
+                Some(5),
+                Some(6)
+            ]
+        );
+
+        // The changed line is mapped to the old line, but the added line isn't.
+        commit.set_ignored(true);
+        assert_eq!(
+            old_line_numbers(&commit),
+            [
+                Some(1),
+                Some(2),
+                Some(3),
+                Some(4),
+                None,
+                Some(9),
+                Some(5),
+                Some(6)
+            ]
+        );
+    }
 }
@@ -0,0 +1,94 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+use super::{BlameError, FileCommit, FileHistory};
+
+/// An async `Stream` of the commits read by `FileHistory::read_stream()`.
+///
+/// Each commit is also added to the `FileHistory` before it's returned,
+/// so that `FileHistory::content()` is available when the stream completes.
+/// The stream doesn't depend on a specific async runtime.
+///
+/// Dropping the stream before it completes makes the reading fail;
+/// use `FileHistory::read_cancel()` to stop reading.
+pub struct FileCommitStream<'a> {
+    history: &'a mut FileHistory,
+    rx: mpsc::UnboundedReceiver<FileCommit>,
+}
+
+impl<'a> FileCommitStream<'a> {
+    pub(super) fn new(
+        history: &'a mut FileHistory,
+        rx: mpsc::UnboundedReceiver<FileCommit>,
+    ) -> Self {
+        Self { history, rx }
+    }
+}
+
+impl Stream for FileCommitStream<'_> {
+    type Item = Result<FileCommit, BlameError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match this.rx.poll_recv(cx) {
+            Poll::Ready(Some(commit)) => {
+                let commit = this.history.push_commit(commit);
+                Poll::Ready(Some(Ok(commit.clone())))
+            }
+            // The reading thread has completed when the channel is closed.
+            Poll::Ready(None) => match this
+                .history
+                .read_join()
+                .and_then(|()| Ok(this.history.read_done()?))
+            {
+                Ok(_) => Poll::Ready(None),
+                Err(error) => Poll::Ready(Some(Err(error))),
+            },
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{future, path::Path};
+
+    use crate::extensions::tests::TempRepository;
+
+    use super::*;
+
+    #[test]
+    fn read_stream() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2\n3\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add 3")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+        let commit_ids = runtime.block_on(async {
+            let mut stream = history.read_stream()?;
+            let mut commit_ids = Vec::new();
+            while let Some(commit) = future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await
+            {
+                let commit = commit?;
+                assert_eq!(commit.index(), commit_ids.len());
+                commit_ids.push(commit.commit_id());
+            }
+            anyhow::Ok(commit_ids)
+        })?;
+        assert_eq!(commit_ids, [commit_id2, commit_id1]);
+        assert!(!history.is_reading());
+        assert_eq!(history.commits().len(), 2);
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        assert_eq!(content.lines()[2].commit_id(), Some(commit_id2));
+        Ok(())
+    }
+}
@@ -1,17 +1,32 @@
 use std::collections::HashMap;
-use std::ops::{Deref, Index};
-use std::slice::{self, SliceIndex};
+use std::ops::{Bound, Index, RangeBounds};
+use std::slice;
+use std::sync::Arc;
 
-use super::FileCommit;
+use super::{BlameError, FileCommit};
 
 /// A collection of `FileCommit` objects, providing efficient lookup by OID.
-#[derive(Debug, Default)]
+///
+/// The commits are stored in chunks shared by `Arc`, so that cloning is cheap,
+/// and pushing to a clone copies only the last chunk, not all the commits.
+/// This allows sharing the commits read so far with other threads while the
+/// reading continues.
+#[derive(Clone, Debug, Default)]
 pub struct FileCommits {
+    /// All chunks except the last one have `CHUNK_LEN` commits.
+    chunks: Vec<Arc<FileCommitsChunk>>,
+    len: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+struct FileCommitsChunk {
     items: Vec<FileCommit>,
     index_map: HashMap<git2::Oid, usize>,
 }
 
 impl FileCommits {
+    const CHUNK_LEN: usize = 1024;
+
     /// Creates a new, empty `FileCommits` collection.
     pub fn new() -> Self {
         Self::default()
@@ -21,101 +36,307 @@ impl FileCommits {
     ///
     /// The `FileCommit`'s internal index will be updated to its position in this collection.
     pub fn push(&mut self, mut commit: FileCommit) {
-        let index = self.items.len();
+        let index = self.len;
         commit.set_index(index); // Update the commit's own index
-        self.index_map.insert(commit.commit_id(), index);
-        self.items.push(commit);
+        if index.is_multiple_of(Self::CHUNK_LEN) {
+            self.chunks.push(Arc::new(FileCommitsChunk {
+                items: Vec::with_capacity(Self::CHUNK_LEN),
+                index_map: HashMap::new(),
+            }));
+        }
+        // Copies the last chunk if it's shared.
+        let chunk = Arc::make_mut(self.chunks.last_mut().unwrap());
+        chunk.index_map.insert(commit.commit_id(), index);
+        chunk.items.push(commit);
+        self.len += 1;
     }
 
     /// Returns the number of commits in the collection.
     pub fn len(&self) -> usize {
-        self.items.len()
+        self.len
     }
 
     /// Returns `true` if the collection contains no commits.
     pub fn is_empty(&self) -> bool {
-        self.items.is_empty()
+        self.len == 0
     }
 
     /// Returns a reference to the `FileCommit` at the given index, or `None` if the index is out of bounds.
     pub fn get(&self, index: usize) -> Option<&FileCommit> {
-        self.items.get(index)
+        self.chunks
+            .get(index / Self::CHUNK_LEN)?
+            .items
+            .get(index % Self::CHUNK_LEN)
+    }
+
+    /// Returns a mutable reference to the `FileCommit` at the given index.
+    /// The chunk containing it is copied if it's shared.
+    pub(super) fn get_mut(&mut self, index: usize) -> Option<&mut FileCommit> {
+        let chunk = self.chunks.get_mut(index / Self::CHUNK_LEN)?;
+        Arc::make_mut(chunk).items.get_mut(index % Self::CHUNK_LEN)
     }
 
     /// Returns the index of the `FileCommit` with the given `Oid`, or `None` if not found.
     fn index_from_commit_id_opt(&self, commit_id: git2::Oid) -> Option<usize> {
         if commit_id.is_zero() {
-            if self.items.is_empty() {
+            if self.is_empty() {
                 return Some(0);
             }
             return None;
         }
-        self.index_map.get(&commit_id).copied()
+        self.chunks
+            .iter()
+            .rev()
+            .find_map(|chunk| chunk.index_map.get(&commit_id).copied())
     }
 
-    pub fn index_from_commit_id(&self, commit_id: git2::Oid) -> anyhow::Result<usize> {
+    /// Returns `true` if the collection contains the commit of the `commit_id`.
+    pub fn contains(&self, commit_id: git2::Oid) -> bool {
+        self.chunks
+            .iter()
+            .any(|chunk| chunk.index_map.contains_key(&commit_id))
+    }
+
+    pub fn index_from_commit_id(&self, commit_id: git2::Oid) -> Result<usize, BlameError> {
         self.index_from_commit_id_opt(commit_id)
-            .ok_or_else(|| anyhow::anyhow!("Commit {commit_id:?} not found"))
+            .ok_or(BlameError::CommitNotFound(commit_id))
     }
 
     /// Returns a reference to the `FileCommit` with the given `Oid`, or `None` if not found.
     fn get_by_commit_id_opt(&self, commit_id: git2::Oid) -> Option<&FileCommit> {
         self.index_from_commit_id_opt(commit_id)
-            .and_then(|index| self.items.get(index))
+            .and_then(|index| self.get(index))
     }
 
-    pub fn get_by_commit_id(&self, commit_id: git2::Oid) -> anyhow::Result<&FileCommit> {
+    pub fn get_by_commit_id(&self, commit_id: git2::Oid) -> Result<&FileCommit, BlameError> {
         self.get_by_commit_id_opt(commit_id)
-            .ok_or_else(|| anyhow::anyhow!("Commit {commit_id:?} not found"))
+            .ok_or(BlameError::CommitNotFound(commit_id))
+    }
+
+    pub(super) fn get_mut_by_commit_id(
+        &mut self,
+        commit_id: git2::Oid,
+    ) -> anyhow::Result<&mut FileCommit> {
+        let index = self.index_from_commit_id(commit_id)?;
+        Ok(self.get_mut(index).unwrap())
+    }
+
+    /// Returns the oldest and the newest commit times in seconds,
+    /// or `None` if it's empty.
+    pub fn time_range(&self) -> Option<(i64, i64)> {
+        let times = self.iter().map(|commit| commit.time().seconds());
+        let oldest = times.clone().min()?;
+        let newest = times.max()?;
+        Some((oldest, newest))
     }
 
     /// Returns an iterator over the commits in the collection.
-    pub fn iter(&self) -> slice::Iter<'_, FileCommit> {
-        self.items.iter()
+    pub fn iter(&self) -> FileCommitsIter<'_> {
+        self.range(..)
+    }
+
+    /// Returns an iterator over the commits in the `range` of indexes.
+    ///
+    /// # Panics
+    /// Panics if the range is out of bounds, consistent with slice indexing.
+    pub fn range(&self, range: impl RangeBounds<usize>) -> FileCommitsIter<'_> {
+        let start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) => end + 1,
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => self.len,
+        };
+        assert!(
+            start <= end && end <= self.len,
+            "range {start}..{end} out of range for {} commits",
+            self.len
+        );
+        if start == end {
+            return FileCommitsIter::default();
+        }
+        let (first, last) = (start / Self::CHUNK_LEN, (end - 1) / Self::CHUNK_LEN);
+        let (start, end) = (start % Self::CHUNK_LEN, (end - 1) % Self::CHUNK_LEN + 1);
+        if first == last {
+            return FileCommitsIter {
+                front: self.chunks[first].items[start..end].iter(),
+                ..FileCommitsIter::default()
+            };
+        }
+        FileCommitsIter {
+            front: self.chunks[first].items[start..].iter(),
+            chunks: self.chunks[first + 1..last].iter(),
+            back: self.chunks[last].items[..end].iter(),
+        }
     }
 
     /// Returns a reference to the first `FileCommit` in the collection, or `None` if it's empty.
     pub fn first(&self) -> Option<&FileCommit> {
-        self.items.first()
+        self.get(0)
     }
 
-    /// Returns a slice containing all commits.
-    pub fn as_slice(&self) -> &[FileCommit] {
-        self.items.as_slice()
+    /// Returns a reference to the last `FileCommit` in the collection, or `None` if it's empty.
+    pub fn last(&self) -> Option<&FileCommit> {
+        self.get(self.len.checked_sub(1)?)
     }
-}
-
-/// Allows `&FileCommits` to be automatically dereferenced to `&[FileCommit]`.
-impl Deref for FileCommits {
-    type Target = [FileCommit];
 
-    fn deref(&self) -> &Self::Target {
-        &self.items
+    /// Returns a mutable reference to the last `FileCommit` in the collection, or `None` if it's empty.
+    pub(super) fn last_mut(&mut self) -> Option<&mut FileCommit> {
+        self.get_mut(self.len.checked_sub(1)?)
     }
 }
 
-/// Allows indexing `FileCommits` by types that implement `SliceIndex`
-/// (e.g., `usize`, `Range<usize>`, `RangeFrom<usize>`, etc.).
-/// This provides direct access to the underlying `Vec<FileCommit>`'s indexing capabilities,
-/// allowing for retrieval of single `FileCommit` references or slices (`&[FileCommit]`).
+/// Allows indexing `FileCommits` by `usize`.
 ///
 /// # Panics
 /// Panics if the index is out of bounds, consistent with slice indexing.
-impl<I: SliceIndex<[FileCommit]>> Index<I> for FileCommits {
-    type Output = I::Output;
+impl Index<usize> for FileCommits {
+    type Output = FileCommit;
 
     #[inline]
-    fn index(&self, index: I) -> &Self::Output {
-        &self.items[index]
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.chunks[index / Self::CHUNK_LEN].items[index % Self::CHUNK_LEN]
     }
 }
 
 /// Allows iterating over `&FileCommits` to get `&FileCommit`.
 impl<'a> IntoIterator for &'a FileCommits {
     type Item = &'a FileCommit;
-    type IntoIter = slice::Iter<'a, FileCommit>;
+    type IntoIter = FileCommitsIter<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.items.iter()
+        self.iter()
+    }
+}
+
+/// An iterator over the commits of `FileCommits`.
+/// See `FileCommits::iter()` and `FileCommits::range()`.
+#[derive(Clone, Debug, Default)]
+pub struct FileCommitsIter<'a> {
+    front: slice::Iter<'a, FileCommit>,
+    chunks: slice::Iter<'a, Arc<FileCommitsChunk>>,
+    back: slice::Iter<'a, FileCommit>,
+}
+
+impl<'a> Iterator for FileCommitsIter<'a> {
+    type Item = &'a FileCommit;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(commit) = self.front.next() {
+                return Some(commit);
+            }
+            match self.chunks.next() {
+                Some(chunk) => self.front = chunk.items.iter(),
+                None => return self.back.next(),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+
+impl DoubleEndedIterator for FileCommitsIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(commit) = self.back.next_back() {
+                return Some(commit);
+            }
+            match self.chunks.next_back() {
+                Some(chunk) => self.back = chunk.items.iter(),
+                None => return self.front.next_back(),
+            }
+        }
+    }
+}
+
+impl ExactSizeIterator for FileCommitsIter<'_> {
+    fn len(&self) -> usize {
+        self.front.len()
+            + self
+                .chunks
+                .as_slice()
+                .iter()
+                .map(|chunk| chunk.items.len())
+                .sum::<usize>()
+            + self.back.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn commit_id(i: usize) -> git2::Oid {
+        git2::Oid::from_str(&format!("{:040x}", i + 1)).unwrap()
+    }
+
+    fn new_commits(len: usize) -> FileCommits {
+        let mut commits = FileCommits::new();
+        for i in 0..len {
+            commits.push(FileCommit::new(commit_id(i), Path::new("test.txt")));
+        }
+        commits
+    }
+
+    #[test]
+    fn push_to_shared() {
+        let len = FileCommits::CHUNK_LEN * 2 + 10;
+        let mut commits = new_commits(len);
+        let shared = commits.clone();
+        commits.push(FileCommit::new(commit_id(len), Path::new("test.txt")));
+        assert_eq!(commits.len(), len + 1);
+        assert_eq!(shared.len(), len);
+        // Only the last chunk is copied.
+        assert!(Arc::ptr_eq(&commits.chunks[0], &shared.chunks[0]));
+        assert!(Arc::ptr_eq(&commits.chunks[1], &shared.chunks[1]));
+        assert!(!Arc::ptr_eq(&commits.chunks[2], &shared.chunks[2]));
+        assert!(shared.get(len).is_none());
+        assert_eq!(commits[len].commit_id(), commit_id(len));
+        assert_eq!(commits.index_from_commit_id(commit_id(len)).unwrap(), len);
+        assert!(!shared.contains(commit_id(len)));
+
+        commits.get_mut(0).unwrap().set_boundary();
+        assert!(commits[0].is_boundary());
+        assert!(!shared[0].is_boundary());
+        assert!(!Arc::ptr_eq(&commits.chunks[0], &shared.chunks[0]));
+        assert!(Arc::ptr_eq(&commits.chunks[1], &shared.chunks[1]));
+    }
+
+    #[test]
+    fn iter() {
+        let len = FileCommits::CHUNK_LEN * 2 + 10;
+        let commits = new_commits(len);
+        let indexes: Vec<_> = commits.iter().map(FileCommit::index).collect();
+        assert_eq!(indexes, (0..len).collect::<Vec<_>>());
+        let indexes: Vec<_> = commits.iter().rev().map(FileCommit::index).collect();
+        assert_eq!(indexes, (0..len).rev().collect::<Vec<_>>());
+        assert_eq!(commits.iter().len(), len);
+        assert_eq!(commits.last().unwrap().index(), len - 1);
+
+        let chunk_len = FileCommits::CHUNK_LEN;
+        for range in [
+            0..0,
+            3..3,
+            3..10,
+            0..chunk_len,
+            chunk_len - 1..chunk_len + 1,
+            5..len - 5,
+            chunk_len..len,
+        ] {
+            let indexes: Vec<_> = commits
+                .range(range.clone())
+                .map(FileCommit::index)
+                .collect();
+            assert_eq!(indexes, range.clone().collect::<Vec<_>>(), "{range:?}");
+            assert_eq!(commits.range(range.clone()).len(), range.len(), "{range:?}");
+        }
     }
 }
@@ -1,14 +1,20 @@
 use std::{
     cmp,
+    collections::{HashMap, HashSet},
     ops::Range,
     path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    thread,
 };
 
 use log::*;
 
 use crate::extensions::GitTools;
 
-use super::{BlameError, DiffPart, FileCommit, FileCommits, FileHistory, Line, LineNumberMap};
+use super::{
+    BlameError, DiffPart, DiffRange, FileCommit, FileCommits, FileHistory, Line, LineFilter,
+    LineNumberMap, LogGraph, SearchPattern, SearchTarget,
+};
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum ContentType {
@@ -16,13 +22,136 @@ pub enum ContentType {
     Log,
 }
 
+/// The lines changed by `FileContent::update_commits()`, so that renderers
+/// can repaint only them.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LineDamage {
+    /// The indexes of the lines whose attributions changed, in no particular
+    /// order, possibly duplicated.
+    Lines(Vec<usize>),
+    /// Lines were inserted or removed, so all lines may have changed.
+    All,
+}
+
+impl Default for LineDamage {
+    fn default() -> Self {
+        Self::Lines(vec![])
+    }
+}
+
+impl LineDamage {
+    fn add(&mut self, line_index: usize) {
+        if let Self::Lines(line_indexes) = self {
+            line_indexes.push(line_index);
+        }
+    }
+}
+
+/// The line endings of a file.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum LineEnding {
+    /// `\n`, or no line endings.
+    #[default]
+    Lf,
+    /// `\r\n`.
+    CrLf,
+    /// Both `\n` and `\r\n`.
+    Mixed,
+}
+
+impl LineEnding {
+    fn from_counts(lf: usize, crlf: usize) -> Self {
+        match (lf, crlf) {
+            (_, 0) => Self::Lf,
+            (0, _) => Self::CrLf,
+            _ => Self::Mixed,
+        }
+    }
+}
+
+/// The commit IDs, the original line numbers, and the line numbers by
+/// `HistoryProvider::blame()`.
+type BlameResult = anyhow::Result<Vec<(git2::Oid, usize, usize)>>;
+
+#[derive(Clone)]
 pub struct FileContent {
     content_type: ContentType,
     commit_id: git2::Oid,
     path: PathBuf,
-    lines: Vec<Line>,
+    /// The lines, shared with the clones and `BlameSnapshot`s until either is
+    /// modified.
+    lines: Arc<Vec<Line>>,
+    /// The sorted line indexes of the first lines of the hunks.
+    /// A hunk is a run of lines of the same commit, or a run of lines not
+    /// attributed to any commits yet.
+    hunk_starts: Vec<usize>,
+    line_ending: LineEnding,
     current_line_index: usize,
     applied_commits_len: usize,
+    /// The line number maps composed from the commits applied so far.
+    applied_maps: AppliedLineNumberMaps,
+    /// The commit that failed to apply, if any. The lines not attributed by
+    /// then are attributed by `git blame` instead.
+    apply_failed_commit_id: Option<git2::Oid>,
+    /// The commit IDs and the original line numbers by `git blame`,
+    /// keyed by the line numbers.
+    git_blame_commits: HashMap<usize, (git2::Oid, usize)>,
+    /// Whether the `git_blame_commits` has only the visible lines.
+    is_git_blame_partial: bool,
+    /// The results of blaming the rest of the lines in a thread, set when the
+    /// thread completes.
+    git_blame_rest: Option<Arc<OnceLock<BlameResult>>>,
+    /// The line indexes visible to the user, to attribute first.
+    visible_line_range: Option<Range<usize>>,
+    /// The moved lines not attributed yet. Each item has the line number, and
+    /// the line number before the move, in the next commit to apply.
+    moved_lines: Vec<(usize, usize)>,
+    /// The lines changed since the last `take_line_damage()`.
+    line_damage: LineDamage,
+    /// The graph of the `ContentType::Log`, created on the first update.
+    log_graph: Option<LogGraph>,
+    /// The filter of the `ContentType::Log`. Commits not matching it are hidden.
+    log_filter: LineFilter,
+}
+
+/// The `LineNumberMap`s between the line numbers of `FileContent::lines`
+/// and the line numbers after the `len`-th commit to apply, composed from
+/// the newer commits, so that applying a commit needs only one map instead
+/// of one per newer commit.
+#[derive(Clone, Debug, Default)]
+struct AppliedLineNumberMaps {
+    /// The number of the commits composed.
+    len: usize,
+    /// Maps the line numbers after the `len`-th commit to the line numbers of
+    /// the lines.
+    to_lines: LineNumberMap,
+    /// Maps the line numbers of the lines to the line numbers after the
+    /// `len`-th commit.
+    from_lines: LineNumberMap,
+}
+
+impl AppliedLineNumberMaps {
+    /// Compose the `commits` from the `first_index` up to the `end`, so that
+    /// the maps are to the line numbers after `commits[first_index + end]`.
+    ///
+    /// The commit at the `end` isn't composed until a newer commit is
+    /// applied, because the root commit may add lines to `usize::MAX`.
+    fn compose_until(
+        &mut self,
+        commits: &FileCommits,
+        first_index: usize,
+        end: usize,
+    ) -> anyhow::Result<()> {
+        for commit in commits.range(first_index + self.len..first_index + end) {
+            let parts = commit.diff_parts()?;
+            self.to_lines = LineNumberMap::new_new_from_old(&parts).compose(&self.to_lines);
+            self.from_lines = self
+                .from_lines
+                .compose(&LineNumberMap::new_old_from_new(&parts));
+        }
+        self.len = cmp::max(self.len, end);
+        Ok(())
+    }
 }
 
 impl FileContent {
@@ -31,9 +160,21 @@ impl FileContent {
             content_type: ContentType::File,
             commit_id,
             path: path.to_path_buf(),
-            lines: vec![],
+            lines: Arc::default(),
+            hunk_starts: vec![],
+            line_ending: LineEnding::Lf,
             current_line_index: 0,
             applied_commits_len: 0,
+            applied_maps: AppliedLineNumberMaps::default(),
+            apply_failed_commit_id: None,
+            git_blame_commits: HashMap::new(),
+            is_git_blame_partial: false,
+            git_blame_rest: None,
+            visible_line_range: None,
+            moved_lines: vec![],
+            line_damage: LineDamage::All,
+            log_graph: None,
+            log_filter: LineFilter::default(),
         }
     }
 
@@ -42,9 +183,21 @@ impl FileContent {
             content_type: ContentType::Log,
             commit_id,
             path: path.to_path_buf(),
-            lines: vec![],
+            lines: Arc::default(),
+            hunk_starts: vec![],
+            line_ending: LineEnding::Lf,
             current_line_index: 0,
             applied_commits_len: 0,
+            applied_maps: AppliedLineNumberMaps::default(),
+            apply_failed_commit_id: None,
+            git_blame_commits: HashMap::new(),
+            is_git_blame_partial: false,
+            git_blame_rest: None,
+            visible_line_range: None,
+            moved_lines: vec![],
+            line_damage: LineDamage::All,
+            log_graph: None,
+            log_filter: LineFilter::default(),
         }
     }
 
@@ -65,14 +218,83 @@ impl FileContent {
         &self.path
     }
 
+    /// The line endings of the file. They're not in the `Line::content()`.
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
     pub fn lines(&self) -> &Vec<Line> {
         &self.lines
     }
 
+    /// The `lines()`, shared until either is modified.
+    pub(super) fn shared_lines(&self) -> Arc<Vec<Line>> {
+        self.lines.clone()
+    }
+
     pub fn lines_len(&self) -> usize {
         self.lines.len()
     }
 
+    /// Set the line indexes visible to the user, to attribute them first.
+    /// See `GitEngine::Git2Blame`.
+    pub fn set_visible_line_range(&mut self, line_range: Option<Range<usize>>) {
+        self.visible_line_range = line_range;
+    }
+
+    /// The line numbers of the `set_visible_line_range()`.
+    fn visible_line_numbers(&self) -> Option<Range<usize>> {
+        let line_range = self.visible_line_range.as_ref()?;
+        let start = self.lines.get(line_range.start)?.line_number();
+        let end = self
+            .lines
+            .get(line_range.end.checked_sub(1)?)?
+            .line_number();
+        Some(start..end + 1)
+    }
+
+    /// True if only the visible lines are attributed by
+    /// `HistoryProvider::blame()`. Call `update_commits()` again to attribute
+    /// the rest.
+    pub fn is_blame_partial(&self) -> bool {
+        self.is_git_blame_partial
+    }
+
+    /// The commit IDs blamed on the lines in the `line_range` by the blame
+    /// engines, that the lines aren't attributed to yet because the commits
+    /// haven't been read. See `FileHistory::set_priority_commit_ids()`.
+    pub fn unread_blame_commit_ids(&self, line_range: Range<usize>) -> HashSet<git2::Oid> {
+        let end = cmp::min(line_range.end, self.lines.len());
+        let start = cmp::min(line_range.start, end);
+        self.lines[start..end]
+            .iter()
+            .filter(|line| !line.is_deleted() && line.commit_id().is_none())
+            .filter_map(|line| self.git_blame_commits.get(&line.line_number()))
+            .map(|(commit_id, _)| *commit_id)
+            .collect()
+    }
+
+    /// The line numbers from the first to the last lines in the `line_range`
+    /// not attributed yet, or `None` if all lines are attributed.
+    /// See `FileHistory::set_priority_lines()`.
+    pub fn unattributed_line_numbers(&self, line_range: Range<usize>) -> Option<Range<usize>> {
+        let end = cmp::min(line_range.end, self.lines.len());
+        let start = cmp::min(line_range.start, end);
+        let mut line_numbers = self.lines[start..end]
+            .iter()
+            .filter(|line| !line.is_deleted() && line.commit_id().is_none())
+            .map(|line| line.line_number());
+        let first = line_numbers.next()?;
+        let last = line_numbers.next_back().unwrap_or(first);
+        Some(first..last + 1)
+    }
+
+    /// The lines changed since the last call, and reset it.
+    /// All lines are damaged initially.
+    pub fn take_line_damage(&mut self) -> LineDamage {
+        std::mem::take(&mut self.line_damage)
+    }
+
     pub fn saturate_line_index(&self, line_index: usize) -> usize {
         cmp::min(line_index, self.lines_len().saturating_sub(1))
     }
@@ -101,32 +323,30 @@ impl FileContent {
         ))
     }
 
-    pub fn line_index_from_number(&self, line_number: usize) -> anyhow::Result<usize> {
+    /// The index of the first line of the `line_number`. Deleted lines have
+    /// the same line numbers as the lines after them.
+    pub fn line_index_from_number(&self, line_number: usize) -> Result<usize, BlameError> {
         let first_line_number = self.first_line_number()?;
         if line_number < first_line_number || line_number > self.last_line_number()? {
-            anyhow::bail!("Not a valid line number: {line_number}");
-        }
-        let mut start_index = line_number - first_line_number;
-        let start_line = &self.lines[start_index];
-        if start_line.line_number() == line_number {
-            return Ok(start_index);
-        }
-        assert!(line_number > start_line.line_number());
-        start_index += 1;
-        let lines = &self.lines[start_index..];
-        assert!(line_number >= lines.first().unwrap().line_number());
-        lines
-            .iter()
-            .position(|line| line.line_number() == line_number)
-            .map(|i| i + start_index)
-            .ok_or_else(|| anyhow::anyhow!("No line number: {line_number}"))
+            return Err(BlameError::InvalidLineNumber(line_number));
+        }
+        let line_index = self
+            .lines
+            .partition_point(|line| line.line_number() < line_number);
+        // Each line number has at least one line, so the line is not before
+        // the index it would be at without deleted lines.
+        debug_assert!(line_index >= line_number - first_line_number);
+        match self.lines.get(line_index) {
+            Some(line) if line.line_number() == line_number => Ok(line_index),
+            _ => Err(anyhow::anyhow!("No line number: {line_number}").into()),
+        }
     }
 
     fn line_index_from_number_end(&self, line_number: usize) -> anyhow::Result<usize> {
         if line_number == self.last_line_number()? + 1 {
             return Ok(self.lines.len());
         }
-        self.line_index_from_number(line_number)
+        Ok(self.line_index_from_number(line_number)?)
     }
 
     pub fn current_line_index(&self) -> usize {
@@ -137,93 +357,183 @@ impl FileContent {
         self.current_line_index = self.saturate_line_index(line_index);
     }
 
-    pub fn set_current_line_number(&mut self, line_number: usize) -> anyhow::Result<()> {
+    pub fn set_current_line_number(&mut self, line_number: usize) -> Result<(), BlameError> {
         self.set_current_line_index(self.line_index_from_number(line_number)?);
         Ok(())
     }
 
+    /// Set the current line to the line of the `line_number`,
+    /// or to the next line if it's not in the lines, such as hidden commits in
+    /// the log.
+    pub fn set_current_line_number_or_next(&mut self, line_number: usize) {
+        let line_index = self
+            .lines
+            .iter()
+            .position(|line| line.line_number() >= line_number)
+            .unwrap_or(self.lines.len());
+        self.set_current_line_index(line_index);
+    }
+
     pub fn current_line(&self) -> &Line {
         &self.lines[self.current_line_index()]
     }
 
-    pub fn search(&self, search: &str, reverse: bool) -> Option<usize> {
-        let search = search.to_lowercase();
-        let mut start_line_index = self.current_line_index();
-        self.search_ranges(
-            &search,
-            reverse,
-            if reverse {
-                [0..start_line_index, start_line_index..self.lines.len()]
-            } else {
-                start_line_index += 1;
-                [start_line_index..self.lines.len(), 0..start_line_index]
-            },
-        )
+    /// The index of the next line attributed to the same commit as the current
+    /// line, skipping the consecutive lines of the commit.
+    pub fn same_commit_line_index(&self, reverse: bool) -> Option<usize> {
+        let commit_id = self.current_line().commit_id()?;
+        let is_same = |hunk_index: &usize| {
+            self.lines[self.hunk_starts[*hunk_index]].commit_id() == Some(commit_id)
+        };
+        let current = self.hunk_index_at(self.current_line_index())?;
+        if reverse {
+            let hunk_index = (0..current).rev().find(is_same)?;
+            Some(self.hunk_range(hunk_index).end - 1)
+        } else {
+            let hunk_index = (current + 1..self.hunk_starts.len()).find(is_same)?;
+            Some(self.hunk_starts[hunk_index])
+        }
     }
 
-    fn search_ranges(
-        &self,
-        search: &str,
-        reverse: bool,
-        line_index_ranges: [Range<usize>; 2],
-    ) -> Option<usize> {
-        for line_index_range in line_index_ranges {
-            if let Some(line_index) = self.search_range(search, reverse, line_index_range.clone()) {
-                return Some(line_index);
+    /// The first line of the next hunk, or of the previous hunk if `reverse`.
+    /// When `reverse` and the current line isn't the first line of its hunk,
+    /// the first line of the current hunk.
+    pub fn diff_line_index(&self, reverse: bool) -> Option<usize> {
+        let current_line_index = self.current_line_index();
+        let current = self.hunk_index_at(current_line_index)?;
+        let hunk_index = if reverse {
+            if self.hunk_starts[current] < current_line_index {
+                current
+            } else {
+                current.checked_sub(1)?
             }
-        }
-        None
+        } else {
+            current + 1
+        };
+        self.hunk_starts.get(hunk_index).copied()
     }
 
-    fn search_range(
+    /// Search the next line that matches the `search`,
+    /// wrapping around at the end.
+    /// When searching commit metadata in files,
+    /// only the first lines of consecutive matching lines match.
+    pub fn search(
         &self,
-        search: &str,
+        search: &SearchPattern,
+        commits: &FileCommits,
         reverse: bool,
-        line_index_range: Range<usize>,
     ) -> Option<usize> {
-        let start_line_index = line_index_range.start;
-        let lines = self.lines[line_index_range].iter().enumerate();
-        let result = if reverse {
-            self.search_lines_enumerate(search, lines.rev())
+        let mut start_line_index = self.current_line_index();
+        let line_index_ranges = if reverse {
+            [0..start_line_index, start_line_index..self.lines.len()]
         } else {
-            self.search_lines_enumerate(search, lines)
+            start_line_index += 1;
+            [start_line_index..self.lines.len(), 0..start_line_index]
         };
-        result.map(|i| start_line_index + i)
+        for line_index_range in line_index_ranges {
+            let is_match = |line_index: &usize| self.is_search_match(search, commits, *line_index);
+            let line_index = if reverse {
+                line_index_range.rev().find(is_match)
+            } else {
+                line_index_range.clone().find(is_match)
+            };
+            if line_index.is_some() {
+                return line_index;
+            }
+        }
+        None
     }
 
-    fn search_lines_enumerate<'a>(
+    fn is_search_match(
         &self,
-        search: &str,
-        lines: impl Iterator<Item = (usize, &'a Line)>,
-    ) -> Option<usize> {
-        for (i, line) in lines {
-            if line.content().to_lowercase().contains(search) {
-                return Some(i);
-            }
+        search: &SearchPattern,
+        commits: &FileCommits,
+        line_index: usize,
+    ) -> bool {
+        let is_line_match = |line: &Line| search.is_line_match(line, commits);
+        if !is_line_match(&self.lines[line_index]) {
+            return false;
         }
-        None
+        if search.target() == SearchTarget::Content
+            || self.content_type == ContentType::Log
+            || line_index == 0
+        {
+            return true;
+        }
+        !is_line_match(&self.lines[line_index - 1])
     }
 
-    pub fn read(&mut self, git: &GitTools) -> anyhow::Result<()> {
+    pub fn read(&mut self, git: &GitTools) -> Result<(), BlameError> {
         let commit_id = if self.commit_id.is_zero() {
             git.head_commit_id()?
         } else {
             self.commit_id
         };
+        self.read_at(git, commit_id)
+    }
+
+    /// Read the content at the `commit_id`.
+    /// This is useful when the `commit_id()` is zero, but the content should
+    /// be read from a commit other than the `HEAD`.
+    pub fn read_at(&mut self, git: &GitTools, commit_id: git2::Oid) -> Result<(), BlameError> {
         let content = git.content_as_string(commit_id, &self.path)?;
         self.read_string(&content);
         Ok(())
     }
 
-    fn read_string(&mut self, content: &str) {
-        self.read_lines(content.lines().map(|line| line.to_string()));
+    /// The symbol to show `\r`s not followed by `\n`.
+    const CR_SYMBOL: &str = "\u{240D}";
+
+    /// Read the lines of the `content`. The lines share one copy of the
+    /// `content`, the same as `str::lines()` but without a `String` per line.
+    /// A `Line` is still created for each line; see `LineContent`.
+    pub(super) fn read_string(&mut self, content: &str) {
+        let buffer: Arc<str> = Arc::from(content);
+        let mut lines = vec![];
+        let mut start = 0;
+        let mut lf = 0;
+        let mut crlf = 0;
+        for line in buffer.split_inclusive('\n') {
+            let end = start + line.len();
+            let content = match line.strip_suffix('\n') {
+                Some(line) => match line.strip_suffix('\r') {
+                    Some(line) => {
+                        crlf += 1;
+                        line
+                    }
+                    None => {
+                        lf += 1;
+                        line
+                    }
+                },
+                None => line.strip_suffix('\r').unwrap_or(line),
+            };
+            let line_number = lines.len() + 1;
+            lines.push(if content.contains('\r') {
+                // `git` doesn't break lines at lone `\r`s, but terminals do.
+                // Show them as symbols instead.
+                Line::new(line_number, content.replace('\r', Self::CR_SYMBOL))
+            } else {
+                Line::new_shared(line_number, &buffer, start..start + content.len())
+            });
+            start = end;
+        }
+        self.lines = Arc::new(lines);
+        self.line_ending = LineEnding::from_counts(lf, crlf);
+        self.reset_hunk_starts();
+        self.line_damage = LineDamage::All;
     }
 
+    #[cfg(test)]
     fn read_lines(&mut self, lines: impl Iterator<Item = String>) {
-        self.lines = lines
-            .enumerate()
-            .map(|(i, line)| Line::new(i + 1, line))
-            .collect();
+        self.lines = Arc::new(
+            lines
+                .enumerate()
+                .map(|(i, line)| Line::new(i + 1, line))
+                .collect(),
+        );
+        self.reset_hunk_starts();
+        self.line_damage = LineDamage::All;
     }
 
     #[cfg(test)]
@@ -237,10 +547,10 @@ impl FileContent {
         self.read_lines(lines);
     }
 
-    pub fn update_commits(&mut self, history: &FileHistory) -> anyhow::Result<()> {
+    pub fn update_commits(&mut self, history: &FileHistory) -> Result<(), BlameError> {
         match self.content_type {
-            ContentType::File => self.update_file(history),
-            ContentType::Log => self.update_logs(history),
+            ContentType::File => Ok(self.update_file(history)?),
+            ContentType::Log => Ok(self.update_logs(history)?),
         }
     }
 
@@ -251,13 +561,20 @@ impl FileContent {
             self.applied_commits_len,
             commits.len()
         );
-        assert!(commits.len() > self.applied_commits_len);
+        anyhow::ensure!(
+            commits.len() >= self.applied_commits_len,
+            "The history has fewer commits than applied: {} < {}",
+            commits.len(),
+            self.applied_commits_len
+        );
         let start_time = std::time::Instant::now();
 
         if self.commit_id().is_zero() {
-            self.commit_id = commits[0].commit_id();
+            let Some(first_commit) = commits.first() else {
+                anyhow::bail!("No commits to update the content");
+            };
+            self.commit_id = first_commit.commit_id();
         }
-        assert!(!self.commit_id().is_zero());
 
         let first_index = commits.index_from_commit_id(self.commit_id())?;
         let skip = self.applied_commits_len.saturating_sub(first_index);
@@ -265,7 +582,16 @@ impl FileContent {
             "apply_commits: {first_index}..{} skip={skip}",
             commits.len()
         );
-        self.apply_commits(commits, first_index, skip)?;
+        if history.provider().is_blame() {
+            self.apply_git_blame(history)?;
+        } else {
+            self.apply_commits(commits, first_index, skip)?;
+            if self.apply_failed_commit_id.is_some() {
+                self.apply_git_blame(history)?;
+            } else {
+                self.apply_read_ahead(history);
+            }
+        }
         self.update_lines_after_apply();
         self.applied_commits_len = commits.len();
         trace!("update_commits done, elapsed: {:?}", start_time.elapsed());
@@ -279,49 +605,423 @@ impl FileContent {
         skip: usize,
     ) -> anyhow::Result<()> {
         // Don't apply if any earlier commits have failed to apply.
-        for commit in &commits[0..first_index + skip] {
+        for commit in commits.range(0..first_index + skip) {
             if commit.is_apply_failed() {
                 trace!("is_apply_failed {}", commit.commit_id());
+                self.apply_failed_commit_id = Some(commit.commit_id());
                 return Ok(());
             }
         }
-        let commits = &commits[first_index..];
-        for commit_index in skip..commits.len() {
-            let commit = &commits[commit_index];
+        let mut maps = std::mem::take(&mut self.applied_maps);
+        if maps.len > skip {
+            maps = AppliedLineNumberMaps::default();
+        }
+        for commit_index in skip..commits.len() - first_index {
+            let commit = &commits[first_index + commit_index];
             if commit.is_apply_failed() {
                 trace!("is_apply_failed {commit_index} {}", commit.commit_id());
+                self.apply_failed_commit_id = Some(commit.commit_id());
                 break;
             }
-            if commit_index == 0 {
-                self.apply_diff_parts(commit.diff_parts(), commit)?;
-            } else {
-                // If `commit_index > 0`, the line numbers in `commit.diff_parts().new`
-                // aren't the line numbers in `self.lines`. Map them to the line
-                // numbers of `self.lines`.
-                let mut adjusted_parts = commit.diff_parts().clone();
-                for j in (0..commit_index).rev() {
-                    let parts = &commits[j].diff_parts();
-                    let map = LineNumberMap::new_new_from_old(parts);
-                    map.apply_to_parts(&mut adjusted_parts);
+            self.apply_moved_lines(commit)?;
+            maps.compose_until(commits, first_index, commit_index)?;
+            if let Err(error) = self.apply_commit(commit, commit_index, &maps) {
+                if matches!(error.downcast_ref(), Some(BlameError::FileDeleted(_))) {
+                    self.applied_maps = maps;
+                    return Err(error);
                 }
-                self.apply_diff_parts(&adjusted_parts, commit)?;
+                // The diff parts are inconsistent with the lines. Stop applying,
+                // and recover the remaining lines by `git blame`.
+                warn!("Applying {} failed: {error}", commit.commit_id());
+                commit.set_apply_failed();
+                self.apply_failed_commit_id = Some(commit.commit_id());
+                break;
+            }
+        }
+        if self.apply_failed_commit_id.is_none()
+            && commits.len() > first_index
+            && let Some(commit) = commits.last()
+            && commit.is_boundary()
+        {
+            maps.compose_until(commits, first_index, commits.len() - first_index - 1)?;
+            self.apply_boundary(commit, &maps.from_lines)?;
+        }
+        self.applied_maps = maps;
+        Ok(())
+    }
+
+    /// Attribute the lines not attributed to any commits to the boundary
+    /// `commit`, the oldest commit, and mark the lines of the `commit` as the
+    /// boundary. See `FileCommit::is_boundary()`.
+    /// The `from_lines` maps the line numbers of `self.lines` to the line
+    /// numbers after the `commit`.
+    fn apply_boundary(
+        &mut self,
+        commit: &FileCommit,
+        from_lines: &LineNumberMap,
+    ) -> anyhow::Result<()> {
+        let mut line_indexes = vec![];
+        let mut unattributed_line_indexes = vec![];
+        for (line_index, line) in self.lines.iter().enumerate() {
+            if line.is_deleted() || line.is_boundary() {
+                continue;
+            }
+            match line.commit_id() {
+                None => unattributed_line_indexes.push(line_index),
+                Some(commit_id) if commit_id == commit.commit_id() => {}
+                Some(_) => continue,
+            }
+            line_indexes.push(line_index);
+        }
+        if line_indexes.is_empty() {
+            return Ok(());
+        }
+        let lines = Arc::make_mut(&mut self.lines);
+        for line_index in line_indexes {
+            lines[line_index].set_commit(commit);
+            self.line_damage.add(line_index);
+        }
+        self.set_original_line_numbers(from_lines, &unattributed_line_indexes)
+    }
+
+    /// The commit that failed to apply, if any.
+    /// See `FileCommit::is_apply_failed()`.
+    pub fn apply_failed_commit_id(&self) -> Option<git2::Oid> {
+        self.apply_failed_commit_id
+    }
+
+    fn apply_commit(
+        &mut self,
+        commit: &FileCommit,
+        commit_index: usize,
+        maps: &AppliedLineNumberMaps,
+    ) -> anyhow::Result<()> {
+        let mut line_indexes = vec![];
+        let parts = Self::diff_parts_to_apply(commit)?;
+        if commit_index == 0 {
+            self.apply_diff_parts(&parts, commit, &mut line_indexes)?;
+        } else {
+            // If `commit_index > 0`, the line numbers in `commit.diff_parts().new`
+            // aren't the line numbers in `self.lines`. Map them to the line
+            // numbers of `self.lines`.
+            let mut adjusted_parts = Arc::unwrap_or_clone(parts);
+            maps.to_lines.apply_to_parts(&mut adjusted_parts)?;
+            self.apply_diff_parts(&adjusted_parts, commit, &mut line_indexes)?;
+        }
+        for line_index in &line_indexes {
+            self.line_damage.add(*line_index);
+        }
+        self.set_original_line_numbers(&maps.from_lines, &line_indexes)?;
+        self.add_moved_lines(&maps.to_lines, commit)
+    }
+
+    /// Mark the lines moved by the `commit` as moved, and record them to
+    /// attribute to the commits that originally added them.
+    /// The `to_lines` maps the line numbers after the `commit` to the line
+    /// numbers of `self.lines`.
+    fn add_moved_lines(
+        &mut self,
+        to_lines: &LineNumberMap,
+        commit: &FileCommit,
+    ) -> anyhow::Result<()> {
+        if commit.moved_parts().is_empty() {
+            return Ok(());
+        }
+        // Map each moved line to the line numbers of `self.lines`.
+        // If the range becomes empty, the line is deleted by newer commits.
+        let mut parts: Vec<DiffPart> = commit
+            .moved_parts()
+            .iter()
+            .flat_map(|part| part.new.line_numbers.clone())
+            .map(|line_number| {
+                let range = DiffRange {
+                    line_numbers: line_number..line_number + 1,
+                };
+                DiffPart {
+                    old: range.clone(),
+                    new: range,
+                }
+            })
+            .collect();
+        to_lines.apply_to_parts(&mut parts)?;
+        let moved_parts = commit.moved_parts().iter();
+        let old_line_numbers = moved_parts.flat_map(|part| part.old.line_numbers.clone());
+        for (part, old_line_number) in parts.iter().zip(old_line_numbers) {
+            if part.new.is_empty() {
+                continue;
+            }
+            let line_number = part.new.line_numbers.start;
+            if let Some(line_index) = self.line_index_from_number_not_deleted(line_number)
+                && (self.lines[line_index].commit_id().is_none()
+                    || self.lines[line_index].is_read_ahead())
+            {
+                Arc::make_mut(&mut self.lines)[line_index].set_moved(true);
+                self.line_damage.add(line_index);
+                self.moved_lines.push((line_number, old_line_number));
+            }
+        }
+        trace!("add_moved_lines: {:?}", self.moved_lines);
+        Ok(())
+    }
+
+    /// Attribute the moved lines to the `commit` if it added them.
+    /// Otherwise map their line numbers before the move to the next commit.
+    fn apply_moved_lines(&mut self, commit: &FileCommit) -> anyhow::Result<()> {
+        if self.moved_lines.is_empty() {
+            return Ok(());
+        }
+        let parts = commit.diff_parts()?;
+        let map = LineNumberMap::new_old_from_new(&parts);
+        let moved_lines = std::mem::take(&mut self.moved_lines);
+        for (line_number, old_line_number) in moved_lines {
+            if let Some(old_line_number) = commit.old_line_number(old_line_number, &parts, &map) {
+                self.moved_lines.push((line_number, old_line_number));
+                continue;
+            }
+            if let Some(line_index) = self.line_index_from_number_not_deleted(line_number) {
+                let line = &mut Arc::make_mut(&mut self.lines)[line_index];
+                line.set_commit(commit);
+                line.set_original_line_number(old_line_number);
+                self.line_damage.add(line_index);
+            }
+        }
+        Ok(())
+    }
+
+    /// The index of the line of the `line_number`, skipping deleted lines.
+    pub fn line_index_from_number_not_deleted(&self, line_number: usize) -> Option<usize> {
+        let line_index = self.line_index_from_number(line_number).ok()?;
+        self.lines[line_index..]
+            .iter()
+            .position(|line| !line.is_deleted() && line.line_number() == line_number)
+            .map(|offset| line_index + offset)
+    }
+
+    /// The diff parts of the `commit` to apply.
+    ///
+    /// If the `commit` is ignored, the changed lines that have corresponding
+    /// old lines are excluded, so that they are attributed to older commits.
+    /// Deleted lines are also excluded.
+    ///
+    /// The lines moved by the `commit` are also excluded, so that they are
+    /// attributed to the commits that originally added them.
+    fn diff_parts_to_apply(commit: &FileCommit) -> anyhow::Result<Arc<Vec<DiffPart>>> {
+        let parts = commit.diff_parts()?;
+        if !commit.is_ignored() && commit.moved_parts().is_empty() {
+            return Ok(parts);
+        }
+        let mut parts = Arc::unwrap_or_clone(parts);
+        if commit.is_ignored() {
+            parts = parts
+                .into_iter()
+                .filter_map(|mut part| {
+                    let matched_len = cmp::min(part.old.len(), part.new.len());
+                    part.old.line_numbers.start += matched_len;
+                    part.new.line_numbers.start += matched_len;
+                    (!part.new.is_empty()).then_some(part)
+                })
+                .collect();
+        }
+        if !commit.moved_parts().is_empty() {
+            parts = Self::exclude_moved_parts(parts, commit.moved_parts());
+        }
+        Ok(Arc::new(parts))
+    }
+
+    /// Exclude the new lines of the `moved_parts` from the `parts`.
+    /// Deletions are also excluded if all the deleted lines are moved.
+    fn exclude_moved_parts(parts: Vec<DiffPart>, moved_parts: &[DiffPart]) -> Vec<DiffPart> {
+        let mut results = vec![];
+        for part in parts {
+            if part.new.is_empty() {
+                let is_moved = part.old.line_numbers.clone().all(|line_number| {
+                    moved_parts
+                        .iter()
+                        .any(|moved| moved.old.line_numbers.contains(&line_number))
+                });
+                if !is_moved {
+                    results.push(part);
+                }
+                continue;
+            }
+            let end = part.new.line_numbers.end;
+            let mut start = part.new.line_numbers.start;
+            let mut old = part.old.clone();
+            let mut push = |new: Range<usize>, old: &mut DiffRange| {
+                results.push(DiffPart {
+                    old: old.clone(),
+                    new: DiffRange { line_numbers: new },
+                });
+                // Only the first piece has the old lines.
+                old.line_numbers.start = old.line_numbers.end;
+            };
+            for moved in moved_parts {
+                let moved = &moved.new.line_numbers;
+                if moved.end <= start || moved.start >= end {
+                    continue;
+                }
+                if moved.start > start {
+                    push(start..moved.start, &mut old);
+                }
+                start = moved.end;
+            }
+            if start < end {
+                push(start..end, &mut old);
+            }
+        }
+        results
+    }
+
+    /// Attribute lines not attributed yet, by the result of `git blame`.
+    /// This is used by the `GitEngine::GitBlame` and the
+    /// `GitEngine::Git2Blame` engines, and to recover when applying commits
+    /// failed. Lines whose commits aren't read yet are attributed in later
+    /// calls.
+    fn apply_git_blame(&mut self, history: &FileHistory) -> anyhow::Result<()> {
+        if self.git_blame_commits.is_empty() {
+            debug!("apply_git_blame: {} {:?}", self.commit_id, self.path);
+            // Blame the visible lines first, and the rest in a thread.
+            let line_numbers = self.visible_line_numbers();
+            self.is_git_blame_partial = line_numbers.is_some();
+            let results = history.provider().blame(
+                history.git(),
+                self.commit_id,
+                &self.path,
+                line_numbers,
+                history.options(),
+            )?;
+            self.extend_git_blame_commits(results);
+            if self.is_git_blame_partial {
+                self.git_blame_rest = Some(self.start_git_blame_rest(history));
+            }
+        } else if let Some(rest) = &self.git_blame_rest
+            && let Some(result) = rest.get()
+        {
+            debug!(
+                "apply_git_blame: the rest {} {:?}",
+                self.commit_id, self.path
+            );
+            let results = match result {
+                Ok(results) => results.clone(),
+                Err(error) => anyhow::bail!("{error:#}"),
+            };
+            self.git_blame_rest = None;
+            self.is_git_blame_partial = false;
+            self.extend_git_blame_commits(results);
+        }
+        for (line_index, line) in Arc::make_mut(&mut self.lines).iter_mut().enumerate() {
+            if line.is_deleted() {
+                continue;
+            }
+            if let Some(commit_id) = line.commit_id()
+                && !history.commit_by_id(commit_id)?.is_apply_failed()
+            {
+                continue;
+            }
+            let Some((commit_id, original)) = self.git_blame_commits.get(&line.line_number())
+            else {
+                continue;
+            };
+            // The commit may be read ahead of the newer commits.
+            if let Ok(commit) = history.commit_by_id(*commit_id) {
+                line.set_commit(commit);
+                line.set_original_line_number(*original);
+                self.line_damage.add(line_index);
             }
         }
         Ok(())
     }
 
+    fn extend_git_blame_commits(&mut self, results: Vec<(git2::Oid, usize, usize)>) {
+        self.git_blame_commits.extend(
+            results
+                .into_iter()
+                .map(|(commit_id, original, line_number)| (line_number, (commit_id, original))),
+        );
+    }
+
+    /// Blame all the lines in a thread, so that the blame of large files
+    /// doesn't block the UI. The result is set when the thread completes.
+    fn start_git_blame_rest(&self, history: &FileHistory) -> Arc<OnceLock<BlameResult>> {
+        let rest = Arc::new(OnceLock::new());
+        let result = rest.clone();
+        let repository_path = history.git().repository_path().to_path_buf();
+        let provider = history.shared_provider();
+        let options = history.options().clone();
+        let commit_id = self.commit_id;
+        let path = self.path.clone();
+        thread::spawn(move || {
+            let _ = result.set(
+                GitTools::from_repository_path(&repository_path)
+                    .map_err(BlameError::from)
+                    .and_then(|git| provider.blame(&git, commit_id, &path, None, &options))
+                    .map_err(anyhow::Error::from),
+            );
+        });
+        rest
+    }
+
+    /// Attribute lines not attributed yet to the commits read ahead of the
+    /// newer commits by `FileHistory::set_priority_lines()`, until the
+    /// commits are applied in order.
+    fn apply_read_ahead(&mut self, history: &FileHistory) {
+        let Some(blame) = history.priority_blame(self.commit_id, &self.path) else {
+            return;
+        };
+        for (line_number, (commit_id, original)) in blame {
+            let Some(commit) = history.read_ahead_commit(*commit_id) else {
+                continue;
+            };
+            let Some(line_index) = self.line_index_from_number_not_deleted(*line_number) else {
+                continue;
+            };
+            let line = &mut Arc::make_mut(&mut self.lines)[line_index];
+            if line.commit_id().is_some() || line.is_moved() {
+                continue;
+            }
+            line.set_read_ahead_commit(commit);
+            line.set_original_line_number(*original);
+            self.line_damage.add(line_index);
+        }
+    }
+
+    /// Set the line numbers at the commit that introduced the lines, by mapping
+    /// the line numbers of `line_indexes` by the `from_lines`, which maps the
+    /// line numbers of `self.lines` to the line numbers after the commit.
+    fn set_original_line_numbers(
+        &mut self,
+        from_lines: &LineNumberMap,
+        line_indexes: &[usize],
+    ) -> anyhow::Result<()> {
+        let mut line_numbers: Vec<usize> = line_indexes
+            .iter()
+            .map(|line_index| self.lines[*line_index].line_number())
+            .collect();
+        from_lines.apply_to_values(line_numbers.iter_mut())?;
+        for (line_index, line_number) in line_indexes.iter().zip(line_numbers) {
+            Arc::make_mut(&mut self.lines)[*line_index].set_original_line_number(line_number);
+        }
+        Ok(())
+    }
+
     fn apply_diff_parts(
         &mut self,
-        parts: &Vec<DiffPart>,
+        parts: &[DiffPart],
         commit: &FileCommit,
+        line_indexes: &mut Vec<usize>,
     ) -> anyhow::Result<()> {
         for part in parts {
-            self.apply_diff_part(part, commit)?;
+            self.apply_diff_part(part, commit, line_indexes)?;
         }
         Ok(())
     }
 
-    fn apply_diff_part(&mut self, part: &DiffPart, commit: &FileCommit) -> anyhow::Result<()> {
+    fn apply_diff_part(
+        &mut self,
+        part: &DiffPart,
+        commit: &FileCommit,
+        line_indexes: &mut Vec<usize>,
+    ) -> anyhow::Result<()> {
         let commit_id = commit.commit_id();
         trace!("apply: #{} {part:?} {}", commit.index(), commit_id);
         let new_line_numbers = part.new.line_numbers();
@@ -335,13 +1035,14 @@ impl FileContent {
         let line_index = self.line_index_from_number(new_line_numbers.start)?;
         trace!("apply: index={line_index} for {new_line_numbers:?}");
         for line_index in line_index..self.lines_len() {
-            let line = &mut self.lines[line_index];
+            let line = &mut Arc::make_mut(&mut self.lines)[line_index];
             // trace!("apply: [{line_index}]={} {:?}", line.line_number(), line.commit_id());
             if line.line_number() >= new_line_numbers.end {
                 break;
             }
-            if line.commit_id().is_none() {
-                line.set_commit_id(commit_id);
+            if (line.commit_id().is_none() || line.is_read_ahead()) && !line.is_moved() {
+                line.set_commit(commit);
+                line_indexes.push(line_index);
             }
         }
 
@@ -352,13 +1053,18 @@ impl FileContent {
         trace!("insert_deleted_part: {part:?}");
         let commit_id = commit.commit_id();
         let new_line_numbers = part.new.line_numbers();
-        assert!(new_line_numbers.is_empty());
+        anyhow::ensure!(
+            new_line_numbers.is_empty(),
+            "The deleted part has new lines: {part:?}"
+        );
         if new_line_numbers.start == 0 {
             commit.set_apply_failed();
             return Err(BlameError::FileDeleted(commit_id).into());
         }
         let old_line_numbers = &part.old.line_numbers;
-        assert!(old_line_numbers.start > 0);
+        if old_line_numbers.start == 0 {
+            anyhow::bail!("The deleted lines start at 0: {part:?}");
+        }
         if old_line_numbers.is_empty() {
             return Ok(()); // Line number mapping may have created this.
         }
@@ -374,7 +1080,8 @@ impl FileContent {
             }
         }
         let line = Line::new_deleted(new_line_numbers.start, commit_id);
-        self.lines.insert(line_index, line);
+        Arc::make_mut(&mut self.lines).insert(line_index, line);
+        self.line_damage = LineDamage::All;
         if self.current_line_index >= line_index {
             self.current_line_index += 1;
         }
@@ -382,10 +1089,16 @@ impl FileContent {
     }
 
     fn update_lines_after_apply(&mut self) {
-        let mut last_line: Option<&mut Line> = None;
+        // `line_index_from_number()` relies on this.
+        debug_assert!(
+            self.lines.is_sorted_by_key(|line| line.line_number()),
+            "The line numbers must be non-decreasing"
+        );
+        self.hunk_starts.clear();
+        let mut last_line: Option<(usize, &mut Line)> = None;
         let mut last_commit_id: Option<git2::Oid> = None;
         let mut index_in_hunk = 0;
-        for line in &mut self.lines {
+        for (line_index, line) in Arc::make_mut(&mut self.lines).iter_mut().enumerate() {
             let commit_id = line.commit_id();
             let is_first_line_in_hunk;
             if let Some(commit_id) = commit_id {
@@ -400,22 +1113,117 @@ impl FileContent {
                 index_in_hunk = 0;
                 is_first_line_in_hunk = last_commit_id.is_some();
             }
-            line.set_index_in_hunk(index_in_hunk);
-            if let Some(last_line) = last_line {
+            if line_index == 0 || is_first_line_in_hunk {
+                self.hunk_starts.push(line_index);
+            }
+            if line.index_in_hunk() != index_in_hunk {
+                line.set_index_in_hunk(index_in_hunk);
+                self.line_damage.add(line_index);
+            }
+            if let Some((last_line_index, last_line)) = last_line
+                && last_line.is_last_line_in_hunk() != is_first_line_in_hunk
+            {
                 last_line.set_is_last_line_in_hunk(is_first_line_in_hunk);
+                self.line_damage.add(last_line_index);
             }
 
-            last_line = Some(line);
+            last_line = Some((line_index, line));
             last_commit_id = commit_id;
         }
     }
 
+    /// Make all lines one hunk, for lines not attributed to any commits yet.
+    fn reset_hunk_starts(&mut self) {
+        self.hunk_starts.clear();
+        if !self.lines.is_empty() {
+            self.hunk_starts.push(0);
+        }
+    }
+
+    /// The line index ranges of the hunks.
+    pub fn hunk_ranges(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        (0..self.hunk_starts.len()).map(|hunk_index| self.hunk_range(hunk_index))
+    }
+
+    /// The line index range of the hunk containing the `line_index`.
+    pub fn hunk_range_at(&self, line_index: usize) -> Option<Range<usize>> {
+        self.hunk_index_at(line_index)
+            .map(|hunk_index| self.hunk_range(hunk_index))
+    }
+
+    fn hunk_index_at(&self, line_index: usize) -> Option<usize> {
+        if line_index >= self.lines.len() {
+            return None;
+        }
+        self.hunk_starts
+            .partition_point(|start| *start <= line_index)
+            .checked_sub(1)
+    }
+
+    fn hunk_range(&self, hunk_index: usize) -> Range<usize> {
+        let start = self.hunk_starts[hunk_index];
+        let end = self
+            .hunk_starts
+            .get(hunk_index + 1)
+            .copied()
+            .unwrap_or(self.lines.len());
+        start..end
+    }
+
+    /// Set the filter of the `ContentType::Log`, and update the lines.
+    /// The current line is kept, or moved to the next older commit if it's
+    /// hidden.
+    pub fn set_log_filter(
+        &mut self,
+        filter: &LineFilter,
+        history: &FileHistory,
+    ) -> Result<(), BlameError> {
+        assert_eq!(self.content_type, ContentType::Log);
+        let commits = history.commits();
+        if !commits.is_empty() && !commits.iter().any(|commit| filter.is_match(Some(commit))) {
+            return Err(anyhow::anyhow!("No commits match the filter").into());
+        }
+        let line_number = self
+            .lines
+            .get(self.current_line_index)
+            .map(Line::line_number);
+        self.log_filter = filter.clone();
+        self.update_logs(history)?;
+        if let Some(line_number) = line_number {
+            self.set_current_line_number_or_next(line_number);
+        }
+        Ok(())
+    }
+
     fn update_logs(&mut self, history: &FileHistory) -> anyhow::Result<()> {
-        self.lines.clear();
+        Arc::make_mut(&mut self.lines).clear();
+        self.hunk_starts.clear();
+        self.line_damage = LineDamage::All;
         let commits = history.commits();
-        for commit in commits {
-            let line = Line::new_log(commit);
-            self.lines.push(line);
+        let log_graph = match &mut self.log_graph {
+            Some(log_graph) => log_graph,
+            None => self
+                .log_graph
+                .insert(LogGraph::new(history.git(), commits)?),
+        };
+        // The graph can't connect commits across hidden commits,
+        // so only the decorations are shown when filtered.
+        let is_filtered = !self.log_filter.is_empty();
+        let prefixes = if is_filtered {
+            commits
+                .iter()
+                .map(|commit| log_graph.decorations(commit.commit_id()))
+                .collect()
+        } else {
+            log_graph.prefixes(commits)
+        };
+        for (commit, prefix) in commits.iter().zip(prefixes) {
+            if is_filtered && !self.log_filter.is_match(Some(commit)) {
+                continue;
+            }
+            let line = Line::new_log(commit, &prefix);
+            self.hunk_starts.push(self.lines.len());
+            Arc::make_mut(&mut self.lines).push(line);
         }
         Ok(())
     }
@@ -423,22 +1231,391 @@ impl FileContent {
 
 #[cfg(test)]
 mod tests {
+    use crate::{
+        blame::{FileHistoryBuilder, GitEngine},
+        extensions::tests::TempRepository,
+    };
+
     use super::*;
 
     #[test]
     fn line_index_from_number() -> anyhow::Result<()> {
         let mut content = FileContent::new_for_test();
         for i in 1..=10 {
-            content.lines.push(Line::new(i, i.to_string()));
+            Arc::make_mut(&mut content.lines).push(Line::new(i, i.to_string()));
         }
         assert_eq!(content.last_line_number()?, 10);
         assert_eq!(content.line_index_from_number(1)?, 0);
         assert_eq!(content.line_index_from_number(10)?, 9);
-        content.lines.insert(5, Line::new(6, "6-2".to_string()));
+        Arc::make_mut(&mut content.lines).insert(5, Line::new(6, "6-2".to_string()));
         assert_eq!(content.line_index_from_number(5)?, 4);
         assert_eq!(content.line_index_from_number(6)?, 5);
         assert_eq!(content.line_index_from_number(7)?, 7);
         assert_eq!(content.line_index_from_number(10)?, 10);
+        Arc::make_mut(&mut content.lines).insert(0, Line::new(1, "1-2".to_string()));
+        Arc::make_mut(&mut content.lines).insert(0, Line::new(1, "1-3".to_string()));
+        assert_eq!(content.line_index_from_number(1)?, 0);
+        assert_eq!(content.line_index_from_number(2)?, 3);
+        assert_eq!(content.line_index_from_number(7)?, 9);
+        assert!(content.line_index_from_number(0).is_err());
+        assert!(content.line_index_from_number(11).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn read_string() {
+        let mut content = FileContent::new_for_test();
+        for (string, line_ending) in [
+            ("", LineEnding::Lf),
+            ("a", LineEnding::Lf),
+            ("a\n", LineEnding::Lf),
+            ("a\r\nb\n\nc", LineEnding::Mixed),
+            ("a\rb\r\n\r", LineEnding::CrLf),
+            ("\n\n", LineEnding::Lf),
+        ] {
+            content.read_string(string);
+            let lines: Vec<&str> = content.lines().iter().map(|line| line.content()).collect();
+            let expected: Vec<String> = string
+                .lines()
+                .map(|line| {
+                    line.strip_suffix('\r')
+                        .unwrap_or(line)
+                        .replace('\r', "\u{240D}")
+                })
+                .collect();
+            assert_eq!(lines, expected, "{string:?}");
+            let line_numbers: Vec<usize> = content.lines().iter().map(Line::line_number).collect();
+            assert_eq!(line_numbers, (1..=lines.len()).collect::<Vec<_>>());
+            assert_eq!(content.line_ending(), line_ending, "{string:?}");
+        }
+    }
+
+    #[test]
+    fn git2_blame() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n3\n4\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\nA\n3\nB\n")?;
+        let commit_id2 = git.commit(commit_id1, "Modify 2 and 4")?;
+
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(path))
+            .engine(GitEngine::Git2Blame)
+            .build();
+        history.read_start()?;
+        let mut content = history.content(git2::Oid::ZERO_SHA1)?;
+        history.read_join()?;
+        history.read_poll()?;
+        let commit_ids = |content: &FileContent| -> Vec<Option<git2::Oid>> {
+            content.lines().iter().map(Line::commit_id).collect()
+        };
+
+        // Only the visible lines are attributed first.
+        content.set_visible_line_range(Some(0..2));
+        content.update_commits(&history)?;
+        assert!(content.is_blame_partial());
+        assert_eq!(
+            commit_ids(&content),
+            [Some(commit_id1), Some(commit_id2), None, None]
+        );
+
+        // The rest are blamed in a thread.
+        for _ in 0..1000 {
+            content.update_commits(&history)?;
+            if !content.is_blame_partial() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(!content.is_blame_partial());
+        let expected = [
+            Some(commit_id1),
+            Some(commit_id2),
+            Some(commit_id1),
+            Some(commit_id2),
+        ];
+        assert_eq!(commit_ids(&content), expected);
+
+        // The same as the diffs of commits.
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_all()?;
+        assert_eq!(
+            commit_ids(&history.content(git2::Oid::ZERO_SHA1)?),
+            expected
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn apply_failed() -> anyhow::Result<()> {
+        let mut content = FileContent::new_for_test();
+        content.set_lines_len_for_test(4);
+        let commit_ids: Vec<git2::Oid> = (1..=3u8)
+            .map(|i| git2::Oid::from_bytes(&[i; 20]).unwrap())
+            .collect();
+        let mut commits = FileCommits::new();
+        for (commit_id, parts) in commit_ids.iter().zip([
+            vec![DiffPart::from_ranges(2..2, 2..3)],
+            // Not ascending.
+            vec![
+                DiffPart::from_ranges(3..4, 3..4),
+                DiffPart::from_ranges(1..2, 1..2),
+            ],
+            vec![DiffPart::from_ranges(0..0, 1..4)],
+        ]) {
+            let mut commit = FileCommit::new(*commit_id, Path::new(""));
+            commit.set_diff_parts_for_test(parts);
+            commits.push(commit);
+        }
+        content.apply_commits(&commits, 0, 0)?;
+        assert!(commits[1].is_apply_failed());
+        assert_eq!(content.apply_failed_commit_id(), Some(commit_ids[1]));
+        let line_commit_ids: Vec<Option<git2::Oid>> =
+            content.lines.iter().map(|line| line.commit_id()).collect();
+        assert_eq!(line_commit_ids, [None, Some(commit_ids[0]), None, None]);
+        Ok(())
+    }
+
+    #[test]
+    fn line_damage() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n3\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2\n3\n4\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add 4")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        let mut content = history.content(git2::Oid::ZERO_SHA1)?;
+        assert_eq!(content.take_line_damage(), LineDamage::All);
+        assert_eq!(content.take_line_damage(), LineDamage::Lines(vec![]));
+        history.read_join()?;
+        history.read_poll()?;
+        content.update_commits(&history)?;
+        let LineDamage::Lines(mut line_indexes) = content.take_line_damage() else {
+            panic!("Lines are not inserted nor removed");
+        };
+        line_indexes.sort();
+        line_indexes.dedup();
+        assert_eq!(line_indexes, [0, 1, 2, 3]);
+
+        git.add_file_content(path, "1\n3\n4\n")?;
+        git.commit(commit_id2, "Delete 2")?;
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        let mut content = history.content(git2::Oid::ZERO_SHA1)?;
+        content.take_line_damage();
+        history.read_join()?;
+        history.read_poll()?;
+        content.update_commits(&history)?;
+        assert_eq!(content.take_line_damage(), LineDamage::All);
+        Ok(())
+    }
+
+    #[test]
+    fn original_line_number() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n3\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "A\nB\n1\n2\nC\n3\n")?;
+        git.commit(commit_id1, "Add lines")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        history.read_join()?;
+        history.read_poll()?;
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let original_line_numbers: Vec<Option<usize>> = content
+            .lines()
+            .iter()
+            .map(|line| line.original_line_number())
+            .collect();
+        assert_eq!(
+            original_line_numbers,
+            [Some(1), Some(2), Some(1), Some(2), Some(5), Some(3)]
+        );
+        assert_eq!(content.lines()[2].original_path(&history), Some(path));
+        Ok(())
+    }
+
+    #[test]
+    fn apply_many_commits() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n3\n4\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "A\n1\n2\n3\n4\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add A")?;
+        git.add_file_content(path, "A\n1\n3\n4\nB\n")?;
+        let commit_id3 = git.commit(commit_id2, "Delete 2, add B")?;
+        git.add_file_content(path, "C\nA\n1\n3\nD\nB\n")?;
+        let commit_id4 = git.commit(commit_id3, "Add C, replace 4 with D")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_all()?;
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let lines: Vec<(&str, Option<git2::Oid>, Option<usize>)> = content
+            .lines()
+            .iter()
+            .filter(|line| !line.is_deleted())
+            .map(|line| {
+                (
+                    line.content(),
+                    line.commit_id(),
+                    line.original_line_number(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                ("C", Some(commit_id4), Some(1)),
+                ("A", Some(commit_id2), Some(1)),
+                ("1", Some(commit_id1), Some(1)),
+                ("3", Some(commit_id1), Some(3)),
+                ("D", Some(commit_id4), Some(5)),
+                ("B", Some(commit_id3), Some(5)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn ignore_rev() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n3\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\nX\nY\n3\n")?;
+        let commit_id2 = git.commit(commit_id1, "Replace line 2")?;
+
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(path))
+            .ignore_rev(&commit_id2.to_string())
+            .build();
+        history.read_start()?;
+        history.read_join()?;
+        history.read_poll()?;
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let commit_ids: Vec<Option<git2::Oid>> = content
+            .lines()
+            .iter()
+            .map(|line| line.commit_id())
+            .collect();
+        assert_eq!(
+            commit_ids,
+            [
+                Some(commit_id1),
+                Some(commit_id1),
+                Some(commit_id2),
+                Some(commit_id1)
+            ]
+        );
+        assert_eq!(content.lines()[1].original_line_number(), Some(2));
+        Ok(())
+    }
+
+    #[test]
+    fn boundary() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\nA\n2\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add A")?;
+        git.add_file_content(path, "1\nA\n2\nB\n")?;
+        let commit_id3 = git.commit(commit_id2, "Add B")?;
+
+        for max_commits in [2, 3] {
+            let mut history = FileHistoryBuilder::new(&git.to_file_path(path))
+                .max_commits(max_commits)
+                .build();
+            history.read_all()?;
+            let content = history.content(git2::Oid::ZERO_SHA1)?;
+            let lines: Vec<_> = content
+                .lines()
+                .iter()
+                .map(|line| {
+                    (
+                        line.commit_id().unwrap(),
+                        line.original_line_number().unwrap(),
+                        line.is_boundary(),
+                    )
+                })
+                .collect();
+            if max_commits == 2 {
+                // The lines older than the limit are attributed to the oldest
+                // commit read, as the boundary.
+                assert!(history.commits()[1].is_boundary());
+                assert_eq!(
+                    lines,
+                    [
+                        (commit_id2, 1, true),
+                        (commit_id2, 2, true),
+                        (commit_id2, 3, true),
+                        (commit_id3, 4, false),
+                    ]
+                );
+            } else {
+                assert!(!history.commits()[1].is_boundary());
+                assert_eq!(
+                    lines,
+                    [
+                        (commit_id1, 1, true),
+                        (commit_id2, 2, false),
+                        (commit_id1, 2, true),
+                        (commit_id3, 4, false),
+                    ]
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn detect_moves() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        let moved = "first_function_name();\nsecond_function_name();\n";
+        git.add_file_content(path, &format!("{moved}0\n1\n2\n3\n"))?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, &format!("0\n1\n2\n3\n{moved}"))?;
+        let commit_id2 = git.commit(commit_id1, "Move the functions")?;
+        git.add_file_content(path, &format!("0\n1\n2\n3\n{moved}4\n"))?;
+        let commit_id3 = git.commit(commit_id2, "Add line")?;
+
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(path))
+            .detect_moves(true)
+            .build();
+        history.read_start()?;
+        history.read_join()?;
+        history.read_poll()?;
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let lines: Vec<(Option<git2::Oid>, bool, Option<usize>)> = content
+            .lines()
+            .iter()
+            .filter(|line| !line.is_deleted())
+            .map(|line| {
+                (
+                    line.commit_id(),
+                    line.is_moved(),
+                    line.original_line_number(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (Some(commit_id1), false, Some(3)),
+                (Some(commit_id1), false, Some(4)),
+                (Some(commit_id1), false, Some(5)),
+                (Some(commit_id1), false, Some(6)),
+                (Some(commit_id1), true, Some(1)),
+                (Some(commit_id1), true, Some(2)),
+                (Some(commit_id3), false, Some(7)),
+            ]
+        );
         Ok(())
     }
 
@@ -448,7 +1625,12 @@ mod tests {
         content.set_lines_for_test(((0..10).chain(0..10)).map(|i| i.to_string()));
         let mut test = |start_index: usize, search: &str| -> (Option<usize>, Option<usize>) {
             content.set_current_line_index(start_index);
-            (content.search(search, false), content.search(search, true))
+            let search = SearchPattern::new(search);
+            let commits = FileCommits::new();
+            (
+                content.search(&search, &commits, false),
+                content.search(&search, &commits, true),
+            )
         };
 
         assert_eq!(test(0, "X"), (None, None));
@@ -458,6 +1640,154 @@ mod tests {
         assert_eq!(test(10, "5"), (Some(15), Some(5)));
         assert_eq!(test(15, "5"), (Some(5), Some(5)));
         assert_eq!(test(18, "5"), (Some(5), Some(15)));
+
+        assert_eq!(test(0, "^[5-6]$"), (Some(5), Some(16)));
+        Ok(())
+    }
+
+    #[test]
+    fn same_commit_line_index() {
+        let mut content = FileContent::new_for_test();
+        content.set_lines_len_for_test(8);
+        let commit_ids: Vec<git2::Oid> = (1..=2u8)
+            .map(|i| git2::Oid::from_bytes(&[i; 20]).unwrap())
+            .collect();
+        for (line, commit_index) in Arc::make_mut(&mut content.lines)
+            .iter_mut()
+            .zip([0, 0, 1, 1, 0, 1, 0, 0])
+        {
+            line.set_commit_id(commit_ids[commit_index]);
+        }
+        content.update_lines_after_apply();
+        let mut test = |line_index: usize| {
+            content.set_current_line_index(line_index);
+            (
+                content.same_commit_line_index(true),
+                content.same_commit_line_index(false),
+            )
+        };
+        assert_eq!(test(0), (None, Some(4)));
+        assert_eq!(test(1), (None, Some(4)));
+        assert_eq!(test(4), (Some(1), Some(6)));
+        assert_eq!(test(7), (Some(4), None));
+        assert_eq!(test(2), (None, Some(5)));
+    }
+
+    #[test]
+    fn diff_line_index() {
+        let mut content = FileContent::new_for_test();
+        content.set_lines_len_for_test(8);
+        let commit_ids: Vec<git2::Oid> = (1..=2u8)
+            .map(|i| git2::Oid::from_bytes(&[i; 20]).unwrap())
+            .collect();
+        for (line, commit_index) in Arc::make_mut(&mut content.lines)
+            .iter_mut()
+            .zip([0, 0, 1, 1, 1, 0, 0, 1])
+        {
+            line.set_commit_id(commit_ids[commit_index]);
+        }
+        content.update_lines_after_apply();
+        let mut test = |line_index: usize| {
+            content.set_current_line_index(line_index);
+            (
+                content.diff_line_index(true),
+                content.diff_line_index(false),
+            )
+        };
+        assert_eq!(test(0), (None, Some(2)));
+        assert_eq!(test(1), (Some(0), Some(2)));
+        assert_eq!(test(2), (Some(0), Some(5)));
+        assert_eq!(test(3), (Some(2), Some(5)));
+        assert_eq!(test(6), (Some(5), Some(7)));
+        assert_eq!(test(7), (Some(5), None));
+    }
+
+    #[test]
+    fn hunk_range_at() {
+        let mut content = FileContent::new_for_test();
+        content.set_lines_len_for_test(8);
+        assert_eq!(content.hunk_range_at(3), Some(0..8));
+
+        let commit_ids: Vec<git2::Oid> = (1..=2u8)
+            .map(|i| git2::Oid::from_bytes(&[i; 20]).unwrap())
+            .collect();
+        for (line, commit_index) in Arc::make_mut(&mut content.lines)
+            .iter_mut()
+            .zip([0, 0, 1, 1, 1, 0])
+        {
+            line.set_commit_id(commit_ids[commit_index]);
+        }
+        content.update_lines_after_apply();
+        let ranges: Vec<Option<Range<usize>>> = (0..9).map(|i| content.hunk_range_at(i)).collect();
+        assert_eq!(
+            ranges,
+            [
+                Some(0..2),
+                Some(0..2),
+                Some(2..5),
+                Some(2..5),
+                Some(2..5),
+                Some(5..6),
+                Some(6..8),
+                Some(6..8),
+                None,
+            ]
+        );
+        assert_eq!(content.hunk_ranges().count(), 4);
+    }
+
+    #[test]
+    fn search_commit() -> anyhow::Result<()> {
+        let mut commits = FileCommits::new();
+        let commit_ids: Vec<git2::Oid> = (1..=3u8)
+            .map(|i| git2::Oid::from_bytes(&[i; 20]).unwrap())
+            .collect();
+        for (commit_id, author) in commit_ids.iter().zip(["alice", "bob", "alice"]) {
+            let mut commit = FileCommit::new(*commit_id, Path::new(""));
+            commit.set_metadata_for_test(author, &format!("Change by {author}"));
+            commits.push(commit);
+        }
+        let mut content = FileContent::new_for_test();
+        content.set_lines_len_for_test(8);
+        for (line, commit_index) in Arc::make_mut(&mut content.lines)
+            .iter_mut()
+            .zip([0, 0, 1, 1, 2, 2, 0, 1])
+        {
+            line.set_commit_id(commit_ids[commit_index]);
+        }
+        let search = SearchPattern::from_commit_query("author:ali")?;
+        // The consecutive lines of the same author are skipped.
+        assert_eq!(content.search(&search, &commits, false), Some(4));
+        content.set_current_line_index(4);
+        assert_eq!(content.search(&search, &commits, false), Some(0));
+        assert_eq!(content.search(&search, &commits, true), Some(0));
+        let search = SearchPattern::from_commit_query("msg:by bob")?;
+        assert_eq!(content.search(&search, &commits, false), Some(7));
+        Ok(())
+    }
+
+    #[test]
+    fn search_log() -> anyhow::Result<()> {
+        let mut commits = FileCommits::new();
+        for (i, author) in (1..=4u8).zip(["alice", "alice", "bob", "alice"]) {
+            let mut commit = FileCommit::new(git2::Oid::from_bytes(&[i; 20])?, Path::new(""));
+            commit.set_metadata_for_test(author, &format!("Change {i}"));
+            commits.push(commit);
+        }
+        let mut content = FileContent::new_log(git2::Oid::ZERO_SHA1, Path::new(""));
+        content.lines = Arc::new(commits.iter().map(|c| Line::new_log(c, "")).collect());
+        // Consecutive commits of the same author are not skipped in the log.
+        let search = SearchPattern::from_commit_query("author:alice")?;
+        assert_eq!(content.search(&search, &commits, false), Some(1));
+        // The content search matches the authors too.
+        let search = SearchPattern::new("bob");
+        assert_eq!(content.search(&search, &commits, false), Some(2));
+        let search = SearchPattern::new("change 4");
+        assert_eq!(content.search(&search, &commits, true), Some(3));
+
+        Arc::make_mut(&mut content.lines).remove(1);
+        content.set_current_line_number_or_next(1);
+        assert_eq!(content.current_line().line_number(), 2);
         Ok(())
     }
 }
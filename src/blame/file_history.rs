@@ -1,32 +1,141 @@
 use std::{
     cmp,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    ops::Range,
     path::{Path, PathBuf},
-    sync::mpsc,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc,
+    },
     thread,
+    time::{Duration, Instant},
 };
 
 use log::*;
 
-use crate::extensions::GitTools;
+use crate::extensions::{GitTools, add_current_thread_nice};
 
-use super::{CommitIterator, DiffPart, FileCommit, FileCommits, FileContent, LineNumberMap};
+#[cfg(feature = "async")]
+use super::FileCommitStream;
+use super::{
+    BlameError, BlameOptions, BlameSnapshot, CancellationToken, CommitIterator, CommitMetadata,
+    ContentCache, DiffPart, DiffPartStore, FileCommit, FileCommits, FileContent,
+    FileHistoryBuilder, GitEngine, HistoryProvider, LineNumberMap, MetadataProvider, ReadAhead,
+    ReadProgress,
+};
+
+/// The counters shared with the reading thread to compute `ReadProgress`.
+#[derive(Clone, Debug)]
+struct ReadProgressCounters {
+    discovered: Arc<AtomicUsize>,
+    diffed: Arc<AtomicUsize>,
+    start_time: Instant,
+    tx: Option<mpsc::Sender<ReadProgress>>,
+}
+
+impl ReadProgressCounters {
+    fn progress(&self) -> ReadProgress {
+        ReadProgress::new(
+            self.discovered.load(Ordering::Relaxed),
+            self.diffed.load(Ordering::Relaxed),
+            self.start_time.elapsed(),
+        )
+    }
+
+    fn set_discovered(&self, discovered: usize) {
+        self.discovered.store(discovered, Ordering::Relaxed);
+    }
+
+    fn add_diffed(&self) {
+        self.diffed.fetch_add(1, Ordering::Relaxed);
+        if let Some(tx) = &self.tx {
+            // The receiver may have been dropped; it shouldn't stop reading.
+            tx.send(self.progress()).ok();
+        }
+    }
+}
 
 pub struct FileHistory {
     path: PathBuf,
+    options: BlameOptions,
+    engine: GitEngine,
+    provider: Arc<dyn HistoryProvider>,
+    metadata_providers: Vec<Arc<dyn MetadataProvider>>,
     git: Option<GitTools>,
+    head_commit_id: Option<git2::Oid>,
+    start_commit_id: Option<git2::Oid>,
+    is_not_committed: bool,
+    /// The `GitTools::uncommitted_blob_ids()` when `read_start()` was called.
+    uncommitted_blob_ids: (Option<git2::Oid>, Option<git2::Oid>),
+    /// Shared with the histories created by `fork()`. Pushing to it copies
+    /// only the last chunk if it's shared. See `FileCommits`.
     commits: FileCommits,
-    read_thread: Option<thread::JoinHandle<anyhow::Result<()>>>,
+    /// The commits of the base history of `read_start_after()`, to add after
+    /// the commits read.
+    base_commits: Option<FileCommits>,
+    read_thread: Option<thread::JoinHandle<anyhow::Result<ReadProgress>>>,
     rx: Option<mpsc::Receiver<FileCommit>>,
+    metadata_thread: Option<thread::JoinHandle<anyhow::Result<()>>>,
+    metadata_rx: Option<mpsc::Receiver<(git2::Oid, Vec<CommitMetadata>)>>,
+    progress_tx: Option<mpsc::Sender<ReadProgress>>,
+    progress_counters: Option<ReadProgressCounters>,
+    progress: ReadProgress,
+    cancellation_token: CancellationToken,
+    content_cache: Mutex<ContentCache>,
+    /// The commits to diff before others. Shared with the reading thread.
+    priority_commit_ids: Arc<Mutex<HashSet<git2::Oid>>>,
+    /// The commits read ahead for the `set_priority_lines()`.
+    read_ahead: ReadAhead,
+    /// Whether the reading is paused. Shared with the reading thread.
+    is_read_paused: Arc<AtomicBool>,
+    /// The file to spill the diffs to, created when the
+    /// `BlameOptions::memory_budget()` is exceeded.
+    diff_part_store: Option<Arc<DiffPartStore>>,
 }
 
 impl FileHistory {
+    /// The history of the file at the `path` with the default options.
+    /// Use `FileHistoryBuilder` to set options.
     pub fn new(path: &Path) -> Self {
+        FileHistoryBuilder::new(path).build()
+    }
+
+    pub(super) fn new_with_options(
+        path: &Path,
+        options: BlameOptions,
+        engine: GitEngine,
+        provider: Arc<dyn HistoryProvider>,
+        metadata_providers: Vec<Arc<dyn MetadataProvider>>,
+        progress_tx: Option<mpsc::Sender<ReadProgress>>,
+        cancellation_token: CancellationToken,
+    ) -> Self {
         Self {
             path: path.to_path_buf(),
+            options,
+            engine,
+            provider,
+            metadata_providers,
             git: None,
+            head_commit_id: None,
+            start_commit_id: None,
+            is_not_committed: false,
+            uncommitted_blob_ids: (None, None),
             commits: FileCommits::new(),
+            base_commits: None,
             read_thread: None,
             rx: None,
+            metadata_thread: None,
+            metadata_rx: None,
+            progress_tx,
+            progress_counters: None,
+            progress: ReadProgress::default(),
+            cancellation_token,
+            content_cache: Mutex::new(ContentCache::new(ContentCache::DEFAULT_CAPACITY)),
+            priority_commit_ids: Arc::default(),
+            read_ahead: ReadAhead::default(),
+            is_read_paused: Arc::default(),
+            diff_part_store: None,
         }
     }
 
@@ -44,6 +153,31 @@ impl FileHistory {
         false
     }
 
+    /// The path of the file. This is relative to the working directory after
+    /// `read_start()`.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn options(&self) -> &BlameOptions {
+        &self.options
+    }
+
+    /// The engine to compute the diffs of commits and attribute lines.
+    pub fn git_engine(&self) -> GitEngine {
+        self.engine
+    }
+
+    /// The `HistoryProvider` to read the history.
+    pub fn provider(&self) -> &dyn HistoryProvider {
+        self.provider.as_ref()
+    }
+
+    /// The `provider()`, shared for other threads.
+    pub(crate) fn shared_provider(&self) -> Arc<dyn HistoryProvider> {
+        self.provider.clone()
+    }
+
     pub fn git(&self) -> &GitTools {
         self.git.as_ref().unwrap()
     }
@@ -64,20 +198,168 @@ impl FileHistory {
         &self.commits
     }
 
+    /// The commits read so far, which can be shared with other threads.
+    /// This is a snapshot sharing the commits without copying; the commits
+    /// read after this call are not included.
+    pub fn shared_commits(&self) -> FileCommits {
+        self.commits.clone()
+    }
+
+    /// A new `FileHistory` with the commits read so far, sharing them without
+    /// copying. The new history doesn't continue reading.
+    ///
+    /// `FileHistory` is `Send`, so the new history can be moved to other
+    /// threads to query while this history continues reading.
+    pub fn fork(&self) -> Result<FileHistory, BlameError> {
+        let git = match &self.git {
+            Some(git) => Some(GitTools::from_repository_path(git.repository_path())?),
+            None => None,
+        };
+        Ok(Self {
+            path: self.path.clone(),
+            options: self.options.clone(),
+            engine: self.engine,
+            provider: self.provider.clone(),
+            metadata_providers: self.metadata_providers.clone(),
+            git,
+            head_commit_id: self.head_commit_id,
+            start_commit_id: self.start_commit_id,
+            is_not_committed: self.is_not_committed,
+            uncommitted_blob_ids: self.uncommitted_blob_ids,
+            commits: self.commits.clone(),
+            base_commits: None,
+            read_thread: None,
+            rx: None,
+            metadata_thread: None,
+            metadata_rx: None,
+            progress_tx: None,
+            progress_counters: None,
+            progress: self.read_progress(),
+            cancellation_token: CancellationToken::new(),
+            content_cache: Mutex::new(ContentCache::new(ContentCache::DEFAULT_CAPACITY)),
+            priority_commit_ids: Arc::default(),
+            read_ahead: ReadAhead::default(),
+            is_read_paused: Arc::default(),
+            diff_part_store: None,
+        })
+    }
+
+    /// A `FileHistoryBuilder` for a new history of the same file, with the
+    /// same options, engine, and providers; e.g., to reload the history after
+    /// the file or the repository has changed.
+    pub fn to_builder(&self) -> FileHistoryBuilder {
+        let path = match &self.git {
+            Some(git) => git.workdir_path().join(&self.path),
+            None => self.path.clone(),
+        };
+        let mut builder = FileHistoryBuilder::new(&path)
+            .options(self.options.clone())
+            .engine(self.engine)
+            .provider(self.provider.clone());
+        for provider in &self.metadata_providers {
+            builder = builder.metadata_provider(provider.clone());
+        }
+        if let Some(tx) = &self.progress_tx {
+            builder = builder.progress(tx.clone());
+        }
+        builder
+    }
+
     pub fn commit(&self, index: usize) -> &FileCommit {
         &self.commits[index]
     }
 
+    /// The commit of the `commit_id` in `commits()`, or read ahead of them for
+    /// the `set_priority_lines()`.
+    pub fn commit_by_id(&self, commit_id: git2::Oid) -> Result<&FileCommit, BlameError> {
+        match self.read_ahead.commit(commit_id) {
+            Some(commit) => Ok(commit),
+            None => self.commits.get_by_commit_id(commit_id),
+        }
+    }
+
+    /// The number of the commits, and the blames, received for the
+    /// `set_priority_lines()` so far. When this increases, lines may be
+    /// attributed by `FileContent::update_commits()` even if `commits()`
+    /// doesn't increase.
+    pub fn read_ahead_len(&self) -> usize {
+        self.read_ahead.received_len()
+    }
+
+    /// The commit read ahead of `commits()` for the `set_priority_lines()`,
+    /// if it's not in `commits()` yet.
+    pub(super) fn read_ahead_commit(&self, commit_id: git2::Oid) -> Option<&FileCommit> {
+        self.read_ahead.commit(commit_id)
+    }
+
+    /// The commit IDs and the original line numbers, keyed by the line numbers
+    /// of the `path` at the `commit_id`, blamed for the `set_priority_lines()`.
+    /// This is `None` if no commits are read ahead.
+    pub(super) fn priority_blame(
+        &self,
+        commit_id: git2::Oid,
+        path: &Path,
+    ) -> Option<&HashMap<usize, (git2::Oid, usize)>> {
+        self.read_ahead.blame(commit_id, path)
+    }
+
+    /// The newest commit in this history that is the `commit_id`,
+    /// or an ancestor of the `commit_id`;
+    /// i.e., the commit that the file at the `commit_id` was last changed.
+    ///
+    /// Fails if the file doesn't exist at the `commit_id`.
+    pub fn commit_at_or_before(&self, commit_id: git2::Oid) -> Result<&FileCommit, BlameError> {
+        let repository = self.git().repository();
+        for commit in self.commits.iter() {
+            let candidate_id = commit.commit_id();
+            if candidate_id == commit_id
+                || repository.graph_descendant_of(commit_id, candidate_id)?
+            {
+                let tree = repository.find_commit(commit_id)?.tree()?;
+                if tree.get_path(commit.path()).is_err() {
+                    break;
+                }
+                return Ok(commit);
+            }
+        }
+        Err(BlameError::FileNotFound {
+            path: self.path.clone(),
+            commit_id,
+        })
+    }
+
+    /// The newest commit in this history that is the parent of the commit at
+    /// `index`, or an ancestor of the parent.
+    ///
+    /// This is not always the commit at `index + 1`, if the history has merges.
+    pub fn parent_commit(&self, index: usize) -> Result<&FileCommit, BlameError> {
+        let commit = self.commit(index);
+        let commit_id = commit.commit_id();
+        let Some(parent_commit_id) = commit.parent_commit_id() else {
+            return Err(BlameError::NoCommitsBefore(commit_id));
+        };
+        let repository = self.git().repository();
+        for candidate in self.commits.range(index + 1..) {
+            let candidate_id = candidate.commit_id();
+            if candidate_id == parent_commit_id
+                || repository.graph_descendant_of(parent_commit_id, candidate_id)?
+            {
+                return Ok(candidate);
+            }
+        }
+        Err(BlameError::NoCommitsBefore(commit_id))
+    }
+
     pub fn map_line_number_by_commit_ids(
         &self,
         line_number: usize,
         new_commit_id: git2::Oid,
         old_commit_id: git2::Oid,
-    ) -> anyhow::Result<usize> {
+    ) -> Result<usize, BlameError> {
         assert!(old_commit_id != new_commit_id);
         let current_index = self.commits.index_from_commit_id(old_commit_id)?;
         let new_index = self.commits.index_from_commit_id(new_commit_id)?;
-        Ok(self.map_line_number_by_commit_indexes(line_number, new_index, current_index))
+        self.map_line_number_by_commit_indexes(line_number, new_index, current_index)
     }
 
     pub fn map_line_number_by_commit_indexes(
@@ -85,25 +367,56 @@ impl FileHistory {
         line_number: usize,
         new_index: usize,
         old_index: usize,
-    ) -> usize {
+    ) -> Result<usize, BlameError> {
         let new_line_number = match new_index.cmp(&old_index) {
             cmp::Ordering::Less => self.map_line_number_by_commit_index_iterator(
                 line_number,
                 (new_index..old_index).rev(),
                 LineNumberMap::new_new_from_old,
-            ),
+            )?,
             cmp::Ordering::Greater => self.map_line_number_by_commit_index_iterator(
                 line_number,
                 old_index..new_index,
                 LineNumberMap::new_old_from_new,
-            ),
+            )?,
             cmp::Ordering::Equal => unreachable!("new and current should not be equal"),
         };
         debug!(
             "map_line_number_by_indexes: {line_number}@{old_index} \
             -> {new_line_number}@{new_index}"
         );
-        new_line_number
+        Ok(new_line_number)
+    }
+
+    /// The `LineNumberMap` from the line numbers at the `old_index` commit
+    /// to the `new_index` commit. This maps the same as
+    /// `map_line_number_by_commit_indexes()`, but faster for many line numbers.
+    pub fn line_number_map_by_commit_indexes(
+        &self,
+        new_index: usize,
+        old_index: usize,
+    ) -> Result<LineNumberMap, BlameError> {
+        let maps: Vec<LineNumberMap> = match new_index.cmp(&old_index) {
+            cmp::Ordering::Less => (new_index..old_index)
+                .rev()
+                .map(|index| {
+                    Ok(LineNumberMap::new_new_from_old(
+                        &*self.commit(index).diff_parts()?,
+                    ))
+                })
+                .collect::<anyhow::Result<_>>()?,
+            cmp::Ordering::Greater => (old_index..new_index)
+                .map(|index| {
+                    Ok(LineNumberMap::new_old_from_new(
+                        &*self.commit(index).diff_parts()?,
+                    ))
+                })
+                .collect::<anyhow::Result<_>>()?,
+            cmp::Ordering::Equal => Vec::new(),
+        };
+        Ok(maps
+            .iter()
+            .fold(LineNumberMap::default(), |map, next| map.compose(next)))
     }
 
     fn map_line_number_by_commit_index_iterator(
@@ -111,68 +424,783 @@ impl FileHistory {
         line_number: usize,
         indexes: impl Iterator<Item = usize>,
         get_line_number_map: fn(&Vec<DiffPart>) -> LineNumberMap,
-    ) -> usize {
+    ) -> anyhow::Result<usize> {
         let mut new_line_number = line_number;
         for index in indexes {
             let commit = self.commit(index);
-            let line_number_map = get_line_number_map(commit.diff_parts());
+            let line_number_map = get_line_number_map(&*commit.diff_parts()?);
             new_line_number = line_number_map.map(new_line_number);
         }
         debug!("map_line_number: {line_number} -> {new_line_number}");
-        new_line_number
+        Ok(new_line_number)
+    }
+
+    /// The commits that changed any of the `line_numbers` in the newest commit,
+    /// the newest first, like `git log -L`.
+    /// Each item has the line numbers of the range in the commit,
+    /// which may grow or shrink as lines are added or deleted in the range.
+    ///
+    /// The range is followed until all its lines were added,
+    /// or until the commits read so far.
+    pub fn commits_changing_lines(
+        &self,
+        line_numbers: Range<usize>,
+    ) -> Result<Vec<(&FileCommit, Range<usize>)>, BlameError> {
+        let mut results = vec![];
+        let mut line_numbers = line_numbers;
+        for commit in self.commits.iter() {
+            if line_numbers.is_empty() {
+                break;
+            }
+            let parts = commit.diff_parts()?;
+            if Self::is_range_changed(&parts, &line_numbers) {
+                results.push((commit, line_numbers.clone()));
+            }
+            if commit.parent_commit_id().is_none() {
+                break;
+            }
+            line_numbers = Self::map_range_to_old(&parts, &line_numbers);
+        }
+        Ok(results)
+    }
+
+    /// Whether any of the `parts` adds, modifies, or deletes lines in the
+    /// `line_numbers` of the new side.
+    fn is_range_changed(parts: &[DiffPart], line_numbers: &Range<usize>) -> bool {
+        parts.iter().any(|part| {
+            let new = part.new.line_numbers();
+            if new.is_empty() {
+                // Lines deleted between `new.start - 1` and `new.start`.
+                line_numbers.start < new.start && new.start < line_numbers.end
+            } else {
+                new.start < line_numbers.end && line_numbers.start < new.end
+            }
+        })
+    }
+
+    /// Map the `line_numbers` of the new side to the old side.
+    /// Lines in a part expand to the whole old lines of the part,
+    /// and lines added by a part shrink the range.
+    fn map_range_to_old(parts: &[DiffPart], line_numbers: &Range<usize>) -> Range<usize> {
+        let map_line_number = |mut line_number: usize, is_end: bool| -> usize {
+            let mut delta: isize = 0;
+            for part in parts {
+                let new = part.new.line_numbers();
+                if new.contains(&line_number) {
+                    let old = part.old.line_numbers();
+                    if !old.is_empty() {
+                        return if is_end { old.end } else { old.start };
+                    }
+                    // The line was added. Map the line before or after the
+                    // added lines instead.
+                    if is_end {
+                        return new.start.saturating_add_signed(-delta);
+                    }
+                    line_number = new.end;
+                } else if new.end > line_number {
+                    break;
+                }
+                delta += new.len() as isize - part.old.len() as isize;
+            }
+            line_number.saturating_add_signed(-delta) + usize::from(is_end)
+        };
+        let start = map_line_number(line_numbers.start, false);
+        let end = map_line_number(line_numbers.end - 1, true);
+        start..cmp::max(start, end)
+    }
+
+    /// The commit that last changed the line at the `line_number` of the file
+    /// at the `rev`, like `git blame -L N,N <rev>`.
+    ///
+    /// This maps the line number through the diffs of the commits read so
+    /// far, without computing the whole `FileContent`. The ignored commits
+    /// and the moved lines are handled in the same way as `content()`.
+    pub fn blame_line(&self, rev: &str, line_number: usize) -> Result<&FileCommit, BlameError> {
+        let commit_id = self.git().commit_id_from_rev(rev)?;
+        let commit = self.commit_at_or_before(commit_id)?;
+        let lines_len = self
+            .provider
+            .content(self.git(), commit_id, commit.path())?
+            .lines()
+            .count();
+        if line_number == 0 || line_number > lines_len {
+            return Err(BlameError::InvalidLineNumber(line_number));
+        }
+        let mut old_line_number = line_number;
+        for commit in self.commits.range(commit.index()..) {
+            let parts = commit.diff_parts()?;
+            let map = LineNumberMap::new_old_from_new(&parts);
+            match commit.old_line_number(old_line_number, &parts, &map) {
+                Some(next_line_number) => old_line_number = next_line_number,
+                None => {
+                    debug!(
+                        "blame_line: {line_number}@{rev} -> {old_line_number}@{}",
+                        commit.commit_id()
+                    );
+                    return Ok(commit);
+                }
+            }
+        }
+        Err(BlameError::NotAttributed(line_number))
+    }
+
+    /// The `HEAD` commit when `read_start()` was called.
+    pub fn head_commit_id(&self) -> Option<git2::Oid> {
+        self.head_commit_id
+    }
+
+    /// Whether the `HEAD`, or the file in the index or in the working
+    /// directory, has changed since `read_start()` was called.
+    pub fn has_changed(&self) -> Result<bool, BlameError> {
+        let git = self.git();
+        let head_commit_id = if git.is_head_unborn() {
+            None
+        } else {
+            Some(git.head_commit_id()?)
+        };
+        Ok(head_commit_id != self.head_commit_id
+            || git.uncommitted_blob_ids(&self.path)? != self.uncommitted_blob_ids)
+    }
+
+    /// The commit the history starts from when `read_start()` was called.
+    /// This is the `HEAD`, unless `BlameOptions::set_at()` is set.
+    pub fn start_commit_id(&self) -> Option<git2::Oid> {
+        self.start_commit_id
     }
 
+    /// Whether the file is not committed yet when `read_start()` was called;
+    /// i.e., the repository doesn't have any commits, or the file is only in
+    /// the index or in the working directory.
+    ///
+    /// The history is then empty, and the `content()` is read from the index,
+    /// or from the working directory if the file is not in the index.
+    pub fn is_not_committed(&self) -> bool {
+        self.is_not_committed
+    }
+
+    /// Whether the reading thread, or the thread to read the `CommitMetadata`,
+    /// is running.
     pub fn is_reading(&self) -> bool {
-        self.read_thread.is_some()
+        self.read_thread.is_some() || self.metadata_thread.is_some()
     }
 
-    pub fn read_start(&mut self) -> anyhow::Result<()> {
-        self.ensure_git()?;
-        let path = self.path.clone();
-        let repository_path = self.git().repository_path().to_path_buf();
-        debug!("path: {path:?}, repo: {repository_path:?}");
+    /// The progress of the reading started by `read_start()`.
+    /// The `elapsed()` stops when the reading is completed.
+    pub fn read_progress(&self) -> ReadProgress {
+        if let Some(counters) = &self.progress_counters {
+            return counters.progress();
+        }
+        self.progress
+    }
+
+    /// Diff the `commit_ids` before other commits while reading, such as the
+    /// commits blamed on the visible lines, replacing the previous ones.
+    ///
+    /// The commits are still added to `commits()` in order, but they are
+    /// diffed ahead of the newer commits, so they are ready as soon as the
+    /// newer commits are.
+    pub fn set_priority_commit_ids(&self, commit_ids: HashSet<git2::Oid>) {
+        *self.priority_commit_ids.lock().unwrap() = commit_ids;
+    }
+
+    /// Attribute the `line_numbers` of the `path` at the `commit_id` before
+    /// other lines while reading, such as the visible lines not attributed
+    /// yet, replacing the previous ones.
+    ///
+    /// The lines are blamed in a thread, and the commits blamed on them are
+    /// diffed first as in `set_priority_commit_ids()`. They're available by
+    /// `commit_by_id()` before they're added to `commits()`, so that
+    /// `FileContent::update_commits()` can attribute the lines to them ahead of
+    /// the newer commits.
+    pub fn set_priority_lines(
+        &mut self,
+        commit_id: git2::Oid,
+        path: &Path,
+        line_numbers: Range<usize>,
+    ) {
+        self.read_ahead.set_lines(commit_id, path, line_numbers);
+    }
+
+    /// Whether the reading is paused by `set_read_paused()`.
+    pub fn is_read_paused(&self) -> bool {
+        self.is_read_paused.load(Ordering::Relaxed)
+    }
+
+    /// Pause or resume reading. While paused, the reading thread doesn't
+    /// start reading new commits, so that it doesn't slow other processes.
+    /// The commits being read are still completed.
+    pub fn set_read_paused(&self, is_paused: bool) {
+        debug!("set_read_paused: {is_paused}");
+        self.is_read_paused.store(is_paused, Ordering::Relaxed);
+    }
+
+    /// Start reading the history in a thread.
+    /// Call `read_poll()` to add the commits read so far to `commits()`,
+    /// and to attach the `CommitMetadata` read so far.
+    pub fn read_start(&mut self) -> Result<(), BlameError> {
+        Ok(self.read_start_from(None)?)
+    }
+
+    /// Same as `read_start()`, except that only the commits since the `base`
+    /// are read, and the commits of the `base` are added after them, if the
+    /// `base` has completed reading the same file with the same options, and
+    /// the start commit is the same as or a descendant of the start commit of
+    /// the `base`; e.g., when new commits are added after the `base` was read.
+    /// Otherwise the whole history is read.
+    pub fn read_start_after(&mut self, base: &FileHistory) -> Result<(), BlameError> {
+        Ok(self.read_start_from(Some(base))?)
+    }
+
+    fn read_start_from(&mut self, base: Option<&FileHistory>) -> anyhow::Result<()> {
         let (tx, rx) = mpsc::channel::<FileCommit>();
         self.rx = Some(rx);
+        let Some(metadata_tx) = self.metadata_start()? else {
+            return self.read_start_with(base, move |commit| Ok(tx.send(commit)?));
+        };
+        self.read_start_with(base, move |commit| {
+            // Send to `rx` first, so that `read_poll()` can find the commit
+            // when its metadata is ready.
+            let metadata_commit = commit.clone();
+            tx.send(commit)?;
+            // The metadata thread may have been stopped by `read_cancel()`.
+            metadata_tx.send(metadata_commit).ok();
+            Ok(())
+        })
+    }
+
+    /// Start the thread to read the `CommitMetadata` by the
+    /// `metadata_providers`, if any. Returns the sender of the commits to read.
+    fn metadata_start(&mut self) -> anyhow::Result<Option<mpsc::Sender<FileCommit>>> {
+        if self.metadata_providers.is_empty() {
+            return Ok(None);
+        }
+        self.ensure_git()?;
+        let (commit_tx, commit_rx) = mpsc::channel::<FileCommit>();
+        let (tx, rx) = mpsc::channel();
+        self.metadata_rx = Some(rx);
+        let repository_path = self.git().repository_path().to_path_buf();
+        let providers = self.metadata_providers.clone();
+        let cancellation_token = self.cancellation_token.clone();
+        self.metadata_thread = Some(thread::spawn(move || {
+            let git = GitTools::from_repository_path(&repository_path)?;
+            for commit in commit_rx {
+                if cancellation_token.is_cancelled() {
+                    break;
+                }
+                let mut metadata = Vec::new();
+                for provider in &providers {
+                    match provider.metadata(&git, &commit) {
+                        Ok(items) => metadata.extend(items),
+                        Err(error) => warn!(
+                            "Failed to read the metadata of {}: {error}",
+                            commit.commit_id()
+                        ),
+                    }
+                }
+                if !metadata.is_empty() && tx.send((commit.commit_id(), metadata)).is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        }));
+        Ok(Some(commit_tx))
+    }
+
+    /// Start reading the history in a thread, same as `read_start()`,
+    /// and return an async `Stream` of the commits.
+    /// `read_poll()` and `read_wait()` are not needed with the stream.
+    #[cfg(feature = "async")]
+    pub fn read_stream(&mut self) -> Result<FileCommitStream<'_>, BlameError> {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<FileCommit>();
+        self.rx = None;
+        self.read_start_with(None, move |commit| Ok(tx.send(commit)?))?;
+        Ok(FileCommitStream::new(self, rx))
+    }
+
+    #[cfg(feature = "async")]
+    pub(super) fn push_commit(&mut self, commit: FileCommit) -> &FileCommit {
+        self.commits.push(commit);
+        self.commit(self.commits.len() - 1)
+    }
+
+    /// Start the reading thread, which calls `send` for each commit read.
+    /// See `read_start_after()` for the `base`.
+    fn read_start_with(
+        &mut self,
+        base: Option<&FileHistory>,
+        send: impl Fn(FileCommit) -> anyhow::Result<()> + Send + 'static,
+    ) -> anyhow::Result<()> {
+        self.ensure_git()?;
+        self.uncommitted_blob_ids = self.git().uncommitted_blob_ids(&self.path)?;
+        if self.git().is_head_unborn() {
+            debug!("read_start: no commits in the repository");
+            self.is_not_committed = true;
+            return Ok(());
+        }
+        let head_commit_id = self.git().head_commit_id()?;
+        self.head_commit_id = Some(head_commit_id);
+        let start_commit_id = self.provider.start_commit_id(self.git(), &self.options)?;
+        // If the `rev` or the `at` is specified, the file should be there.
+        if self.options.rev().is_none()
+            && self.options.at().is_none()
+            && !self.git().has_path(start_commit_id, &self.path)?
+        {
+            debug!("read_start: {:?} is not committed", self.path);
+            self.is_not_committed = true;
+            return Ok(());
+        }
+        self.start_commit_id = Some(start_commit_id);
+        let mut options = self.options.clone();
+        if let Some(base) = base
+            && let Some(base_commit_id) = self.incremental_base_commit_id(base, start_commit_id)?
+        {
+            if base_commit_id == start_commit_id {
+                debug!("read_start: same as the base");
+                self.commits = base.commits.clone();
+                self.progress = base.read_progress();
+                return Ok(());
+            }
+            debug!("read_start: after the base {base_commit_id}");
+            options.set_exclude_commit_id(Some(base_commit_id));
+            self.base_commits = Some(base.commits.clone());
+        }
+        let repository_path = self.git().repository_path().to_path_buf();
+        debug!(
+            "path: {:?}, repo: {repository_path:?}, start: {start_commit_id}",
+            self.path
+        );
+        let progress_counters = ReadProgressCounters {
+            discovered: Arc::default(),
+            diffed: Arc::default(),
+            start_time: Instant::now(),
+            tx: self.progress_tx.clone(),
+        };
+        self.progress_counters = Some(progress_counters.clone());
+        let provider = self.provider.clone();
+        let ignore_commit_ids = options.ignore_commit_ids(self.git())?;
+        let cancellation_token = self.cancellation_token.clone();
+        let priority_commit_ids = self.priority_commit_ids.clone();
+        self.read_ahead = ReadAhead::start(
+            self.git(),
+            ignore_commit_ids.clone(),
+            priority_commit_ids.clone(),
+            cancellation_token.clone(),
+        );
+        let read_ahead_tx = self.read_ahead.commit_tx();
+        let is_read_paused = self.is_read_paused.clone();
+        let path = self.path.clone();
         self.read_thread = Some(thread::spawn(move || {
-            Self::read_thread(&path, &repository_path, tx)
+            if let Err(error) = Self::set_thread_nice(&options) {
+                warn!("Failed to set the niceness: {error}");
+            }
+            Self::read_thread(
+                &repository_path,
+                path,
+                start_commit_id,
+                &options,
+                provider.as_ref(),
+                &ignore_commit_ids,
+                &priority_commit_ids,
+                &is_read_paused,
+                send,
+                read_ahead_tx,
+                progress_counters,
+                &cancellation_token,
+            )
         }));
         Ok(())
     }
 
-    pub fn read_join(&mut self) -> anyhow::Result<()> {
+    /// The start commit of the `base` if the history from the
+    /// `start_commit_id` is the commits since it, followed by the commits of
+    /// the `base`. See `read_start_after()`.
+    fn incremental_base_commit_id(
+        &self,
+        base: &FileHistory,
+        start_commit_id: git2::Oid,
+    ) -> anyhow::Result<Option<git2::Oid>> {
+        let Some(base_commit_id) = base.start_commit_id else {
+            return Ok(None);
+        };
+        if base.is_reading()
+            || base.cancellation_token.is_cancelled()
+            || base.is_not_committed
+            || base.path != self.path
+            || base.options != self.options
+            || !Arc::ptr_eq(&base.provider, &self.provider)
+        {
+            return Ok(None);
+        }
+        if start_commit_id != base_commit_id
+            && !self
+                .git()
+                .repository()
+                .graph_descendant_of(start_commit_id, base_commit_id)?
+        {
+            return Ok(None);
+        }
+        Ok(Some(base_commit_id))
+    }
+
+    /// Wait for the reading thread, and the thread to read the
+    /// `CommitMetadata`, to finish.
+    pub fn read_join(&mut self) -> Result<(), BlameError> {
+        self.read_join_thread()?;
+        Ok(self.metadata_join()?)
+    }
+
+    fn metadata_join(&mut self) -> anyhow::Result<()> {
+        if let Some(metadata_thread) = self.metadata_thread.take() {
+            metadata_thread.join().unwrap()?; // TODO: handle error
+        }
+        Ok(())
+    }
+
+    fn read_join_thread(&mut self) -> anyhow::Result<()> {
         if let Some(read_thread) = self.read_thread.take() {
-            read_thread.join().unwrap()?; // TODO: handle error
+            self.read_ahead.stop();
+            self.progress = read_thread.join().unwrap()?; // TODO: handle error
+            self.progress_counters = None;
+        }
+        Ok(())
+    }
+
+    /// Read the whole history, and return when the reading is completed.
+    /// This is the same as `read_start()`, `read_join()`, and `read_poll()`.
+    pub fn read_all(&mut self) -> Result<(), BlameError> {
+        self.read_all_with(None, |_| {})
+    }
+
+    /// Same as `read_all()`, calling `on_progress` periodically while reading,
+    /// and once when it's completed.
+    ///
+    /// If the reading doesn't complete in the `timeout`, it's cancelled and
+    /// `BlameError::Timeout` is returned. The commits read so far are kept.
+    pub fn read_all_with(
+        &mut self,
+        timeout: Option<Duration>,
+        mut on_progress: impl FnMut(ReadProgress),
+    ) -> Result<(), BlameError> {
+        const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+        let start_time = Instant::now();
+        self.read_start()?;
+        while let Some(rx) = self.rx.as_ref() {
+            let wait = match timeout {
+                Some(timeout) => {
+                    let remaining = timeout.saturating_sub(start_time.elapsed());
+                    if remaining.is_zero() {
+                        self.read_cancel()?;
+                        self.read_poll()?;
+                        on_progress(self.read_progress());
+                        return Err(BlameError::Timeout(timeout));
+                    }
+                    cmp::min(remaining, PROGRESS_INTERVAL)
+                }
+                None => PROGRESS_INTERVAL,
+            };
+            let applied_len = self.commits.len();
+            match rx.recv_timeout(wait) {
+                Ok(commit) => self.commits.push(commit),
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    self.read_join()?;
+                    break;
+                }
+            }
+            self.read_poll_after(applied_len)?;
+            on_progress(self.read_progress());
         }
+        self.read_join()?;
+        self.read_poll()?;
+        on_progress(self.read_progress());
         Ok(())
     }
 
+    /// Stop reading, and wait for the reading thread to finish.
+    /// The commits read so far are kept, and can be read by `read_poll()`.
+    /// Further reading after this is not possible.
+    pub fn read_cancel(&mut self) -> Result<(), BlameError> {
+        if !self.is_reading() {
+            return Ok(());
+        }
+        debug!("read_cancel: {}", self.read_progress());
+        self.cancellation_token.cancel();
+        self.read_join()
+    }
+
+    /// Read the diffs of the `commits` in the `BlameOptions::jobs()` worker
+    /// threads, and `send` them in the order of the `commits`.
+    ///
+    /// The `priority_commit_ids` within `PRIORITY_LOOK_AHEAD` commits are
+    /// dispatched to the workers first, and sent to the `read_ahead_tx` if
+    /// they're read before the older commits.
+    #[allow(clippy::too_many_arguments)]
     fn read_thread(
-        path: &Path,
         repository_path: &Path,
-        tx: mpsc::Sender<FileCommit>,
-    ) -> anyhow::Result<()> {
-        let start_time = std::time::Instant::now();
-        let mut commits = CommitIterator::new(path, repository_path);
-        commits.start()?;
+        path: PathBuf,
+        start_commit_id: git2::Oid,
+        options: &BlameOptions,
+        provider: &dyn HistoryProvider,
+        ignore_commit_ids: &HashSet<git2::Oid>,
+        priority_commit_ids: &Mutex<HashSet<git2::Oid>>,
+        is_read_paused: &AtomicBool,
+        send: impl Fn(FileCommit) -> anyhow::Result<()>,
+        read_ahead_tx: Option<mpsc::Sender<FileCommit>>,
+        progress_counters: ReadProgressCounters,
+        cancellation_token: &CancellationToken,
+    ) -> anyhow::Result<ReadProgress> {
         let git = GitTools::from_repository_path(repository_path)?;
-        let mut path = path.to_path_buf();
-        for commit_id in &mut commits {
-            trace!("Commit ID: {commit_id:?}, Path: {path:?}");
-            let mut diff = FileCommit::new(commit_id, &path);
-            diff.read(&git)?;
-            if let Some(old_path) = diff.old_path_if_rename() {
-                debug!("read_thread: rename detected {old_path:?} -> {path:?}");
-                path = old_path.to_path_buf();
+        let mut commits =
+            provider.commits(&git, &path, options, start_commit_id, cancellation_token)?;
+        let jobs = options.jobs();
+        debug!("read_thread: {jobs} jobs");
+        let (job_tx, job_rx) = mpsc::channel::<(usize, FileCommit)>();
+        let job_rx = Mutex::new(job_rx);
+        thread::scope(|scope| -> anyhow::Result<()> {
+            // Move the `job_tx` in, to stop the workers when this returns.
+            let job_tx = job_tx;
+            let (result_tx, result_rx) = mpsc::channel();
+            for _ in 0..jobs {
+                let result_tx = result_tx.clone();
+                let job_rx = &job_rx;
+                scope.spawn(move || {
+                    // The error is reported by the reading thread.
+                    Self::set_thread_nice(options).ok();
+                    Self::read_worker(repository_path, options, provider, job_rx, result_tx)
+                });
+            }
+            drop(result_tx);
+
+            // Keep the workers busy, while limiting the results waiting for
+            // the older commits.
+            let max_in_flight = jobs * 2;
+            let mut path = path;
+            let mut results: BTreeMap<usize, anyhow::Result<FileCommit>> = BTreeMap::new();
+            // The commits read from `commits` but not dispatched in order yet,
+            // and their indexes.
+            let mut pending: VecDeque<(usize, git2::Oid)> = VecDeque::new();
+            // The indexes in the `pending` dispatched ahead for the priority.
+            let mut dispatched_ahead: HashSet<usize> = HashSet::new();
+            // The indexes dispatched ahead, until their results are received.
+            let mut reading_ahead: HashSet<usize> = HashSet::new();
+            let mut read = 0;
+            let mut dispatched: usize = 0;
+            let mut sent = 0;
+            let mut has_more = true;
+            loop {
+                if cancellation_token.is_cancelled() {
+                    has_more = false;
+                    pending.clear();
+                }
+                progress_counters.set_discovered(read + commits.size_hint().0);
+                let is_paused = is_read_paused.load(Ordering::Relaxed);
+                if !is_paused && !priority_commit_ids.lock().unwrap().is_empty() {
+                    while has_more && pending.len() < Self::PRIORITY_LOOK_AHEAD {
+                        let Some(commit) = commits.next().transpose()? else {
+                            has_more = false;
+                            break;
+                        };
+                        pending.push_back((read, commit.commit_id()));
+                        read += 1;
+                    }
+                    let priority_commit_ids = priority_commit_ids.lock().unwrap();
+                    for (index, commit_id) in &pending {
+                        if priority_commit_ids.contains(commit_id)
+                            && dispatched_ahead.insert(*index)
+                        {
+                            reading_ahead.insert(*index);
+                            trace!("Commit ID: {commit_id:?}, Path: {path:?}, priority");
+                            job_tx.send((*index, FileCommit::new(*commit_id, &path)))?;
+                        }
+                    }
+                }
+                // The results dispatched ahead may be sent before they're
+                // dispatched in order, making `sent` larger than `dispatched`.
+                while !is_paused && dispatched.saturating_sub(sent) < max_in_flight {
+                    let (index, commit_id) = match pending.pop_front() {
+                        Some(pending) => pending,
+                        None => {
+                            let commit = has_more.then(|| commits.next()).flatten();
+                            let Some(commit) = commit.transpose()? else {
+                                has_more = false;
+                                break;
+                            };
+                            read += 1;
+                            (read - 1, commit.commit_id())
+                        }
+                    };
+                    debug_assert_eq!(index, dispatched);
+                    if !dispatched_ahead.remove(&index) {
+                        trace!("Commit ID: {commit_id:?}, Path: {path:?}");
+                        job_tx.send((index, FileCommit::new(commit_id, &path)))?;
+                    }
+                    dispatched += 1;
+                }
+                if sent >= dispatched {
+                    if is_paused && (has_more || !pending.is_empty()) {
+                        thread::sleep(Self::PAUSE_INTERVAL);
+                        continue;
+                    }
+                    break;
+                }
+
+                // Check the cancellation while waiting for slow diffs.
+                let (index, result) = loop {
+                    match result_rx.recv_timeout(CommitIterator::CANCEL_CHECK_INTERVAL) {
+                        Ok(result) => break result,
+                        Err(mpsc::RecvTimeoutError::Timeout) => {
+                            if cancellation_token.is_cancelled() {
+                                return Ok(());
+                            }
+                            progress_counters.set_discovered(read + commits.size_hint().0);
+                        }
+                        Err(error) => return Err(error.into()),
+                    }
+                };
+                if reading_ahead.remove(&index)
+                    && index > sent
+                    && let (Some(read_ahead_tx), Ok(commit)) = (&read_ahead_tx, &result)
+                {
+                    let mut commit = commit.clone();
+                    commit.set_index(index);
+                    commit.set_ignored(ignore_commit_ids.contains(&commit.commit_id()));
+                    // The receiver may have been dropped; it shouldn't stop reading.
+                    read_ahead_tx.send(commit).ok();
+                }
+                results.insert(index, result);
+                while let Some(result) = results.remove(&sent) {
+                    let mut diff = result?;
+                    if diff.path() != path {
+                        // The path was renamed by a commit read after this
+                        // commit was dispatched. Read it again with the new path.
+                        debug!("read_thread: re-read {} for {path:?}", diff.commit_id());
+                        diff = FileCommit::new(diff.commit_id(), &path);
+                        diff.read_with_provider(&git, options, provider)?;
+                    }
+                    diff.set_ignored(ignore_commit_ids.contains(&diff.commit_id()));
+                    if let Some(old_path) = diff.old_path_if_rename() {
+                        debug!("read_thread: rename detected {old_path:?} -> {path:?}");
+                        path = old_path.to_path_buf();
+                    }
+                    send(diff)?;
+                    progress_counters.add_diffed();
+                    sent += 1;
+                }
+            }
+            Ok(())
+        })?;
+        if cancellation_token.is_cancelled() {
+            debug!("read_thread: cancelled");
+        }
+        let progress = progress_counters.progress();
+        info!("read_thread: {progress}");
+        Ok(progress)
+    }
+
+    /// The interval to check whether the reading is resumed.
+    const PAUSE_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Apply the `BlameOptions::nice()` to the current thread.
+    fn set_thread_nice(options: &BlameOptions) -> anyhow::Result<()> {
+        match options.nice() {
+            Some(nice) => add_current_thread_nice(nice),
+            None => Ok(()),
+        }
+    }
+
+    /// The number of the commits to look ahead for the
+    /// `set_priority_commit_ids()`.
+    const PRIORITY_LOOK_AHEAD: usize = 1000;
+
+    /// Read the diffs of the commits from the `jobs` until it's closed, and
+    /// send them with their indexes to the `results`.
+    fn read_worker(
+        repository_path: &Path,
+        options: &BlameOptions,
+        provider: &dyn HistoryProvider,
+        jobs: &Mutex<mpsc::Receiver<(usize, FileCommit)>>,
+        results: mpsc::Sender<(usize, anyhow::Result<FileCommit>)>,
+    ) {
+        let git = GitTools::from_repository_path(repository_path);
+        loop {
+            let Ok((index, mut diff)) = jobs.lock().unwrap().recv() else {
+                break;
+            };
+            let result = match &git {
+                Ok(git) => diff
+                    .read_with_provider(git, options, provider)
+                    .map(|_| diff),
+                Err(error) => Err(anyhow::anyhow!("{error:#}")),
+            };
+            if results.send((index, result)).is_err() {
+                break;
             }
-            tx.send(diff)?;
         }
-        commits.join()?;
-        info!("read_thread: elapsed {:?}", start_time.elapsed());
+    }
+
+    /// Add the commits read so far to `commits()`, and attach the
+    /// `CommitMetadata` read so far.
+    /// Returns `true` if any commits were added or changed.
+    pub fn read_poll(&mut self) -> Result<bool, BlameError> {
+        Ok(self.read_poll_after(self.commits.len())?)
+    }
+
+    /// Same as `read_poll()`, where the commits from the `applied_len` are
+    /// new to the callers.
+    ///
+    /// The commits before the `applied_len` have been applied by the callers
+    /// after the last poll, such as by `FileContent::update_commits()`, so
+    /// their diffs can be spilled. The new commits are kept in memory, because
+    /// they're applied next.
+    fn read_poll_after(&mut self, applied_len: usize) -> anyhow::Result<bool> {
+        let has_new_commits = self.read_poll_commits()?;
+        let has_read_ahead = self.read_ahead.poll(&self.commits);
+        self.spill_diff_parts(applied_len)?;
+        let has_new_metadata = self.read_poll_metadata()?;
+        Ok(has_new_commits || has_read_ahead || has_new_metadata)
+    }
+
+    /// Spill the diffs of old commits before the `end` if the diffs in memory
+    /// exceed the `BlameOptions::memory_budget()`. The newest commits are kept
+    /// in memory.
+    fn spill_diff_parts(&mut self, end: usize) -> anyhow::Result<()> {
+        let Some(memory_budget) = self.options.memory_budget() else {
+            return Ok(());
+        };
+        // A quarter of the budget is to cache the diffs read back.
+        let cache_size = memory_budget / 4;
+        let memory_budget = memory_budget - cache_size;
+        let mut memory_size = 0;
+        let mut spill_start = None;
+        for (index, commit) in self.commits.range(..end).enumerate() {
+            memory_size += commit.diff_parts_memory_size();
+            if memory_size > memory_budget {
+                spill_start = Some(index);
+                break;
+            }
+        }
+        let Some(spill_start) = spill_start else {
+            return Ok(());
+        };
+        let store = match &self.diff_part_store {
+            Some(store) => store.clone(),
+            None => self
+                .diff_part_store
+                .insert(Arc::new(DiffPartStore::new(cache_size)?))
+                .clone(),
+        };
+        for index in spill_start..end {
+            let commit = self.commits.get_mut(index).unwrap();
+            if commit.diff_parts_memory_size() > 0 {
+                commit.spill_diff_parts(&store)?;
+            }
+        }
+        debug!("spill_diff_parts: {spill_start}..{end}, {memory_size} bytes");
         Ok(())
     }
 
-    pub fn read_poll(&mut self) -> anyhow::Result<bool> {
-        let start_time = std::time::Instant::now();
+    fn read_poll_commits(&mut self) -> anyhow::Result<bool> {
+        let start_time = Instant::now();
         let Some(rx) = self.rx.as_mut() else {
             return Ok(false);
         };
@@ -206,7 +1234,11 @@ impl FileHistory {
                         start_time.elapsed(),
                     );
                     trace!("{:#?}", self.commits);
-                    self.read_join()?;
+                    self.read_join_thread()?;
+                    self.rx = None;
+                    if self.read_done()? {
+                        count += 1;
+                    }
                     break;
                 }
             }
@@ -214,7 +1246,150 @@ impl FileHistory {
         Ok(count > 0)
     }
 
-    pub fn content(&self, commit_id: git2::Oid) -> anyhow::Result<FileContent> {
+    /// Complete the reading after the reading thread has sent all the commits.
+    /// Returns `true` if any commits were added or changed.
+    pub(super) fn read_done(&mut self) -> anyhow::Result<bool> {
+        let has_base_commits = self.append_base_commits();
+        Ok(self.set_boundary_if_cut_off()? || has_base_commits)
+    }
+
+    /// Add the commits of the base history of `read_start_after()` after the
+    /// commits read since it. Returns `true` if any commits were added.
+    fn append_base_commits(&mut self) -> bool {
+        let Some(base_commits) = self.base_commits.take() else {
+            return false;
+        };
+        if self.cancellation_token.is_cancelled() {
+            return false;
+        }
+        let max_commits = self.options.max_commits();
+        let commits = &mut self.commits;
+        let len = commits.len();
+        for commit in base_commits.iter() {
+            if max_commits.is_some_and(|max_commits| commits.len() >= max_commits) {
+                break;
+            }
+            if !commits.contains(commit.commit_id()) {
+                commits.push(commit.clone());
+            }
+        }
+        debug!("append_base_commits: {len} + {}", commits.len() - len);
+        commits.len() > len
+    }
+
+    /// Mark the oldest commit as the boundary if the reading was cut off by
+    /// the `BlameOptions::max_commits()` or `since()` before the commit adding
+    /// the file. See `FileCommit::is_boundary()`.
+    /// Returns `true` if the commit is marked.
+    fn set_boundary_if_cut_off(&mut self) -> anyhow::Result<bool> {
+        if self.cancellation_token.is_cancelled()
+            || (self.options.max_commits().is_none() && self.options.since().is_none())
+        {
+            return Ok(false);
+        }
+        let Some(commit) = self.commits.last() else {
+            return Ok(false);
+        };
+        if commit.is_boundary() {
+            return Ok(false);
+        }
+        let Some(parent_commit_id) = commit.parent_commit_id() else {
+            return Ok(false);
+        };
+        let old_path = commit.old_path_if_rename().unwrap_or(commit.path());
+        if !self.git().has_path(parent_commit_id, old_path)? {
+            return Ok(false);
+        }
+        debug!("set_boundary_if_cut_off: {}", commit.commit_id());
+        if let Some(commit) = self.commits.last_mut() {
+            commit.set_boundary();
+        }
+        Ok(true)
+    }
+
+    fn read_poll_metadata(&mut self) -> anyhow::Result<bool> {
+        let Some(rx) = self.metadata_rx.as_ref() else {
+            return Ok(false);
+        };
+        let mut count = 0;
+        let mut is_disconnected = false;
+        loop {
+            match rx.try_recv() {
+                Ok((commit_id, metadata)) => match self.commits.get_mut_by_commit_id(commit_id) {
+                    Ok(commit) => {
+                        commit.set_metadata(metadata);
+                        count += 1;
+                    }
+                    Err(error) => debug!("read_poll_metadata: {error}"),
+                },
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    is_disconnected = true;
+                    break;
+                }
+            }
+        }
+        if count > 0 {
+            debug!("read_poll_metadata: {count} items");
+        }
+        if is_disconnected {
+            debug!("read_poll_metadata: disconnected");
+            self.metadata_rx = None;
+            self.metadata_join()?;
+        }
+        Ok(count > 0)
+    }
+
+    /// Same as `read_poll()`, except that this function blocks until at least
+    /// one commit is read, or until the reading is completed.
+    pub fn read_wait(&mut self) -> Result<bool, BlameError> {
+        let Some(rx) = self.rx.as_mut() else {
+            return Ok(false);
+        };
+        let applied_len = self.commits.len();
+        match rx.recv() {
+            Ok(commit_data) => self.commits.push(commit_data),
+            Err(mpsc::RecvError) => {
+                debug!(
+                    "read_wait: disconnected, total {} items",
+                    self.commits.len()
+                );
+                self.read_join()?;
+                self.rx = None;
+                return Ok(self.read_done()?);
+            }
+        }
+        self.read_poll_after(applied_len)?;
+        Ok(true)
+    }
+
+    /// The annotated lines at the `commit_id`, or at the newest commit if it's
+    /// `Oid::ZERO_SHA1`, as a `BlameSnapshot` that owns all its data.
+    /// The commit must have been read.
+    pub fn snapshot(&self, commit_id: git2::Oid) -> Result<BlameSnapshot, BlameError> {
+        let content = self.content(commit_id)?;
+        Ok(BlameSnapshot::new(self, &content))
+    }
+
+    /// The annotated lines at the `commit_id`, or at the newest commit if it's
+    /// `Oid::ZERO_SHA1`, with the commits read so far applied.
+    ///
+    /// Recently used contents are cached, and only the commits read since
+    /// then are applied to them.
+    pub fn content(&self, commit_id: git2::Oid) -> Result<FileContent, BlameError> {
+        let cached = self.content_cache.lock().unwrap().get(commit_id);
+        if let Some(mut content) = cached {
+            debug!("content for {commit_id} from the cache");
+            content.set_current_line_index(0);
+            if !self.commits.is_empty() {
+                content.update_commits(self)?;
+            }
+            self.content_cache
+                .lock()
+                .unwrap()
+                .insert(commit_id, content.clone());
+            return Ok(content);
+        }
         debug!("content for {commit_id}");
         let path = if commit_id.is_zero() {
             &self.path
@@ -226,10 +1401,758 @@ impl FileHistory {
         if self.is_path_empty() {
             return Ok(content);
         }
-        content.read(self.git())?;
+        if self.is_not_committed && commit_id.is_zero() {
+            content.read_string(&self.git().uncommitted_content_as_string(path)?);
+            return Ok(content);
+        }
+        let read_commit_id = match self.start_commit_id {
+            Some(start_commit_id) if commit_id.is_zero() => start_commit_id,
+            _ if commit_id.is_zero() => self.git().head_commit_id()?,
+            _ => commit_id,
+        };
+        content.read_string(&self.provider.content(self.git(), read_commit_id, path)?);
         if !self.commits.is_empty() {
             content.update_commits(self)?;
         }
+        self.content_cache
+            .lock()
+            .unwrap()
+            .insert(commit_id, content.clone());
         Ok(content)
     }
 }
+
+impl Drop for FileHistory {
+    /// Stop the reading thread, without waiting for it to finish.
+    fn drop(&mut self) {
+        self.cancellation_token.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        blame::{JjProvider, Line, PullRequestMetadataProvider},
+        extensions::{GitError, tests::TempRepository},
+    };
+
+    #[test]
+    fn commits_changing_lines() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n3\n4\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\nB\n3\n4\n")?;
+        let commit_id2 = git.commit(commit_id1, "Modify 2")?;
+        git.add_file_content(path, "0\n1\nB\n3\n4\n")?;
+        let commit_id3 = git.commit(commit_id2, "Add 0")?;
+        git.add_file_content(path, "0\n1\nB\n3\nD\n")?;
+        git.commit(commit_id3, "Modify 4")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        history.read_join()?;
+        history.read_poll()?;
+        let to_ids = |results: Vec<(&FileCommit, Range<usize>)>| {
+            results
+                .into_iter()
+                .map(|(commit, line_numbers)| (commit.commit_id(), line_numbers))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            to_ids(history.commits_changing_lines(3..4)?),
+            [(commit_id2, 2..3), (commit_id1, 2..3)]
+        );
+        assert_eq!(
+            to_ids(history.commits_changing_lines(1..3)?),
+            [(commit_id3, 1..3), (commit_id1, 1..2)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn line_number_map_by_commit_indexes() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n3\n4\n5\n6\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "A\nB\n1\n2\n3\n4\n5\n6\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add A and B")?;
+        git.add_file_content(path, "A\nB\n1\n2\n5\n6\n")?;
+        let commit_id3 = git.commit(commit_id2, "Delete 3 and 4")?;
+        git.add_file_content(path, "A\nB\n1\nX\nY\nZ\n5\n6\n")?;
+        git.commit(commit_id3, "Replace 2")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        history.read_join()?;
+        history.read_poll()?;
+        assert_eq!(history.commits().len(), 4);
+        for (new_index, old_index) in [(0, 3), (3, 0), (1, 2), (0, 0)] {
+            let map = history.line_number_map_by_commit_indexes(new_index, old_index)?;
+            for line_number in 0..=10 {
+                let expected = if new_index == old_index {
+                    line_number
+                } else {
+                    history.map_line_number_by_commit_indexes(line_number, new_index, old_index)?
+                };
+                assert_eq!(map.map(line_number), expected);
+            }
+        }
+        assert_eq!(history.line_number_map_by_commit_indexes(0, 3)?.map(5), 7);
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2\n3\n4\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add 3 and 4")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        history.read_join()?;
+        history.read_poll()?;
+        let snapshot = history.snapshot(git2::Oid::ZERO_SHA1)?;
+        assert_eq!(snapshot.commit_id(), commit_id2);
+        assert_eq!(snapshot.lines().len(), 4);
+        assert_eq!(snapshot.commits().len(), 2);
+
+        let snapshot = history.snapshot(commit_id1)?;
+        assert_eq!(snapshot.commit_id(), commit_id1);
+        let lines: Vec<(&str, Option<git2::Oid>)> = snapshot
+            .lines()
+            .iter()
+            .map(|line| (line.content(), line.commit_id()))
+            .collect();
+        assert_eq!(lines, [("1", Some(commit_id1)), ("2", Some(commit_id1))]);
+        assert_eq!(snapshot.commits().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn content_cache() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2\n3\n4\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add 3 and 4")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        let mut content = history.content(git2::Oid::ZERO_SHA1)?;
+        content.set_current_line_index(2);
+        history.read_join()?;
+        history.read_poll()?;
+
+        // The commits read after it was cached should be applied.
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        assert_eq!(content.current_line_index(), 0);
+        let commit_ids: Vec<Option<git2::Oid>> = content
+            .lines()
+            .iter()
+            .map(|line| line.commit_id())
+            .collect();
+        assert_eq!(
+            commit_ids,
+            [
+                Some(commit_id1),
+                Some(commit_id1),
+                Some(commit_id2),
+                Some(commit_id2)
+            ]
+        );
+        assert_eq!(history.content_cache.lock().unwrap().len(), 1);
+        history.content(commit_id1)?;
+        assert_eq!(history.content_cache.lock().unwrap().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn read_all() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2\n3\n4\n")?;
+        git.commit(commit_id1, "Add 3 and 4")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_all()?;
+        assert!(!history.is_reading());
+        assert_eq!(history.commits().len(), 2);
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        let mut progresses = Vec::new();
+        history.read_all_with(Some(Duration::from_secs(60)), |progress| {
+            progresses.push(progress)
+        })?;
+        assert_eq!(history.commits().len(), 2);
+        let last = progresses.last().unwrap();
+        assert_eq!(last.commits_discovered(), 2);
+        assert_eq!(last.commits_diffed(), 2);
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        let error = history
+            .read_all_with(Some(Duration::ZERO), |_| {})
+            .unwrap_err();
+        assert!(matches!(error, BlameError::Timeout(_)));
+        assert!(!history.is_reading());
+        Ok(())
+    }
+
+    #[test]
+    fn errors() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.bin");
+        git.add_file_content(path, "1\0\n")?;
+        let commit_id = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_all()?;
+        let Err(error) = history.content(git2::Oid::ZERO_SHA1) else {
+            panic!("A binary file should be an error");
+        };
+        assert!(matches!(error, BlameError::Git(GitError::BinaryFile(_))));
+
+        let other_path = Path::new("other.txt");
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(other_path))
+            .rev(&commit_id.to_string())
+            .build();
+        let error = history.read_all().unwrap_err();
+        assert!(matches!(error, BlameError::Git(GitError::PathNotFound(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn not_committed() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let contents = |history: &FileHistory| -> anyhow::Result<Vec<String>> {
+            let content = history.content(git2::Oid::ZERO_SHA1)?;
+            assert!(
+                content
+                    .lines()
+                    .iter()
+                    .all(|line| line.commit_id().is_none())
+            );
+            Ok(content
+                .lines()
+                .iter()
+                .map(|line| line.content().to_string())
+                .collect())
+        };
+
+        // No commits in the repository.
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_all()?;
+        assert!(history.is_not_committed());
+        assert!(history.commits().is_empty());
+        assert_eq!(contents(&history)?, ["1", "2"]);
+
+        // Only in the index.
+        let other_path = Path::new("other.txt");
+        git.add_file_content(other_path, "a\n")?;
+        let commit_id = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        let new_path = Path::new("new.txt");
+        git.add_file_content(new_path, "x\n")?;
+        let mut history = FileHistory::new(&git.to_file_path(new_path));
+        history.read_all()?;
+        assert!(history.is_not_committed());
+        assert_eq!(contents(&history)?, ["x"]);
+
+        // The file should exist at the `rev`.
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(new_path))
+            .rev(&commit_id.to_string())
+            .build();
+        history.read_all()?;
+        assert!(!history.is_not_committed());
+        assert!(history.content(git2::Oid::ZERO_SHA1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn has_changed() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n")?;
+        let commit_id = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        let file_path = git.to_file_path(path);
+        let mut history = FileHistory::new(&file_path);
+        history.read_all()?;
+        assert!(!history.has_changed()?);
+
+        // Modified in the working directory.
+        std::fs::write(&file_path, "1\n2\n")?;
+        assert!(history.has_changed()?);
+        let mut history = FileHistory::new(&file_path);
+        history.read_all()?;
+        assert!(!history.has_changed()?);
+
+        // Staged.
+        git.add_file_content(path, "1\n2\n")?;
+        assert!(history.has_changed()?);
+        let mut history = FileHistory::new(&file_path);
+        history.read_all()?;
+        assert!(!history.has_changed()?);
+
+        // Committed.
+        git.commit(commit_id, "Add 2")?;
+        assert!(history.has_changed()?);
+        Ok(())
+    }
+
+    #[test]
+    fn read_jobs() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let old_path = Path::new("old.txt");
+        let new_path = Path::new("new.txt");
+        let mut content = "1\n2\n3\n4\n5\n6\n7\n8\n".to_string();
+        git.add_file_content(old_path, &content)?;
+        let mut commit_id = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        for i in 0..4 {
+            content = format!("{i}\n{i}\n{content}");
+            git.add_file_content(old_path, &content)?;
+            commit_id = git.commit(commit_id, &format!("Change {i}"))?;
+        }
+        git.rename_file(old_path, new_path)?;
+        commit_id = git.commit(commit_id, "Rename file")?;
+        for i in 4..8 {
+            content = format!("{content}{i}\n{i}\n");
+            git.add_file_content(new_path, &content)?;
+            commit_id = git.commit(commit_id, &format!("Change {i}"))?;
+        }
+
+        let read_with_priority = |jobs, priority_commit_ids| -> anyhow::Result<Vec<_>> {
+            let mut history = FileHistoryBuilder::new(&git.to_file_path(new_path))
+                .jobs(jobs)
+                .build();
+            history.set_priority_commit_ids(priority_commit_ids);
+            history.read_all()?;
+            history
+                .commits()
+                .iter()
+                .map(|commit| {
+                    Ok((
+                        commit.commit_id(),
+                        commit.path().to_path_buf(),
+                        commit.diff_parts()?.to_vec(),
+                    ))
+                })
+                .collect::<anyhow::Result<Vec<(git2::Oid, PathBuf, Vec<DiffPart>)>>>()
+        };
+        let read = |jobs| read_with_priority(jobs, HashSet::new());
+        let expected = read(1)?;
+        assert_eq!(expected.len(), 10);
+        assert_eq!(expected[0].0, commit_id);
+        assert_eq!(expected[4].1, new_path);
+        assert_eq!(expected[5].1, old_path);
+        assert_eq!(read(4)?, expected);
+
+        // The priority commits, across the rename, don't change the results.
+        let priority_commit_ids = HashSet::from([expected[9].0, expected[6].0]);
+        assert_eq!(
+            read_with_priority(1, priority_commit_ids.clone())?,
+            expected
+        );
+        assert_eq!(read_with_priority(4, priority_commit_ids)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn memory_budget() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        let mut content = String::new();
+        let mut commit_id = git2::Oid::ZERO_SHA1;
+        for i in 0..4 {
+            content = format!("{i}\n{content}");
+            git.add_file_content(path, &content)?;
+            commit_id = git.commit(commit_id, &format!("Add {i}"))?;
+        }
+
+        // 3 parts in memory, and 1 part to cache the diffs read back.
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(path))
+            .memory_budget(4 * size_of::<DiffPart>())
+            .engine(GitEngine::Git2)
+            .build();
+        history.read_all()?;
+        let memory_sizes = |history: &FileHistory| -> Vec<usize> {
+            history
+                .commits()
+                .iter()
+                .map(FileCommit::diff_parts_memory_size)
+                .collect()
+        };
+        let part_size = size_of::<DiffPart>();
+        assert_eq!(memory_sizes(&history), [part_size, part_size, part_size, 0]);
+
+        // The new commits are kept in memory until the next poll,
+        // because the callers apply them next.
+        let mut reading = FileHistoryBuilder::new(&git.to_file_path(path))
+            .memory_budget(size_of::<DiffPart>())
+            .engine(GitEngine::Git2)
+            .build();
+        reading.read_start()?;
+        while reading.read_wait()? {
+            let last = reading.commits().iter().last().unwrap();
+            assert_eq!(last.diff_parts_memory_size(), part_size);
+        }
+        // The last batch is spilled on the next poll.
+        reading.read_poll()?;
+        assert_eq!(memory_sizes(&reading), [0; 4]);
+
+        // Spilled diffs are read back when they're needed.
+        let mut expected = FileHistoryBuilder::new(&git.to_file_path(path))
+            .engine(GitEngine::Git2)
+            .build();
+        expected.read_all()?;
+        let commit_ids = |history: &FileHistory| -> anyhow::Result<Vec<Option<git2::Oid>>> {
+            let content = history.content(git2::Oid::ZERO_SHA1)?;
+            Ok(content
+                .lines()
+                .iter()
+                .map(|line| line.commit_id())
+                .collect())
+        };
+        assert_eq!(commit_ids(&history)?, commit_ids(&expected)?);
+        assert_eq!(memory_sizes(&history), [part_size, part_size, part_size, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn read_paused() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n")?;
+        let commit_id = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2\n")?;
+        git.commit(commit_id, "Add 2")?;
+
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(path))
+            .nice(1)
+            .build();
+        history.set_read_paused(true);
+        history.read_start()?;
+        thread::sleep(FileHistory::PAUSE_INTERVAL * 2);
+        history.read_poll()?;
+        assert!(history.is_reading());
+        assert!(history.commits().is_empty());
+
+        history.set_read_paused(false);
+        history.read_join()?;
+        history.read_poll()?;
+        assert_eq!(history.commits().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn fork() -> anyhow::Result<()> {
+        fn assert_send<T: Send>() {}
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send::<FileHistory>();
+        assert_send_sync::<FileCommits>();
+
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2\n3\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add 3")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        let shared_commits = history.shared_commits();
+        history.read_join()?;
+        history.read_poll()?;
+        assert_eq!(history.commits().len(), 2);
+        // The commits read after `shared_commits()` aren't included.
+        assert!(shared_commits.is_empty());
+        assert_eq!(history.shared_commits().len(), 2);
+
+        let forked = history.fork()?;
+        assert!(!forked.is_reading());
+        let commit_ids = thread::spawn(move || -> anyhow::Result<Vec<Option<git2::Oid>>> {
+            let content = forked.content(git2::Oid::ZERO_SHA1)?;
+            Ok(content
+                .lines()
+                .iter()
+                .map(|line| line.commit_id())
+                .collect())
+        })
+        .join()
+        .unwrap()?;
+        assert_eq!(
+            commit_ids,
+            [Some(commit_id1), Some(commit_id1), Some(commit_id2)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn to_builder() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(path))
+            .max_commits(10)
+            .engine(GitEngine::Git2)
+            .provider(Arc::new(JjProvider::new(GitEngine::Git2.provider())))
+            .metadata_provider(Arc::new(PullRequestMetadataProvider))
+            .build();
+        history.read_all()?;
+        let mut rebuilt = history.to_builder().build();
+        assert_eq!(rebuilt.options().max_commits(), Some(10));
+        assert_eq!(rebuilt.git_engine(), GitEngine::Git2);
+        assert_eq!(rebuilt.provider().name(), "jj");
+        assert_eq!(rebuilt.metadata_providers.len(), 1);
+        rebuilt.read_all()?;
+        assert_eq!(rebuilt.path(), history.path());
+        assert_eq!(rebuilt.commits()[0].commit_id(), commit_id1);
+        Ok(())
+    }
+
+    #[test]
+    fn read_start_after() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add 2")?;
+        let mut base = FileHistory::new(&git.to_file_path(path));
+        base.read_all()?;
+
+        git.add_file_content(path, "1\n2\n3\n")?;
+        let commit_id3 = git.commit(commit_id2, "Add 3")?;
+        let mut history = base.to_builder().build();
+        history.read_start_after(&base)?;
+        history.read_join()?;
+        history.read_poll()?;
+        // Only the new commit is read.
+        assert_eq!(history.read_progress().commits_discovered(), 1);
+        let commit_ids: Vec<_> = history.commits().iter().map(|c| c.commit_id()).collect();
+        assert_eq!(commit_ids, [commit_id3, commit_id2, commit_id1]);
+        assert_eq!(history.commits()[2].index(), 2);
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let line_commit_ids: Vec<_> = content.lines().iter().map(Line::commit_id).collect();
+        assert_eq!(
+            line_commit_ids,
+            [Some(commit_id1), Some(commit_id2), Some(commit_id3)]
+        );
+
+        // The same start commit shares the commits without reading.
+        let mut same = history.to_builder().build();
+        same.read_start_after(&history)?;
+        assert!(!same.is_reading());
+        assert_eq!(same.commits().len(), 3);
+
+        // Different options read the whole history.
+        let mut other = FileHistoryBuilder::new(&git.to_file_path(path))
+            .max_commits(10)
+            .build();
+        other.read_start_after(&base)?;
+        other.read_join()?;
+        other.read_poll()?;
+        assert_eq!(other.read_progress().commits_discovered(), 3);
+        assert_eq!(other.commits().len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn read_ahead() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2\n3\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add 3")?;
+        git.add_file_content(path, "0\n1\n2\n3\n")?;
+        let commit_id3 = git.commit(commit_id2, "Add 0")?;
+        let mut full = FileHistory::new(&git.to_file_path(path));
+        full.read_all()?;
+        assert_eq!(full.commits().len(), 3);
+
+        // Only the newest commit is read in order, and the oldest commit is
+        // read ahead for the lines 2 and 3.
+        let mut history = full.fork()?;
+        let mut commits = FileCommits::new();
+        commits.push(full.commit(0).clone());
+        history.commits = commits;
+        history.read_ahead = ReadAhead::start(
+            history.git(),
+            HashSet::new(),
+            history.priority_commit_ids.clone(),
+            CancellationToken::new(),
+        );
+        history.set_priority_lines(commit_id3, path, 2..4);
+        let start_time = Instant::now();
+        while !history
+            .priority_commit_ids
+            .lock()
+            .unwrap()
+            .contains(&commit_id1)
+        {
+            assert!(start_time.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(10));
+        }
+        let read_ahead_tx = history.read_ahead.commit_tx().unwrap();
+        read_ahead_tx.send(full.commit(2).clone())?;
+        while history.read_ahead_len() < 2 {
+            assert!(start_time.elapsed() < Duration::from_secs(10));
+            history.read_poll()?;
+        }
+        assert_eq!(history.commit_by_id(commit_id1)?.index(), 2);
+        assert!(history.commits().get_by_commit_id(commit_id1).is_err());
+
+        let mut content = history.content(git2::Oid::ZERO_SHA1)?;
+        let attributions = |content: &FileContent| -> Vec<(Option<git2::Oid>, bool)> {
+            content
+                .lines()
+                .iter()
+                .map(|line| (line.commit_id(), line.is_read_ahead()))
+                .collect()
+        };
+        assert_eq!(
+            attributions(&content),
+            [
+                (Some(commit_id3), false),
+                (Some(commit_id1), true),
+                (Some(commit_id1), true),
+                (None, false)
+            ]
+        );
+
+        // Applying the commits in order replaces the commits read ahead.
+        history.commits = full.shared_commits();
+        history.read_poll()?;
+        assert!(history.read_ahead_commit(commit_id1).is_none());
+        content.update_commits(&history)?;
+        assert_eq!(
+            attributions(&content),
+            [
+                (Some(commit_id3), false),
+                (Some(commit_id1), false),
+                (Some(commit_id1), false),
+                (Some(commit_id2), false)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn read_cancel() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n")?;
+        let mut commit_id = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        for i in 2..=10 {
+            let content: String = (1..=i).map(|n| format!("{n}\n")).collect();
+            git.add_file_content(path, &content)?;
+            commit_id = git.commit(commit_id, &format!("Add {i}"))?;
+        }
+
+        // Pause to cancel before any commits are read.
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.set_read_paused(true);
+        history.read_start()?;
+        history.read_cancel()?;
+        assert!(!history.is_reading());
+        history.read_poll()?;
+        assert!(history.commits().is_empty());
+        assert_eq!(history.read_progress().commits_diffed(), 0);
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        history.read_cancel()?;
+        history.read_poll()?;
+        let commits_len = history.commits().len();
+        assert!(commits_len <= 10);
+        assert_eq!(history.read_progress().commits_diffed(), commits_len);
+
+        // Cancelling after the reading is completed is a no-op.
+        history.read_cancel()?;
+        Ok(())
+    }
+
+    #[test]
+    fn blame_line() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "a\nb\nc\nd\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "a\nB\nC\nd\n")?;
+        let commit_id2 = git.commit(commit_id1, "Modify b and c")?;
+        git.add_file_content(path, "x\ny\na\nB\nC\nd\n")?;
+        let commit_id3 = git.commit(commit_id2, "Add x and y")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        history.read_join()?;
+        history.read_poll()?;
+        let blame = |rev: &str, line_number| -> anyhow::Result<git2::Oid> {
+            Ok(history.blame_line(rev, line_number)?.commit_id())
+        };
+        let head: Vec<git2::Oid> = (1..=6)
+            .map(|line_number| blame("HEAD", line_number))
+            .collect::<anyhow::Result<_>>()?;
+        assert_eq!(
+            head,
+            [
+                commit_id3, commit_id3, commit_id1, commit_id2, commit_id2, commit_id1
+            ]
+        );
+        let rev2 = commit_id2.to_string();
+        assert_eq!(blame(&rev2, 1)?, commit_id1);
+        assert_eq!(blame(&rev2, 2)?, commit_id2);
+        assert_eq!(blame(&rev2, 4)?, commit_id1);
+
+        for line_number in [0, 7] {
+            let error = blame("HEAD", line_number).unwrap_err();
+            assert!(matches!(
+                error.downcast_ref(),
+                Some(BlameError::InvalidLineNumber(_))
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn commit_at_or_before() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        let other_path = Path::new("other.txt");
+        git.add_file_content(other_path, "1\n")?;
+        let commit_id0 = git.commit(git2::Oid::ZERO_SHA1, "Add other file")?;
+        git.add_file_content(path, "1\n")?;
+        let commit_id1 = git.commit(commit_id0, "Add file")?;
+        git.add_file_content(other_path, "2\n")?;
+        let commit_id2 = git.commit(commit_id1, "Modify other file")?;
+        git.add_file_content(path, "2\n")?;
+        let commit_id3 = git.commit(commit_id2, "Modify file")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        history.read_join()?;
+        history.read_poll()?;
+        let target = |commit_id| -> anyhow::Result<git2::Oid> {
+            Ok(history.commit_at_or_before(commit_id)?.commit_id())
+        };
+        assert_eq!(target(commit_id3)?, commit_id3);
+        assert_eq!(target(commit_id2)?, commit_id1);
+        assert_eq!(target(commit_id1)?, commit_id1);
+        let error = target(commit_id0).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(BlameError::FileNotFound { commit_id, .. }) if *commit_id == commit_id0
+        ));
+        Ok(())
+    }
+}
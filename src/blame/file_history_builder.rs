@@ -0,0 +1,310 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, mpsc},
+};
+
+use log::*;
+
+use super::{
+    BlameOptions, CancellationToken, FileCommit, FileHistory, GitEngine, HistoryProvider,
+    MetadataProvider, ReadProgress,
+};
+
+/// Builds a `FileHistory` with options.
+/// # Examples
+/// ```no_run
+/// use git_iblame::blame::FileHistoryBuilder;
+///
+/// # use std::path::Path;
+/// fn main() -> anyhow::Result<()> {
+///   let mut history = FileHistoryBuilder::new(Path::new("path/to/file"))
+///     .rev("main")
+///     .ignore_rev("1234567")
+///     .max_commits(100)
+///     .build();
+///   history.read_start()?;
+///   Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct FileHistoryBuilder {
+    path: PathBuf,
+    options: BlameOptions,
+    engine: Option<GitEngine>,
+    provider: Option<Arc<dyn HistoryProvider>>,
+    metadata_providers: Vec<Arc<dyn MetadataProvider>>,
+    progress_tx: Option<mpsc::Sender<ReadProgress>>,
+    cancellation_token: CancellationToken,
+}
+
+impl FileHistoryBuilder {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            options: BlameOptions::default(),
+            engine: None,
+            provider: None,
+            metadata_providers: Vec::new(),
+            progress_tx: None,
+            cancellation_token: CancellationToken::default(),
+        }
+    }
+
+    /// Replace all the options with the `options`.
+    pub fn options(mut self, options: BlameOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// See `BlameOptions::set_rev()`.
+    pub fn rev(mut self, rev: &str) -> Self {
+        self.options.set_rev(Some(rev));
+        self
+    }
+
+    /// See `BlameOptions::set_at()`.
+    pub fn at(mut self, date: &str) -> Self {
+        self.options.set_at(Some(date));
+        self
+    }
+
+    /// See `BlameOptions::set_follow()`.
+    pub fn follow(mut self, follow: bool) -> Self {
+        self.options.set_follow(follow);
+        self
+    }
+
+    /// See `BlameOptions::set_rename_threshold()`.
+    pub fn rename_threshold(mut self, percent: u16) -> Self {
+        self.options.set_rename_threshold(Some(percent));
+        self
+    }
+
+    /// See `BlameOptions::set_detect_moves()`.
+    pub fn detect_moves(mut self, detect_moves: bool) -> Self {
+        self.options.set_detect_moves(detect_moves);
+        self
+    }
+
+    /// See `BlameOptions::add_ignore_rev()`.
+    pub fn ignore_rev(mut self, rev: &str) -> Self {
+        self.options.add_ignore_rev(rev);
+        self
+    }
+
+    /// See `BlameOptions::set_max_commits()`.
+    pub fn max_commits(mut self, max_commits: usize) -> Self {
+        self.options.set_max_commits(Some(max_commits));
+        self
+    }
+
+    /// See `BlameOptions::set_since()`.
+    pub fn since(mut self, date: &str) -> Self {
+        self.options.set_since(Some(date));
+        self
+    }
+
+    /// See `BlameOptions::set_jobs()`.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.options.set_jobs(Some(jobs));
+        self
+    }
+
+    /// See `BlameOptions::set_nice()`.
+    pub fn nice(mut self, nice: i32) -> Self {
+        self.options.set_nice(Some(nice));
+        self
+    }
+
+    /// See `BlameOptions::set_memory_budget()`.
+    pub fn memory_budget(mut self, bytes: usize) -> Self {
+        self.options.set_memory_budget(Some(bytes));
+        self
+    }
+
+    /// The engine to compute the diffs of commits and attribute lines.
+    /// The default is the engine set by `FileCommit::use_git()` or
+    /// `FileCommit::use_git2()` when `build()` is called.
+    pub fn engine(mut self, engine: GitEngine) -> Self {
+        self.engine = Some(engine);
+        self
+    }
+
+    /// Read the history and attribute lines by the `provider`, instead of the
+    /// provider of the `engine()`. See `HistoryProvider::is_blame()`.
+    pub fn provider(mut self, provider: Arc<dyn HistoryProvider>) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Add the `provider` to attach `CommitMetadata` to the commits.
+    /// See `MetadataProvider`.
+    pub fn metadata_provider(mut self, provider: Arc<dyn MetadataProvider>) -> Self {
+        self.metadata_providers.push(provider);
+        self
+    }
+
+    /// Send the `ReadProgress` to the `tx` each time a commit is read.
+    /// The reading continues even if the receiver is dropped.
+    /// See also `FileHistory::read_progress()`.
+    pub fn progress(mut self, tx: mpsc::Sender<ReadProgress>) -> Self {
+        self.progress_tx = Some(tx);
+        self
+    }
+
+    /// Stop reading when the `token` is cancelled from other threads.
+    /// The reading also stops by `FileHistory::read_cancel()`,
+    /// or when the `FileHistory` is dropped.
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = token;
+        self
+    }
+
+    pub fn build(self) -> FileHistory {
+        let mut engine = self.engine.unwrap_or_else(FileCommit::git_engine);
+        if self.provider.is_none()
+            && let Some(fallback) = engine.fallback_engine(&self.options)
+        {
+            warn!(
+                "The {} engine doesn't support the options, using the {} engine",
+                engine.name(),
+                fallback.name()
+            );
+            engine = fallback;
+        }
+        let provider = self.provider.unwrap_or_else(|| engine.provider());
+        FileHistory::new_with_options(
+            &self.path,
+            self.options,
+            engine,
+            provider,
+            self.metadata_providers,
+            self.progress_tx,
+            self.cancellation_token,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extensions::tests::TempRepository;
+
+    use super::*;
+
+    #[test]
+    fn build() {
+        let history = FileHistoryBuilder::new(Path::new("a"))
+            .rev("main")
+            .follow(false)
+            .ignore_rev("b")
+            .ignore_rev("c")
+            .max_commits(3)
+            .engine(GitEngine::GitBlame)
+            .build();
+        assert_eq!(history.path(), Path::new("a"));
+        assert_eq!(history.options().rev(), Some("main"));
+        assert!(!history.options().follow());
+        assert_eq!(history.options().ignore_revs(), ["b", "c"]);
+        assert_eq!(history.options().max_commits(), Some(3));
+        assert_eq!(history.git_engine(), GitEngine::GitBlame);
+
+        let history = FileHistoryBuilder::new(Path::new("a")).build();
+        assert!(history.options().follow());
+        assert_eq!(history.git_engine(), FileCommit::git_engine());
+
+        // `GitEngine::Git2Blame` can't ignore revisions nor detect moves.
+        let builder = FileHistoryBuilder::new(Path::new("a")).engine(GitEngine::Git2Blame);
+        assert_eq!(builder.clone().build().git_engine(), GitEngine::Git2Blame);
+        let history = builder.clone().ignore_rev("b").build();
+        assert_eq!(history.git_engine(), GitEngine::Git2);
+        assert_eq!(history.provider().name(), "git2");
+        let history = builder.detect_moves(true).build();
+        assert_eq!(history.git_engine(), GitEngine::Git2);
+    }
+
+    #[test]
+    fn max_commits() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2\n3\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add 3")?;
+        git.add_file_content(path, "1\n2\n3\n4\n")?;
+        let commit_id3 = git.commit(commit_id2, "Add 4")?;
+
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(path))
+            .max_commits(2)
+            .build();
+        history.read_start()?;
+        history.read_join()?;
+        history.read_poll()?;
+        assert_eq!(history.commits().len(), 2);
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let commit_ids: Vec<Option<git2::Oid>> = content
+            .lines()
+            .iter()
+            .map(|line| line.commit_id())
+            .collect();
+        // The lines older than the limit are attributed to the boundary.
+        assert_eq!(
+            commit_ids,
+            [
+                Some(commit_id2),
+                Some(commit_id2),
+                Some(commit_id2),
+                Some(commit_id3)
+            ]
+        );
+        assert!(content.lines()[0].is_boundary());
+        Ok(())
+    }
+
+    #[test]
+    fn progress() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2\n3\n")?;
+        git.commit(commit_id1, "Add 3")?;
+
+        let (tx, rx) = mpsc::channel();
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(path))
+            .progress(tx)
+            .build();
+        assert_eq!(history.read_progress(), ReadProgress::default());
+        history.read_start()?;
+        history.read_join()?;
+        let diffed: Vec<usize> = rx.try_iter().map(|p| p.commits_diffed()).collect();
+        assert_eq!(diffed, [1, 2]);
+        let progress = history.read_progress();
+        assert_eq!(progress.commits_discovered(), 2);
+        assert_eq!(progress.commits_diffed(), 2);
+        assert_eq!(progress.elapsed(), history.read_progress().elapsed());
+        Ok(())
+    }
+
+    #[test]
+    fn cancellation_token() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2\n3\n")?;
+        git.commit(commit_id1, "Add 3")?;
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(path))
+            .cancellation_token(token)
+            .build();
+        history.read_start()?;
+        history.read_join()?;
+        history.read_poll()?;
+        assert!(!history.is_reading());
+        assert_eq!(history.commits().len(), 0);
+        assert_eq!(history.read_progress().commits_diffed(), 0);
+        Ok(())
+    }
+}
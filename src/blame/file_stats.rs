@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use super::{BlameError, FileContent, FileHistory};
+
+/// The number of lines attributed to an author.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AuthorStats {
+    pub name: String,
+    pub email: String,
+    pub lines: usize,
+}
+
+/// The number of lines a commit added and deleted in the file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CommitChurn {
+    pub commit_id: git2::Oid,
+    pub author_email: String,
+    pub time: git2::Time,
+    pub summary: String,
+    pub added: usize,
+    pub deleted: usize,
+}
+
+/// The ownership and the churn statistics of a file.
+#[derive(Debug, Default)]
+pub struct FileStats {
+    lines: usize,
+    authors: Vec<AuthorStats>,
+    commits: Vec<CommitChurn>,
+}
+
+impl FileStats {
+    /// Compute from the `history` and its `content`.
+    /// The `history` should be read to the end to count all lines.
+    pub fn new(history: &FileHistory, content: &FileContent) -> Result<Self, BlameError> {
+        let mut authors: Vec<AuthorStats> = vec![];
+        let mut index_by_email: HashMap<&str, usize> = HashMap::new();
+        let mut lines = 0;
+        for line in content.lines().iter().filter(|line| !line.is_deleted()) {
+            let Some(commit_id) = line.commit_id() else {
+                continue;
+            };
+            let commit = history.commits().get_by_commit_id(commit_id)?;
+            let index = *index_by_email
+                .entry(commit.author_email())
+                .or_insert_with(|| {
+                    authors.push(AuthorStats {
+                        name: commit.author_name().to_string(),
+                        email: commit.author_email().to_string(),
+                        lines: 0,
+                    });
+                    authors.len() - 1
+                });
+            authors[index].lines += 1;
+            lines += 1;
+        }
+        authors.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.email.cmp(&b.email)));
+
+        let commits = history
+            .commits()
+            .iter()
+            .map(|commit| {
+                let diff_parts = commit.diff_parts()?;
+                let mut added = 0;
+                for part in diff_parts.iter() {
+                    added += if part.new.line_numbers.end == usize::MAX {
+                        // The end is unknown when the commit added the file.
+                        let content = history
+                            .git()
+                            .content_as_string(commit.commit_id(), commit.path())?;
+                        (content.lines().count() + 1).saturating_sub(part.new.start_line_number())
+                    } else {
+                        part.new.len()
+                    };
+                }
+                Ok(CommitChurn {
+                    commit_id: commit.commit_id(),
+                    author_email: commit.author_email().to_string(),
+                    time: commit.time(),
+                    summary: commit.summary().cloned().unwrap_or_default(),
+                    added,
+                    deleted: diff_parts.iter().map(|part| part.old.len()).sum(),
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            lines,
+            authors,
+            commits,
+        })
+    }
+
+    /// The number of lines attributed to commits.
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    /// The authors, in the descending order of the number of lines.
+    pub fn authors(&self) -> &[AuthorStats] {
+        &self.authors
+    }
+
+    /// The commits that changed the file, newest first.
+    pub fn commits(&self) -> &[CommitChurn] {
+        &self.commits
+    }
+
+    /// The percentage of the `lines` in all lines.
+    pub fn percent(&self, lines: usize) -> f64 {
+        if self.lines == 0 {
+            return 0.0;
+        }
+        lines as f64 * 100.0 / self.lines as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::extensions::tests::TempRepository;
+
+    use super::*;
+
+    #[test]
+    fn new() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n3\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\nA\n3\n")?;
+        let commit_id2 = git.commit(commit_id1, "Change 2")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        history.read_join()?;
+        history.read_poll()?;
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let stats = FileStats::new(&history, &content)?;
+        assert_eq!(stats.lines(), 3);
+        assert_eq!(stats.authors().len(), 1);
+        assert_eq!(stats.authors()[0].email, "test@test.com");
+        assert_eq!(stats.authors()[0].lines, 3);
+        assert_eq!(stats.percent(3), 100.0);
+
+        let churns: Vec<(git2::Oid, usize, usize)> = stats
+            .commits()
+            .iter()
+            .map(|churn| (churn.commit_id, churn.added, churn.deleted))
+            .collect();
+        assert_eq!(churns, [(commit_id2, 1, 1), (commit_id1, 3, 0)]);
+        Ok(())
+    }
+}
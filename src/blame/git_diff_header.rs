@@ -0,0 +1,276 @@
+/// The paths in the header of a file in the outputs of `git show` or
+/// `git diff`; e.g.,
+/// ```text
+/// diff --git a/old.txt b/new.txt
+/// similarity index 90%
+/// rename from old.txt
+/// rename to new.txt
+/// --- a/old.txt
+/// +++ b/new.txt
+/// ```
+/// The paths in the `diff --git` line are ambiguous when they contain spaces,
+/// so the paths in the other lines are preferred when they're present.
+/// Paths with special characters, or with non-ASCII characters when
+/// `core.quotePath` is set, are quoted with C-style escapes.
+#[derive(Debug, Default)]
+pub(crate) struct GitDiffHeader {
+    /// The old and the new paths guessed from the `diff --git` line.
+    diff_paths: Option<(String, String)>,
+    old_path: Option<String>,
+    new_path: Option<String>,
+    is_new_file: bool,
+}
+
+impl GitDiffHeader {
+    /// Start a header if the `line` is a `diff --git` line.
+    pub fn new(line: &str) -> Option<Self> {
+        let paths = line.strip_prefix("diff --git ")?;
+        Some(Self {
+            diff_paths: Self::parse_diff_paths(paths),
+            ..Default::default()
+        })
+    }
+
+    /// Read a line after the `diff --git` line, before the first hunk.
+    /// Lines without paths are ignored.
+    pub fn read_line(&mut self, line: &str) {
+        if let Some(path) = line
+            .strip_prefix("rename from ")
+            .or_else(|| line.strip_prefix("copy from "))
+        {
+            self.old_path = Some(unquote(path));
+        } else if let Some(path) = line
+            .strip_prefix("rename to ")
+            .or_else(|| line.strip_prefix("copy to "))
+        {
+            self.new_path = Some(unquote(path));
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            if path == "/dev/null" {
+                self.is_new_file = true;
+            } else if let Some(path) = Self::strip_prefix(&Self::unquote_patch_path(path), "a/") {
+                self.old_path = Some(path);
+            }
+        } else if let Some(path) = line.strip_prefix("+++ ")
+            && let Some(path) = Self::strip_prefix(&Self::unquote_patch_path(path), "b/")
+        {
+            self.new_path = Some(path);
+        }
+    }
+
+    pub fn old_path(&self) -> Option<&str> {
+        self.old_path
+            .as_deref()
+            .or_else(|| self.diff_paths.as_ref().map(|(old, _)| old.as_str()))
+    }
+
+    pub fn new_path(&self) -> Option<&str> {
+        self.new_path
+            .as_deref()
+            .or_else(|| self.diff_paths.as_ref().map(|(_, new)| new.as_str()))
+    }
+
+    /// Whether the file is added; i.e., the old file is `/dev/null`.
+    pub fn is_new_file(&self) -> bool {
+        self.is_new_file
+    }
+
+    /// Parse `a/old b/new` of the `diff --git` line.
+    /// If neither is quoted and they have spaces, they're assumed to be the
+    /// same, or split at the first ` b/` otherwise.
+    fn parse_diff_paths(paths: &str) -> Option<(String, String)> {
+        let (old, new) = if paths.starts_with('"') {
+            let (old, rest) = unquote_prefix(paths)?;
+            (old, unquote(rest.strip_prefix(' ')?))
+        } else if paths.ends_with('"') {
+            let index = paths.rfind(" \"b/")?;
+            (paths[..index].to_string(), unquote(&paths[index + 1..]))
+        } else if let Some(path) = Self::same_paths(paths) {
+            (format!("a/{path}"), format!("b/{path}"))
+        } else {
+            let (old, new) = paths.split_once(" b/")?;
+            (old.to_string(), format!("b/{new}"))
+        };
+        Some((
+            Self::strip_prefix(&old, "a/")?,
+            Self::strip_prefix(&new, "b/")?,
+        ))
+    }
+
+    /// The `path` if the `paths` is `a/path b/path`.
+    fn same_paths(paths: &str) -> Option<&str> {
+        let len = paths.len().checked_sub(5)?;
+        if len % 2 != 0 || !paths.is_char_boundary(2 + len / 2) {
+            return None;
+        }
+        let (old, new) = paths.split_at(2 + len / 2);
+        let path = old.strip_prefix("a/")?;
+        (new.strip_prefix(" b/")? == path).then_some(path)
+    }
+
+    /// Unquote the path of `---` or `+++` lines, which has a trailing tab
+    /// if it has spaces.
+    fn unquote_patch_path(path: &str) -> String {
+        unquote(path.strip_suffix('\t').unwrap_or(path))
+    }
+
+    fn strip_prefix(path: &str, prefix: &str) -> Option<String> {
+        path.strip_prefix(prefix).map(str::to_string)
+    }
+}
+
+/// Unquote the C-style quoted `value` if it's quoted; e.g., `"a\tb"`.
+fn unquote(value: &str) -> String {
+    match unquote_prefix(value) {
+        Some((unquoted, "")) => unquoted,
+        _ => value.to_string(),
+    }
+}
+
+/// Unquote the C-style quoted string at the start of the `value`,
+/// and return it with the rest of the `value`.
+/// Escaped bytes, such as `\303\251`, are decoded as UTF-8.
+fn unquote_prefix(value: &str) -> Option<(String, &str)> {
+    let quoted = value.strip_prefix('"')?;
+    let mut bytes = Vec::new();
+    let mut iter = quoted.bytes().enumerate();
+    while let Some((index, byte)) = iter.next() {
+        match byte {
+            b'"' => {
+                let unquoted = String::from_utf8_lossy(&bytes).to_string();
+                return Some((unquoted, &quoted[index + 1..]));
+            }
+            b'\\' => {
+                let (_, escaped) = iter.next()?;
+                bytes.push(match escaped {
+                    b'a' => 0x07,
+                    b'b' => 0x08,
+                    b't' => b'\t',
+                    b'n' => b'\n',
+                    b'v' => 0x0B,
+                    b'f' => 0x0C,
+                    b'r' => b'\r',
+                    b'0'..=b'3' => {
+                        let mut value = escaped - b'0';
+                        for _ in 0..2 {
+                            let (_, digit) = iter.next()?;
+                            if !(b'0'..=b'7').contains(&digit) {
+                                return None;
+                            }
+                            value = value * 8 + (digit - b'0');
+                        }
+                        value
+                    }
+                    escaped => escaped,
+                });
+            }
+            byte => bytes.push(byte),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(lines: &[&str]) -> (Option<String>, Option<String>, bool) {
+        let mut header = GitDiffHeader::new(lines[0]).unwrap();
+        for line in &lines[1..] {
+            header.read_line(line);
+        }
+        (
+            header.old_path().map(str::to_string),
+            header.new_path().map(str::to_string),
+            header.is_new_file(),
+        )
+    }
+
+    fn paths(old: &str, new: &str) -> (Option<String>, Option<String>, bool) {
+        (Some(old.to_string()), Some(new.to_string()), false)
+    }
+
+    #[test]
+    fn diff_line() {
+        assert_eq!(
+            parse(&["diff --git a/a.txt b/a.txt"]),
+            paths("a.txt", "a.txt")
+        );
+        assert_eq!(
+            parse(&["diff --git a/x b/y.txt b/x b/y.txt"]),
+            paths("x b/y.txt", "x b/y.txt")
+        );
+        assert_eq!(
+            parse(&[r#"diff --git "a/\303\251 \"q\".txt" "b/\303\251 \"q\".txt""#]),
+            paths("é \"q\".txt", "é \"q\".txt")
+        );
+        assert_eq!(
+            parse(&[r#"diff --git a/old.txt "b/tab\there.txt""#]),
+            paths("old.txt", "tab\there.txt")
+        );
+        assert!(GitDiffHeader::new("index 0123..4567").is_none());
+    }
+
+    #[test]
+    fn rename() {
+        assert_eq!(
+            parse(&[
+                "diff --git a/x b/old.txt b/x b/new.txt",
+                "similarity index 100%",
+                "rename from x b/old.txt",
+                "rename to x b/new.txt",
+            ]),
+            paths("x b/old.txt", "x b/new.txt")
+        );
+        assert_eq!(
+            parse(&[
+                r#"diff --git a/old.txt "b/\303\251.txt""#,
+                "similarity index 90%",
+                "rename from old.txt",
+                r#"rename to "\303\251.txt""#,
+                "index 0123..4567 100644",
+                "--- a/old.txt",
+                r#"+++ "b/\303\251.txt""#,
+            ]),
+            paths("old.txt", "é.txt")
+        );
+    }
+
+    #[test]
+    fn patch_lines() {
+        assert_eq!(
+            parse(&[
+                "diff --git a/s p a c e b/s p a c e",
+                "index 0123..4567 100644",
+                "--- a/s p a c e\t",
+                "+++ b/s p a c e\t",
+            ]),
+            paths("s p a c e", "s p a c e")
+        );
+        assert_eq!(
+            parse(&[
+                "diff --git a/new.txt b/new.txt",
+                "new file mode 100644",
+                "--- /dev/null",
+                "+++ b/new.txt",
+            ]),
+            (
+                Some("new.txt".to_string()),
+                Some("new.txt".to_string()),
+                true
+            )
+        );
+    }
+
+    #[test]
+    fn unquote() {
+        assert_eq!(super::unquote("a b"), "a b");
+        assert_eq!(super::unquote(r#""a\\b\"c\n""#), "a\\b\"c\n");
+        assert_eq!(super::unquote(r#""\303\251""#), "é");
+        // Not closed.
+        assert_eq!(super::unquote(r#""a"#), r#""a"#);
+        assert_eq!(
+            unquote_prefix(r#""a" "b""#),
+            Some(("a".to_string(), r#" "b""#))
+        );
+    }
+}
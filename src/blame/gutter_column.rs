@@ -0,0 +1,176 @@
+use std::str::FromStr;
+
+/// The kinds of columns in the left pane.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GutterColumnKind {
+    /// The line number.
+    LineNumber,
+    /// The line number at the commit that introduced the line.
+    OriginalLineNumber,
+    /// The commit index, the date, the summary, the author, and the commit ID
+    /// in the consecutive lines of a hunk. This is the default.
+    Blame,
+    /// The index of the commit in the history.
+    CommitIndex,
+    /// The abbreviated commit ID.
+    ShortId,
+    /// The abbreviated change ID, such as in `jj`.
+    /// Empty if the commit doesn't have a change ID.
+    ChangeId,
+    /// The author, in the `AuthorFormat`.
+    Author,
+    /// The date and time of the commit.
+    Date,
+    /// The summary of the commit.
+    Summary,
+    /// The age of the line, colored from hot (recent) to cold (old).
+    Heat,
+}
+
+impl GutterColumnKind {
+    const NAMES: &[(&str, GutterColumnKind)] = &[
+        ("number", GutterColumnKind::LineNumber),
+        ("orig", GutterColumnKind::OriginalLineNumber),
+        ("blame", GutterColumnKind::Blame),
+        ("index", GutterColumnKind::CommitIndex),
+        ("sha", GutterColumnKind::ShortId),
+        ("change", GutterColumnKind::ChangeId),
+        ("author", GutterColumnKind::Author),
+        ("date", GutterColumnKind::Date),
+        ("summary", GutterColumnKind::Summary),
+        ("heat", GutterColumnKind::Heat),
+    ];
+
+    fn default_width(&self) -> usize {
+        match self {
+            GutterColumnKind::LineNumber | GutterColumnKind::OriginalLineNumber => 4,
+            GutterColumnKind::Blame => 25,
+            GutterColumnKind::CommitIndex => 5,
+            GutterColumnKind::ShortId => 7,
+            GutterColumnKind::ChangeId => 8,
+            GutterColumnKind::Author => 20,
+            GutterColumnKind::Date => 16,
+            GutterColumnKind::Summary => 30,
+            GutterColumnKind::Heat => 1,
+        }
+    }
+}
+
+/// A column in the left pane, and its width.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GutterColumn {
+    kind: GutterColumnKind,
+    width: usize,
+}
+
+impl GutterColumn {
+    pub fn new(kind: GutterColumnKind) -> Self {
+        Self {
+            kind,
+            width: kind.default_width(),
+        }
+    }
+
+    pub fn with_width(kind: GutterColumnKind, width: usize) -> Self {
+        Self { kind, width }
+    }
+
+    pub fn kind(&self) -> GutterColumnKind {
+        self.kind
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Widen the column to resize the left pane by `delta` columns, or narrow
+    /// it if negative. The column is the first `GutterColumnKind::Blame`,
+    /// `GutterColumnKind::Summary`, or `GutterColumnKind::Author`, whose
+    /// contents are often truncated.
+    pub fn resize(columns: &mut [GutterColumn], delta: isize) -> anyhow::Result<()> {
+        let index = [
+            GutterColumnKind::Blame,
+            GutterColumnKind::Summary,
+            GutterColumnKind::Author,
+        ]
+        .iter()
+        .find_map(|kind| columns.iter().position(|column| column.kind == *kind));
+        let Some(index) = index else {
+            anyhow::bail!("No columns to resize");
+        };
+        let column = &mut columns[index];
+        column.width = column.width.saturating_add_signed(delta).max(1);
+        Ok(())
+    }
+
+    /// The default columns; the line number and the `GutterColumnKind::Blame`.
+    pub fn default_columns() -> Vec<GutterColumn> {
+        vec![
+            GutterColumn::new(GutterColumnKind::LineNumber),
+            GutterColumn::new(GutterColumnKind::Blame),
+        ]
+    }
+}
+
+impl FromStr for GutterColumn {
+    type Err = anyhow::Error;
+
+    /// Parse a column name, optionally followed by `:` and the width;
+    /// e.g., "author" or "author:30".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, width) = match s.split_once(':') {
+            Some((name, width)) => (name, Some(width.parse::<usize>()?)),
+            None => (s, None),
+        };
+        let Some((_, kind)) = GutterColumnKind::NAMES.iter().find(|(n, _)| *n == name) else {
+            let names: Vec<&str> = GutterColumnKind::NAMES.iter().map(|(n, _)| *n).collect();
+            anyhow::bail!(
+                "Unknown column \"{name}\", possible values are: {}",
+                names.join(", ")
+            );
+        };
+        Ok(match width {
+            Some(width) => GutterColumn::with_width(*kind, width),
+            None => GutterColumn::new(*kind),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_str() -> anyhow::Result<()> {
+        assert_eq!(
+            "sha".parse::<GutterColumn>()?,
+            GutterColumn::with_width(GutterColumnKind::ShortId, 7)
+        );
+        assert_eq!(
+            "author:30".parse::<GutterColumn>()?,
+            GutterColumn::with_width(GutterColumnKind::Author, 30)
+        );
+        assert!("unknown".parse::<GutterColumn>().is_err());
+        assert!("author:x".parse::<GutterColumn>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn resize() -> anyhow::Result<()> {
+        let mut columns = GutterColumn::default_columns();
+        GutterColumn::resize(&mut columns, 4)?;
+        assert_eq!(columns[0].width(), 4);
+        assert_eq!(columns[1].width(), 29);
+        GutterColumn::resize(&mut columns, -100)?;
+        assert_eq!(columns[1].width(), 1);
+
+        let mut columns = vec!["author:10".parse()?, "summary:10".parse()?];
+        GutterColumn::resize(&mut columns, -4)?;
+        assert_eq!(columns[0].width(), 10);
+        assert_eq!(columns[1].width(), 6);
+
+        let mut columns = vec!["number".parse()?, "sha".parse()?];
+        assert!(GutterColumn::resize(&mut columns, 4).is_err());
+        Ok(())
+    }
+}
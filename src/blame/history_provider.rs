@@ -0,0 +1,351 @@
+use std::{fmt, ops::Range, path::Path};
+
+use crate::extensions::GitTools;
+
+use super::{BlameError, BlameOptions, CancellationToken, CommitIterator, FileCommit};
+
+/// The iterator of the commits returned by `HistoryProvider::commits()`.
+pub type FileCommitIterator = Box<dyn Iterator<Item = Result<FileCommit, BlameError>> + Send>;
+
+/// The source of the history of a file, such as a version control system.
+///
+/// `FileHistory` reads the history through this in its reading thread.
+/// The built-in providers are for the `GitEngine`s; see `GitEngine::provider()`.
+/// Use `FileHistoryBuilder::provider()` to plug in other providers.
+pub trait HistoryProvider: fmt::Debug + Send + Sync {
+    /// The name for messages and logs, such as the `--engine` option.
+    fn name(&self) -> &str;
+
+    /// The commit to start reading the history from.
+    ///
+    /// The default implementation is the `BlameOptions::rev()` at the
+    /// `BlameOptions::at()`, or the `HEAD` if they're not set.
+    fn start_commit_id(
+        &self,
+        git: &GitTools,
+        options: &BlameOptions,
+    ) -> Result<git2::Oid, BlameError> {
+        Ok(options.start_commit_id(git)?)
+    }
+
+    /// The commits that changed the file at the `path`, newest first,
+    /// from the `start_commit_id`. This is called in the reading thread, and
+    /// the iterator should stop when the `cancellation_token` is cancelled.
+    ///
+    /// Only the `FileCommit::commit_id()` of the items is used; the commits
+    /// are read by `read_commit()` with the path at each commit, following
+    /// renames. The lower bound of `Iterator::size_hint()`, if any, is shown
+    /// in the progress as the commits found but not read yet.
+    ///
+    /// The default implementation lists the commits by `git log`.
+    fn commits(
+        &self,
+        git: &GitTools,
+        path: &Path,
+        options: &BlameOptions,
+        start_commit_id: git2::Oid,
+        cancellation_token: &CancellationToken,
+    ) -> Result<FileCommitIterator, BlameError> {
+        let mut commits = CommitIterator::new(path, git.repository_path());
+        commits.set_options(options.clone());
+        commits.set_start_commit_id(Some(start_commit_id));
+        commits.set_cancellation_token(cancellation_token.clone());
+        commits.start()?;
+        Ok(Box::new(commits.into_file_commits()))
+    }
+
+    /// Read the metadata of the `commit`, and the diff of the file at its
+    /// `FileCommit::path()` from its parent.
+    fn read_commit(
+        &self,
+        git: &GitTools,
+        commit: &mut FileCommit,
+        options: &BlameOptions,
+    ) -> Result<(), BlameError>;
+
+    /// The content of the file at the `path` in the `commit_id`.
+    ///
+    /// The default implementation reads the blob by `GitTools`.
+    fn content(
+        &self,
+        git: &GitTools,
+        commit_id: git2::Oid,
+        path: &Path,
+    ) -> Result<String, BlameError> {
+        Ok(git.content_as_string(commit_id, path)?)
+    }
+
+    /// True if lines are attributed by `blame()` instead of applying the
+    /// diffs. `blame()` is still used if the diffs fail to apply.
+    fn is_blame(&self) -> bool {
+        false
+    }
+
+    /// Blame the file at the `path` in the `commit_id`, only the
+    /// `line_numbers` if specified. Returns a list of the commit ID, the line
+    /// number in the commit, and the line number in the `commit_id`, for each
+    /// line, sorted by the line numbers in the `commit_id`.
+    ///
+    /// The default implementation runs `git blame`, with the
+    /// `BlameOptions::ignore_revs()` and the `BlameOptions::detect_moves()`.
+    fn blame(
+        &self,
+        git: &GitTools,
+        commit_id: git2::Oid,
+        path: &Path,
+        line_numbers: Option<Range<usize>>,
+        options: &BlameOptions,
+    ) -> Result<Vec<(git2::Oid, usize, usize)>, BlameError> {
+        Ok(git.blame(commit_id, path, line_numbers, &options.blame_args())?)
+    }
+}
+
+/// The `HistoryProvider` that computes the diffs by the `git` command.
+#[derive(Debug, Default)]
+pub struct GitProvider;
+
+impl HistoryProvider for GitProvider {
+    fn name(&self) -> &str {
+        "git"
+    }
+
+    fn read_commit(
+        &self,
+        git: &GitTools,
+        commit: &mut FileCommit,
+        options: &BlameOptions,
+    ) -> Result<(), BlameError> {
+        Ok(commit.read_by_git(git, options)?)
+    }
+}
+
+/// The `HistoryProvider` that computes the diffs by the `git2` crate.
+#[derive(Debug, Default)]
+pub struct Git2Provider;
+
+impl HistoryProvider for Git2Provider {
+    fn name(&self) -> &str {
+        "git2"
+    }
+
+    fn read_commit(
+        &self,
+        git: &GitTools,
+        commit: &mut FileCommit,
+        options: &BlameOptions,
+    ) -> Result<(), BlameError> {
+        Ok(commit.read_by_git2(git, options)?)
+    }
+
+    fn blame(
+        &self,
+        git: &GitTools,
+        commit_id: git2::Oid,
+        path: &Path,
+        line_numbers: Option<Range<usize>>,
+        _options: &BlameOptions,
+    ) -> Result<Vec<(git2::Oid, usize, usize)>, BlameError> {
+        Ok(git.blame_by_git2(commit_id, path, line_numbers)?)
+    }
+}
+
+/// The `HistoryProvider` that attributes lines by `git blame`.
+/// Diffs are computed in the same way as `GitProvider`.
+#[derive(Debug, Default)]
+pub struct GitBlameProvider;
+
+impl HistoryProvider for GitBlameProvider {
+    fn name(&self) -> &str {
+        "blame"
+    }
+
+    fn read_commit(
+        &self,
+        git: &GitTools,
+        commit: &mut FileCommit,
+        options: &BlameOptions,
+    ) -> Result<(), BlameError> {
+        GitProvider.read_commit(git, commit, options)
+    }
+
+    fn is_blame(&self) -> bool {
+        true
+    }
+}
+
+/// The `HistoryProvider` that attributes lines by `git2::Blame`.
+/// Diffs are computed in the same way as `Git2Provider`.
+#[derive(Debug, Default)]
+pub struct Git2BlameProvider;
+
+impl HistoryProvider for Git2BlameProvider {
+    fn name(&self) -> &str {
+        "git2-blame"
+    }
+
+    fn read_commit(
+        &self,
+        git: &GitTools,
+        commit: &mut FileCommit,
+        options: &BlameOptions,
+    ) -> Result<(), BlameError> {
+        Git2Provider.read_commit(git, commit, options)
+    }
+
+    fn is_blame(&self) -> bool {
+        true
+    }
+
+    fn blame(
+        &self,
+        git: &GitTools,
+        commit_id: git2::Oid,
+        path: &Path,
+        line_numbers: Option<Range<usize>>,
+        options: &BlameOptions,
+    ) -> Result<Vec<(git2::Oid, usize, usize)>, BlameError> {
+        Git2Provider.blame(git, commit_id, path, line_numbers, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::{blame::FileHistoryBuilder, extensions::tests::TempRepository};
+
+    use super::*;
+
+    /// A provider that counts the calls, and reads the content in upper case.
+    #[derive(Debug, Default)]
+    struct TestProvider {
+        read_commit_count: AtomicUsize,
+        blame_count: AtomicUsize,
+        is_blame: bool,
+    }
+
+    impl HistoryProvider for TestProvider {
+        fn name(&self) -> &str {
+            "test"
+        }
+
+        fn read_commit(
+            &self,
+            git: &GitTools,
+            commit: &mut FileCommit,
+            options: &BlameOptions,
+        ) -> Result<(), BlameError> {
+            self.read_commit_count.fetch_add(1, Ordering::Relaxed);
+            GitProvider.read_commit(git, commit, options)
+        }
+
+        fn content(
+            &self,
+            git: &GitTools,
+            commit_id: git2::Oid,
+            path: &Path,
+        ) -> Result<String, BlameError> {
+            Ok(git.content_as_string(commit_id, path)?.to_uppercase())
+        }
+
+        fn is_blame(&self) -> bool {
+            self.is_blame
+        }
+
+        fn blame(
+            &self,
+            git: &GitTools,
+            commit_id: git2::Oid,
+            path: &Path,
+            line_numbers: Option<Range<usize>>,
+            options: &BlameOptions,
+        ) -> Result<Vec<(git2::Oid, usize, usize)>, BlameError> {
+            self.blame_count.fetch_add(1, Ordering::Relaxed);
+            Git2Provider.blame(git, commit_id, path, line_numbers, options)
+        }
+    }
+
+    #[test]
+    fn provider() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "a\nb\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "a\nb\nc\nd\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add c and d")?;
+
+        let provider = Arc::new(TestProvider::default());
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(path))
+            .provider(provider.clone())
+            .build();
+        assert_eq!(history.provider().name(), "test");
+        history.read_all()?;
+        assert_eq!(history.commits().len(), 2);
+        assert_eq!(provider.read_commit_count.load(Ordering::Relaxed), 2);
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let lines: Vec<(&str, Option<git2::Oid>)> = content
+            .lines()
+            .iter()
+            .map(|line| (line.content(), line.commit_id()))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                ("A", Some(commit_id1)),
+                ("B", Some(commit_id1)),
+                ("C", Some(commit_id2)),
+                ("D", Some(commit_id2)),
+            ]
+        );
+        assert_eq!(provider.blame_count.load(Ordering::Relaxed), 0);
+
+        // Lines are attributed by the `blame()` of the provider.
+        let provider = Arc::new(TestProvider {
+            is_blame: true,
+            ..Default::default()
+        });
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(path))
+            .provider(provider.clone())
+            .build();
+        history.read_all()?;
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let commit_ids: Vec<Option<git2::Oid>> = content
+            .lines()
+            .iter()
+            .map(|line| line.commit_id())
+            .collect();
+        assert_eq!(
+            commit_ids,
+            [
+                Some(commit_id1),
+                Some(commit_id1),
+                Some(commit_id2),
+                Some(commit_id2)
+            ]
+        );
+        assert_eq!(provider.blame_count.load(Ordering::Relaxed), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn git_blame_options() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "a\nb\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "a\nB\n")?;
+        let commit_id2 = git.commit(commit_id1, "Reformat b")?;
+
+        let mut options = BlameOptions::new();
+        let blame = |options: &BlameOptions| {
+            GitBlameProvider.blame(&git.git, commit_id2, path, None, options)
+        };
+        assert_eq!(blame(&options)?, [(commit_id1, 1, 1), (commit_id2, 2, 2)]);
+        options.add_ignore_rev(&commit_id2.to_string());
+        assert_eq!(blame(&options)?, [(commit_id1, 1, 1), (commit_id1, 2, 2)]);
+        Ok(())
+    }
+}
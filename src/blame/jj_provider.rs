@@ -0,0 +1,253 @@
+use std::{
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+    process,
+    sync::{Arc, Mutex},
+};
+
+use log::*;
+
+use crate::extensions::GitTools;
+
+use super::{
+    BlameError, BlameOptions, CancellationToken, FileCommit, FileCommitIterator, HistoryProvider,
+};
+
+/// The `HistoryProvider` for [Jujutsu] (`jj`) repositories colocated with git.
+///
+/// The history is read by the `inner` provider, because all `jj` commits are
+/// also git commits. In addition, this provider:
+/// * Starts from the working-copy commit (`@`) instead of the git `HEAD`,
+///   unless the `BlameOptions::rev()` or `BlameOptions::at()` is set.
+///   The working copy isn't snapshotted; i.e., the `@` is as of the last
+///   `jj` command.
+/// * Sets the change IDs to `FileCommit::change_id()`. Divergent changes
+///   have `??` at the end, as `jj log` shows them.
+///
+/// If the `jj` command fails, this works the same as the `inner` provider.
+///
+/// [Jujutsu]: https://jj-vcs.github.io/jj/
+#[derive(Debug)]
+pub struct JjProvider {
+    inner: Arc<dyn HistoryProvider>,
+    /// The change IDs of the commits that changed each path, read by `jj log`
+    /// when first needed for the path.
+    change_ids: Mutex<HashMap<PathBuf, Arc<HashMap<git2::Oid, String>>>>,
+}
+
+impl JjProvider {
+    pub fn new(inner: Arc<dyn HistoryProvider>) -> Self {
+        Self {
+            inner,
+            change_ids: Mutex::default(),
+        }
+    }
+
+    /// Whether the `path` is in a `jj` workspace.
+    pub fn is_in_workspace(path: &Path) -> bool {
+        let Ok(path) = path.canonicalize() else {
+            return false;
+        };
+        path.ancestors().any(|dir| dir.join(".jj").is_dir())
+    }
+
+    fn jj_log(git: &GitTools, revset: &str, template: &str) -> anyhow::Result<String> {
+        let output = process::Command::new("jj")
+            .arg("--repository")
+            .arg(git.workdir_path())
+            .args(["--color", "never", "--ignore-working-copy"])
+            .args(["log", "--no-graph", "-r", revset, "-T"])
+            .arg(template)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "jj log failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// The change IDs of the commits that changed the `path`.
+    fn change_ids(&self, git: &GitTools, path: &Path) -> Arc<HashMap<git2::Oid, String>> {
+        // Hold the lock while reading, so that other threads don't read the
+        // same `path` again.
+        let mut change_ids = self.change_ids.lock().unwrap();
+        change_ids
+            .entry(path.to_path_buf())
+            .or_insert_with(|| {
+                const TEMPLATE: &str =
+                    r#"commit_id ++ " " ++ change_id ++ if(divergent, "??") ++ "\n""#;
+                match Self::jj_log(git, &Self::revset(path), TEMPLATE) {
+                    Ok(output) => Arc::new(Self::parse_change_ids(&output)),
+                    Err(error) => {
+                        warn!("Failed to read the change IDs of {path:?}: {error}");
+                        Arc::default()
+                    }
+                }
+            })
+            .clone()
+    }
+
+    /// The revset of the ancestors of `@` that changed the `path`.
+    fn revset(path: &Path) -> String {
+        let path = path
+            .to_string_lossy()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"");
+        format!(r#"::@ & files(root:"{path}")"#)
+    }
+
+    /// Parse the lines of the commit ID and the change ID.
+    fn parse_change_ids(output: &str) -> HashMap<git2::Oid, String> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let (commit_id, change_id) = line.split_once(' ')?;
+                let commit_id = git2::Oid::from_str(commit_id).ok()?;
+                Some((commit_id, change_id.to_string()))
+            })
+            .collect()
+    }
+}
+
+impl HistoryProvider for JjProvider {
+    fn name(&self) -> &str {
+        "jj"
+    }
+
+    fn start_commit_id(
+        &self,
+        git: &GitTools,
+        options: &BlameOptions,
+    ) -> Result<git2::Oid, BlameError> {
+        if options.rev().is_none() && options.at().is_none() {
+            match Self::jj_log(git, "@", "commit_id") {
+                Ok(output) => return Ok(git2::Oid::from_str(output.trim())?),
+                Err(error) => warn!("Failed to read the working-copy commit: {error}"),
+            }
+        }
+        self.inner.start_commit_id(git, options)
+    }
+
+    fn commits(
+        &self,
+        git: &GitTools,
+        path: &Path,
+        options: &BlameOptions,
+        start_commit_id: git2::Oid,
+        cancellation_token: &CancellationToken,
+    ) -> Result<FileCommitIterator, BlameError> {
+        self.inner
+            .commits(git, path, options, start_commit_id, cancellation_token)
+    }
+
+    fn read_commit(
+        &self,
+        git: &GitTools,
+        commit: &mut FileCommit,
+        options: &BlameOptions,
+    ) -> Result<(), BlameError> {
+        self.inner.read_commit(git, commit, options)?;
+        let change_id = self
+            .change_ids(git, commit.path())
+            .get(&commit.commit_id())
+            .cloned();
+        commit.set_change_id(change_id);
+        Ok(())
+    }
+
+    fn content(
+        &self,
+        git: &GitTools,
+        commit_id: git2::Oid,
+        path: &Path,
+    ) -> Result<String, BlameError> {
+        self.inner.content(git, commit_id, path)
+    }
+
+    fn is_blame(&self) -> bool {
+        self.inner.is_blame()
+    }
+
+    fn blame(
+        &self,
+        git: &GitTools,
+        commit_id: git2::Oid,
+        path: &Path,
+        line_numbers: Option<Range<usize>>,
+        options: &BlameOptions,
+    ) -> Result<Vec<(git2::Oid, usize, usize)>, BlameError> {
+        self.inner
+            .blame(git, commit_id, path, line_numbers, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        blame::{FileHistoryBuilder, GitProvider},
+        extensions::tests::TempRepository,
+    };
+
+    use super::*;
+
+    #[test]
+    fn parse_change_ids() {
+        let commit_id1 = "0123456789012345678901234567890123456789";
+        let commit_id2 = "abcdefabcdefabcdefabcdefabcdefabcdefabcd";
+        let output = format!("{commit_id1} kkmpptxz\n{commit_id2} qpvuntsm??\n\n");
+        let change_ids = JjProvider::parse_change_ids(&output);
+        assert_eq!(change_ids.len(), 2);
+        assert_eq!(
+            change_ids[&git2::Oid::from_str(commit_id1).unwrap()],
+            "kkmpptxz"
+        );
+        assert_eq!(
+            change_ids[&git2::Oid::from_str(commit_id2).unwrap()],
+            "qpvuntsm??"
+        );
+    }
+
+    #[test]
+    fn revset() {
+        assert_eq!(
+            JjProvider::revset(Path::new("src/main.rs")),
+            r#"::@ & files(root:"src/main.rs")"#
+        );
+        assert_eq!(
+            JjProvider::revset(Path::new(r#"a"b\c"#)),
+            r#"::@ & files(root:"a\"b\\c")"#
+        );
+    }
+
+    #[test]
+    fn is_in_workspace() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = git.to_file_path(Path::new("test.txt"));
+        git.add_file_content(Path::new("test.txt"), "1\n")?;
+        assert!(!JjProvider::is_in_workspace(&path));
+        std::fs::create_dir(git.worktree_path().join(".jj"))?;
+        assert!(JjProvider::is_in_workspace(&path));
+        Ok(())
+    }
+
+    #[test]
+    fn fallback() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+
+        // `jj` fails because the repository isn't a `jj` repository.
+        let provider = Arc::new(JjProvider::new(Arc::new(GitProvider)));
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(path))
+            .provider(provider)
+            .build();
+        history.read_all()?;
+        let commit = history.commits().get_by_commit_id(commit_id)?;
+        assert_eq!(commit.change_id(), None);
+        Ok(())
+    }
+}
@@ -1,13 +1,15 @@
-use std::{borrow::Cow, fmt, io::Write};
+use std::{fmt, ops::Range, path::Path, sync::Arc};
 
-use crossterm::{queue, style};
-use git2_time_chrono_ext::Git2TimeChronoExt;
-use unicode_width_utils::UnicodeWidth;
+use chrono::{DateTime, TimeDelta, Utc};
 
 use super::{FileCommit, FileHistory};
-use crate::extensions::OrDefault;
 
-#[derive(Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
 enum LineType {
     #[default]
     Line,
@@ -15,13 +17,67 @@ enum LineType {
     Log,
 }
 
-#[derive(Debug, Default)]
+/// The content of a `Line`.
+///
+/// The lines of a file share the content of the whole file, and each line
+/// has only the range in it, so that very large files don't allocate a
+/// `String` per line.
+///
+/// This reduces the allocations, but not the number of `Line`s; the
+/// `FileContent` still has a `Line` per line of the file to attribute, so its
+/// memory is proportional to the file, not to the lines on the screen.
+#[derive(Clone, Debug)]
+enum LineContent {
+    Owned(String),
+    Shared(Arc<str>, Range<usize>),
+}
+
+impl Default for LineContent {
+    fn default() -> Self {
+        Self::Owned(String::new())
+    }
+}
+
+impl LineContent {
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Owned(content) => content,
+            Self::Shared(buffer, range) => &buffer[range.clone()],
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for LineContent {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Line {
     line_type: LineType,
     line_number: usize,
-    content: String,
+    content: LineContent,
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "super::serialize::option_oid")
+    )]
     commit_id: Option<git2::Oid>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "super::serialize::option_time")
+    )]
+    commit_time: Option<git2::Time>,
+    is_boundary: bool,
+    is_moved: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    is_read_ahead: bool,
+    original_line_number: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(skip))]
     index_in_hunk: usize,
+    #[cfg_attr(feature = "serde", serde(skip))]
     is_last_line_in_hunk: bool,
 }
 
@@ -29,7 +85,18 @@ impl Line {
     pub fn new(line_number: usize, content: String) -> Self {
         Self {
             line_number,
-            content,
+            content: LineContent::Owned(content),
+            ..Default::default()
+        }
+    }
+
+    /// A line whose content is the `range` of the `buffer`, which is shared
+    /// with other lines.
+    pub fn new_shared(line_number: usize, buffer: &Arc<str>, range: Range<usize>) -> Self {
+        debug_assert!(buffer.get(range.clone()).is_some());
+        Self {
+            line_number,
+            content: LineContent::Shared(buffer.clone(), range),
             ..Default::default()
         }
     }
@@ -44,12 +111,19 @@ impl Line {
         }
     }
 
-    pub fn new_log(commit: &FileCommit) -> Self {
+    /// A line of the log view. The `prefix` is the graph and the decorations
+    /// before the summary.
+    pub fn new_log(commit: &FileCommit, prefix: &str) -> Self {
         Self {
             line_type: LineType::Log,
             line_number: commit.index(),
-            content: commit.summary().cloned().unwrap_or_default(),
+            content: LineContent::Owned(format!(
+                "{prefix}{}",
+                commit.summary().map_or("", |s| s.as_str())
+            )),
             commit_id: Some(commit.commit_id()),
+            commit_time: Some(commit.time()),
+            is_boundary: commit.is_boundary(),
             ..Default::default()
         }
     }
@@ -58,8 +132,18 @@ impl Line {
         self.line_number
     }
 
+    /// True if this is a line to indicate deleted lines.
+    pub fn is_deleted(&self) -> bool {
+        self.line_type == LineType::Deleted
+    }
+
+    /// True if this is a line of a commit in the log.
+    pub fn is_log(&self) -> bool {
+        self.line_type == LineType::Log
+    }
+
     pub fn content(&self) -> &str {
-        &self.content
+        self.content.as_str()
     }
 
     pub fn commit_id(&self) -> Option<git2::Oid> {
@@ -70,176 +154,114 @@ impl Line {
         self.commit_id = Some(commit_id);
     }
 
-    pub fn set_index_in_hunk(&mut self, index_in_hunk: usize) {
-        self.index_in_hunk = index_in_hunk;
+    /// Set the commit this line is attributed to.
+    pub fn set_commit(&mut self, commit: &FileCommit) {
+        self.set_commit_id(commit.commit_id());
+        self.commit_time = Some(commit.time());
+        self.is_boundary = commit.is_boundary();
+        self.is_read_ahead = false;
     }
 
-    pub fn set_is_last_line_in_hunk(&mut self, value: bool) {
-        self.is_last_line_in_hunk = value;
+    /// Set the commit read ahead of the newer commits. It's replaced when the
+    /// commits are applied in order. See `FileHistory::set_priority_lines()`.
+    pub fn set_read_ahead_commit(&mut self, commit: &FileCommit) {
+        self.set_commit(commit);
+        self.is_read_ahead = true;
     }
-}
 
-impl fmt::Display for Line {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}: {}", self.line_number, self.content)
+    /// True if the commit is set by `set_read_ahead_commit()`.
+    pub fn is_read_ahead(&self) -> bool {
+        self.is_read_ahead
     }
-}
 
-impl Line {
-    pub fn render(
-        &self,
-        out: &mut impl Write,
-        history: &FileHistory,
-        is_current_line: bool,
-        constraint: &LineConstraint,
-    ) -> anyhow::Result<()> {
-        let commit = self
-            .commit_id
-            .map(|commit_id| history.commits().get_by_commit_id(commit_id))
-            .transpose()?;
-        let mut should_reset = false;
-        if is_current_line {
-            queue!(
-                out,
-                style::SetColors(style::Colors::new(style::Color::Black, style::Color::Cyan)),
-            )?;
-            should_reset = true;
-        } else if let Some(commit) = commit
-            && commit.is_apply_failed()
-        {
-            queue!(
-                out,
-                style::SetColors(style::Colors::new(style::Color::Red, style::Color::Black)),
-            )?;
-            should_reset = true;
-        }
+    /// The time of the commit this line is attributed to,
+    /// or `None` if the commit isn't known yet.
+    pub fn commit_time(&self) -> Option<git2::Time> {
+        self.commit_time
+    }
 
-        if self.is_last_line_in_hunk {
-            queue!(out, style::SetAttribute(style::Attribute::Underlined))?;
-            should_reset = true;
-        }
+    /// The age of this line at `now`; i.e., the duration since the commit.
+    pub fn age(&self, now: DateTime<Utc>) -> Option<TimeDelta> {
+        self.commit_time
+            .map(|time| TimeDelta::seconds(now.timestamp() - time.seconds()))
+    }
 
-        let blame = self.left_pane(commit)?;
-        let left_pane = match self.line_type {
-            LineType::Line | LineType::Log => format!("{:4}:{blame:25.25}|", self.line_number),
-            LineType::Deleted => format!("    :{blame:25.25}|"),
-        };
-        let left_pane_len = left_pane.len();
-        queue!(out, style::Print(left_pane))?;
-
-        if should_reset {
-            queue!(
-                out,
-                style::ResetColor,
-                style::SetAttribute(style::Attribute::Reset)
-            )?;
-        }
+    /// The age of this line at `now` in days.
+    pub fn age_days(&self, now: DateTime<Utc>) -> Option<i64> {
+        self.age(now).map(|age| age.num_days())
+    }
 
-        match self.line_type {
-            LineType::Line | LineType::Log => {
-                let content = constraint.truncate(&self.content, left_pane_len);
-                queue!(out, style::Print(content))?;
-            }
-            LineType::Deleted => {
-                let content = "##deleted##";
-                queue!(
-                    out,
-                    style::SetForegroundColor(style::Color::DarkGrey),
-                    style::Print(content),
-                    style::ResetColor,
-                )?;
-            }
-        }
-        Ok(())
-    }
-
-    fn left_pane(&self, commit: Option<&FileCommit>) -> anyhow::Result<Cow<'static, str>> {
-        let left_pane = if let Some(commit) = commit {
-            match self.index_in_hunk {
-                0 => {
-                    let datetime = commit.time().to_local_date_time().map_or_else(
-                        |e| format!("Invalid date/time: {e}"),
-                        |datetime| datetime.format("%Y-%m-%d %H:%M").to_string(),
-                    );
-                    match self.line_type {
-                        LineType::Line | LineType::Deleted => {
-                            format!("#{} {}", commit.index(), datetime)
-                        }
-                        LineType::Log => {
-                            format!("{} {}", datetime, commit.author_email())
-                        }
-                    }
-                    .into()
-                }
-                1 => commit.summary().map(|s| format!("  {s}")).or_default(),
-                2 => format!("  {}", commit.author_email()).into(),
-                3 => format!("  {}", commit.commit_id()).into(),
-                _ => "".into(),
-            }
-        } else {
-            "...".into()
-        };
-        Ok(left_pane)
+    /// The line number at the commit that introduced this line,
+    /// or `None` if the commit isn't known yet.
+    pub fn original_line_number(&self) -> Option<usize> {
+        self.original_line_number
     }
-}
 
-pub(crate) struct LineConstraint {
-    max_columns: usize,
-    uw: UnicodeWidth,
-}
+    pub fn set_original_line_number(&mut self, line_number: usize) {
+        self.original_line_number = Some(line_number);
+    }
 
-impl LineConstraint {
-    const TAB_SIZE: u8 = 4;
+    /// The path at the commit that introduced this line,
+    /// or `None` if the commit isn't known yet.
+    /// This is different from the current path if the file was renamed.
+    pub fn original_path<'a>(&self, history: &'a FileHistory) -> Option<&'a Path> {
+        let commit_id = self.commit_id?;
+        let commit = history.commit_by_id(commit_id).ok()?;
+        Some(commit.path())
+    }
 
-    pub(crate) fn new(max_columns: usize) -> Self {
-        let mut uw = UnicodeWidth::new();
-        uw.set_tab_size(Self::TAB_SIZE);
-        uw.set_expand_tab(true);
-        Self { max_columns, uw }
+    /// True if this line is attributed to a boundary commit.
+    /// See `FileCommit::is_boundary()`.
+    pub fn is_boundary(&self) -> bool {
+        self.is_boundary
     }
 
-    fn truncate<'a>(&self, input: &'a str, margin: usize) -> Cow<'a, str> {
-        let max_columns = self.max_columns.saturating_sub(margin);
-        self.uw.truncate(input, max_columns)
+    /// True if this line was moved within the file by a later commit.
+    /// See `BlameOptions::set_detect_moves()`.
+    pub fn is_moved(&self) -> bool {
+        self.is_moved
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub fn set_moved(&mut self, is_moved: bool) {
+        self.is_moved = is_moved;
+    }
 
-    #[test]
-    fn truncate() {
-        let c = LineConstraint::new(5);
-        assert_eq!(c.truncate("abc", 0), "abc");
-        assert_eq!(c.truncate("abc", 3), "ab");
-        assert_eq!(c.truncate("abc", 5), "");
+    /// The index of the line in the consecutive lines of the same commit.
+    pub fn index_in_hunk(&self) -> usize {
+        self.index_in_hunk
     }
 
-    #[test]
-    fn truncate_tab() {
-        let c = LineConstraint::new(5);
-        assert_eq!(c.truncate("\t", 1), "    ");
-        assert_eq!(c.truncate("\t", 2), "");
+    pub fn set_index_in_hunk(&mut self, index_in_hunk: usize) {
+        self.index_in_hunk = index_in_hunk;
+    }
+
+    /// Whether the line is the last line of the consecutive lines of the same
+    /// commit.
+    pub fn is_last_line_in_hunk(&self) -> bool {
+        self.is_last_line_in_hunk
+    }
 
-        assert_eq!(c.truncate("a\t", 0), "a   ");
-        assert_eq!(c.truncate("a\t", 1), "a   ");
-        assert_eq!(c.truncate("a\t", 2), "a");
+    pub fn set_is_last_line_in_hunk(&mut self, value: bool) {
+        self.is_last_line_in_hunk = value;
+    }
+}
 
-        assert_eq!(c.truncate("123\t", 0), "123 ");
-        let c = LineConstraint::new(10);
-        assert_eq!(c.truncate("1234\t", 0), "1234    ");
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.line_number, self.content())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
     #[test]
-    fn truncate_wide() {
-        let c = LineConstraint::new(11);
-        assert_eq!(c.truncate("あいうえお", 0), "あいうえお");
-        assert_eq!(c.truncate("あいうえお", 1), "あいうえお");
-        assert_eq!(c.truncate("あいうえお", 2), "あいうえ");
-        assert_eq!(c.truncate("あいうえお", 3), "あいうえ");
-        assert_eq!(c.truncate("あいうえお", 9), "あ");
-        assert_eq!(c.truncate("あいうえお", 10), "");
-        assert_eq!(c.truncate("あいうえお", 11), "");
+    fn age() {
+        let mut line = Line::new(1, String::new());
+        let now = DateTime::from_timestamp(1_000_000_000, 0).unwrap();
+        assert_eq!(line.age_days(now), None);
+        line.commit_time = Some(git2::Time::new(1_000_000_000 - 3 * 24 * 60 * 60 - 1, 0));
+        assert_eq!(line.age_days(now), Some(3));
     }
 }
@@ -0,0 +1,84 @@
+use super::FileCommit;
+
+/// A filter to highlight lines by their commits.
+/// Lines that don't match the filter are dimmed.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LineFilter {
+    after: Option<i64>,
+    before: Option<i64>,
+    /// Lowercased, to match case-insensitively.
+    author: Option<String>,
+}
+
+impl LineFilter {
+    /// `true` if this filter doesn't filter any lines.
+    pub fn is_empty(&self) -> bool {
+        self.after.is_none() && self.before.is_none() && self.author.is_none()
+    }
+
+    /// Match only commits at or after the time in seconds since the epoch.
+    pub fn set_after(&mut self, after: Option<i64>) {
+        self.after = after;
+    }
+
+    /// Match only commits before the time in seconds since the epoch.
+    pub fn set_before(&mut self, before: Option<i64>) {
+        self.before = before;
+    }
+
+    /// Match only commits whose author emails contain the `author`,
+    /// case-insensitively.
+    pub fn set_author(&mut self, author: Option<&str>) {
+        self.author = author.map(str::to_lowercase);
+    }
+
+    pub fn is_match(&self, commit: Option<&FileCommit>) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let Some(commit) = commit else {
+            return false;
+        };
+        let time = commit.time().seconds();
+        self.after.is_none_or(|after| time >= after)
+            && self.before.is_none_or(|before| time < before)
+            && self
+                .author
+                .as_ref()
+                .is_none_or(|author| commit.author_email().to_lowercase().contains(author))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn is_match() {
+        let mut commit = FileCommit::new(git2::Oid::ZERO_SHA1, Path::new(""));
+        commit.set_time_for_test(git2::Time::new(100, 0));
+        let mut filter = LineFilter::default();
+        assert!(filter.is_match(Some(&commit)));
+        assert!(filter.is_match(None));
+
+        filter.set_after(Some(100));
+        assert!(filter.is_match(Some(&commit)));
+        assert!(!filter.is_match(None));
+        filter.set_after(Some(101));
+        assert!(!filter.is_match(Some(&commit)));
+
+        filter.set_after(None);
+        filter.set_before(Some(100));
+        assert!(!filter.is_match(Some(&commit)));
+        filter.set_before(Some(101));
+        assert!(filter.is_match(Some(&commit)));
+
+        commit.set_metadata_for_test("Alice@example.com", "Summary");
+        filter.set_author(Some("alice"));
+        assert!(filter.is_match(Some(&commit)));
+        filter.set_author(Some("bob"));
+        assert!(!filter.is_match(Some(&commit)));
+    }
+}
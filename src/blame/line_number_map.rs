@@ -1,10 +1,14 @@
-use std::{cmp::Ordering, ops::Range};
+use std::{
+    cmp::{self, Ordering},
+    ops::{Range, RangeInclusive},
+};
 
+use anyhow::{Context, bail};
 use log::*;
 
 use super::{DiffPart, DiffRange};
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 struct LineNumberMapItem {
     range: Range<usize>,
     add: usize,
@@ -12,7 +16,40 @@ struct LineNumberMapItem {
     is_delete: bool,
 }
 
-#[derive(Debug, Default)]
+/// How a `LineNumberMapSegment` maps the line numbers.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum LineNumberMapping {
+    /// Add the value to the line numbers.
+    Shift(isize),
+    /// Map all line numbers to the value.
+    Const(usize),
+}
+
+impl LineNumberMapping {
+    fn map(&self, value: usize) -> usize {
+        match *self {
+            Self::Shift(_) if value == usize::MAX => usize::MAX,
+            Self::Shift(delta) => {
+                debug_assert!(value.checked_add_signed(delta).is_some(), "{value}+{delta}");
+                value.saturating_add_signed(delta)
+            }
+            Self::Const(to) => to,
+        }
+    }
+}
+
+/// A range of line numbers and how they're mapped.
+type LineNumberMapSegment = (RangeInclusive<usize>, LineNumberMapping);
+
+/// Maps line numbers before a diff to line numbers after it, or the reverse.
+///
+/// Line numbers in unchanged lines are shifted by the lines added or deleted
+/// above them. Deleted lines are mapped to the line number where they were.
+/// `usize::MAX` is mapped to `usize::MAX`, so that it can be the end of
+/// open-ended ranges.
+///
+/// The `default()` is the identity map.
+#[derive(Clone, Debug, Default)]
 pub struct LineNumberMap {
     items: Vec<LineNumberMapItem>,
 }
@@ -22,6 +59,8 @@ impl LineNumberMap {
     pub fn new_new_from_old(parts: &Vec<DiffPart>) -> LineNumberMap {
         Self::new(parts, |part| (&part.old, &part.new))
     }
+
+    /// To map new line numbers to old.
     pub fn new_old_from_new(parts: &Vec<DiffPart>) -> LineNumberMap {
         Self::new(parts, |part| (&part.new, &part.old))
     }
@@ -78,49 +117,236 @@ impl LineNumberMap {
         LineNumberMap { items }
     }
 
+    /// The map that is the same as applying `self` and then `other`;
+    /// i.e., `self.compose(&other).map(n) == other.map(self.map(n))`.
+    ///
+    /// This is useful to map many line numbers across multiple diffs,
+    /// such as through a sequence of commits.
+    pub fn compose(&self, other: &LineNumberMap) -> LineNumberMap {
+        let other_segments = other.segments();
+        let mut segments = Vec::new();
+        for (range, mapping) in self.segments() {
+            let delta = match mapping {
+                LineNumberMapping::Const(to) => {
+                    Self::push_segment(
+                        &mut segments,
+                        range,
+                        LineNumberMapping::Const(other.map(to)),
+                    );
+                    continue;
+                }
+                LineNumberMapping::Shift(delta) => delta,
+            };
+            // Split the `range` by the segments of `other` its image overlaps.
+            let mut start = *range.start();
+            for (other_range, other_mapping) in &other_segments {
+                if *other_range.end() < mapping.map(start) {
+                    continue;
+                }
+                let end = if *other_range.end() == usize::MAX {
+                    *range.end()
+                } else {
+                    let end = (*other_range.end() as isize - delta) as usize;
+                    cmp::min(end, *range.end())
+                };
+                let composed = match *other_mapping {
+                    LineNumberMapping::Shift(other_delta) => {
+                        LineNumberMapping::Shift(delta + other_delta)
+                    }
+                    other_mapping => other_mapping,
+                };
+                Self::push_segment(&mut segments, start..=end, composed);
+                if end == *range.end() {
+                    break;
+                }
+                start = end + 1;
+            }
+        }
+        Self::from_segments(segments)
+    }
+
+    /// The map in the opposite direction.
+    ///
+    /// Line numbers that multiple line numbers are mapped to, such as deleted
+    /// lines, are mapped back to the largest of them. Line numbers that no line
+    /// numbers are mapped to, such as added lines, are mapped to the smallest
+    /// line number that is mapped after them.
+    pub fn invert(&self) -> LineNumberMap {
+        let mut segments = Vec::new();
+        let mut next = 0;
+        for (range, mapping) in self.segments() {
+            let start = mapping.map(*range.start());
+            let end = mapping.map(*range.end());
+            if start > next {
+                Self::push_segment(
+                    &mut segments,
+                    next..=start - 1,
+                    LineNumberMapping::Const(*range.start()),
+                );
+            }
+            let inverted = match mapping {
+                LineNumberMapping::Shift(delta) => LineNumberMapping::Shift(-delta),
+                LineNumberMapping::Const(_) => LineNumberMapping::Const(*range.end()),
+            };
+            Self::push_segment(&mut segments, start..=end, inverted);
+            next = end.saturating_add(1);
+        }
+        Self::from_segments(segments)
+    }
+
+    /// The segments that cover all line numbers, in the ascending order.
+    fn segments(&self) -> Vec<LineNumberMapSegment> {
+        let Some(first) = self.items.first() else {
+            return vec![(0..=usize::MAX, LineNumberMapping::Shift(0))];
+        };
+        let mut segments = Vec::new();
+        if first.range.start > 0 {
+            segments.push((0..=first.range.start - 1, LineNumberMapping::Shift(0)));
+        }
+        // Consistent with `apply_to_values()`, the `range.end` of each item is
+        // inclusive, and the `range.start` is effective only for the first item.
+        let mut start = first.range.start;
+        for item in &self.items {
+            if item.range.end < start {
+                continue;
+            }
+            let mapping = if item.is_delete {
+                LineNumberMapping::Const(item.range.start + item.add - item.sub)
+            } else {
+                LineNumberMapping::Shift(item.add as isize - item.sub as isize)
+            };
+            segments.push((start..=item.range.end, mapping));
+            start = item.range.end.saturating_add(1);
+        }
+        segments
+    }
+
+    /// Push the `segment` to the `segments`, merging it with the last segment
+    /// if they map the same way. The `segment` wins where they overlap.
+    fn push_segment(
+        segments: &mut Vec<LineNumberMapSegment>,
+        range: RangeInclusive<usize>,
+        mapping: LineNumberMapping,
+    ) {
+        while let Some((last_range, _)) = segments.last_mut() {
+            if *last_range.start() >= *range.start() {
+                segments.pop();
+                continue;
+            }
+            if *last_range.end() >= *range.start() {
+                *last_range = *last_range.start()..=*range.start() - 1;
+            }
+            break;
+        }
+        if let Some((last_range, last_mapping)) = segments.last_mut()
+            && *last_mapping == mapping
+            && *last_range.end() + 1 == *range.start()
+        {
+            *last_range = *last_range.start()..=*range.end();
+            return;
+        }
+        segments.push((range, mapping));
+    }
+
+    fn from_segments(segments: Vec<LineNumberMapSegment>) -> LineNumberMap {
+        debug_assert_eq!(segments.first().map(|(range, _)| *range.start()), Some(0));
+        debug_assert_eq!(
+            segments.last().map(|(range, _)| *range.end()),
+            Some(usize::MAX)
+        );
+        if let [(_, LineNumberMapping::Shift(0))] = segments.as_slice() {
+            return LineNumberMap::default();
+        }
+        let items = segments
+            .into_iter()
+            .map(|(range, mapping)| {
+                let (start, end) = range.into_inner();
+                let (add, sub, is_delete) = match mapping {
+                    LineNumberMapping::Shift(delta) if delta >= 0 => (delta as usize, 0, false),
+                    LineNumberMapping::Shift(delta) => (0, delta.unsigned_abs(), false),
+                    LineNumberMapping::Const(to) => (to, start, true),
+                };
+                LineNumberMapItem {
+                    range: start..end,
+                    add,
+                    sub,
+                    is_delete,
+                }
+            })
+            .collect();
+        LineNumberMap { items }
+    }
+
+    /// Map the line number `old` to the other side of the diff.
     pub fn map(&self, old: usize) -> usize {
         let mut values = [old];
-        self.apply_to_values(values.iter_mut());
+        // A single value is always ascending, so this fails only if the map
+        // is broken. The `old` is returned as is then.
+        if let Err(error) = self.apply_to_values(values.iter_mut()) {
+            debug_assert!(false, "{error}");
+            warn!("LineNumberMap::map({old}): {error}");
+        }
         values[0]
     }
 
-    pub fn apply_to_parts(&self, parts: &mut Vec<DiffPart>) {
+    /// Map the line numbers of the `parts`.
+    /// The `parts` must be ascending, and the result is validated to be
+    /// ascending too.
+    pub fn apply_to_parts(&self, parts: &mut Vec<DiffPart>) -> anyhow::Result<()> {
         trace!("apply_to_parts: self: {self:?}");
         trace!("apply_to_parts: parts: {parts:?}");
-        DiffPart::validate_ascending_parts(parts).unwrap();
+        DiffPart::validate_ascending_parts(parts)?;
         let old_ranges = parts.iter_mut().map(|part| &mut part.old);
-        self.apply_to_ranges(old_ranges);
+        self.apply_to_ranges(old_ranges)?;
         let new_ranges = parts.iter_mut().map(|part| &mut part.new);
-        self.apply_to_ranges(new_ranges);
-        DiffPart::validate_ascending_parts(parts).unwrap();
+        self.apply_to_ranges(new_ranges)?;
+        DiffPart::validate_ascending_parts(parts).context("The mapped parts aren't ascending")
     }
 
-    pub fn apply_to_ranges<'a>(&self, ranges: impl Iterator<Item = &'a mut DiffRange>) {
+    pub fn apply_to_ranges<'a>(
+        &self,
+        ranges: impl Iterator<Item = &'a mut DiffRange>,
+    ) -> anyhow::Result<()> {
         let values =
             ranges.flat_map(|lines| [&mut lines.line_numbers.start, &mut lines.line_numbers.end]);
-        self.apply_to_values(values);
+        self.apply_to_values(values)
     }
 
-    pub fn apply_to_values<'a>(&self, values: impl Iterator<Item = &'a mut usize>) {
-        if self.items.is_empty() {
-            return;
+    /// Map the `values`, which must be ascending.
+    /// Returns an error if they aren't, leaving some of them mapped.
+    pub fn apply_to_values<'a>(
+        &self,
+        values: impl Iterator<Item = &'a mut usize>,
+    ) -> anyhow::Result<()> {
+        let Some(last_item) = self.items.last() else {
+            return Ok(());
+        };
+        if last_item.range.end != usize::MAX {
+            bail!("The map doesn't cover all line numbers: {self:?}");
         }
-        assert_eq!(self.items.last().unwrap().range.end, usize::MAX);
         let mut item_iter = self.items.iter();
-        let mut item = item_iter.next().unwrap();
+        let mut item = &self.items[0];
         let mut last_value = 0;
         let mut last_new_value = 0;
         for value in values {
-            assert!(*value >= last_value);
+            if *value < last_value {
+                bail!(
+                    "The line numbers aren't ascending: {} < {last_value}",
+                    *value
+                );
+            }
             last_value = *value;
 
             if *value < item.range.start {
                 continue;
             }
             while *value > item.range.end {
-                item = item_iter.next().unwrap();
+                // The last item ends at `MAX`, so this always finds one.
+                item = item_iter.next().unwrap_or(last_item);
+            }
+            if *value < item.range.start {
+                bail!("The map isn't ascending: {self:?}");
             }
-            assert!(*value >= item.range.start);
             let new_value = if item.is_delete {
                 item.range.start + item.add - item.sub
             } else if *value == usize::MAX {
@@ -130,10 +356,13 @@ impl LineNumberMap {
             };
 
             // trace!("apply_to_values: {} -> {}", *value, new_value);
-            assert!(new_value >= last_new_value);
+            if new_value < last_new_value {
+                bail!("The mapped line numbers aren't ascending: {new_value} < {last_new_value}");
+            }
             last_new_value = new_value;
             *value = new_value;
         }
+        Ok(())
     }
 }
 
@@ -196,4 +425,134 @@ mod tests {
         assert_eq!(map.map(359), 365);
         assert_eq!(map.map(361), 366);
     }
+
+    fn parts(ranges: &[(Range<usize>, Range<usize>)]) -> Vec<DiffPart> {
+        ranges
+            .iter()
+            .map(|(old, new)| DiffPart {
+                old: DiffRange {
+                    line_numbers: old.clone(),
+                },
+                new: DiffRange {
+                    line_numbers: new.clone(),
+                },
+            })
+            .collect()
+    }
+
+    const VALUES: [usize; 22] = [
+        0,
+        1,
+        2,
+        3,
+        4,
+        5,
+        6,
+        7,
+        8,
+        9,
+        10,
+        11,
+        12,
+        13,
+        14,
+        15,
+        16,
+        17,
+        18,
+        19,
+        20,
+        usize::MAX,
+    ];
+
+    fn test_parts() -> Vec<Vec<DiffPart>> {
+        vec![
+            vec![],
+            parts(&[(3..3, 3..5)]),
+            parts(&[(3..5, 3..3)]),
+            parts(&[(2..4, 2..3), (8..8, 7..10), (12..13, 14..17)]),
+            parts(&[(1..3, 1..6), (5..9, 8..9)]),
+            parts(&[(0..0, 1..4)]),
+        ]
+    }
+
+    #[test]
+    fn compose() {
+        let all_parts = test_parts();
+        for parts1 in &all_parts {
+            for parts2 in &all_parts {
+                let map1 = LineNumberMap::new_new_from_old(parts1);
+                let map2 = LineNumberMap::new_old_from_new(parts2);
+                let composed = map1.compose(&map2);
+                for value in VALUES {
+                    assert_eq!(
+                        composed.map(value),
+                        map2.map(map1.map(value)),
+                        "{value} by {parts1:?} and {parts2:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn apply_to_values_not_ascending() {
+        let map = LineNumberMap::new_new_from_old(&parts(&[(3..3, 3..5)]));
+        let mut values = [1, 4, 2];
+        assert!(map.apply_to_values(values.iter_mut()).is_err());
+
+        let mut parts = parts(&[(5..6, 5..6), (1..2, 1..2)]);
+        assert!(map.apply_to_parts(&mut parts).is_err());
+    }
+
+    #[test]
+    fn compose_apply_to_values() {
+        let map1 = LineNumberMap::new_new_from_old(&parts(&[(3..3, 3..5)]));
+        let map2 = LineNumberMap::new_new_from_old(&parts(&[(6..8, 6..6)]));
+        let composed = map1.compose(&map2);
+        let mut values = VALUES;
+        composed.apply_to_values(values.iter_mut()).unwrap();
+        let expected = VALUES.map(|value| map2.map(map1.map(value)));
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn invert() {
+        for parts in [
+            parts(&[(3..3, 3..5)]),
+            parts(&[(3..5, 3..3)]),
+            parts(&[(2..4, 2..3)]),
+        ] {
+            let new_from_old = LineNumberMap::new_new_from_old(&parts);
+            let old_from_new = LineNumberMap::new_old_from_new(&parts);
+            let inverted = new_from_old.invert();
+            for value in VALUES {
+                assert_eq!(
+                    inverted.map(value),
+                    old_from_new.map(value),
+                    "{value} by {parts:?}"
+                );
+            }
+        }
+
+        let candidates: Vec<usize> = (0..=40).chain([usize::MAX]).collect();
+        for parts in test_parts() {
+            let map = LineNumberMap::new_old_from_new(&parts);
+            let inverted = map.invert();
+            for value in VALUES {
+                let inverted_value = inverted.map(value);
+                let mapped = map.map(inverted_value);
+                if candidates.iter().any(|v| map.map(*v) == value) {
+                    assert_eq!(mapped, value, "{value} by {parts:?}");
+                    assert!(
+                        candidates
+                            .iter()
+                            .all(|v| *v <= inverted_value || map.map(*v) != value)
+                    );
+                } else {
+                    assert!(mapped > value, "{value} by {parts:?}");
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,128 @@
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use log::*;
+
+use super::{BlameOptions, FileHistoryBuilder, GitEngine};
+
+/// The time to load the whole history of a file by a `GitEngine`,
+/// to quantify performance regressions and pathologies of repositories.
+#[derive(Debug)]
+pub(crate) struct LoadBenchmark {
+    engine: GitEngine,
+    commits_len: usize,
+    lines_len: usize,
+    /// The time to read all the commits and their diffs.
+    read: Duration,
+    /// The time to apply the diffs of all the commits to the content.
+    apply: Duration,
+}
+
+impl LoadBenchmark {
+    /// The engines to measure.
+    pub const ENGINES: [GitEngine; 4] = [
+        GitEngine::Git,
+        GitEngine::Git2,
+        GitEngine::GitBlame,
+        GitEngine::Git2Blame,
+    ];
+
+    /// Load the history of the file at the `path` by the `engine`,
+    /// `runs` times, and keep the fastest of each phase.
+    pub fn new(
+        path: &Path,
+        options: &BlameOptions,
+        engine: GitEngine,
+        runs: usize,
+    ) -> anyhow::Result<Self> {
+        let mut result = Self {
+            engine,
+            commits_len: 0,
+            lines_len: 0,
+            read: Duration::MAX,
+            apply: Duration::MAX,
+        };
+        for _ in 0..runs.max(1) {
+            let start_time = Instant::now();
+            let mut history = FileHistoryBuilder::new(path)
+                .options(options.clone())
+                .engine(engine)
+                .build();
+            history.read_start()?;
+            history.read_join()?;
+            history.read_poll()?;
+            result.read = result.read.min(start_time.elapsed());
+
+            let start_time = Instant::now();
+            let content = history.content(git2::Oid::ZERO_SHA1)?;
+            result.apply = result.apply.min(start_time.elapsed());
+            result.commits_len = history.commits().len();
+            result.lines_len = content.lines_len();
+        }
+        debug!("LoadBenchmark: {result:?}");
+        Ok(result)
+    }
+
+    pub fn engine(&self) -> GitEngine {
+        self.engine
+    }
+
+    /// The number of the commits in the history.
+    pub fn commits_len(&self) -> usize {
+        self.commits_len
+    }
+
+    /// The number of the lines, including deleted lines.
+    pub fn lines_len(&self) -> usize {
+        self.lines_len
+    }
+
+    /// The time to read all the commits and their diffs.
+    pub fn read(&self) -> Duration {
+        self.read
+    }
+
+    /// The time to apply the diffs of all the commits to the content.
+    pub fn apply(&self) -> Duration {
+        self.apply
+    }
+
+    /// The time to load the history; i.e., `read()` and `apply()`.
+    pub fn total(&self) -> Duration {
+        self.read + self.apply
+    }
+
+    /// The number of the commits applied per second.
+    pub fn apply_throughput(&self) -> f64 {
+        self.commits_len as f64 / self.apply.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extensions::tests::TempRepository;
+
+    use super::*;
+
+    #[test]
+    fn new() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2\n")?;
+        git.commit(commit_id1, "Add 2")?;
+
+        let engine = GitEngine::GitBlame;
+        let benchmark =
+            LoadBenchmark::new(&git.to_file_path(path), &BlameOptions::new(), engine, 2)?;
+        assert_eq!(benchmark.engine(), engine);
+        assert_eq!(benchmark.commits_len(), 2);
+        assert_eq!(benchmark.lines_len(), 2);
+        assert_eq!(benchmark.total(), benchmark.read() + benchmark.apply());
+        assert!(benchmark.apply_throughput() > 0.0);
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use crate::extensions::GitTools;
+
+use super::FileCommits;
+
+/// The ancestry graph of the commits in the log view, like `git log --graph`,
+/// and the branches and the tags pointing to them.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LogGraph {
+    /// The parents of the commits, rewritten to the commits changing the file.
+    /// Commits not in this map, such as commits before renames, are drawn
+    /// linearly.
+    parents: HashMap<git2::Oid, Vec<git2::Oid>>,
+    references: HashMap<git2::Oid, Vec<String>>,
+    /// The commit IDs expected in each lane, or `None` if the lane is free.
+    lanes: Vec<Option<git2::Oid>>,
+}
+
+impl LogGraph {
+    pub fn new(git: &GitTools, commits: &FileCommits) -> anyhow::Result<Self> {
+        let Some(first) = commits.first() else {
+            return Ok(Self::default());
+        };
+        Ok(Self {
+            parents: git.file_parents(first.commit_id(), first.path())?,
+            references: git.references()?,
+            lanes: vec![],
+        })
+    }
+
+    /// The graph and the decorations of each commit in the `commits`.
+    pub fn prefixes(&mut self, commits: &FileCommits) -> Vec<String> {
+        self.lanes.clear();
+        commits
+            .iter()
+            .enumerate()
+            .map(|(index, commit)| {
+                let commit_id = commit.commit_id();
+                let parents: Vec<git2::Oid> = match self.parents.get(&commit_id) {
+                    Some(parents) => parents
+                        .iter()
+                        .filter(|parent| commits.index_from_commit_id(**parent).is_ok())
+                        .copied()
+                        .collect(),
+                    None => commits
+                        .get(index + 1)
+                        .map(|c| c.commit_id())
+                        .into_iter()
+                        .collect(),
+                };
+                let mut prefix = self.row(commit_id, &parents);
+                prefix.push_str(&self.decorations(commit_id));
+                prefix
+            })
+            .collect()
+    }
+
+    /// The branches and the tags pointing to the `commit_id`,
+    /// such as `"(HEAD, main) "`, or an empty string if none.
+    pub fn decorations(&self, commit_id: git2::Oid) -> String {
+        self.references
+            .get(&commit_id)
+            .map_or_else(String::new, |names| format!("({}) ", names.join(", ")))
+    }
+
+    /// The graph of the row of the `commit_id`, and update the lanes for the
+    /// next rows by its `parents`.
+    fn row(&mut self, commit_id: git2::Oid, parents: &[git2::Oid]) -> String {
+        let column = match self.lane_of(commit_id) {
+            Some(column) => column,
+            None => self.allocate_lane(commit_id, None),
+        };
+        let mut cells: Vec<char> = self
+            .lanes
+            .iter()
+            .map(|lane| if lane.is_some() { '\u{2502}' } else { ' ' })
+            .collect();
+        let mut connectors = vec![' '; cells.len()];
+        cells[column] = '\u{25cf}';
+
+        // Other lanes expecting this commit merge into this lane.
+        for other in 0..self.lanes.len() {
+            if other != column && self.lanes[other] == Some(commit_id) {
+                self.lanes[other] = None;
+                cells[other] = if other > column {
+                    '\u{256f}'
+                } else {
+                    '\u{2570}'
+                };
+                Self::connect(&mut cells, &mut connectors, column, other);
+            }
+        }
+
+        self.lanes[column] = None;
+        for (i, parent) in parents.iter().enumerate() {
+            match self.lane_of(*parent) {
+                Some(other) if i == 0 && other > column => {
+                    // Move the lane on the right to this lane.
+                    self.lanes[column] = Some(*parent);
+                    self.lanes[other] = None;
+                    cells[other] = '\u{256f}';
+                    Self::connect(&mut cells, &mut connectors, column, other);
+                }
+                Some(other) => {
+                    // The parent is expected in another lane.
+                    cells[other] = if other > column {
+                        '\u{2524}'
+                    } else {
+                        '\u{251c}'
+                    };
+                    Self::connect(&mut cells, &mut connectors, column, other);
+                }
+                None if i == 0 => self.lanes[column] = Some(*parent),
+                None => {
+                    let other = self.allocate_lane(*parent, Some(column));
+                    cells.resize(self.lanes.len(), ' ');
+                    connectors.resize(self.lanes.len(), ' ');
+                    cells[other] = if other > column {
+                        '\u{256e}'
+                    } else {
+                        '\u{256d}'
+                    };
+                    Self::connect(&mut cells, &mut connectors, column, other);
+                }
+            }
+        }
+        while self.lanes.last() == Some(&None) {
+            self.lanes.pop();
+        }
+
+        let mut graph: String = cells
+            .iter()
+            .zip(connectors.iter())
+            .flat_map(|(cell, connector)| [*cell, *connector])
+            .collect();
+        graph.truncate(graph.trim_end().len());
+        graph.push(' ');
+        graph
+    }
+
+    fn lane_of(&self, commit_id: git2::Oid) -> Option<usize> {
+        self.lanes.iter().position(|lane| *lane == Some(commit_id))
+    }
+
+    /// Allocate a free lane for the `commit_id`, except the `excluded` lane.
+    fn allocate_lane(&mut self, commit_id: git2::Oid, excluded: Option<usize>) -> usize {
+        let free = self
+            .lanes
+            .iter()
+            .enumerate()
+            .position(|(i, lane)| lane.is_none() && Some(i) != excluded);
+        match free {
+            Some(i) => {
+                self.lanes[i] = Some(commit_id);
+                i
+            }
+            None => {
+                self.lanes.push(Some(commit_id));
+                self.lanes.len() - 1
+            }
+        }
+    }
+
+    /// Draw the horizontal line between the `column` and the `other` columns.
+    fn connect(cells: &mut [char], connectors: &mut [char], column: usize, other: usize) {
+        let (start, end) = if column < other {
+            (column, other)
+        } else {
+            (other, column)
+        };
+        for cell in &mut cells[start + 1..end] {
+            *cell = match *cell {
+                ' ' => '\u{2500}',
+                '\u{2502}' => '\u{253c}',
+                cell => cell,
+            };
+        }
+        connectors[start..end].fill('\u{2500}');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn row() {
+        let id = |i: u8| git2::Oid::from_bytes(&[i; 20]).unwrap();
+        // 1 merges 2 and 3, which are children of 4.
+        let mut graph = LogGraph::default();
+        let rows = [
+            graph.row(id(1), &[id(2), id(3)]),
+            graph.row(id(2), &[id(4)]),
+            graph.row(id(3), &[id(4)]),
+            graph.row(id(4), &[]),
+        ];
+        assert_eq!(rows, ["●─╮ ", "● │ ", "├─● ", "● "]);
+        assert!(graph.lanes.is_empty());
+
+        // A linear history.
+        let rows = [graph.row(id(1), &[id(2)]), graph.row(id(2), &[])];
+        assert_eq!(rows, ["● ", "● "]);
+    }
+
+    #[test]
+    fn row_crossing() {
+        // 1 merges 2 and 3, 2 merges 4 and 5, and 3 and 4 are children of 5.
+        let id = |i: u8| git2::Oid::from_bytes(&[i; 20]).unwrap();
+        let mut graph = LogGraph::default();
+        let rows = [
+            graph.row(id(1), &[id(2), id(3)]),
+            graph.row(id(2), &[id(4), id(5)]),
+            graph.row(id(3), &[id(5)]),
+            graph.row(id(4), &[id(5)]),
+            graph.row(id(5), &[]),
+        ];
+        assert_eq!(rows, ["●─╮ ", "●─┼─╮ ", "│ ●─╯ ", "●─╯ ", "● "]);
+    }
+}
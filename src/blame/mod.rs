@@ -1,26 +1,107 @@
 mod blame_error;
 pub use blame_error::*;
 
+mod blame_snapshot;
+pub use blame_snapshot::*;
+
+mod blame_iterator;
+pub use blame_iterator::*;
+
+mod blame_options;
+pub use blame_options::*;
+
+mod cancellation_token;
+pub use cancellation_token::*;
+
 mod commit_iterator;
 pub use commit_iterator::*;
 
+mod commit_metadata;
+pub use commit_metadata::*;
+
+mod content_cache;
+pub(crate) use content_cache::*;
+
+mod csv_writer;
+pub use csv_writer::*;
+
+#[cfg(feature = "tui")]
+mod engine_benchmark;
+#[cfg(feature = "tui")]
+pub(crate) use engine_benchmark::*;
+
 mod file_commits;
 pub use file_commits::*;
 
 mod diff_part;
 pub use diff_part::*;
 
+mod diff_part_store;
+pub(crate) use diff_part_store::*;
+
 mod file_commit;
 pub use file_commit::*;
 
+#[cfg(feature = "async")]
+mod file_commit_stream;
+#[cfg(feature = "async")]
+pub use file_commit_stream::*;
+
 mod file_content;
 pub use file_content::*;
 
 mod file_history;
 pub use file_history::*;
 
+mod file_history_builder;
+pub use file_history_builder::*;
+
+mod file_stats;
+pub use file_stats::*;
+
+mod git_diff_header;
+pub(crate) use git_diff_header::*;
+
+mod gutter_column;
+pub use gutter_column::*;
+
+mod history_provider;
+pub use history_provider::*;
+
+mod jj_provider;
+pub use jj_provider::*;
+
 mod line;
 pub use line::*;
 
+mod line_filter;
+pub use line_filter::*;
+
 mod line_number_map;
 pub use line_number_map::*;
+
+#[cfg(feature = "tui")]
+mod load_benchmark;
+#[cfg(feature = "tui")]
+pub(crate) use load_benchmark::*;
+
+mod log_graph;
+pub(crate) use log_graph::*;
+
+mod move_detector;
+pub(crate) use move_detector::*;
+
+mod porcelain;
+pub use porcelain::*;
+
+mod read_ahead;
+pub(crate) use read_ahead::*;
+
+mod read_progress;
+pub use read_progress::*;
+
+#[cfg(feature = "serde")]
+mod serialize;
+
+mod search_pattern;
+pub use search_pattern::*;
@@ -0,0 +1,160 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use log::*;
+
+use super::{DiffPart, DiffRange};
+
+#[derive(Debug)]
+struct MoveDetectorLine {
+    line_number: usize,
+    hash: u64,
+    alnum_len: usize,
+}
+
+impl MoveDetectorLine {
+    fn new(line_number: usize, content: &[u8]) -> Self {
+        let content = content.strip_suffix(b"\n").unwrap_or(content);
+        let content = content.strip_suffix(b"\r").unwrap_or(content);
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        Self {
+            line_number,
+            hash: hasher.finish(),
+            alnum_len: content.iter().filter(|b| b.is_ascii_alphanumeric()).count(),
+        }
+    }
+}
+
+/// Detects blocks of lines moved within a file,
+/// by matching the hashes of the removed lines and the added lines of a diff.
+#[derive(Debug, Default)]
+pub(crate) struct MoveDetector {
+    old_lines: Vec<MoveDetectorLine>,
+    new_lines: Vec<MoveDetectorLine>,
+}
+
+impl MoveDetector {
+    /// The minimum number of alphanumeric characters in a moved block.
+    /// This is the same as the default of `git blame -M`.
+    const MIN_ALNUM_LEN: usize = 20;
+
+    pub fn add_old_line(&mut self, line_number: usize, content: &[u8]) {
+        self.old_lines
+            .push(MoveDetectorLine::new(line_number, content));
+    }
+
+    pub fn add_new_line(&mut self, line_number: usize, content: &[u8]) {
+        self.new_lines
+            .push(MoveDetectorLine::new(line_number, content));
+    }
+
+    /// Compute the moved blocks. The `old` of each `DiffPart` is the block
+    /// before the move, and the `new` is the block after the move.
+    /// The results are sorted by the `new`.
+    pub fn moved_parts(&self) -> Vec<DiffPart> {
+        let mut old_indexes_by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (index, line) in self.old_lines.iter().enumerate() {
+            old_indexes_by_hash
+                .entry(line.hash)
+                .or_default()
+                .push(index);
+        }
+        let mut is_old_used = vec![false; self.old_lines.len()];
+        let mut parts = vec![];
+        let mut new_index = 0;
+        while new_index < self.new_lines.len() {
+            let mut longest: Option<(usize, usize)> = None;
+            if let Some(old_indexes) = old_indexes_by_hash.get(&self.new_lines[new_index].hash) {
+                for old_index in old_indexes {
+                    let len = self.match_len(new_index, *old_index, &is_old_used);
+                    if longest.is_none_or(|(_, longest_len)| len > longest_len) {
+                        longest = Some((*old_index, len));
+                    }
+                }
+            }
+            let Some((old_index, len)) = longest else {
+                new_index += 1;
+                continue;
+            };
+            let new_lines = &self.new_lines[new_index..new_index + len];
+            let alnum_len: usize = new_lines.iter().map(|line| line.alnum_len).sum();
+            if alnum_len < Self::MIN_ALNUM_LEN {
+                new_index += 1;
+                continue;
+            }
+            is_old_used[old_index..old_index + len].fill(true);
+            let old_start = self.old_lines[old_index].line_number;
+            let new_start = new_lines[0].line_number;
+            let part = DiffPart {
+                old: DiffRange {
+                    line_numbers: old_start..old_start + len,
+                },
+                new: DiffRange {
+                    line_numbers: new_start..new_start + len,
+                },
+            };
+            trace!("moved_parts: {part:?}");
+            parts.push(part);
+            new_index += len;
+        }
+        parts
+    }
+
+    /// The number of consecutive lines that match from the `new_index` and the
+    /// `old_index`.
+    fn match_len(&self, new_index: usize, old_index: usize, is_old_used: &[bool]) -> usize {
+        let new_lines = &self.new_lines[new_index..];
+        let old_lines = &self.old_lines[old_index..];
+        let new_start = new_lines[0].line_number;
+        let old_start = old_lines[0].line_number;
+        new_lines
+            .iter()
+            .zip(old_lines)
+            .zip(&is_old_used[old_index..])
+            .enumerate()
+            .take_while(|(i, ((new_line, old_line), is_used))| {
+                !**is_used
+                    && new_line.hash == old_line.hash
+                    && new_line.line_number == new_start + i
+                    && old_line.line_number == old_start + i
+            })
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moved_parts() {
+        let mut detector = MoveDetector::default();
+        for (i, content) in ["fn first_function() {", "    first_call();", "}"]
+            .iter()
+            .enumerate()
+        {
+            detector.add_old_line(i + 1, content.as_bytes());
+        }
+        detector.add_new_line(5, b"}\n");
+        for (i, content) in ["fn first_function() {\n", "    first_call();\n", "}\n"]
+            .iter()
+            .enumerate()
+        {
+            detector.add_new_line(i + 7, content.as_bytes());
+        }
+        assert_eq!(detector.moved_parts(), [DiffPart::from_ranges(1..4, 7..10)]);
+    }
+
+    #[test]
+    fn moved_parts_too_short() {
+        let mut detector = MoveDetector::default();
+        detector.add_old_line(1, b"}");
+        detector.add_old_line(2, b"");
+        detector.add_new_line(5, b"}");
+        detector.add_new_line(6, b"");
+        assert_eq!(detector.moved_parts(), []);
+    }
+}
@@ -0,0 +1,273 @@
+use std::{collections::HashSet, io::Write, path::Path};
+
+use super::{BlameError, FileContent, FileHistory, Line};
+
+/// Writes lines in the format of `git blame --porcelain`,
+/// or `git blame --line-porcelain` if `is_line_porcelain`.
+#[derive(Debug, Default)]
+pub struct PorcelainWriter {
+    is_line_porcelain: bool,
+}
+
+impl PorcelainWriter {
+    pub fn new(is_line_porcelain: bool) -> Self {
+        Self { is_line_porcelain }
+    }
+
+    /// Write the lines of the `content`, except the deleted lines.
+    ///
+    /// Only the lines not committed yet are attributed to the zero commit ID.
+    /// The lines not attributed to any commits, such as when the reading was
+    /// cancelled, are attributed to the oldest commit read, as the boundary.
+    pub fn write(
+        &self,
+        out: &mut impl Write,
+        history: &FileHistory,
+        content: &FileContent,
+    ) -> Result<(), BlameError> {
+        let lines: Vec<&Line> = content
+            .lines()
+            .iter()
+            .filter(|line| !line.is_deleted())
+            .collect();
+        let boundary_commit_id = match history.commits().last() {
+            Some(commit) if !history.is_not_committed() => commit.commit_id(),
+            _ => git2::Oid::ZERO_SHA1,
+        };
+        let mut written_commit_ids = HashSet::new();
+        let mut group_len = 0;
+        for (i, line) in lines.iter().enumerate() {
+            let commit_id = line.commit_id().unwrap_or(boundary_commit_id);
+            let original_line_number = line.original_line_number().unwrap_or(line.line_number());
+            write!(
+                out,
+                "{commit_id} {original_line_number} {}",
+                line.line_number()
+            )?;
+            if group_len == 0 {
+                // The number of lines of the same commit, in consecutive line
+                // numbers in both the original and the final files.
+                group_len = 1 + lines[i + 1..]
+                    .iter()
+                    .zip(1..)
+                    .take_while(|(next, offset)| {
+                        next.commit_id() == line.commit_id()
+                            && next.original_line_number()
+                                == line.original_line_number().map(|n| n + offset)
+                    })
+                    .count();
+                write!(out, " {group_len}")?;
+            }
+            writeln!(out)?;
+            group_len -= 1;
+            if self.is_line_porcelain || written_commit_ids.insert(commit_id) {
+                if commit_id.is_zero() {
+                    self.write_not_committed(out, content.path())?;
+                } else {
+                    self.write_commit(out, history, line, commit_id)?;
+                }
+            }
+            writeln!(out, "\t{}", line.content())?;
+        }
+        Ok(())
+    }
+
+    fn write_commit(
+        &self,
+        out: &mut impl Write,
+        history: &FileHistory,
+        line: &Line,
+        commit_id: git2::Oid,
+    ) -> anyhow::Result<()> {
+        let Ok(file_commit) = history.commits().get_by_commit_id(commit_id) else {
+            return Ok(());
+        };
+        let commit = history.git().repository().find_commit(commit_id)?;
+        for (name, signature) in [
+            ("author", commit.author()),
+            ("committer", commit.committer()),
+        ] {
+            let time = signature.when();
+            Self::write_signature(
+                out,
+                name,
+                &String::from_utf8_lossy(signature.name_bytes()),
+                &String::from_utf8_lossy(signature.email_bytes()),
+                time.seconds(),
+                time.offset_minutes(),
+            )?;
+        }
+        writeln!(
+            out,
+            "summary {}",
+            file_commit.summary().map_or("", |s| s.as_str())
+        )?;
+        if file_commit.is_boundary() || line.commit_id().is_none() {
+            writeln!(out, "boundary")?;
+        } else if let Some(parent_commit_id) = file_commit.parent_commit_id() {
+            let old_path = file_commit
+                .old_path_if_rename()
+                .unwrap_or(file_commit.path());
+            writeln!(out, "previous {parent_commit_id} {}", old_path.display())?;
+        }
+        let path = line.original_path(history).unwrap_or(file_commit.path());
+        writeln!(out, "filename {}", path.display())?;
+        Ok(())
+    }
+
+    /// Write the header of the lines not committed yet, in the same way as
+    /// `git blame`, which attributes them to the zero commit ID.
+    fn write_not_committed(&self, out: &mut impl Write, path: &Path) -> anyhow::Result<()> {
+        let now = chrono::Local::now();
+        for name in ["author", "committer"] {
+            Self::write_signature(
+                out,
+                name,
+                "Not Committed Yet",
+                "not.committed.yet",
+                now.timestamp(),
+                now.offset().local_minus_utc() / 60,
+            )?;
+        }
+        writeln!(out, "summary Version of {0} from {0}", path.display())?;
+        writeln!(out, "filename {}", path.display())?;
+        Ok(())
+    }
+
+    fn write_signature(
+        out: &mut impl Write,
+        name: &str,
+        user_name: &str,
+        email: &str,
+        seconds: i64,
+        offset_minutes: i32,
+    ) -> anyhow::Result<()> {
+        writeln!(out, "{name} {user_name}")?;
+        writeln!(out, "{name}-mail <{email}>")?;
+        writeln!(out, "{name}-time {seconds}")?;
+        writeln!(out, "{name}-tz {}", Self::tz_str(offset_minutes))?;
+        Ok(())
+    }
+
+    /// The time zone offset in `git`'s format; e.g., `+0900` or `-0130`.
+    fn tz_str(offset_minutes: i32) -> String {
+        let sign = if offset_minutes < 0 { '-' } else { '+' };
+        let offset = offset_minutes.abs();
+        format!("{sign}{:02}{:02}", offset / 60, offset % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{blame::FileHistoryBuilder, extensions::tests::TempRepository};
+
+    use super::*;
+
+    #[test]
+    fn tz_str() {
+        assert_eq!(PorcelainWriter::tz_str(540), "+0900");
+        assert_eq!(PorcelainWriter::tz_str(-90), "-0130");
+        assert_eq!(PorcelainWriter::tz_str(0), "+0000");
+    }
+
+    #[test]
+    fn write() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\nA\n2\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add A")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        history.read_join()?;
+        history.read_poll()?;
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let mut out = vec![];
+        PorcelainWriter::new(false).write(&mut out, &history, &content)?;
+        let out = String::from_utf8(out)?;
+        let headers: Vec<&str> = out
+            .lines()
+            .filter(|line| line.starts_with(&commit_id1.to_string()[..7]))
+            .chain(
+                out.lines()
+                    .filter(|line| line.starts_with(&commit_id2.to_string()[..7])),
+            )
+            .collect();
+        assert_eq!(
+            headers,
+            [
+                format!("{commit_id1} 1 1 1"),
+                format!("{commit_id1} 2 3 1"),
+                format!("{commit_id2} 2 2 1"),
+            ]
+        );
+        assert_eq!(out.matches("author Test User\n").count(), 2);
+        assert_eq!(out.matches("boundary\n").count(), 1);
+        assert!(out.contains(&format!("previous {commit_id1} test.txt\n")));
+        assert!(out.contains("filename test.txt\n\tA\n"));
+
+        let mut out = vec![];
+        PorcelainWriter::new(true).write(&mut out, &history, &content)?;
+        let out = String::from_utf8(out)?;
+        assert_eq!(out.matches("author Test User\n").count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn write_boundary() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\nA\n2\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add A")?;
+        git.add_file_content(path, "1\nA\n2\nB\n")?;
+        let commit_id3 = git.commit(commit_id2, "Add B")?;
+
+        let mut history = FileHistoryBuilder::new(&git.to_file_path(path))
+            .max_commits(2)
+            .build();
+        history.read_all()?;
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let mut out = vec![];
+        PorcelainWriter::new(false).write(&mut out, &history, &content)?;
+        let out = String::from_utf8(out)?;
+        assert!(!out.contains(&git2::Oid::ZERO_SHA1.to_string()), "{out}");
+        assert!(!out.contains("Not Committed Yet"));
+        assert!(out.starts_with(&format!("{commit_id2} 1 1 3\n")), "{out}");
+        assert!(out.contains(&format!("{commit_id3} 4 4 1\n")));
+        assert_eq!(out.matches("boundary\n").count(), 1);
+        assert!(out.contains("summary Add A\nboundary\nfilename test.txt\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn write_not_committed() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_all()?;
+        assert!(history.is_not_committed());
+        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let mut out = vec![];
+        PorcelainWriter::new(false).write(&mut out, &history, &content)?;
+        let out = String::from_utf8(out)?;
+        let zero = git2::Oid::ZERO_SHA1;
+        assert!(
+            out.starts_with(&format!("{zero} 1 1 2\nauthor Not Committed Yet\n")),
+            "{out}"
+        );
+        assert!(
+            out.contains("summary Version of test.txt from test.txt\nfilename test.txt\n\t1\n")
+        );
+        assert!(out.ends_with(&format!("\t1\n{zero} 2 2\n\t2\n")));
+        assert_eq!(out.matches("author-mail <not.committed.yet>\n").count(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, mpsc},
+    thread,
+};
+
+use log::*;
+
+use crate::extensions::GitTools;
+
+use super::{CancellationToken, FileCommit, FileCommits};
+
+/// The lines to attribute before others. See `FileHistory::set_priority_lines()`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct PriorityLines {
+    commit_id: git2::Oid,
+    path: PathBuf,
+    line_numbers: Range<usize>,
+}
+
+impl PriorityLines {
+    fn contains(&self, other: &PriorityLines) -> bool {
+        self.commit_id == other.commit_id
+            && self.path == other.path
+            && self.line_numbers.start <= other.line_numbers.start
+            && self.line_numbers.end >= other.line_numbers.end
+    }
+}
+
+/// The results of blaming `PriorityLines`: the commit IDs and the original
+/// line numbers, keyed by the line numbers.
+type BlameResults = HashMap<usize, (git2::Oid, usize)>;
+
+/// Reads the commits blamed on the priority lines ahead of the order of the
+/// history, so that the lines can be attributed before the newer commits are
+/// read. See `FileHistory::set_priority_lines()`.
+///
+/// The lines are blamed in a thread, and the commit IDs are set to the
+/// `priority_commit_ids` shared with the reading thread, which diffs them
+/// first, and sends them back to `commit_tx()`.
+#[derive(Debug, Default)]
+pub(crate) struct ReadAhead {
+    lines_tx: Option<mpsc::Sender<PriorityLines>>,
+    blame_rx: Option<mpsc::Receiver<(PriorityLines, BlameResults)>>,
+    commit_tx: Option<mpsc::Sender<FileCommit>>,
+    commit_rx: Option<mpsc::Receiver<FileCommit>>,
+    /// The last lines sent to the thread.
+    lines: Option<PriorityLines>,
+    /// The commit ID and the path of the `blame`.
+    blame_key: Option<(git2::Oid, PathBuf)>,
+    blame: BlameResults,
+    /// The commits read ahead, until they're added to the history.
+    commits: HashMap<git2::Oid, FileCommit>,
+    /// The number of the blames and the commits received so far.
+    received_len: usize,
+}
+
+impl ReadAhead {
+    /// Start the thread to blame the lines.
+    ///
+    /// The thread isn't joined, so that a slow blame doesn't block cancelling
+    /// the reading. It stops when this is stopped or dropped.
+    pub fn start(
+        git: &GitTools,
+        ignore_commit_ids: HashSet<git2::Oid>,
+        priority_commit_ids: Arc<Mutex<HashSet<git2::Oid>>>,
+        cancellation_token: CancellationToken,
+    ) -> Self {
+        let (lines_tx, lines_rx) = mpsc::channel::<PriorityLines>();
+        let (blame_tx, blame_rx) = mpsc::channel();
+        let (commit_tx, commit_rx) = mpsc::channel();
+        let repository_path = git.repository_path().to_path_buf();
+        thread::spawn(move || {
+            let git = match GitTools::from_repository_path(&repository_path) {
+                Ok(git) => git,
+                Err(error) => {
+                    warn!("read_ahead: {error}");
+                    return;
+                }
+            };
+            while let Ok(mut lines) = lines_rx.recv() {
+                // Only the latest lines are needed.
+                while let Ok(newer) = lines_rx.try_recv() {
+                    lines = newer;
+                }
+                if cancellation_token.is_cancelled() {
+                    break;
+                }
+                let results = match git.blame_by_git2(
+                    lines.commit_id,
+                    &lines.path,
+                    Some(lines.line_numbers.clone()),
+                ) {
+                    Ok(results) => results,
+                    Err(error) => {
+                        warn!("Failed to blame {lines:?}: {error}");
+                        continue;
+                    }
+                };
+                // Ignored commits aren't attributed to; leave them to the
+                // commits applied in order.
+                let results: BlameResults = results
+                    .into_iter()
+                    .filter(|(commit_id, _, _)| !ignore_commit_ids.contains(commit_id))
+                    .map(|(commit_id, original, line_number)| (line_number, (commit_id, original)))
+                    .collect();
+                debug!("read_ahead: {lines:?}, {} lines", results.len());
+                *priority_commit_ids.lock().unwrap() =
+                    results.values().map(|(commit_id, _)| *commit_id).collect();
+                if blame_tx.send((lines, results)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            lines_tx: Some(lines_tx),
+            blame_rx: Some(blame_rx),
+            commit_tx: Some(commit_tx),
+            commit_rx: Some(commit_rx),
+            ..Default::default()
+        }
+    }
+
+    /// The sender of the commits read ahead, for the reading thread.
+    pub fn commit_tx(&self) -> Option<mpsc::Sender<FileCommit>> {
+        self.commit_tx.clone()
+    }
+
+    /// Stop the thread. The commits and the blame received so far are kept
+    /// until they're added to the history.
+    pub fn stop(&mut self) {
+        self.lines_tx = None;
+        self.commit_tx = None;
+    }
+
+    /// Blame the `line_numbers` of the `path` at the `commit_id`, unless the
+    /// last lines contain them.
+    pub fn set_lines(&mut self, commit_id: git2::Oid, path: &Path, line_numbers: Range<usize>) {
+        let Some(lines_tx) = &self.lines_tx else {
+            return;
+        };
+        let lines = PriorityLines {
+            commit_id,
+            path: path.to_path_buf(),
+            line_numbers,
+        };
+        if self
+            .lines
+            .as_ref()
+            .is_some_and(|last| last.contains(&lines))
+        {
+            return;
+        }
+        if lines_tx.send(lines.clone()).is_ok() {
+            self.lines = Some(lines);
+        }
+    }
+
+    /// Receive the blame and the commits read so far, and drop the commits
+    /// added to the `history`. Returns `true` if any are received.
+    pub fn poll(&mut self, history: &FileCommits) -> bool {
+        let mut has_new = false;
+        if let Some(blame_rx) = &self.blame_rx {
+            for (lines, results) in blame_rx.try_iter() {
+                let key = (lines.commit_id, lines.path);
+                if self.blame_key.as_ref() != Some(&key) {
+                    self.blame_key = Some(key);
+                    self.blame.clear();
+                }
+                self.blame.extend(results);
+                self.received_len += 1;
+                has_new = true;
+            }
+        }
+        if let Some(commit_rx) = &self.commit_rx {
+            for commit in commit_rx.try_iter() {
+                self.commits.insert(commit.commit_id(), commit);
+                self.received_len += 1;
+                has_new = true;
+            }
+        }
+        self.commits
+            .retain(|commit_id, _| history.get_by_commit_id(*commit_id).is_err());
+        if self.commits.is_empty() && self.commit_tx.is_none() {
+            // The reading is completed; all the commits are in the history.
+            self.blame_key = None;
+            self.blame.clear();
+        }
+        has_new
+    }
+
+    /// The number of the blames and the commits received so far, which
+    /// increases when lines may be attributed to the commits read ahead.
+    pub fn received_len(&self) -> usize {
+        self.received_len
+    }
+
+    /// The commit read ahead, if it's not added to the history yet.
+    pub fn commit(&self, commit_id: git2::Oid) -> Option<&FileCommit> {
+        self.commits.get(&commit_id)
+    }
+
+    /// The results of blaming the lines of the `path` at the `commit_id`, if
+    /// any commits blamed on them are read ahead.
+    pub fn blame(&self, commit_id: git2::Oid, path: &Path) -> Option<&BlameResults> {
+        if self.commits.is_empty() {
+            return None;
+        }
+        match &self.blame_key {
+            Some((key_commit_id, key_path)) if *key_commit_id == commit_id && key_path == path => {
+                Some(&self.blame)
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::{fmt, time::Duration};
+
+/// The progress of reading a `FileHistory`.
+/// See `FileHistory::read_progress()` and `FileHistoryBuilder::progress()`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ReadProgress {
+    commits_discovered: usize,
+    commits_diffed: usize,
+    elapsed: Duration,
+}
+
+impl ReadProgress {
+    pub fn new(commits_discovered: usize, commits_diffed: usize, elapsed: Duration) -> Self {
+        Self {
+            commits_discovered,
+            commits_diffed,
+            elapsed,
+        }
+    }
+
+    /// The number of the commits that changed the file, found so far.
+    /// This may increase while reading.
+    pub fn commits_discovered(&self) -> usize {
+        self.commits_discovered
+    }
+
+    /// The number of the commits whose diffs are computed.
+    pub fn commits_diffed(&self) -> usize {
+        self.commits_diffed
+    }
+
+    /// The time since the reading started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The ratio of `commits_diffed()` to `commits_discovered()`,
+    /// in the range of 0 to 1.
+    pub fn ratio(&self) -> f64 {
+        if self.commits_discovered == 0 {
+            return 0.0;
+        }
+        (self.commits_diffed as f64 / self.commits_discovered as f64).min(1.0)
+    }
+}
+
+impl fmt::Display for ReadProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} commits, {:.1}s",
+            self.commits_diffed,
+            self.commits_discovered,
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ratio() {
+        assert_eq!(ReadProgress::default().ratio(), 0.0);
+        assert_eq!(ReadProgress::new(4, 1, Duration::ZERO).ratio(), 0.25);
+        assert_eq!(ReadProgress::new(4, 4, Duration::ZERO).ratio(), 1.0);
+    }
+
+    #[test]
+    fn display() {
+        let progress = ReadProgress::new(340, 12, Duration::from_millis(1300));
+        assert_eq!(progress.to_string(), "12/340 commits, 1.3s");
+    }
+}
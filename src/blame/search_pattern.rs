@@ -0,0 +1,125 @@
+use regex::{Regex, RegexBuilder};
+
+use super::{FileCommits, Line};
+
+/// What a `SearchPattern` searches.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SearchTarget {
+    /// The content of lines.
+    /// In the log, the author of the commit of lines too.
+    #[default]
+    Content,
+    /// The author name or email of the commit of lines.
+    Author,
+    /// The summary of the commit of lines.
+    Summary,
+}
+
+/// A compiled pattern to search lines.
+///
+/// The pattern is a case-insensitive regular expression.
+/// If it's not a valid regular expression, it's searched literally.
+#[derive(Clone, Debug)]
+pub struct SearchPattern {
+    regex: Regex,
+    target: SearchTarget,
+}
+
+impl SearchPattern {
+    pub fn new(pattern: &str) -> Self {
+        Self::new_with_target(pattern, SearchTarget::Content)
+    }
+
+    pub fn new_with_target(pattern: &str, target: SearchTarget) -> Self {
+        let regex = Self::build(pattern).unwrap_or_else(|_| {
+            Self::build(&regex::escape(pattern)).expect("An escaped pattern should be valid")
+        });
+        Self { regex, target }
+    }
+
+    /// Create a pattern to search the commit metadata from a query,
+    /// such as `author:name` or `msg:summary`.
+    pub fn from_commit_query(query: &str) -> anyhow::Result<Self> {
+        let Some((target, pattern)) = query.split_once(':') else {
+            anyhow::bail!("The search should start with \"author:\" or \"msg:\"");
+        };
+        let target = match target {
+            "author" => SearchTarget::Author,
+            "msg" => SearchTarget::Summary,
+            _ => anyhow::bail!("Unknown search \"{target}:\", should be \"author:\" or \"msg:\""),
+        };
+        Ok(Self::new_with_target(pattern, target))
+    }
+
+    fn build(pattern: &str) -> Result<Regex, regex::Error> {
+        RegexBuilder::new(pattern).case_insensitive(true).build()
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    pub fn target(&self) -> SearchTarget {
+        self.target
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+
+    /// Whether the `line` matches this pattern.
+    /// The `commits` are used to search the commit metadata.
+    pub fn is_line_match(&self, line: &Line, commits: &FileCommits) -> bool {
+        if self.target == SearchTarget::Content && self.is_match(line.content()) {
+            return true;
+        }
+        if self.target == SearchTarget::Content && !line.is_log() {
+            return false;
+        }
+        let Some(commit) = line
+            .commit_id()
+            .and_then(|commit_id| commits.get_by_commit_id(commit_id).ok())
+        else {
+            return false;
+        };
+        match self.target {
+            SearchTarget::Content | SearchTarget::Author => {
+                self.is_match(commit.author_name()) || self.is_match(commit.author_email())
+            }
+            SearchTarget::Summary => commit
+                .summary()
+                .is_some_and(|summary| self.is_match(summary)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_match() {
+        let pattern = SearchPattern::new("fo+");
+        assert!(pattern.is_match("xFOOx"));
+        assert!(!pattern.is_match("f"));
+
+        // Invalid regular expressions are searched literally.
+        let pattern = SearchPattern::new("f(");
+        assert_eq!(pattern.as_str(), r"f\(");
+        assert!(pattern.is_match("f(x)"));
+        assert!(!pattern.is_match("fx"));
+    }
+
+    #[test]
+    fn from_commit_query() -> anyhow::Result<()> {
+        let pattern = SearchPattern::from_commit_query("author:alice")?;
+        assert_eq!(pattern.target(), SearchTarget::Author);
+        assert_eq!(pattern.as_str(), "alice");
+        let pattern = SearchPattern::from_commit_query("msg:fix:crash")?;
+        assert_eq!(pattern.target(), SearchTarget::Summary);
+        assert_eq!(pattern.as_str(), "fix:crash");
+        assert!(SearchPattern::from_commit_query("alice").is_err());
+        assert!(SearchPattern::from_commit_query("date:2025").is_err());
+        Ok(())
+    }
+}
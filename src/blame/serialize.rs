@@ -0,0 +1,49 @@
+//! The `serialize_with` functions for the types that don't implement
+//! `serde::Serialize`.
+
+use std::sync::Arc;
+
+use serde::{Serialize, Serializer, ser::SerializeStruct};
+
+/// Serialize a `git2::Oid` as a hexadecimal string.
+pub(crate) fn oid<S: Serializer>(oid: &git2::Oid, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(oid)
+}
+
+pub(crate) fn option_oid<S: Serializer>(
+    oid: &Option<git2::Oid>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match oid {
+        Some(oid) => serializer.collect_str(oid),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Serialize the value in an `Arc`, without enabling the `rc` feature of
+/// `serde`.
+pub(crate) fn arc<T: Serialize, S: Serializer>(
+    value: &Arc<T>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    value.as_ref().serialize(serializer)
+}
+
+/// Serialize a `git2::Time` as the seconds since the epoch, and the offset
+/// from UTC in minutes.
+pub(crate) fn time<S: Serializer>(time: &git2::Time, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut state = serializer.serialize_struct("Time", 2)?;
+    state.serialize_field("seconds", &time.seconds())?;
+    state.serialize_field("offset_minutes", &time.offset_minutes())?;
+    state.end()
+}
+
+pub(crate) fn option_time<S: Serializer>(
+    time: &Option<git2::Time>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => self::time(time, serializer),
+        None => serializer.serialize_none(),
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    collections::HashMap,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, ensure};
+use log::*;
+
+/// The commit-graph files of a repository, to walk the commits changing a
+/// path by the changed-path Bloom filters without reading the commit objects
+/// and the trees.
+/// See <https://git-scm.com/docs/gitformat-commit-graph>.
+#[derive(Debug)]
+pub(crate) struct CommitGraph {
+    /// The layers from the base; i.e., in the order of the graph positions.
+    layers: Vec<CommitGraphLayer>,
+}
+
+impl CommitGraph {
+    /// The commit-graph files `git` reads; i.e., the `commit-graph` file if it
+    /// exists, or the layers in the `commit-graph-chain`, the base first.
+    pub fn paths(repository: &git2::Repository) -> Vec<PathBuf> {
+        let info_path = repository.commondir().join("objects/info");
+        let path = info_path.join("commit-graph");
+        if path.is_file() {
+            return vec![path];
+        }
+        let Ok(chain) = fs::read_to_string(info_path.join("commit-graphs/commit-graph-chain"))
+        else {
+            return Vec::new();
+        };
+        chain
+            .lines()
+            .map(|hash| info_path.join(format!("commit-graphs/graph-{hash}.graph")))
+            .collect()
+    }
+
+    /// Read the commit-graph files of the `repository`. `None` if it doesn't
+    /// have them, or if they can't be read.
+    pub fn open(repository: &git2::Repository) -> Option<Self> {
+        // `git` doesn't use the commit-graph in shallow repositories.
+        if repository.is_shallow() {
+            return None;
+        }
+        let paths = Self::paths(repository);
+        if paths.is_empty() {
+            return None;
+        }
+        let mut layers: Vec<CommitGraphLayer> = Vec::with_capacity(paths.len());
+        for path in &paths {
+            let base = layers.last().map_or(0, |layer| layer.base + layer.len);
+            match CommitGraphLayer::read(path, base) {
+                Ok(layer) => layers.push(layer),
+                Err(error) => {
+                    debug!("CommitGraph: {path:?}: {error}");
+                    return None;
+                }
+            }
+        }
+        Some(Self { layers })
+    }
+
+    /// True if all the layers have the changed-path Bloom filters.
+    pub fn has_changed_path_filters(&self) -> bool {
+        self.layers.iter().all(|layer| layer.bloom.is_some())
+    }
+
+    /// The commit time and the ID of the first parent of the `commit_id`, if
+    /// its changed-path Bloom filter says that the commit didn't change the
+    /// path of the `key` from the first parent. `None` if it may have, or if
+    /// the commit-graph doesn't know.
+    pub fn unchanged_first_parent(
+        &self,
+        commit_id: git2::Oid,
+        key: &BloomKey,
+    ) -> Option<(i64, git2::Oid)> {
+        let (layer, index) = self
+            .layers
+            .iter()
+            .find_map(|layer| Some((layer, layer.index_of(commit_id)?)))?;
+        if layer.maybe_changed(index, key)? {
+            return None;
+        }
+        let position = layer.first_parent_position(index)?;
+        let parent_layer = self
+            .layers
+            .iter()
+            .find(|layer| (layer.base..layer.base + layer.len).contains(&position))?;
+        let parent_index = position - parent_layer.base;
+        Some((
+            parent_layer.commit_time(parent_index),
+            parent_layer.commit_id(parent_index),
+        ))
+    }
+}
+
+/// A commit-graph file, or a layer of the commit-graph chain.
+#[derive(Debug)]
+struct CommitGraphLayer {
+    data: Vec<u8>,
+    /// The graph position of the first commit of this layer; i.e., the number
+    /// of the commits in the base layers.
+    base: usize,
+    len: usize,
+    /// The OID Lookup chunk.
+    commit_ids: Range<usize>,
+    /// The Commit Data chunk.
+    commits: Range<usize>,
+    bloom: Option<BloomFilters>,
+}
+
+/// The Bloom Filter Index and the Bloom Filter Data chunks.
+#[derive(Debug)]
+struct BloomFilters {
+    index: Range<usize>,
+    /// The filters, after the header of the Bloom Filter Data chunk.
+    data: Range<usize>,
+    version: u32,
+    num_hashes: u32,
+}
+
+impl CommitGraphLayer {
+    const OID_LEN: usize = 20;
+    const COMMIT_DATA_LEN: usize = Self::OID_LEN + 16;
+    const PARENT_NONE: u32 = 0x7000_0000;
+
+    fn read(path: &Path, base: usize) -> anyhow::Result<Self> {
+        let data = fs::read(path)?;
+        ensure!(
+            data.len() >= 8 && data.starts_with(b"CGPH"),
+            "Not a commit-graph file"
+        );
+        ensure!(
+            data[4] == 1 && data[5] == 1,
+            "Unsupported version {} or hash version {}",
+            data[4],
+            data[5]
+        );
+        // The chunk lookup table has the ID and the offset of each chunk,
+        // and the terminating entry.
+        let chunks_len = data[6] as usize;
+        let table = data
+            .get(8..8 + (chunks_len + 1) * 12)
+            .context("Truncated chunk lookup table")?;
+        let mut chunks = HashMap::new();
+        for (entry, next) in table.chunks_exact(12).zip(table.chunks_exact(12).skip(1)) {
+            let start = be_u64(&entry[4..]) as usize;
+            let end = be_u64(&next[4..]) as usize;
+            ensure!(start <= end && end <= data.len(), "Invalid chunk offsets");
+            chunks.insert(&entry[..4], start..end);
+        }
+        let chunk = |id: &[u8], len: Option<usize>| -> anyhow::Result<Range<usize>> {
+            let range = chunks
+                .get(id)
+                .cloned()
+                .with_context(|| format!("No {} chunk", String::from_utf8_lossy(id)))?;
+            ensure!(
+                len.is_none_or(|len| range.len() == len),
+                "Invalid {} chunk",
+                String::from_utf8_lossy(id)
+            );
+            Ok(range)
+        };
+        let fanout = chunk(b"OIDF", Some(256 * 4))?;
+        let len = be_u32(&data[fanout.end - 4..]) as usize;
+        let commit_ids = chunk(b"OIDL", Some(len * Self::OID_LEN))?;
+        let commits = chunk(b"CDAT", Some(len * Self::COMMIT_DATA_LEN))?;
+        let bloom = match (chunk(b"BIDX", Some(len * 4)), chunk(b"BDAT", None)) {
+            (Ok(index), Ok(filters)) if filters.len() >= 12 => {
+                let version = be_u32(&data[filters.start..]);
+                let num_hashes = be_u32(&data[filters.start + 4..]);
+                (version == 1 || version == 2).then_some(BloomFilters {
+                    index,
+                    data: filters.start + 12..filters.end,
+                    version,
+                    num_hashes,
+                })
+            }
+            _ => None,
+        };
+        Ok(Self {
+            data,
+            base,
+            len,
+            commit_ids,
+            commits,
+            bloom,
+        })
+    }
+
+    /// The index in this layer, or `None` if this layer doesn't have it.
+    fn index_of(&self, commit_id: git2::Oid) -> Option<usize> {
+        let (commit_ids, _) = self.data[self.commit_ids.clone()].as_chunks::<{ Self::OID_LEN }>();
+        commit_ids
+            .binary_search_by(|id| id.as_slice().cmp(commit_id.as_bytes()))
+            .ok()
+    }
+
+    fn commit_id(&self, index: usize) -> git2::Oid {
+        let start = self.commit_ids.start + index * Self::OID_LEN;
+        git2::Oid::from_bytes(&self.data[start..start + Self::OID_LEN]).unwrap()
+    }
+
+    fn commit_data(&self, index: usize) -> &[u8] {
+        let start = self.commits.start + index * Self::COMMIT_DATA_LEN;
+        &self.data[start..start + Self::COMMIT_DATA_LEN]
+    }
+
+    /// The graph position of the first parent, or `None` for root commits.
+    fn first_parent_position(&self, index: usize) -> Option<usize> {
+        let position = be_u32(&self.commit_data(index)[Self::OID_LEN..]);
+        (position != Self::PARENT_NONE).then_some(position as usize)
+    }
+
+    /// The committer time in seconds; the lower 34 bits of the last 8 bytes.
+    fn commit_time(&self, index: usize) -> i64 {
+        let data = &self.commit_data(index)[Self::OID_LEN + 8..];
+        (((be_u32(data) & 0x3) as i64) << 32) | be_u32(&data[4..]) as i64
+    }
+
+    /// Whether the Bloom filter of the commit says that it may have changed
+    /// the path of the `key` from its first parent. `None` if the filter is
+    /// not computed.
+    fn maybe_changed(&self, index: usize, key: &BloomKey) -> Option<bool> {
+        let bloom = self.bloom.as_ref()?;
+        let offsets = &self.data[bloom.index.clone()];
+        let start = match index {
+            0 => 0,
+            _ => be_u32(&offsets[(index - 1) * 4..]) as usize,
+        };
+        let end = be_u32(&offsets[index * 4..]) as usize;
+        let filter = self.data[bloom.data.clone()].get(start..end)?;
+        if filter.is_empty() {
+            return None;
+        }
+        let bits = filter.len() as u64 * 8;
+        Some(
+            key.hashes(bloom.version, bloom.num_hashes)
+                .map(|hash| hash as u64 % bits)
+                .all(|bit| filter[(bit / 8) as usize] & (1 << (bit % 8)) != 0),
+        )
+    }
+}
+
+/// The hashes of a path for the changed-path Bloom filters.
+#[derive(Debug)]
+pub(crate) struct BloomKey {
+    /// The two seeded hashes for the version 1 and 2 of the filters.
+    hashes: [(u32, u32); 2],
+}
+
+impl BloomKey {
+    const SEEDS: [u32; 2] = [0x293a_e76f, 0x7e64_6e2c];
+
+    /// The key of the `path` relative to the root of the repository.
+    pub fn new(path: &Path) -> Self {
+        let path = path
+            .components()
+            .map(|component| component.as_os_str().as_encoded_bytes())
+            .collect::<Vec<_>>()
+            .join(&b'/');
+        let hashes = [1, 2].map(|version| {
+            (
+                murmur3(Self::SEEDS[0], &path, version),
+                murmur3(Self::SEEDS[1], &path, version),
+            )
+        });
+        Self { hashes }
+    }
+
+    fn hashes(&self, version: u32, num_hashes: u32) -> impl Iterator<Item = u32> {
+        let (hash0, hash1) = self.hashes[version as usize - 1];
+        (0..num_hashes).map(move |i| hash0.wrapping_add(i.wrapping_mul(hash1)))
+    }
+}
+
+/// The 32-bit MurmurHash3 as `git` computes for the changed-path Bloom filters.
+/// The version 1 of the filters sign-extends the bytes, as `git` did with the
+/// signed `char`.
+fn murmur3(seed: u32, data: &[u8], version: u32) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+    let byte = |byte: u8| match version {
+        1 => byte as i8 as u32,
+        _ => byte as u32,
+    };
+    let mix = |k: u32| k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    let mut hash = seed;
+    let (blocks, tail) = data.as_chunks::<4>();
+    for block in blocks {
+        let k = byte(block[0]) | byte(block[1]) << 8 | byte(block[2]) << 16 | byte(block[3]) << 24;
+        hash ^= mix(k);
+        hash = hash
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xe654_6b64);
+    }
+    if !tail.is_empty() {
+        let k = tail
+            .iter()
+            .enumerate()
+            .fold(0, |k, (i, b)| k ^ byte(*b) << (i * 8));
+        hash ^= mix(k);
+    }
+    hash ^= data.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(bytes[..4].try_into().unwrap())
+}
+
+fn be_u64(bytes: &[u8]) -> u64 {
+    u64::from_be_bytes(bytes[..8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extensions::tests::TempRepository;
+
+    #[test]
+    fn murmur3_hash() {
+        // The same as `test-tool bloom get_murmur3` in `git`.
+        assert_eq!(murmur3(0, b"", 2), 0);
+        assert_eq!(murmur3(0, b"Hello world!", 2), 0x627b_0c2c);
+        assert_eq!(
+            murmur3(0, b"The quick brown fox jumps over the lazy dog", 2),
+            0x2e4f_f723
+        );
+    }
+
+    #[test]
+    fn unchanged_first_parent() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        // Non-ASCII to test the sign-extended hashes of the version 1.
+        let path = PathBuf::from("dir/t\u{e9}st.txt");
+        let other_path = PathBuf::from("other.txt");
+        git.add_file_content(&path, "1\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(&other_path, "1\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add other file")?;
+        git.add_file_content(&path, "1\n2\n")?;
+        let commit_id3 = git.commit(commit_id2, "Add line")?;
+        let repository = git.repository();
+        assert!(CommitGraph::open(repository).is_none());
+
+        git.write_commit_graph(&[])?;
+        let graph = CommitGraph::open(repository).unwrap();
+        assert!(!graph.has_changed_path_filters());
+        let key = BloomKey::new(&path);
+        assert_eq!(graph.unchanged_first_parent(commit_id2, &key), None);
+
+        git.write_commit_graph(&["--changed-paths"])?;
+        let graph = CommitGraph::open(repository).unwrap();
+        assert!(graph.has_changed_path_filters());
+        let time1 = repository.find_commit(commit_id1)?.time().seconds();
+        assert_eq!(
+            graph.unchanged_first_parent(commit_id2, &key),
+            Some((time1, commit_id1))
+        );
+        assert_eq!(graph.unchanged_first_parent(commit_id3, &key), None);
+        assert_eq!(graph.unchanged_first_parent(commit_id1, &key), None);
+        let other_key = BloomKey::new(&other_path);
+        assert_eq!(graph.unchanged_first_parent(commit_id2, &other_key), None);
+
+        // The commit-graph chain, the parent in the base layer.
+        fs::remove_file(repository.commondir().join("objects/info/commit-graph"))?;
+        git.write_commit_graph(&["--changed-paths", "--split"])?;
+        git.add_file_content(&other_path, "2\n")?;
+        let commit_id4 = git.commit(commit_id3, "Change other file")?;
+        git.write_commit_graph(&["--changed-paths", "--split=no-merge"])?;
+        assert_eq!(CommitGraph::paths(repository).len(), 2);
+        let graph = CommitGraph::open(repository).unwrap();
+        assert!(graph.has_changed_path_filters());
+        let time3 = repository.find_commit(commit_id3)?.time().seconds();
+        assert_eq!(
+            graph.unchanged_first_parent(commit_id4, &key),
+            Some((time3, commit_id3))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,13 @@
+use std::{env, path::PathBuf};
+
+/// The directory of the configuration files;
+/// `$XDG_CONFIG_HOME/git-iblame`, `$HOME/.config/git-iblame`,
+/// or `%APPDATA%\git-iblame` on Windows.
+pub(crate) fn config_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(base.join("git-iblame"))
+}
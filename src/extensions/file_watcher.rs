@@ -0,0 +1,141 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use log::*;
+use notify::Watcher;
+
+/// Watch files and directories using the [`notify`] crate,
+/// and report whether any of them have changed since the last check.
+#[derive(Debug)]
+pub struct FileWatcher {
+    watcher: notify::RecommendedWatcher,
+    rx: mpsc::Receiver<notify::Result<notify::Event>>,
+    /// The paths added by `watch()`. Changes of any paths in them are
+    /// reported.
+    paths: Vec<PathBuf>,
+    /// The files added by `watch_file()`. Changes of other files in their
+    /// directories are ignored.
+    files: Vec<PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn new() -> anyhow::Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let watcher = notify::recommended_watcher(tx)?;
+        Ok(Self {
+            watcher,
+            rx,
+            paths: Vec::new(),
+            files: Vec::new(),
+        })
+    }
+
+    /// Add a path to watch. If `path` is a directory and `recursive` is true,
+    /// its subdirectories are also watched.
+    pub fn watch(&mut self, path: &Path, recursive: bool) -> anyhow::Result<()> {
+        debug!("FileWatcher.watch: {path:?} recursive={recursive}");
+        let mode = if recursive {
+            notify::RecursiveMode::Recursive
+        } else {
+            notify::RecursiveMode::NonRecursive
+        };
+        // Canonicalize to match the paths of the events.
+        let path = path.canonicalize()?;
+        self.watcher.watch(&path, mode)?;
+        self.paths.push(path);
+        Ok(())
+    }
+
+    /// Add a file to watch. Its directory is watched instead of the file, so
+    /// that the file is still watched after editors replace it by renaming a
+    /// temporary file to it.
+    pub fn watch_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        debug!("FileWatcher.watch_file: {path:?}");
+        let (Some(dir_path), Some(file_name)) = (path.parent(), path.file_name()) else {
+            anyhow::bail!("No parent directory of {path:?}");
+        };
+        let dir_path = dir_path.canonicalize()?;
+        self.watcher
+            .watch(&dir_path, notify::RecursiveMode::NonRecursive)?;
+        self.files.push(dir_path.join(file_name));
+        Ok(())
+    }
+
+    fn is_watched(&self, path: &Path) -> bool {
+        self.files.iter().any(|file| file == path)
+            || self.paths.iter().any(|watched| path.starts_with(watched))
+    }
+
+    /// True if any of the watched paths have changed since the last call.
+    pub fn has_changed(&self) -> bool {
+        let mut has_changed = false;
+        for event in self.rx.try_iter() {
+            match event {
+                Ok(event) => {
+                    trace!("FileWatcher: {event:?}");
+                    if !event.kind.is_access()
+                        && (event.paths.is_empty()
+                            || event.paths.iter().any(|path| self.is_watched(path)))
+                    {
+                        has_changed = true;
+                    }
+                }
+                Err(error) => warn!("FileWatcher: {error}"),
+            }
+        }
+        has_changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs, thread,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+
+    /// Wait for the events, up to a few seconds if `has_changed()` is false.
+    fn has_changed_within(watcher: &FileWatcher, timeout: Duration) -> bool {
+        let start_time = Instant::now();
+        loop {
+            if watcher.has_changed() {
+                return true;
+            }
+            if start_time.elapsed() >= timeout {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn watch_file() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("test.txt");
+        fs::write(&path, "1\n")?;
+        let mut watcher = FileWatcher::new()?;
+        watcher.watch_file(&path)?;
+
+        // Other files in the directory are ignored.
+        fs::write(dir.path().join("other.txt"), "1\n")?;
+        assert!(!has_changed_within(&watcher, Duration::from_millis(500)));
+
+        fs::write(&path, "2\n")?;
+        assert!(has_changed_within(&watcher, Duration::from_secs(5)));
+
+        // Replacing by renaming, as editors do on saving.
+        let temp_path = dir.path().join("test.txt.tmp");
+        fs::write(&temp_path, "3\n")?;
+        fs::rename(&temp_path, &path)?;
+        assert!(has_changed_within(&watcher, Duration::from_secs(5)));
+
+        // The file is still watched after it was replaced.
+        fs::write(&path, "4\n")?;
+        assert!(has_changed_within(&watcher, Duration::from_secs(5)));
+        Ok(())
+    }
+}
@@ -0,0 +1,367 @@
+use std::{fs, ops::RangeInclusive, path::Path};
+
+use anyhow::Context;
+
+use super::config_dir;
+
+/// The kinds of web services hosting git repositories.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ForgeKind {
+    GitHub,
+    GitLab,
+    Gitea,
+}
+
+impl ForgeKind {
+    const NAMES: &[(&str, ForgeKind)] = &[
+        ("github", ForgeKind::GitHub),
+        ("gitlab", ForgeKind::GitLab),
+        ("gitea", ForgeKind::Gitea),
+    ];
+
+    fn from_name(name: &str) -> anyhow::Result<Self> {
+        let Some((_, kind)) = Self::NAMES.iter().find(|(n, _)| *n == name) else {
+            let names: Vec<&str> = Self::NAMES.iter().map(|(n, _)| *n).collect();
+            anyhow::bail!(
+                "Unknown forge kind \"{name}\", possible values are: {}",
+                names.join(", ")
+            );
+        };
+        Ok(*kind)
+    }
+
+    fn from_host(host: &str) -> Option<Self> {
+        if host.contains("github") {
+            Some(ForgeKind::GitHub)
+        } else if host.contains("gitlab") {
+            Some(ForgeKind::GitLab)
+        } else if host.contains("gitea") || host.contains("codeberg") {
+            Some(ForgeKind::Gitea)
+        } else {
+            None
+        }
+    }
+
+    fn templates(&self) -> ForgeTemplates {
+        let (commit, file, line, lines) = match self {
+            ForgeKind::GitHub => (
+                "{url}/commit/{commit}",
+                "{url}/blob/{commit}/{path}",
+                "#L{start}",
+                "#L{start}-L{end}",
+            ),
+            ForgeKind::GitLab => (
+                "{url}/-/commit/{commit}",
+                "{url}/-/blob/{commit}/{path}",
+                "#L{start}",
+                "#L{start}-{end}",
+            ),
+            ForgeKind::Gitea => (
+                "{url}/commit/{commit}",
+                "{url}/src/commit/{commit}/{path}",
+                "#L{start}",
+                "#L{start}-L{end}",
+            ),
+        };
+        ForgeTemplates {
+            commit: commit.to_string(),
+            file: file.to_string(),
+            line: line.to_string(),
+            lines: lines.to_string(),
+        }
+    }
+}
+
+/// The templates of the URLs.
+/// `{url}` is replaced with the URL of the repository, `{commit}` with the
+/// commit ID, `{path}` with the path of the file, and `{start}` and `{end}`
+/// with the first and the last line numbers.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct ForgeTemplates {
+    /// The URL of a commit.
+    commit: String,
+    /// The URL of a file at a commit.
+    file: String,
+    /// The suffix of the `file` to highlight a line.
+    line: String,
+    /// The suffix of the `file` to highlight multiple lines.
+    lines: String,
+}
+
+impl ForgeTemplates {
+    /// Override the templates by the keys of the `table`.
+    fn apply(&mut self, table: &toml::Table) -> anyhow::Result<()> {
+        for (key, value) in table {
+            let template = match key.as_str() {
+                "kind" => continue,
+                "commit" => &mut self.commit,
+                "file" => &mut self.file,
+                "line" => &mut self.line,
+                "lines" => &mut self.lines,
+                _ => anyhow::bail!("Unknown key \"{key}\""),
+            };
+            let Some(value) = value.as_str() else {
+                anyhow::bail!("The value of \"{key}\" must be a string");
+            };
+            *template = value.to_string();
+        }
+        Ok(())
+    }
+}
+
+/// A web service hosting git repositories, such as GitHub,
+/// to create the URLs of files and commits.
+///
+/// Hosts can be configured in `forges.toml` in the `config_dir()`,
+/// by the kind of the forge, or by the templates of the URLs;
+/// e.g.,
+/// ```toml
+/// ["git.example.com"]
+/// kind = "gitlab"
+///
+/// ["code.example.org"]
+/// kind = "github"
+/// commit = "{url}/commits/{commit}"
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Forge {
+    /// The URL of the repository without the trailing `/`;
+    /// e.g., `https://github.com/owner/repo`.
+    repository_url: String,
+    templates: ForgeTemplates,
+}
+
+impl Forge {
+    /// The file name of the forge configurations in the `config_dir()`.
+    pub const CONFIG_FILE_NAME: &str = "forges.toml";
+
+    /// Create from the URL of the `origin` remote of the `repository`.
+    pub fn from_repository(repository: &git2::Repository) -> anyhow::Result<Self> {
+        let remote = repository.find_remote("origin")?;
+        let config = match config_dir().map(|dir| dir.join(Self::CONFIG_FILE_NAME)) {
+            Some(path) if path.exists() => {
+                let content = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                content
+                    .parse()
+                    .with_context(|| format!("Failed to parse {}", path.display()))?
+            }
+            _ => toml::Table::new(),
+        };
+        Self::from_remote_url(remote.url()?, &config)
+    }
+
+    /// Create from a remote URL such as `git@github.com:owner/repo.git`,
+    /// `ssh://git@host:22/owner/repo.git`, or `https://host/owner/repo`.
+    /// The `config` is the content of the `CONFIG_FILE_NAME`.
+    pub fn from_remote_url(url: &str, config: &toml::Table) -> anyhow::Result<Self> {
+        let (host, path) = if let Some((_, rest)) = url.split_once("://") {
+            let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+            let host = authority.rsplit('@').next().unwrap_or(authority);
+            let host = host.split(':').next().unwrap_or(host);
+            (host, path)
+        } else if let Some((authority, path)) = url.split_once(':') {
+            // The scp-like syntax; e.g., `git@github.com:owner/repo.git`.
+            let host = authority.rsplit('@').next().unwrap_or(authority);
+            (host, path)
+        } else {
+            anyhow::bail!("Unknown remote URL: {url}");
+        };
+        let path = path.trim_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        if host.is_empty() || path.is_empty() {
+            anyhow::bail!("Unknown remote URL: {url}");
+        }
+
+        let host_config = match config.get(host) {
+            Some(value) => match value.as_table() {
+                Some(table) => Some(table),
+                None => anyhow::bail!("The config of \"{host}\" must be a table"),
+            },
+            None => None,
+        };
+        let kind = match host_config.and_then(|table| table.get("kind")) {
+            Some(kind) => match kind.as_str() {
+                Some(name) => Some(ForgeKind::from_name(name)?),
+                None => anyhow::bail!("The kind of \"{host}\" must be a string"),
+            },
+            None => ForgeKind::from_host(host),
+        };
+        let mut templates = match (kind, host_config) {
+            (Some(kind), _) => kind.templates(),
+            // Configured hosts without kinds are based on GitHub.
+            (None, Some(_)) => ForgeKind::GitHub.templates(),
+            (None, None) => anyhow::bail!(
+                "Unknown forge \"{host}\", please configure it in {}",
+                Self::CONFIG_FILE_NAME
+            ),
+        };
+        if let Some(table) = host_config {
+            templates
+                .apply(table)
+                .with_context(|| format!("Invalid config of \"{host}\""))?;
+        }
+        Ok(Self {
+            repository_url: format!("https://{host}/{path}"),
+            templates,
+        })
+    }
+
+    /// The URL of the `commit_id`.
+    pub fn commit_url(&self, commit_id: git2::Oid) -> String {
+        self.templates
+            .commit
+            .replace("{url}", &self.repository_url)
+            .replace("{commit}", &commit_id.to_string())
+    }
+
+    /// The URL of the `path` at the `commit_id`,
+    /// with the `line_numbers` highlighted.
+    pub fn file_url(
+        &self,
+        commit_id: git2::Oid,
+        path: &Path,
+        line_numbers: RangeInclusive<usize>,
+    ) -> String {
+        let path = path
+            .to_string_lossy()
+            .replace('\\', "/")
+            .replace(' ', "%20");
+        let (start, end) = line_numbers.into_inner();
+        let lines = if end > start {
+            &self.templates.lines
+        } else {
+            &self.templates.line
+        };
+        format!("{}{lines}", self.templates.file)
+            .replace("{url}", &self.repository_url)
+            .replace("{commit}", &commit_id.to_string())
+            .replace("{path}", &path)
+            .replace("{start}", &start.to_string())
+            .replace("{end}", &end.to_string())
+    }
+}
+
+/// Open the `url` in the browser.
+/// The `BROWSER` environment variable can specify the browser.
+pub(crate) fn open_url(url: &str) -> anyhow::Result<()> {
+    let mut command = if let Some(browser) = std::env::var_os("BROWSER") {
+        std::process::Command::new(browser)
+    } else if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    command
+        .arg(url)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to open {url}"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forge(url: &str) -> anyhow::Result<Forge> {
+        Forge::from_remote_url(url, &toml::Table::new())
+    }
+
+    #[test]
+    fn from_remote_url() -> anyhow::Result<()> {
+        let github = Forge {
+            repository_url: "https://github.com/owner/repo".to_string(),
+            templates: ForgeKind::GitHub.templates(),
+        };
+        assert_eq!(forge("git@github.com:owner/repo.git")?, github);
+        assert_eq!(forge("https://github.com/owner/repo")?, github);
+        assert_eq!(forge("ssh://git@github.com:22/owner/repo.git")?, github);
+        assert_eq!(
+            forge("https://user@gitlab.com/group/sub/repo.git/")?,
+            Forge {
+                repository_url: "https://gitlab.com/group/sub/repo".to_string(),
+                templates: ForgeKind::GitLab.templates(),
+            }
+        );
+        assert_eq!(
+            forge("https://codeberg.org/owner/repo.git")?.templates,
+            ForgeKind::Gitea.templates()
+        );
+        assert!(forge("https://example.com/owner/repo").is_err());
+        assert!(forge("/path/to/repo").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn from_remote_url_config() -> anyhow::Result<()> {
+        let config: toml::Table = r#"
+            ["git.example.com"]
+            kind = "gitlab"
+
+            ["code.example.org"]
+            commit = "{url}/commits/{commit}"
+            "#
+        .parse()?;
+        let commit_id = git2::Oid::from_str("0123456789abcdef0123456789abcdef01234567")?;
+        let forge = Forge::from_remote_url("git@git.example.com:owner/repo.git", &config)?;
+        assert_eq!(
+            forge.commit_url(commit_id),
+            format!("https://git.example.com/owner/repo/-/commit/{commit_id}")
+        );
+        let forge = Forge::from_remote_url("git@code.example.org:owner/repo.git", &config)?;
+        assert_eq!(
+            forge.commit_url(commit_id),
+            format!("https://code.example.org/owner/repo/commits/{commit_id}")
+        );
+
+        let config: toml::Table = "[\"git.example.com\"]\nkind = \"unknown\"".parse()?;
+        assert!(Forge::from_remote_url("git@git.example.com:owner/repo", &config).is_err());
+        let config: toml::Table = "[\"git.example.com\"]\nunknown = \"\"".parse()?;
+        assert!(Forge::from_remote_url("git@git.example.com:owner/repo", &config).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn commit_url() -> anyhow::Result<()> {
+        let commit_id = git2::Oid::from_str("0123456789abcdef0123456789abcdef01234567")?;
+        assert_eq!(
+            forge("git@github.com:owner/repo.git")?.commit_url(commit_id),
+            format!("https://github.com/owner/repo/commit/{commit_id}")
+        );
+        assert_eq!(
+            forge("git@gitlab.com:owner/repo.git")?.commit_url(commit_id),
+            format!("https://gitlab.com/owner/repo/-/commit/{commit_id}")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn file_url() -> anyhow::Result<()> {
+        let commit_id = git2::Oid::from_str("0123456789abcdef0123456789abcdef01234567")?;
+        let path = Path::new("src/a b.rs");
+        let github = forge("git@github.com:owner/repo.git")?;
+        assert_eq!(
+            github.file_url(commit_id, path, 10..=12),
+            format!("https://github.com/owner/repo/blob/{commit_id}/src/a%20b.rs#L10-L12")
+        );
+        assert_eq!(
+            github.file_url(commit_id, path, 10..=10),
+            format!("https://github.com/owner/repo/blob/{commit_id}/src/a%20b.rs#L10")
+        );
+        assert_eq!(
+            forge("git@gitlab.com:owner/repo.git")?.file_url(commit_id, path, 10..=12),
+            format!("https://gitlab.com/owner/repo/-/blob/{commit_id}/src/a%20b.rs#L10-12")
+        );
+        assert_eq!(
+            forge("https://gitea.com/owner/repo.git")?.file_url(commit_id, path, 10..=12),
+            format!("https://gitea.com/owner/repo/src/commit/{commit_id}/src/a%20b.rs#L10-L12")
+        );
+        Ok(())
+    }
+}
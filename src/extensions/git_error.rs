@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+/// The errors from `GitTools`.
+///
+/// The errors other than the variants to match are `GitError::Other`;
+/// their types and messages are subject to change.
+/// ```no_run
+/// use git_iblame::extensions::{GitError, GitTools};
+///
+/// # use std::path::Path;
+/// match GitTools::from_file_path(Path::new("/tmp")) {
+///     Err(GitError::NotInRepository(_)) => {}
+///     _ => {}
+/// }
+/// ```
+#[derive(thiserror::Error, Debug)]
+pub enum GitError {
+    #[error("{0:?} is not in a git repository")]
+    NotInRepository(PathBuf),
+    #[error("Bare repository is not supported")]
+    BareRepository,
+    #[error("Path not found: {0:?}")]
+    PathNotFound(PathBuf),
+    #[error("{0:?} is a binary file")]
+    BinaryFile(PathBuf),
+    /// The `path` matches multiple tracked paths when the case is ignored.
+    /// See `GitTools::path_in_workdir()`.
+    #[error("{path:?} is ambiguous, it matches {candidates:?}")]
+    AmbiguousPath {
+        path: PathBuf,
+        candidates: Vec<PathBuf>,
+    },
+    /// A `git` command exited with an error. The `message` is its `stderr`.
+    #[error("git {command} failed: {message}")]
+    CommandFailed { command: String, message: String },
+    /// The `git` command is not found, or is older than
+    /// `GitTools::MIN_GIT_VERSION`. See `GitTools::check_git_command()`.
+    #[error("{0}")]
+    CommandUnavailable(String),
+    #[error(transparent)]
+    Git2(#[from] git2::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// Other errors, for messages.
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+/// Recovers the `GitError` if the `anyhow::Error` has it, even with context
+/// added; the context is dropped in that case.
+impl From<anyhow::Error> for GitError {
+    fn from(error: anyhow::Error) -> Self {
+        error.downcast::<Self>().unwrap_or_else(Self::Other)
+    }
+}
+
+impl GitError {
+    /// Construct `GitError::CommandFailed` from the `stderr` of the `command`.
+    pub(crate) fn command_failed(command: &str, stderr: &[u8]) -> Self {
+        Self::CommandFailed {
+            command: command.to_string(),
+            message: String::from_utf8_lossy(stderr).trim().to_string(),
+        }
+    }
+}
@@ -1,11 +1,19 @@
 use std::{
+    collections::HashMap,
     env,
+    ffi::OsString,
+    fs,
+    io::Read,
+    ops::Range,
     path::{Path, PathBuf},
+    sync::OnceLock,
 };
 
-use anyhow::*;
+use anyhow::{Context, anyhow, bail};
 use log::*;
 
+use super::{CommitGraph, GitError};
+
 pub struct GitTools {
     repository: git2::Repository,
     workdir_path: PathBuf,
@@ -15,28 +23,95 @@ impl GitTools {
     /// Construct from the `Path` to a file in the repository.
     /// The `path` can be a path to a subdirectory inside the working directory
     /// of the repository.
+    ///
+    /// The environment variables are honored as the `git` command does;
+    /// `GIT_DIR` opens the repository instead of searching from the `path`,
+    /// `GIT_WORK_TREE` overrides the working directory, and the search stops
+    /// at the `GIT_CEILING_DIRECTORIES`.
     /// See <https://libgit2.org/docs/reference/main/repository/git_repository_open_ext.html>.
-    pub fn from_file_path(path: &Path) -> anyhow::Result<Self> {
-        let repository = git2::Repository::open_ext(
-            path,
-            git2::RepositoryOpenFlags::empty(),
-            &[] as &[&std::ffi::OsStr],
-        )?;
-        Self::from_repository(repository)
+    pub fn from_file_path(path: &Path) -> Result<Self, GitError> {
+        let repository = Self::open_with_env(path, |name| env::var_os(name))?;
+        Ok(Self::from_repository(repository)?)
     }
 
     /// The 'path' argument must point to either a git repository folder, or an
     /// existing work dir.
+    /// The `GIT_WORK_TREE` environment variable is honored in the same way as
+    /// `from_file_path()`, so that the same repository can be opened again by
+    /// its `repository_path()`.
     /// See <https://libgit2.org/docs/reference/main/repository/git_repository_open.html>.
-    pub fn from_repository_path(repository_path: &Path) -> anyhow::Result<Self> {
+    pub fn from_repository_path(repository_path: &Path) -> Result<Self, GitError> {
         let repository = git2::Repository::open(repository_path)?;
-        Self::from_repository(repository)
+        Self::set_workdir_from_env(&repository, |name| env::var_os(name))?;
+        Ok(Self::from_repository(repository)?)
+    }
+
+    /// Open the repository for the `path` with the environment variables
+    /// by the `var`. See `from_file_path()`.
+    fn open_with_env(
+        path: &Path,
+        var: impl Fn(&str) -> Option<OsString>,
+    ) -> anyhow::Result<git2::Repository> {
+        let repository = if let Some(git_dir) = var("GIT_DIR") {
+            let git_dir = std::path::absolute(git_dir)?;
+            debug!("GIT_DIR={git_dir:?}");
+            git2::Repository::open_ext(
+                &git_dir,
+                git2::RepositoryOpenFlags::NO_SEARCH,
+                &[] as &[&std::ffi::OsStr],
+            )
+            .map_err(|error| Self::open_error(error, &git_dir))?
+        } else {
+            // An empty entry only changes how `git` resolves symbolic links.
+            let ceiling_dirs: Vec<PathBuf> = var("GIT_CEILING_DIRECTORIES")
+                .map(|dirs| {
+                    env::split_paths(&dirs)
+                        .filter(|dir| !dir.as_os_str().is_empty())
+                        .collect()
+                })
+                .unwrap_or_default();
+            git2::Repository::open_ext(path, git2::RepositoryOpenFlags::empty(), &ceiling_dirs)
+                .map_err(|error| Self::open_error(error, path))?
+        };
+        Self::set_workdir_from_env(&repository, var)?;
+        Ok(repository)
+    }
+
+    /// Set the working directory of the `repository` to the `GIT_WORK_TREE`.
+    /// If only the `GIT_DIR` is set, the current directory is the top of the
+    /// working directory, unless `core.worktree` is set, as `git` does.
+    fn set_workdir_from_env(
+        repository: &git2::Repository,
+        var: impl Fn(&str) -> Option<OsString>,
+    ) -> anyhow::Result<()> {
+        let workdir = if let Some(work_tree) = var("GIT_WORK_TREE") {
+            std::path::absolute(work_tree)?
+        } else if var("GIT_DIR").is_some()
+            && !repository.is_bare()
+            && repository.config()?.get_path("core.worktree").is_err()
+        {
+            env::current_dir()?
+        } else {
+            return Ok(());
+        };
+        debug!("GIT_WORK_TREE={workdir:?}");
+        repository.set_workdir(&workdir, false)?;
+        Ok(())
+    }
+
+    /// Convert the "not found" error of opening a repository to
+    /// `GitError::NotInRepository`.
+    fn open_error(error: git2::Error, path: &Path) -> anyhow::Error {
+        if error.code() == git2::ErrorCode::NotFound {
+            return GitError::NotInRepository(path.to_path_buf()).into();
+        }
+        error.into()
     }
 
     fn from_repository(repository: git2::Repository) -> anyhow::Result<Self> {
         let workdir_path = repository
             .workdir()
-            .ok_or_else(|| anyhow!("Bare repository is not supported"))?
+            .ok_or(GitError::BareRepository)?
             .canonicalize()?;
         log::debug!("git.repository: {:?}", repository.path());
         log::debug!("git.workdir: {workdir_path:?}");
@@ -46,14 +121,14 @@ impl GitTools {
         })
     }
 
-    /// If the `path` exists, this is equivalent to [`from_file_path()`].
-    /// Then returns the `GitTools` and the result of [`path_in_workdir()`].
+    /// If the `path` exists, this is equivalent to [`Self::from_file_path()`].
+    /// Then returns the `GitTools` and the result of [`Self::path_in_workdir()`].
     ///
     /// Otherwise, if the `path` is relative,
     /// find the git repository from the current directory.
     /// The `path` is then converted to
     /// the relative path to the work directory of the repository.
-    pub fn from_file_or_relative_path(path: &Path) -> anyhow::Result<(Self, PathBuf)> {
+    pub fn from_file_or_relative_path(path: &Path) -> Result<(Self, PathBuf), GitError> {
         if path.exists() {
             let git = Self::from_file_path(path)?;
             let path = git.path_in_workdir(path)?;
@@ -61,14 +136,15 @@ impl GitTools {
             return Ok((git, path));
         }
         if path.is_relative() {
-            let repository = git2::Repository::discover(env::current_dir()?)?;
+            let current_dir = env::current_dir()?;
+            let repository = Self::open_with_env(&current_dir, |name| env::var_os(name))?;
             let git = Self::from_repository(repository)?;
             let path = git.workdir_path.join(path);
             let path = git.path_in_workdir(&path)?;
             log::debug!("git.path_in_workdir: {path:?}");
             return Ok((git, path));
         }
-        anyhow::bail!("Path not found: {path:?}");
+        Err(GitError::PathNotFound(path.to_path_buf()))
     }
 
     /// Get `git2::Repository`.
@@ -86,10 +162,109 @@ impl GitTools {
         &self.workdir_path
     }
 
-    pub fn path_in_workdir(&self, path: &Path) -> anyhow::Result<PathBuf> {
-        let path = path.canonicalize()?;
-        let path = path.strip_prefix(self.workdir_path())?;
-        Ok(Self::to_posix_path(path))
+    /// Convert the `path` to the relative path from the working directory,
+    /// as `git` tracks it.
+    ///
+    /// Symbolic links in the directories are resolved. If the file itself is
+    /// a symbolic link, the target is preferred if it's tracked, and the link
+    /// otherwise. If `core.ignoreCase` is set, as on case-insensitive file
+    /// systems, the case is corrected to the tracked path.
+    pub fn path_in_workdir(&self, path: &Path) -> Result<PathBuf, GitError> {
+        let resolved = path.canonicalize();
+        // The path with only the directories resolved.
+        let absolute = std::path::absolute(path)?;
+        let link = match (absolute.parent(), absolute.file_name()) {
+            (Some(parent), Some(file_name)) => parent
+                .canonicalize()
+                .ok()
+                .map(|parent| parent.join(file_name)),
+            _ => None,
+        };
+        let ignore_case = self.is_ignore_case();
+        let mut untracked: Option<PathBuf> = None;
+        for candidate in resolved.iter().chain(link.iter()) {
+            let Some(relative) = self.strip_workdir_path(candidate, ignore_case) else {
+                continue;
+            };
+            let relative = Self::to_posix_path(relative);
+            if let Some(tracked) = self.tracked_path(&relative, ignore_case)? {
+                return Ok(tracked);
+            }
+            if untracked.is_none() && candidate.symlink_metadata().is_ok() {
+                untracked = Some(relative);
+            }
+        }
+        if let Some(untracked) = untracked {
+            return Ok(untracked);
+        }
+        let path = resolved?;
+        Err(GitError::NotInRepository(path))
+    }
+
+    /// Whether `core.ignoreCase` is set; i.e., the file system is
+    /// case-insensitive.
+    fn is_ignore_case(&self) -> bool {
+        self.repository
+            .config()
+            .and_then(|config| config.get_bool("core.ignorecase"))
+            .unwrap_or(false)
+    }
+
+    /// The relative path of the absolute `path` from the working directory.
+    fn strip_workdir_path<'a>(&self, path: &'a Path, ignore_case: bool) -> Option<&'a Path> {
+        if let Result::Ok(relative) = path.strip_prefix(&self.workdir_path) {
+            return Some(relative);
+        }
+        if !ignore_case {
+            return None;
+        }
+        let mut components = path.components();
+        for workdir_component in self.workdir_path.components() {
+            let component = components.next()?;
+            if !component
+                .as_os_str()
+                .eq_ignore_ascii_case(workdir_component.as_os_str())
+            {
+                return None;
+            }
+        }
+        Some(components.as_path())
+    }
+
+    /// The `path` as tracked in the index or in the `HEAD`, or `None` if it's
+    /// not tracked. If `ignore_case`, the path is searched in the index with
+    /// the ASCII case ignored, as `git` and `strip_workdir_path()` do.
+    fn tracked_path(&self, path: &Path, ignore_case: bool) -> anyhow::Result<Option<PathBuf>> {
+        let index = self.repository.index()?;
+        if index.get_path(path, 0).is_some()
+            || self
+                .repository
+                .head()
+                .and_then(|head| head.peel_to_tree())
+                .is_ok_and(|tree| tree.get_path(path).is_ok())
+        {
+            return Ok(Some(path.to_path_buf()));
+        }
+        if !ignore_case {
+            return Ok(None);
+        }
+        let path_bytes = path.as_os_str().as_encoded_bytes();
+        let mut candidates: Vec<PathBuf> = index
+            .iter()
+            .filter(|entry| entry.path.eq_ignore_ascii_case(path_bytes))
+            .map(|entry| PathBuf::from(String::from_utf8_lossy(&entry.path).as_ref()))
+            .collect();
+        // Conflicted files have multiple entries.
+        candidates.dedup();
+        match candidates.len() {
+            0 => Ok(None),
+            1 => Ok(candidates.pop()),
+            _ => Err(GitError::AmbiguousPath {
+                path: path.to_path_buf(),
+                candidates,
+            }
+            .into()),
+        }
     }
 
     #[cfg(target_os = "windows")]
@@ -104,15 +279,411 @@ impl GitTools {
         path.to_path_buf()
     }
 
-    pub fn head_commit_id(&self) -> anyhow::Result<git2::Oid> {
+    /// True if the repository has a commit-graph file.
+    /// See <https://git-scm.com/docs/commit-graph>.
+    pub fn has_commit_graph(&self) -> bool {
+        let info_path = self.repository.commondir().join("objects/info");
+        info_path.join("commit-graph").is_file()
+            || info_path.join("commit-graphs/commit-graph-chain").is_file()
+    }
+
+    /// True if the commit-graph files have the changed-path Bloom filters,
+    /// which `git log -- <path>` uses to skip commits not changing the path.
+    /// `git commit-graph write --reachable --changed-paths` writes them.
+    pub fn has_changed_path_filters(&self) -> bool {
+        let paths = CommitGraph::paths(&self.repository);
+        !paths.is_empty()
+            && paths
+                .iter()
+                .all(|path| Self::has_bloom_filter_chunk(path).unwrap_or(false))
+    }
+
+    /// True if the commit-graph file at the `path` has the Bloom filter chunk.
+    /// See <https://git-scm.com/docs/gitformat-commit-graph>.
+    fn has_bloom_filter_chunk(path: &Path) -> std::io::Result<bool> {
+        let mut file = fs::File::open(path)?;
+        let mut header = [0u8; 8];
+        file.read_exact(&mut header)?;
+        if &header[..4] != b"CGPH" {
+            return std::io::Result::Ok(false);
+        }
+        // The chunk lookup table has the ID and the offset of each chunk,
+        // and the terminating entry.
+        let chunks_len = header[6] as usize;
+        let mut table = vec![0u8; (chunks_len + 1) * 12];
+        file.read_exact(&mut table)?;
+        std::io::Result::Ok(table.chunks(12).any(|entry| &entry[..4] == b"BDAT"))
+    }
+
+    pub fn head_commit_id(&self) -> Result<git2::Oid, GitError> {
         let head = self.repository.head()?;
         let commit = head.peel_to_commit()?;
         Ok(commit.id())
     }
 
+    /// Whether the `HEAD` is a branch without commits yet, such as in
+    /// repositories without any commits.
+    pub fn is_head_unborn(&self) -> bool {
+        matches!(self.repository.head(), Err(error) if error.code() == git2::ErrorCode::UnbornBranch)
+    }
+
+    /// Whether the `path` exists in the tree of the `commit_id`.
+    pub fn has_path(&self, commit_id: git2::Oid, path: &Path) -> Result<bool, GitError> {
+        let tree = self.repository.find_commit(commit_id)?.tree()?;
+        Ok(tree.get_path(path).is_ok())
+    }
+
+    /// The shortest unique abbreviation of the `commit_id`.
+    pub fn short_id(&self, commit_id: git2::Oid) -> Result<String, GitError> {
+        let object = self.repository.find_object(commit_id, None)?;
+        let short_id = object.short_id()?;
+        Ok(String::from_utf8_lossy(&short_id).to_string())
+    }
+
+    /// Resolve a revision such as a commit ID, a branch, or a tag to the commit ID.
+    pub fn commit_id_from_rev(&self, rev: &str) -> Result<git2::Oid, GitError> {
+        let object = self.repository.revparse_single(rev)?;
+        let commit = object.peel_to_commit()?;
+        Ok(commit.id())
+    }
+
+    /// Create a `git` command for this repository.
+    fn git_command(&self) -> std::process::Command {
+        let mut command = std::process::Command::new("git");
+        Self::set_command_env(
+            &mut command,
+            self.repository_path(),
+            Some(&self.workdir_path),
+        );
+        command
+    }
+
+    /// If the environment overrides the repository by `GIT_DIR` or
+    /// `GIT_WORK_TREE`, set them to the absolute paths for the `command`,
+    /// because relative paths change by its current directory.
+    /// If the `workdir_path` is `None`, the `GIT_WORK_TREE` is removed.
+    pub(crate) fn set_command_env(
+        command: &mut std::process::Command,
+        repository_path: &Path,
+        workdir_path: Option<&Path>,
+    ) {
+        if env::var_os("GIT_DIR").is_none() && env::var_os("GIT_WORK_TREE").is_none() {
+            return;
+        }
+        command.env("GIT_DIR", repository_path);
+        match workdir_path {
+            Some(workdir_path) => command.env("GIT_WORK_TREE", workdir_path),
+            None => command.env_remove("GIT_WORK_TREE"),
+        };
+    }
+
+    /// The oldest version of the `git` command supported.
+    pub const MIN_GIT_VERSION: (u32, u32) = (2, 16);
+
+    /// Check if the `git` command is available and is not older than
+    /// `MIN_GIT_VERSION`. The result is cached for the process.
+    ///
+    /// Without it, `GitEngine::Git` and `GitEngine::GitBlame` don't work,
+    /// and `CommitIterator` lists the commits by the `git2` crate.
+    pub fn check_git_command() -> Result<(), GitError> {
+        static ERROR: OnceLock<Option<String>> = OnceLock::new();
+        match ERROR.get_or_init(Self::git_command_error) {
+            Some(message) => Err(GitError::CommandUnavailable(message.clone())),
+            None => Ok(()),
+        }
+    }
+
+    fn git_command_error() -> Option<String> {
+        let Some(output) = std::process::Command::new("git")
+            .arg("--version")
+            .output()
+            .ok()
+        else {
+            return Some("git not found".to_string());
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        debug!("check_git_command: {}", stdout.trim());
+        let Some(version) = Self::parse_git_version(&stdout) else {
+            return Some(format!("Unknown git version: {}", stdout.trim()));
+        };
+        if version < Self::MIN_GIT_VERSION {
+            let (major, minor) = Self::MIN_GIT_VERSION;
+            return Some(format!(
+                "git {}.{} is too old, {major}.{minor} or later is needed",
+                version.0, version.1
+            ));
+        }
+        None
+    }
+
+    /// Parse the major and the minor versions from the output of
+    /// `git --version`; e.g., "git version 2.39.5 (Apple Git-154)".
+    fn parse_git_version(output: &str) -> Option<(u32, u32)> {
+        let version = output.trim().strip_prefix("git version ")?;
+        let mut numbers = version.split(['.', ' ']).map(str::parse::<u32>);
+        Some((numbers.next()?.ok()?, numbers.next()?.ok()?))
+    }
+
+    /// Find the latest commit before the `date`, reachable from the
+    /// `commit_id`. The `date` can be in any formats `git` accepts;
+    /// e.g., "2025-01-31", "2025-01-31 12:34", or "2 weeks ago".
+    ///
+    /// If the `git` command is not available, the `date` is limited to the
+    /// formats of `timestamp_from_date()`.
+    pub fn commit_id_at(&self, date: &str, commit_id: git2::Oid) -> Result<git2::Oid, GitError> {
+        debug!("commit_id_at: {date} {commit_id}");
+        if Self::check_git_command().is_err() {
+            let timestamp = Self::timestamp_from_date_without_git(date)?;
+            return match self.commit_id_before(timestamp, commit_id)? {
+                Some(commit_id) => Ok(commit_id),
+                None => Err(anyhow!("No commits before {date}").into()),
+            };
+        }
+        let output = self
+            .git_command()
+            .current_dir(self.workdir_path())
+            .args(["rev-list", "-1"])
+            .arg(format!("--before={date}"))
+            .arg(commit_id.to_string())
+            .output()?;
+        if !output.status.success() {
+            return Err(GitError::command_failed("rev-list", &output.stderr));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let Some(line) = stdout.lines().next() else {
+            return Err(anyhow!("No commits before {date}").into());
+        };
+        Ok(git2::Oid::from_str(line)?)
+    }
+
+    /// The latest commit whose committer date is at or before the `timestamp`,
+    /// reachable from the `commit_id`, same as `git rev-list -1 --before`.
+    fn commit_id_before(
+        &self,
+        timestamp: i64,
+        commit_id: git2::Oid,
+    ) -> anyhow::Result<Option<git2::Oid>> {
+        let mut walk = self.repository.revwalk()?;
+        walk.set_sorting(git2::Sort::TIME)?;
+        walk.push(commit_id)?;
+        for commit_id in walk {
+            let commit_id = commit_id?;
+            if self.repository.find_commit(commit_id)?.time().seconds() <= timestamp {
+                return Ok(Some(commit_id));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Convert the `date` to the seconds since the epoch.
+    /// The `date` can be in any formats `git` accepts, same as `commit_id_at()`.
+    ///
+    /// If the `git` command is not available, only these formats are
+    /// supported; "2025-01-31", "2025-01-31 12:34", "2025-01-31 12:34:56",
+    /// optionally followed by the time zone such as "+0900", and
+    /// "2 weeks ago". Dates without time zones are in the local time zone.
+    pub fn timestamp_from_date(&self, date: &str) -> Result<i64, GitError> {
+        if Self::check_git_command().is_err() {
+            return Ok(Self::timestamp_from_date_without_git(date)?);
+        }
+        let output = self
+            .git_command()
+            .current_dir(self.workdir_path())
+            .arg("rev-parse")
+            .arg(format!("--since={date}"))
+            .output()?;
+        if !output.status.success() {
+            return Err(GitError::command_failed("rev-parse", &output.stderr));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let Some(timestamp) = stdout.trim().strip_prefix("--max-age=") else {
+            return Err(anyhow!("Unexpected output from git rev-parse: {stdout}").into());
+        };
+        Ok(timestamp.parse().map_err(anyhow::Error::from)?)
+    }
+
+    /// The `timestamp_from_date()` without the `git` command.
+    pub(crate) fn timestamp_from_date_without_git(date: &str) -> anyhow::Result<i64> {
+        let date = date.trim();
+        if let Some(ago) = date.strip_suffix(" ago") {
+            if let Some((count, unit)) = ago.split_once(' ')
+                && let Result::Ok(count) = count.parse::<i64>()
+            {
+                let seconds = match unit.strip_suffix('s').unwrap_or(unit) {
+                    "second" => 1,
+                    "minute" => 60,
+                    "hour" => 60 * 60,
+                    "day" => 24 * 60 * 60,
+                    "week" => 7 * 24 * 60 * 60,
+                    "month" => 30 * 24 * 60 * 60,
+                    "year" => 365 * 24 * 60 * 60,
+                    _ => 0,
+                };
+                if seconds > 0 {
+                    return Ok(chrono::Utc::now().timestamp() - count * seconds);
+                }
+            }
+        } else {
+            for format in ["%Y-%m-%d %H:%M:%S %z", "%Y-%m-%d %H:%M %z"] {
+                if let Result::Ok(datetime) = chrono::DateTime::parse_from_str(date, format) {
+                    return Ok(datetime.timestamp());
+                }
+            }
+            let datetime = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+                .into_iter()
+                .find_map(|format| chrono::NaiveDateTime::parse_from_str(date, format).ok())
+                .or_else(|| {
+                    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                        .ok()
+                        .and_then(|date| date.and_hms_opt(0, 0, 0))
+                });
+            if let Some(datetime) = datetime
+                && let Some(datetime) = datetime.and_local_timezone(chrono::Local).earliest()
+            {
+                return Ok(datetime.timestamp());
+            }
+        }
+        bail!(
+            "The date {date:?} needs the git command; \
+             without it, use \"YYYY-MM-DD [HH:MM[:SS]] [+ZZZZ]\" or \"N <units> ago\""
+        )
+    }
+
+    /// The parents of the commits changing the `path`, reachable from the
+    /// `commit_id`. The parents are rewritten to the commits changing the
+    /// `path`, as `git log --parents` does.
+    pub fn file_parents(
+        &self,
+        commit_id: git2::Oid,
+        path: &Path,
+    ) -> Result<HashMap<git2::Oid, Vec<git2::Oid>>, GitError> {
+        debug!("file_parents: {commit_id} {path:?}");
+        if Self::check_git_command().is_err() {
+            return Ok(self.file_parents_by_git2(commit_id, path)?);
+        }
+        let output = self
+            .git_command()
+            .current_dir(self.workdir_path())
+            .args(["log", "--parents", "--format=%H %P"])
+            .arg(commit_id.to_string())
+            .arg("--")
+            .arg(path)
+            .output()?;
+        if !output.status.success() {
+            return Err(GitError::command_failed("log", &output.stderr));
+        }
+        let mut parents = HashMap::new();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let mut commit_ids = line.split_whitespace().map(git2::Oid::from_str);
+            let Some(commit_id) = commit_ids.next() else {
+                continue;
+            };
+            parents.insert(commit_id?, commit_ids.collect::<Result<Vec<_>, _>>()?);
+        }
+        Ok(parents)
+    }
+
+    /// The `file_parents()` by the `git2` crate.
+    ///
+    /// Merges are simplified as `git log -- <path>` does; i.e., if the file is
+    /// the same as in one of the parents, only the parent is followed, and the
+    /// commit is skipped.
+    fn file_parents_by_git2(
+        &self,
+        commit_id: git2::Oid,
+        path: &Path,
+    ) -> anyhow::Result<HashMap<git2::Oid, Vec<git2::Oid>>> {
+        let entry = |commit: &git2::Commit| -> anyhow::Result<Option<git2::Oid>> {
+            Ok(commit.tree()?.get_path(path).ok().map(|entry| entry.id()))
+        };
+        let mut parents = HashMap::new();
+        // The commits not changing the `path`, and the parents they're the
+        // same as, or `None` if the `path` doesn't exist.
+        let mut same_as: HashMap<git2::Oid, Option<git2::Oid>> = HashMap::new();
+        let mut stack = vec![commit_id];
+        while let Some(commit_id) = stack.pop() {
+            if parents.contains_key(&commit_id) || same_as.contains_key(&commit_id) {
+                continue;
+            }
+            let commit = self.repository.find_commit(commit_id)?;
+            let entry_id = entry(&commit)?;
+            let mut parent_ids = vec![];
+            let mut same_parent_id = None;
+            for parent in commit.parents() {
+                if entry(&parent)? == entry_id {
+                    same_parent_id = Some(parent.id());
+                    break;
+                }
+                parent_ids.push(parent.id());
+            }
+            if let Some(parent_id) = same_parent_id {
+                same_as.insert(commit_id, Some(parent_id));
+                stack.push(parent_id);
+            } else if entry_id.is_none() && parent_ids.is_empty() {
+                same_as.insert(commit_id, None);
+            } else {
+                stack.extend(&parent_ids);
+                parents.insert(commit_id, parent_ids);
+            }
+        }
+        // Rewrite the parents to the commits changing the `path`.
+        let rewrite = |mut commit_id: git2::Oid| -> Option<git2::Oid> {
+            while let Some(parent_id) = same_as.get(&commit_id) {
+                commit_id = (*parent_id)?;
+            }
+            Some(commit_id)
+        };
+        for parent_ids in parents.values_mut() {
+            let mut rewritten: Vec<git2::Oid> = vec![];
+            for parent_id in parent_ids
+                .iter()
+                .filter_map(|parent_id| rewrite(*parent_id))
+            {
+                if !rewritten.contains(&parent_id) {
+                    rewritten.push(parent_id);
+                }
+            }
+            *parent_ids = rewritten;
+        }
+        Ok(parents)
+    }
+
+    /// The names of the branches and the tags, and `HEAD`, keyed by the
+    /// commit IDs they point to. Tags are prefixed by `tag: ` as
+    /// `git log --decorate` does.
+    pub fn references(&self) -> Result<HashMap<git2::Oid, Vec<String>>, GitError> {
+        // `HEAD` may not have commits yet.
+        let head_commit_id = self.head_commit_id().ok();
+        let mut references: HashMap<git2::Oid, Vec<String>> = head_commit_id
+            .map(|commit_id| (commit_id, vec!["HEAD".to_string()]))
+            .into_iter()
+            .collect();
+        for reference in self.repository.references()? {
+            let reference = reference?;
+            if reference.kind() != Some(git2::ReferenceType::Direct) {
+                continue;
+            }
+            let Some(commit) = reference.peel_to_commit().ok() else {
+                continue;
+            };
+            let Some(name) = reference.shorthand().ok() else {
+                continue;
+            };
+            let name = if reference.is_tag() {
+                format!("tag: {name}")
+            } else if reference.is_branch() || reference.is_remote() {
+                name.to_string()
+            } else {
+                continue;
+            };
+            references.entry(commit.id()).or_default().push(name);
+        }
+        Ok(references)
+    }
+
     /// Get the content of a `path` at the tree of the `commit_id` as a string.
     /// If `commit_id` is zero, the `head` is used.
-    pub fn content_as_string(&self, commit_id: git2::Oid, path: &Path) -> anyhow::Result<String> {
+    pub fn content_as_string(&self, commit_id: git2::Oid, path: &Path) -> Result<String, GitError> {
         debug!("content_as_string: {commit_id} {path:?}");
         let commit = if commit_id.is_zero() {
             self.repository.head()?.peel_to_commit()?
@@ -125,34 +696,289 @@ impl GitTools {
         let object = entry.to_object(&self.repository)?;
         // https://github.com/rust-lang/git2-rs/issues/1156
         let blob = object.into_blob().unwrap();
-        Ok(std::str::from_utf8(blob.content())?.to_string())
+        if blob.is_binary() {
+            return Err(GitError::BinaryFile(path.to_path_buf()));
+        }
+        Ok(std::str::from_utf8(blob.content())
+            .map_err(anyhow::Error::from)?
+            .to_string())
+    }
+
+    /// Get the content of a `path` not committed yet as a string; i.e., the
+    /// content in the index, or in the working directory if it's not in the
+    /// index.
+    pub fn uncommitted_content_as_string(&self, path: &Path) -> Result<String, GitError> {
+        debug!("uncommitted_content_as_string: {path:?}");
+        let index = self.repository.index()?;
+        let content = match index.get_path(path, 0) {
+            Some(entry) => self.repository.find_blob(entry.id)?.content().to_vec(),
+            None => std::fs::read(self.workdir_path.join(path))
+                .map_err(|_| GitError::PathNotFound(path.to_path_buf()))?,
+        };
+        // The same heuristic as `git`; a NUL byte in the first 8000 bytes.
+        if content.iter().take(8000).any(|byte| *byte == 0) {
+            return Err(GitError::BinaryFile(path.to_path_buf()));
+        }
+        Ok(String::from_utf8(content).map_err(anyhow::Error::from)?)
     }
 
-    pub fn show(&self, commit_id: git2::Oid, paths: &[&Path]) -> anyhow::Result<()> {
-        debug!("git-show: {commit_id} {paths:?}");
+    /// The blob IDs of the `path` in the index and in the working directory,
+    /// to detect changes not committed yet. Either is `None` if the `path`
+    /// is not there.
+    pub fn uncommitted_blob_ids(
+        &self,
+        path: &Path,
+    ) -> Result<(Option<git2::Oid>, Option<git2::Oid>), GitError> {
+        let mut index = self.repository.index()?;
+        // The index is cached; re-read it if it has changed on the disk.
+        index.read(false)?;
+        let index_blob_id = index.get_path(path, 0).map(|entry| entry.id);
+        let workdir_blob_id =
+            git2::Oid::hash_file(git2::ObjectType::Blob, self.workdir_path.join(path)).ok();
+        Ok((index_blob_id, workdir_blob_id))
+    }
+
+    /// Run `git blame --incremental` for the `path` at the tree of the `commit_id`,
+    /// only for the `line_numbers` if specified, with the `args` such as
+    /// `--ignore-rev`.
+    /// Returns a list of the commit ID, the line number in the commit,
+    /// and the line number in the `commit_id`, for each line,
+    /// sorted by the line numbers in the `commit_id`.
+    pub fn blame(
+        &self,
+        commit_id: git2::Oid,
+        path: &Path,
+        line_numbers: Option<Range<usize>>,
+        args: &[String],
+    ) -> Result<Vec<(git2::Oid, usize, usize)>, GitError> {
+        debug!("git-blame: {commit_id} {path:?} {line_numbers:?} {args:?}");
+        let mut command = self.git_command();
+        command
+            .current_dir(self.workdir_path())
+            .args(["blame", "--incremental"])
+            .args(args);
+        if let Some(line_numbers) = &line_numbers {
+            if line_numbers.is_empty() {
+                return Ok(vec![]);
+            }
+            command.arg(format!("-L{},{}", line_numbers.start, line_numbers.end - 1));
+        }
+        let output = command
+            .arg(commit_id.to_string())
+            .arg("--")
+            .arg(path)
+            .output()?;
+        if !output.status.success() {
+            return Err(GitError::command_failed("blame", &output.stderr));
+        }
+        Ok(Self::parse_blame_output(&String::from_utf8_lossy(
+            &output.stdout,
+        ))?)
+    }
+
+    /// Parse the output of `git blame --incremental`.
+    fn parse_blame_output(output: &str) -> anyhow::Result<Vec<(git2::Oid, usize, usize)>> {
+        // https://git-scm.com/docs/git-blame#_the_porcelain_format
+        let re_header = regex::Regex::new(r"^([0-9a-f]{40}) (\d+) (\d+) (\d+)$")?;
+        let mut results = vec![];
+        for line in output.lines() {
+            if let Some(captures) = re_header.captures(line) {
+                let commit_id = git2::Oid::from_str(&captures[1])?;
+                let original_line_number = captures[2].parse::<usize>()?;
+                let line_number = captures[3].parse::<usize>()?;
+                let lines_len = captures[4].parse::<usize>()?;
+                for i in 0..lines_len {
+                    results.push((commit_id, original_line_number + i, line_number + i));
+                }
+            }
+        }
+        results.sort_by_key(|(_, _, line_number)| *line_number);
+        Ok(results)
+    }
+
+    /// The same as `blame()`, but by `git2::Blame`, and only for the
+    /// `line_numbers` if specified.
+    pub fn blame_by_git2(
+        &self,
+        commit_id: git2::Oid,
+        path: &Path,
+        line_numbers: Option<Range<usize>>,
+    ) -> Result<Vec<(git2::Oid, usize, usize)>, GitError> {
+        debug!("blame_by_git2: {commit_id} {path:?} {line_numbers:?}");
+        let mut options = git2::BlameOptions::new();
+        options.newest_commit(commit_id);
+        if let Some(line_numbers) = &line_numbers {
+            if line_numbers.is_empty() {
+                return Ok(vec![]);
+            }
+            options
+                .min_line(line_numbers.start)
+                .max_line(line_numbers.end - 1);
+        }
+        let blame = self.repository.blame_file(path, Some(&mut options))?;
+        let mut results = vec![];
+        for hunk in blame.iter() {
+            let commit_id = hunk.final_commit_id();
+            let original_line_number = hunk.orig_start_line();
+            let line_number = hunk.final_start_line();
+            for i in 0..hunk.lines_in_hunk() {
+                results.push((commit_id, original_line_number + i, line_number + i));
+            }
+        }
+        results.sort_by_key(|(_, _, line_number)| *line_number);
+        Ok(results)
+    }
+
+    /// Run `git show` to the standard output.
+    /// If the `pager` command line is given, such as `delta` or `less -R`,
+    /// the colored output is piped to it instead of the pager of `git`.
+    /// If the `pager` isn't found, this falls back to `git show`.
+    pub fn show(
+        &self,
+        commit_id: git2::Oid,
+        paths: &[&Path],
+        pager: Option<&str>,
+    ) -> Result<(), GitError> {
+        debug!("git-show: {commit_id} {paths:?} pager={pager:?}");
+        Self::check_git_command()?;
+        if let Some(pager) = pager
+            && let Some(mut pager_child) = Self::spawn_pager(pager)?
+        {
+            // The `Command` owns the write end of the pipe, so drop it before
+            // waiting for the pager, or the pager never sees the EOF.
+            let result = {
+                let pager_stdin = pager_child.stdin.take().unwrap();
+                let mut command = self.create_show_with_args(commit_id, &["--color=always"], paths);
+                command.stdout(pager_stdin).spawn()
+            };
+            let mut child = match result {
+                Result::Ok(child) => child,
+                Err(error) => {
+                    let _ = pager_child.kill();
+                    let _ = pager_child.wait();
+                    return Err(error.into());
+                }
+            };
+            child.wait()?;
+            pager_child.wait()?;
+            return Ok(());
+        }
         let mut command = self.create_show(commit_id, paths);
         let mut child = command.spawn()?;
         child.wait()?;
         Ok(())
     }
 
+    /// Open the diff between two commits in the external diff tool by
+    /// `git difftool`, and wait for it to exit.
+    /// If the `old_commit_id` is `None`, the diff is from the empty tree.
+    /// The `tool` is the name for `--tool`, or the `diff.tool` config if `None`.
+    pub fn difftool(
+        &self,
+        old_commit_id: Option<git2::Oid>,
+        new_commit_id: git2::Oid,
+        paths: &[&Path],
+        tool: Option<&str>,
+    ) -> Result<(), GitError> {
+        debug!("git-difftool: {old_commit_id:?} {new_commit_id} {paths:?} tool={tool:?}");
+        Self::check_git_command()?;
+        let old_commit_id = match old_commit_id {
+            Some(commit_id) => commit_id,
+            None => git2::Oid::hash_object(git2::ObjectType::Tree, &[])?,
+        };
+        let mut command = self.git_command();
+        // `git difftool` needs the work tree, unlike other commands.
+        command
+            .current_dir(&self.workdir_path)
+            .arg("difftool")
+            .arg("--no-prompt");
+        if let Some(tool) = tool {
+            command.arg(format!("--tool={tool}"));
+        }
+        command
+            .arg(old_commit_id.to_string())
+            .arg(new_commit_id.to_string());
+        Self::add_paths(&mut command, paths);
+        let status = command.status()?;
+        if !status.success() {
+            return Err(anyhow!("git difftool failed: {status}").into());
+        }
+        Ok(())
+    }
+
+    /// Spawn the `pager` command line that reads the standard input.
+    /// Returns `None` if the command isn't found.
+    fn spawn_pager(pager: &str) -> anyhow::Result<Option<std::process::Child>> {
+        let mut args = pager.split_whitespace();
+        let Some(program) = args.next() else {
+            return Ok(None);
+        };
+        let result = std::process::Command::new(program)
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .spawn();
+        if let Err(error) = &result
+            && error.kind() == std::io::ErrorKind::NotFound
+        {
+            warn!("The pager \"{pager}\" not found, fall back to git: {error}");
+            return Ok(None);
+        }
+        let child = result.with_context(|| format!("Failed to run \"{pager}\""))?;
+        Ok(Some(child))
+    }
+
     pub fn create_show_all(&self, commit_id: git2::Oid) -> std::process::Command {
         self.create_show(commit_id, &[])
     }
 
     pub fn create_show(&self, commit_id: git2::Oid, paths: &[&Path]) -> std::process::Command {
-        let mut command = std::process::Command::new("git");
+        self.create_show_with_args(commit_id, &[], paths)
+    }
+
+    /// Create a `git show` command with the `args` before the `paths`.
+    pub fn create_show_with_args(
+        &self,
+        commit_id: git2::Oid,
+        args: &[&str],
+        paths: &[&Path],
+    ) -> std::process::Command {
+        let mut command = self.git_command();
         command
             .current_dir(self.repository_path())
             .arg("show")
+            .args(args)
             .arg(commit_id.to_string());
+        Self::add_paths(&mut command, paths);
+        command
+    }
+
+    /// Create a `git diff` command between two commits, with the `args`
+    /// before the `paths`.
+    pub fn create_diff(
+        &self,
+        old_commit_id: git2::Oid,
+        new_commit_id: git2::Oid,
+        args: &[&str],
+        paths: &[&Path],
+    ) -> std::process::Command {
+        let mut command = self.git_command();
+        command
+            .current_dir(self.repository_path())
+            .arg("diff")
+            .args(args)
+            .arg(old_commit_id.to_string())
+            .arg(new_commit_id.to_string());
+        Self::add_paths(&mut command, paths);
+        command
+    }
+
+    fn add_paths(command: &mut std::process::Command, paths: &[&Path]) {
         if !paths.is_empty() {
             command.arg("--");
             for path in paths {
                 command.arg(path);
             }
         }
-        command
     }
 }
 
@@ -254,6 +1080,59 @@ pub(crate) mod tests {
             );
             Ok(commit_id)
         }
+
+        /// Write the commit-graph of the reachable commits by
+        /// `git commit-graph write` with the `args`.
+        pub fn write_commit_graph(&self, args: &[&str]) -> anyhow::Result<()> {
+            let status = std::process::Command::new("git")
+                .current_dir(self.worktree_path())
+                .args(["commit-graph", "write", "--reachable"])
+                .args(args)
+                .status()?;
+            assert!(status.success());
+            Ok(())
+        }
+
+        /// Commit the index with the `HEAD` and `other_parent_commit_ids` as
+        /// the parents, and update the `HEAD`.
+        pub fn commit_merge(
+            &self,
+            parent_commit_ids: &[git2::Oid],
+            message: &str,
+        ) -> anyhow::Result<git2::Oid> {
+            self.commit_with_parents(Some("HEAD"), parent_commit_ids, message)
+        }
+
+        /// Commit the index without updating the `HEAD`.
+        pub fn commit_detached(
+            &self,
+            parent_commit_ids: &[git2::Oid],
+            message: &str,
+        ) -> anyhow::Result<git2::Oid> {
+            self.commit_with_parents(None, parent_commit_ids, message)
+        }
+
+        fn commit_with_parents(
+            &self,
+            update_ref: Option<&str>,
+            parent_commit_ids: &[git2::Oid],
+            message: &str,
+        ) -> anyhow::Result<git2::Oid> {
+            let mut index = self.repository().index()?;
+            let signature = self.git.repository.signature()?;
+            let tree_id = index.write_tree()?;
+            let tree = self.git.repository.find_tree(tree_id)?;
+            let parents = parent_commit_ids
+                .iter()
+                .map(|commit_id| self.repository().find_commit(*commit_id))
+                .collect::<Result<Vec<_>, _>>()?;
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            let commit_id = self
+                .git
+                .repository
+                .commit(update_ref, &signature, &signature, message, &tree, &parents)?;
+            Ok(commit_id)
+        }
     }
 
     #[test]
@@ -269,4 +1148,393 @@ pub(crate) mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn content_as_string_binary() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = PathBuf::from("test.bin");
+        git.add_file_content(&path, "\0\x01")?;
+        git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        let error = git
+            .git
+            .content_as_string(git2::Oid::ZERO_SHA1, &path)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            GitError::BinaryFile(error_path) if error_path == path
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn not_in_repository() -> anyhow::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let result = GitTools::from_file_path(dir.path());
+        // The temporary directory may be in a repository on some systems.
+        if let Err(error) = result {
+            assert!(matches!(error, GitError::NotInRepository(_)));
+        }
+
+        let git = TempRepository::new()?;
+        let error = git.git.path_in_workdir(dir.path()).unwrap_err();
+        assert!(matches!(error, GitError::NotInRepository(_)));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn path_in_workdir_symlink() -> anyhow::Result<()> {
+        use std::os::unix::fs::symlink;
+        let git = TempRepository::new()?;
+        let other_dir = tempfile::TempDir::new()?;
+        let other_file_path = other_dir.path().join("other.txt");
+        std::fs::write(&other_file_path, "other\n")?;
+        git.add_file_content(Path::new("sub/a.txt"), "a\n")?;
+        git.add_file_content(Path::new("target.txt"), "target\n")?;
+        symlink("target.txt", git.to_file_path(Path::new("link.txt")))?;
+        symlink(&other_file_path, git.to_file_path(Path::new("outside.txt")))?;
+        symlink("sub", git.to_file_path(Path::new("alias")))?;
+        let mut index = git.repository().index()?;
+        index.add_path(Path::new("link.txt"))?;
+        index.add_path(Path::new("outside.txt"))?;
+        index.write()?;
+
+        let path_in_workdir =
+            |path: &str| git.git.path_in_workdir(&git.to_file_path(Path::new(path)));
+        // The target is preferred if it's tracked.
+        assert_eq!(path_in_workdir("link.txt")?, Path::new("target.txt"));
+        // The link if the target is not in the repository.
+        assert_eq!(path_in_workdir("outside.txt")?, Path::new("outside.txt"));
+        // Links in the directories are resolved.
+        assert_eq!(path_in_workdir("alias/a.txt")?, Path::new("sub/a.txt"));
+
+        // The working directory reached through a link.
+        let link_dir_path = other_dir.path().join("workdir");
+        symlink(git.worktree_path(), &link_dir_path)?;
+        assert_eq!(
+            git.git.path_in_workdir(&link_dir_path.join("sub/a.txt"))?,
+            Path::new("sub/a.txt")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn path_in_workdir_ignore_case() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        git.add_file_content(Path::new("Sub/README.md"), "readme\n")?;
+        // Add to the index without the files, because the files can't coexist
+        // on case-insensitive file systems.
+        let mut index = git.repository().index()?;
+        for path in ["A.txt", "a.txt"] {
+            let entry = git2::IndexEntry {
+                ctime: git2::IndexTime::new(0, 0),
+                mtime: git2::IndexTime::new(0, 0),
+                dev: 0,
+                ino: 0,
+                mode: 0o100644,
+                uid: 0,
+                gid: 0,
+                file_size: 0,
+                id: git2::Oid::ZERO_SHA1,
+                flags: 0,
+                flags_extended: 0,
+                path: path.as_bytes().to_vec(),
+            };
+            index.add_frombuffer(&entry, path.as_bytes())?;
+        }
+        index.write()?;
+        let path_in_workdir =
+            |path: &str| git.git.path_in_workdir(&git.to_file_path(Path::new(path)));
+        assert_eq!(path_in_workdir("a.txt")?, Path::new("a.txt"));
+
+        git.repository()
+            .config()?
+            .set_bool("core.ignorecase", true)?;
+        assert_eq!(
+            path_in_workdir("Sub/readme.md")?,
+            Path::new("Sub/README.md")
+        );
+        assert_eq!(path_in_workdir("A.txt")?, Path::new("A.txt"));
+        let error = path_in_workdir("A.TXT").unwrap_err();
+        let GitError::AmbiguousPath { candidates, .. } = &error else {
+            panic!("{error}");
+        };
+        assert_eq!(
+            candidates,
+            &[PathBuf::from("A.txt"), PathBuf::from("a.txt")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn open_with_env() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let sub_dir = git.worktree_path().join("sub");
+        std::fs::create_dir(&sub_dir)?;
+        let other_dir = tempfile::TempDir::new()?;
+        let other_dir_path = other_dir.path().canonicalize()?;
+        let open = |path: &Path, vars: &[(&str, &Path)]| {
+            GitTools::open_with_env(path, |name| {
+                vars.iter()
+                    .find(|(var_name, _)| *var_name == name)
+                    .map(|(_, value)| value.as_os_str().to_os_string())
+            })
+        };
+
+        let repository = open(&sub_dir, &[])?;
+        assert_eq!(repository.path(), git.git.repository_path());
+
+        let repository = open(
+            &other_dir_path,
+            &[
+                ("GIT_DIR", git.git.repository_path()),
+                ("GIT_WORK_TREE", &other_dir_path),
+            ],
+        )?;
+        assert_eq!(repository.path(), git.git.repository_path());
+        assert_eq!(
+            repository.workdir().map(Path::canonicalize).transpose()?,
+            Some(other_dir_path.clone())
+        );
+
+        let Err(error) = open(
+            &sub_dir,
+            &[("GIT_CEILING_DIRECTORIES", git.worktree_path())],
+        ) else {
+            panic!("GIT_CEILING_DIRECTORIES should stop the search");
+        };
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(GitError::NotInRepository(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_git_version() {
+        assert_eq!(
+            GitTools::parse_git_version("git version 2.39.5\n"),
+            Some((2, 39))
+        );
+        assert_eq!(
+            GitTools::parse_git_version("git version 2.39.5 (Apple Git-154)"),
+            Some((2, 39))
+        );
+        assert_eq!(
+            GitTools::parse_git_version("git version 2.47.1.windows.2"),
+            Some((2, 47))
+        );
+        assert_eq!(GitTools::parse_git_version("hub version 2.14.2"), None);
+        assert!(GitTools::check_git_command().is_ok());
+    }
+
+    #[test]
+    fn uncommitted_content_as_string() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        assert!(git.git.is_head_unborn());
+        let path = PathBuf::from("test.txt");
+        git.add_file_content(&path, "1\n")?;
+        std::fs::write(git.to_file_path(&path), "1\n2\n")?;
+        assert_eq!(git.git.uncommitted_content_as_string(&path)?, "1\n");
+
+        let commit_id = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        assert!(!git.git.is_head_unborn());
+        assert!(git.git.has_path(commit_id, &path)?);
+        let untracked_path = PathBuf::from("untracked.txt");
+        assert!(!git.git.has_path(commit_id, &untracked_path)?);
+        std::fs::write(git.to_file_path(&untracked_path), "a\n")?;
+        assert_eq!(
+            git.git.uncommitted_content_as_string(&untracked_path)?,
+            "a\n"
+        );
+        let error = git
+            .git
+            .uncommitted_content_as_string(Path::new("none.txt"))
+            .unwrap_err();
+        assert!(matches!(error, GitError::PathNotFound(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn commit_id_at() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = PathBuf::from("test.txt");
+        git.add_file_content(&path, "1\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(&path, "1\n2\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add line")?;
+        assert_eq!(git.git.commit_id_at("2099-01-01", commit_id2)?, commit_id2);
+        assert_eq!(git.git.commit_id_at("2099-01-01", commit_id1)?, commit_id1);
+        assert!(git.git.commit_id_at("2000-01-01", commit_id2).is_err());
+
+        // Without the `git` command.
+        let now = chrono::Utc::now().timestamp();
+        assert_eq!(git.git.commit_id_before(now, commit_id2)?, Some(commit_id2));
+        assert_eq!(git.git.commit_id_before(0, commit_id2)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn short_id() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n")?;
+        let commit_id = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        let short_id = git.git.short_id(commit_id)?;
+        assert!(short_id.len() >= 7);
+        assert!(commit_id.to_string().starts_with(&short_id));
+        Ok(())
+    }
+
+    #[test]
+    fn file_parents() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(Path::new("other.txt"), "1\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add other file")?;
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id3 = git.commit(commit_id2, "Add line")?;
+        let parents = git.git.file_parents(commit_id3, path)?;
+        assert_eq!(
+            parents,
+            HashMap::from([(commit_id3, vec![commit_id1]), (commit_id1, vec![])])
+        );
+        assert_eq!(git.git.file_parents_by_git2(commit_id3, path)?, parents);
+
+        // A merge changing the file, and a merge not changing the file.
+        git.add_file_content(path, "0\n1\n2\n")?;
+        let commit_id4 = git.commit_detached(&[commit_id3], "Add line on a branch")?;
+        git.add_file_content(path, "0\n1\n2\n3\n")?;
+        let commit_id5 = git.commit_merge(&[commit_id3, commit_id4], "Merge and add line")?;
+        git.add_file_content(Path::new("other.txt"), "2\n")?;
+        let commit_id6 = git.commit_detached(&[commit_id5], "Change other file")?;
+        let commit_id7 = git.commit_merge(&[commit_id5, commit_id6], "Merge")?;
+        let parents = git.git.file_parents(commit_id7, path)?;
+        assert_eq!(parents[&commit_id5], [commit_id3, commit_id4]);
+        assert_eq!(parents[&commit_id4], [commit_id3]);
+        assert!(!parents.contains_key(&commit_id7));
+        assert_eq!(git.git.file_parents_by_git2(commit_id7, path)?, parents);
+        Ok(())
+    }
+
+    #[test]
+    fn references() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        let object = git.repository().find_object(commit_id1, None)?;
+        git.repository().tag_lightweight("v1", &object, false)?;
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add line")?;
+        let references = git.git.references()?;
+        assert_eq!(references[&commit_id1], ["tag: v1"]);
+        assert_eq!(references[&commit_id2][0], "HEAD");
+        assert_eq!(references[&commit_id2].len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn timestamp_from_date() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        assert_eq!(
+            git.git.timestamp_from_date("2020-01-01 00:00:00 +0000")?,
+            1577836800
+        );
+
+        // Without the `git` command.
+        for date in ["2020-01-01 00:00:00 +0000", "2020-01-01 09:00 +0900"] {
+            assert_eq!(GitTools::timestamp_from_date_without_git(date)?, 1577836800);
+        }
+        let now = chrono::Utc::now().timestamp();
+        let two_weeks_ago = GitTools::timestamp_from_date_without_git("2 weeks ago")?;
+        assert!((now - 14 * 24 * 60 * 60 - two_weeks_ago).abs() <= 1);
+        assert!(GitTools::timestamp_from_date_without_git("2020-01-01").is_ok());
+        assert!(GitTools::timestamp_from_date_without_git("last friday").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn blame() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = PathBuf::from("test.txt");
+        git.add_file_content(&path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(&path, "0\n1\n2\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add line")?;
+        assert_eq!(
+            git.git.blame(commit_id2, &path, None, &[])?,
+            [(commit_id2, 1, 1), (commit_id1, 1, 2), (commit_id1, 2, 3)]
+        );
+        assert_eq!(
+            git.git.blame_by_git2(commit_id2, &path, None)?,
+            git.git.blame(commit_id2, &path, None, &[])?
+        );
+        assert_eq!(
+            git.git.blame_by_git2(commit_id2, &path, Some(2..3))?,
+            [(commit_id1, 1, 2)]
+        );
+        assert_eq!(
+            git.git.blame(commit_id2, &path, Some(2..3), &[])?,
+            [(commit_id1, 1, 2)]
+        );
+        assert_eq!(
+            git.git.blame_by_git2(commit_id1, &path, None)?,
+            [(commit_id1, 1, 1), (commit_id1, 2, 2)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn spawn_pager() -> anyhow::Result<()> {
+        assert!(GitTools::spawn_pager("")?.is_none());
+        assert!(GitTools::spawn_pager("git-iblame-no-such-pager -R")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn show_with_pager() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n")?;
+        let commit_id = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        let repository_path = git.git.repository_path().to_path_buf();
+        // The pager should see the EOF and exit; don't hang the tests if not.
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let result = GitTools::from_repository_path(&repository_path)
+                .and_then(|git| git.show(commit_id, &[Path::new("test.txt")], Some("cat")));
+            let _ = tx.send(result);
+        });
+        rx.recv_timeout(std::time::Duration::from_secs(30))??;
+        Ok(())
+    }
+
+    #[test]
+    fn difftool() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add line")?;
+        let mut config = git.git.repository().config()?;
+        config.set_str(
+            "difftool.test.cmd",
+            "test -e \"$LOCAL\" && test -f \"$REMOTE\"",
+        )?;
+        config.set_str("difftool.fail.cmd", "false")?;
+        config.set_bool("difftool.trustExitCode", true)?;
+        git.git
+            .difftool(Some(commit_id1), commit_id2, &[path], Some("test"))?;
+        git.git.difftool(None, commit_id1, &[path], Some("test"))?;
+        assert!(
+            git.git
+                .difftool(Some(commit_id1), commit_id2, &[path], Some("fail"))
+                .is_err()
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,106 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    path::Path,
+    sync::{Mutex, MutexGuard},
+};
+
+use anyhow::Context;
+
+/// The logger of the `log` crate.
+/// It writes logs by `env_logger`,
+/// and keeps recent warnings and errors to show them in the UI,
+/// because the standard error is hidden by the alternate screen.
+#[derive(Debug)]
+pub(crate) struct Logger {
+    env_logger: env_logger::Logger,
+}
+
+/// The recent warnings and errors, the oldest first.
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+impl Logger {
+    /// The maximum number of the recent messages to keep.
+    const MAX_RECENT: usize = 100;
+
+    /// Set the global logger.
+    /// If the `log_file` is given, logs are written to the file
+    /// instead of the standard error,
+    /// and the default level is `debug` instead of `error`.
+    /// The `RUST_LOG` environment variable can change the level.
+    pub fn init(log_file: Option<&Path>) -> anyhow::Result<()> {
+        let mut builder = match log_file {
+            Some(path) => {
+                let file = File::create(path)
+                    .with_context(|| format!("Failed to create {}", path.display()))?;
+                let env = env_logger::Env::default().default_filter_or("git_iblame=debug");
+                let mut builder = env_logger::Builder::from_env(env);
+                builder.target(env_logger::Target::Pipe(Box::new(file)));
+                builder
+            }
+            None => env_logger::Builder::from_default_env(),
+        };
+        let env_logger = builder.build();
+        // Warnings are always enabled, to keep them in the `RECENT`.
+        let max_level = env_logger.filter().max(log::LevelFilter::Warn);
+        log::set_boxed_logger(Box::new(Self { env_logger }))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+
+    /// The recent warnings and errors, the oldest first.
+    pub fn recent() -> Vec<String> {
+        Self::lock_recent().iter().cloned().collect()
+    }
+
+    fn push_recent(message: String) {
+        let mut recent = Self::lock_recent();
+        if recent.len() >= Self::MAX_RECENT {
+            recent.pop_front();
+        }
+        recent.push_back(message);
+    }
+
+    fn lock_recent() -> MutexGuard<'static, VecDeque<String>> {
+        // The messages are still usable even if another thread panicked.
+        RECENT.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn || self.env_logger.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if record.level() <= log::Level::Warn {
+            Self::push_recent(format!("{}: {}", record.level(), record.args()));
+        }
+        if self.env_logger.matches(record) {
+            self.env_logger.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.env_logger.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_recent() {
+        for i in 0..Logger::MAX_RECENT + 2 {
+            Logger::push_recent(format!("WARN: {i}"));
+        }
+        let recent = Logger::recent();
+        assert_eq!(recent.len(), Logger::MAX_RECENT);
+        assert_eq!(recent[0], "WARN: 2");
+        assert_eq!(
+            recent.last().map(String::as_str),
+            Some(format!("WARN: {}", Logger::MAX_RECENT + 1).as_str())
+        );
+    }
+}
@@ -1,14 +1,47 @@
+mod commit_graph;
+pub(crate) use commit_graph::*;
+
+#[cfg(feature = "tui")]
+mod config_dir;
+#[cfg(feature = "tui")]
+pub(crate) use config_dir::*;
+
+#[cfg(feature = "tui")]
+mod file_watcher;
+#[cfg(feature = "tui")]
+pub(crate) use file_watcher::*;
+
+#[cfg(feature = "tui")]
+mod forge;
+#[cfg(feature = "tui")]
+pub(crate) use forge::*;
+
+mod git_error;
+pub use git_error::*;
+
 mod git_tools;
-pub(crate) use git_tools::*;
+pub use git_tools::*;
 
 mod line_read_buffer;
 pub(crate) use line_read_buffer::*;
 
+#[cfg(feature = "tui")]
+mod logger;
+#[cfg(feature = "tui")]
+pub(crate) use logger::*;
+
+#[cfg(feature = "tui")]
 mod or_default;
+#[cfg(feature = "tui")]
 pub(crate) use or_default::*;
 
 mod range_ext;
 pub use range_ext::*;
 
+mod thread_nice;
+pub(crate) use thread_nice::*;
+
+#[cfg(feature = "tui")]
 mod terminal_raw_mode_scope;
+#[cfg(feature = "tui")]
 pub use terminal_raw_mode_scope::*;
@@ -0,0 +1,52 @@
+#[cfg(target_os = "linux")]
+unsafe extern "C" {
+    fn getpriority(which: std::ffi::c_int, who: u32) -> std::ffi::c_int;
+    fn setpriority(which: std::ffi::c_int, who: u32, prio: std::ffi::c_int) -> std::ffi::c_int;
+}
+
+/// Add the `increment` to the niceness of the current thread, so that it and
+/// the processes it starts don't slow other processes.
+///
+/// This is supported only on Linux, where the niceness is per thread.
+/// Elsewhere, this does nothing and returns an error.
+pub(crate) fn add_current_thread_nice(increment: i32) -> anyhow::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        const PRIO_PROCESS: std::ffi::c_int = 0;
+        // The `who` of 0 is the current thread, not the process, on Linux.
+        let nice = unsafe { getpriority(PRIO_PROCESS, 0) };
+        let nice = nice.saturating_add(increment).clamp(-20, 19);
+        if unsafe { setpriority(PRIO_PROCESS, 0, nice) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = increment;
+        anyhow::bail!("The niceness of threads isn't supported on this platform")
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn add_current_thread_nice() -> anyhow::Result<()> {
+        let nice = || unsafe { getpriority(0, 0) };
+        let before = nice();
+        let after = thread::spawn(move || -> anyhow::Result<_> {
+            super::add_current_thread_nice(1)?;
+            Ok(nice())
+        })
+        .join()
+        .unwrap()?;
+        assert_eq!(after, (before + 1).min(19));
+        // Other threads are not affected.
+        assert_eq!(nice(), before);
+        Ok(())
+    }
+}
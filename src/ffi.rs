@@ -0,0 +1,334 @@
+//! The C ABI of the [`blame`](crate::blame) module, for editor plugins and
+//! other languages to reuse the engine without spawning the `git-iblame`
+//! command.
+//!
+//! The handles are opaque pointers, and must be freed by the corresponding
+//! `_free` functions. Strings returned by the functions are UTF-8 and
+//! null-terminated, and must be freed by [`iblame_string_free()`].
+//!
+//! Functions that can fail return a null pointer or `-1`, and
+//! [`iblame_last_error()`] returns the message of the last error in the
+//! thread. Panics don't unwind into the caller; they're caught and reported
+//! as errors in the same way.
+//!
+//! # Examples
+//! ```c
+//! IblameHistory* history = iblame_history_new("path/to/file");
+//! if (iblame_history_read_all(history) == 0) {
+//!   IblameSnapshot* snapshot = iblame_history_snapshot(history, NULL);
+//!   if (snapshot) {
+//!     char* json = iblame_snapshot_to_json(snapshot);
+//!     puts(json);
+//!     iblame_string_free(json);
+//!     iblame_snapshot_free(snapshot);
+//!   }
+//! } else {
+//!   puts(iblame_last_error());
+//! }
+//! iblame_history_free(history);
+//! ```
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString, c_char, c_int},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    ptr,
+};
+
+use crate::blame::{BlameSnapshot, FileHistory};
+
+/// The opaque handle of a `FileHistory`.
+pub struct IblameHistory(FileHistory);
+
+/// The opaque handle of a `BlameSnapshot`.
+pub struct IblameSnapshot(BlameSnapshot);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(error: impl Into<anyhow::Error>) {
+    let error = error.into();
+    let message = CString::new(format!("{error:#}").replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+/// Call the `f`, and if it panics, set the last error and return the
+/// `on_panic`, so that the panic doesn't unwind into the caller.
+fn catch_panic<T>(on_panic: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("Unknown panic");
+        set_last_error(anyhow::anyhow!("Panicked: {message}"));
+        on_panic
+    })
+}
+
+/// Convert the `result` to the pointer, or to null with the last error set.
+fn into_ptr<T>(result: anyhow::Result<T>) -> *mut T {
+    match result {
+        Ok(value) => Box::into_raw(Box::new(value)),
+        Err(error) => {
+            set_last_error(error);
+            ptr::null_mut()
+        }
+    }
+}
+
+fn into_c_string(result: anyhow::Result<String>) -> *mut c_char {
+    match result.and_then(|string| Ok(CString::new(string)?)) {
+        Ok(string) => string.into_raw(),
+        Err(error) => {
+            set_last_error(error);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// The `string` must be null or a valid null-terminated string.
+unsafe fn to_str<'a>(string: *const c_char) -> anyhow::Result<Option<&'a str>> {
+    if string.is_null() {
+        return Ok(None);
+    }
+    Ok(Some(unsafe { CStr::from_ptr(string) }.to_str()?))
+}
+
+/// The message of the last error in the current thread, or null if none.
+/// The string is valid until the next error in the thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn iblame_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Free a string returned by the functions in this module.
+///
+/// # Safety
+/// The `string` must be null or a string returned by this module,
+/// and must not be used after this call.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iblame_string_free(string: *mut c_char) {
+    catch_panic((), || {
+        if !string.is_null() {
+            drop(unsafe { CString::from_raw(string) });
+        }
+    })
+}
+
+/// The history of the file at the `path`, with the default options.
+/// Returns null on errors.
+///
+/// # Safety
+/// The `path` must be a valid null-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iblame_history_new(path: *const c_char) -> *mut IblameHistory {
+    catch_panic(ptr::null_mut(), || {
+        into_ptr(unsafe { to_str(path) }.and_then(|path| {
+            let path = path.ok_or_else(|| anyhow::anyhow!("The path is null"))?;
+            Ok(IblameHistory(FileHistory::new(Path::new(path))))
+        }))
+    })
+}
+
+/// Read the whole history. See `FileHistory::read_all()`.
+/// Returns 0 on success, or -1 on errors.
+///
+/// # Safety
+/// The `history` must be a valid handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iblame_history_read_all(history: *mut IblameHistory) -> c_int {
+    catch_panic(-1, || {
+        let history = unsafe { &mut (*history).0 };
+        match history.read_all() {
+            Ok(()) => 0,
+            Err(error) => {
+                set_last_error(error);
+                -1
+            }
+        }
+    })
+}
+
+/// The number of commits read, or 0 on errors.
+///
+/// # Safety
+/// The `history` must be a valid handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iblame_history_commit_count(history: *const IblameHistory) -> usize {
+    catch_panic(0, || unsafe { &(*history).0 }.commits().len())
+}
+
+/// The snapshot at the `commit_id` in hex, or at the newest commit if null.
+/// See `FileHistory::snapshot()`. Returns null on errors.
+///
+/// # Safety
+/// The `history` must be a valid handle, and the `commit_id` must be null or
+/// a valid null-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iblame_history_snapshot(
+    history: *const IblameHistory,
+    commit_id: *const c_char,
+) -> *mut IblameSnapshot {
+    catch_panic(ptr::null_mut(), || {
+        let history = unsafe { &(*history).0 };
+        into_ptr(unsafe { to_str(commit_id) }.and_then(|commit_id| {
+            let commit_id = match commit_id {
+                Some(commit_id) => git2::Oid::from_str(commit_id)?,
+                None => git2::Oid::ZERO_SHA1,
+            };
+            Ok(IblameSnapshot(history.snapshot(commit_id)?))
+        }))
+    })
+}
+
+/// Free the `history`, and stop reading if it's reading.
+///
+/// # Safety
+/// The `history` must be null or a valid handle,
+/// and must not be used after this call.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iblame_history_free(history: *mut IblameHistory) {
+    catch_panic((), || {
+        if !history.is_null() {
+            drop(unsafe { Box::from_raw(history) });
+        }
+    })
+}
+
+/// The number of lines in the `snapshot`, or 0 on errors.
+///
+/// # Safety
+/// The `snapshot` must be a valid handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iblame_snapshot_line_count(snapshot: *const IblameSnapshot) -> usize {
+    catch_panic(0, || unsafe { &(*snapshot).0 }.lines().len())
+}
+
+/// The commit ID in hex that the line at the 0-based `index` is attributed
+/// to. Returns null if the line isn't attributed, or on errors.
+///
+/// # Safety
+/// The `snapshot` must be a valid handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iblame_snapshot_line_commit_id(
+    snapshot: *const IblameSnapshot,
+    index: usize,
+) -> *mut c_char {
+    catch_panic(ptr::null_mut(), || {
+        let snapshot = unsafe { &(*snapshot).0 };
+        let Some(line) = snapshot.lines().get(index) else {
+            set_last_error(anyhow::anyhow!(
+                "The line index {index} is out of range 0..{}",
+                snapshot.lines().len()
+            ));
+            return ptr::null_mut();
+        };
+        match line.commit_id() {
+            Some(commit_id) => into_c_string(Ok(commit_id.to_string())),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+/// The `snapshot` in JSON, the same as `git-iblame export --format json`.
+/// Returns null on errors.
+///
+/// # Safety
+/// The `snapshot` must be a valid handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iblame_snapshot_to_json(snapshot: *const IblameSnapshot) -> *mut c_char {
+    catch_panic(ptr::null_mut(), || {
+        let snapshot = unsafe { &(*snapshot).0 };
+        into_c_string(serde_json::to_string(snapshot).map_err(Into::into))
+    })
+}
+
+/// Free the `snapshot`.
+///
+/// # Safety
+/// The `snapshot` must be null or a valid handle,
+/// and must not be used after this call.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn iblame_snapshot_free(snapshot: *mut IblameSnapshot) {
+    catch_panic((), || {
+        if !snapshot.is_null() {
+            drop(unsafe { Box::from_raw(snapshot) });
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extensions::tests::TempRepository;
+
+    use super::*;
+
+    fn take_string(string: *mut c_char) -> Option<String> {
+        if string.is_null() {
+            return None;
+        }
+        let result = unsafe { CStr::from_ptr(string) }
+            .to_string_lossy()
+            .to_string();
+        unsafe { iblame_string_free(string) };
+        Some(result)
+    }
+
+    #[test]
+    fn snapshot() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n3\n")?;
+        let commit_id2 = git.commit(commit_id1, "Modify 2")?;
+
+        let file_path = CString::new(git.to_file_path(path).to_str().unwrap())?;
+        unsafe {
+            let history = iblame_history_new(file_path.as_ptr());
+            assert_eq!(iblame_history_read_all(history), 0);
+            assert_eq!(iblame_history_commit_count(history), 2);
+
+            let snapshot = iblame_history_snapshot(history, ptr::null());
+            assert!(!snapshot.is_null());
+            assert_eq!(iblame_snapshot_line_count(snapshot), 2);
+            assert_eq!(
+                take_string(iblame_snapshot_line_commit_id(snapshot, 1)),
+                Some(commit_id2.to_string())
+            );
+            assert!(iblame_snapshot_line_commit_id(snapshot, 2).is_null());
+            let json = take_string(iblame_snapshot_to_json(snapshot)).unwrap();
+            let json: serde_json::Value = serde_json::from_str(&json)?;
+            assert_eq!(json["commit_id"], commit_id2.to_string());
+            iblame_snapshot_free(snapshot);
+
+            let commit_id1 = CString::new(commit_id1.to_string())?;
+            let snapshot = iblame_history_snapshot(history, commit_id1.as_ptr());
+            assert_eq!(iblame_snapshot_line_count(snapshot), 2);
+            iblame_snapshot_free(snapshot);
+
+            let invalid = CString::new("invalid")?;
+            assert!(iblame_history_snapshot(history, invalid.as_ptr()).is_null());
+            assert!(!iblame_last_error().is_null());
+            iblame_history_free(history);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn catch_panic() {
+        assert_eq!(super::catch_panic(-1, || 0), 0);
+        assert_eq!(super::catch_panic(-1, || panic!("test")), -1);
+        let message = unsafe { CStr::from_ptr(iblame_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "Panicked: test");
+    }
+}
@@ -1,5 +1,69 @@
+//! Interactive enhanced `git blame`.
+//!
+//! This crate is mainly the `git-iblame` command, but its engine to compute
+//! the history of a file and to attribute lines is also available as a
+//! library. The public API consists of the following modules:
+//!
+//! * [`blame`]: The history of a file and the attribution of its lines.
+//!   Start with [`blame::FileHistoryBuilder`] to read a [`blame::FileHistory`],
+//!   then [`blame::FileHistory::snapshot()`] for the annotated lines at a
+//!   commit as a [`blame::BlameSnapshot`],
+//!   or [`blame::FileHistory::content()`] to navigate the lines.
+//!   Implement [`blame::HistoryProvider`] to read the history from other
+//!   sources than `git`, or [`blame::MetadataProvider`] to attach extra
+//!   information to the commits.
+//! * [`extensions`]: [`extensions::GitTools`] and other helpers for `git`.
+//! * `ffi`: The C ABI of the `blame` module, available with the `ffi` feature.
+//! * `ui`: The interactive user interface, available with the `tui` feature.
+//!   Only `ui::Cli` is public, for the `git-iblame` command.
+//!
+//! Other types in the modules are subject to change.
+//!
+//! # Errors
+//!
+//! The functions in [`blame`] return [`blame::BlameError`], and
+//! [`extensions::GitTools`] returns [`extensions::GitError`], so that the
+//! errors to distinguish, such as a binary file or a path not in a
+//! repository, can be matched. The errors from `GitTools` are
+//! [`blame::BlameError::Git`], and the errors from the `git2` crate are
+//! [`extensions::GitError::Git2`]. Other errors are `Other` for messages;
+//! their types and messages are subject to change.
+//! ```no_run
+//! use git_iblame::{blame::{BlameError, FileHistoryBuilder}, extensions::GitError};
+//!
+//! # use std::path::Path;
+//! let mut history = FileHistoryBuilder::new(Path::new("image.png")).build();
+//! match history.read_all() {
+//!   Ok(()) => {}
+//!   Err(BlameError::Git(GitError::NotInRepository(_))) => eprintln!("Not in a repository"),
+//!   Err(error) => eprintln!("{error}"),
+//! }
+//! ```
+//!
+//! # Examples
+//! ```no_run
+//! use git_iblame::blame::FileHistoryBuilder;
+//!
+//! # use std::path::Path;
+//! fn main() -> anyhow::Result<()> {
+//!   let mut history = FileHistoryBuilder::new(Path::new("src/lib.rs")).build();
+//!   history.read_all()?;
+//!   let snapshot = history.snapshot(git2::Oid::ZERO_SHA1)?;
+//!   for line in snapshot.lines() {
+//!     let summary = snapshot
+//!       .commit_of(line)
+//!       .and_then(|commit| commit.summary().cloned());
+//!     println!("{:?} {}", summary, line.content());
+//!   }
+//!   Ok(())
+//! }
+//! ```
+
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
-pub(crate) mod blame;
+pub mod blame;
 pub mod extensions;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "tui")]
 pub mod ui;
@@ -1,13 +1,24 @@
-use std::{cmp, io::Write, ops::Range, path::Path};
+use std::{
+    cmp,
+    collections::HashSet,
+    io::Write,
+    ops::{Range, RangeInclusive},
+    path::Path,
+    time::{Duration, Instant},
+};
 
-use anyhow::bail;
-use crossterm::{cursor, queue, terminal};
+use chrono::TimeDelta;
 use git2::Oid;
 use log::debug;
 
 use crate::{
     blame::*,
-    extensions::{GitTools, RangeExt},
+    extensions::{Forge, GitTools, Logger, RangeExt},
+};
+
+use super::{
+    AuthorFormat, CommitDetail, DiffOverlay, DisplayRow, DisplayRows, FoldTarget, JumpPosition,
+    LineConstraint, Minimap, RenderTarget, TextBuffer, Theme, TimeFormat,
 };
 
 pub struct BlameRenderer {
@@ -15,22 +26,76 @@ pub struct BlameRenderer {
     content: FileContent,
     view_size: (u16, u16),
     rendered_rows: u16,
-    rendered_current_line_index: usize,
-    rendered_view_start_line_index: usize,
-    view_start_line_index: usize,
+    rendered_current_row: usize,
+    rendered_view_start_row: usize,
+    /// The rows to repaint by the next `render()`, because their lines have
+    /// changed since they were rendered.
+    damaged_rows: Vec<usize>,
+    /// Whether the minimap needs to be repainted.
+    is_minimap_damaged: bool,
+    /// Whether the detail pane needs to be repainted.
+    is_detail_damaged: bool,
+    /// When `read_poll()` last updated the content.
+    last_read_poll_time: Option<Instant>,
+    view_start_row: usize,
+    time_format: TimeFormat,
+    author_format: AuthorFormat,
+    heat_range: Option<TimeDelta>,
+    columns: Vec<GutterColumn>,
+    filter: LineFilter,
+    is_detail_pane_visible: bool,
+    detail: CommitDetail,
+    /// Whether the pane shows recent warnings and errors
+    /// instead of the `detail`.
+    is_log_pane_visible: bool,
+    is_minimap_visible: bool,
+    display_rows: DisplayRows,
+    fold_target: Option<FoldTarget>,
+    expanded_line_numbers: HashSet<usize>,
+    /// The line index where the selection started, if selecting.
+    selection_anchor: Option<usize>,
+    /// The commit to highlight its lines, and its short ID.
+    pinned_commit: Option<(Oid, String)>,
+    /// The command line to page the output of `git show`, if configured.
+    pager: Option<String>,
+    /// The tool of `git difftool`, if configured.
+    difftool: Option<String>,
+    theme: Theme,
 }
 
 impl BlameRenderer {
     pub fn new(history: FileHistory) -> anyhow::Result<Self> {
-        let content = history.content(git2::Oid::ZERO_SHA1)?;
+        let mut content = history.content(git2::Oid::ZERO_SHA1)?;
+        content.take_line_damage();
         Ok(Self {
             history,
             content,
             view_size: (0, 0),
             rendered_rows: 0,
-            rendered_current_line_index: 0,
-            rendered_view_start_line_index: 0,
-            view_start_line_index: 0,
+            rendered_current_row: 0,
+            rendered_view_start_row: 0,
+            damaged_rows: vec![],
+            is_minimap_damaged: false,
+            is_detail_damaged: false,
+            last_read_poll_time: None,
+            view_start_row: 0,
+            time_format: TimeFormat::default(),
+            author_format: AuthorFormat::default(),
+            heat_range: None,
+            columns: GutterColumn::default_columns(),
+            filter: LineFilter::default(),
+            is_detail_pane_visible: false,
+            detail: CommitDetail::default(),
+            is_log_pane_visible: false,
+            is_minimap_visible: false,
+            display_rows: DisplayRows::default(),
+            fold_target: None,
+            expanded_line_numbers: HashSet::new(),
+            selection_anchor: None,
+            pinned_commit: None,
+            pager: None,
+            difftool: None,
+            theme: Theme::default(),
         })
     }
 
@@ -60,21 +125,156 @@ impl BlameRenderer {
         self.view_size.1
     }
 
+    /// The number of columns for the content,
+    /// excluding the minimap and the detail pane.
+    fn content_cols(&self) -> u16 {
+        self.view_cols() - self.minimap_cols() - self.detail_pane_cols()
+    }
+
+    fn minimap_cols(&self) -> u16 {
+        if self.is_minimap_visible { 1 } else { 0 }
+    }
+
+    fn detail_pane_cols(&self) -> u16 {
+        if self.is_detail_pane_visible || self.is_log_pane_visible {
+            self.view_cols() * 2 / 5
+        } else {
+            0
+        }
+    }
+
+    /// Toggle the pane to show the details of the current line commit.
+    pub fn toggle_detail_pane(&mut self) {
+        self.is_detail_pane_visible = !self.is_detail_pane_visible;
+        self.is_log_pane_visible = false;
+        self.invalidate_render();
+    }
+
+    /// Toggle the pane to show recent warnings and errors,
+    /// in place of the detail pane.
+    pub fn toggle_log_pane(&mut self) {
+        self.is_log_pane_visible = !self.is_log_pane_visible;
+        self.is_detail_pane_visible = false;
+        self.invalidate_render();
+    }
+
+    /// Toggle the minimap of the hunks and the current line commit.
+    pub fn toggle_minimap(&mut self) {
+        self.is_minimap_visible = !self.is_minimap_visible;
+        self.invalidate_render();
+    }
+
+    /// Pin the current line commit to highlight its lines,
+    /// or unpin if it's already pinned.
+    pub fn toggle_pin(&mut self) -> anyhow::Result<()> {
+        let commit_id = self.current_line_commit_id()?;
+        self.pinned_commit = match &self.pinned_commit {
+            Some((pinned, _)) if *pinned == commit_id => None,
+            _ => Some((commit_id, self.git().short_id(commit_id)?)),
+        };
+        self.invalidate_render();
+        Ok(())
+    }
+
+    /// The status of the pinned commit, and the number of its lines in the
+    /// current content.
+    pub fn pinned_status(&self) -> Option<String> {
+        let (commit_id, short_id) = self.pinned_commit.as_ref()?;
+        let lines = self
+            .content
+            .lines()
+            .iter()
+            .filter(|line| !line.is_deleted() && line.commit_id() == Some(*commit_id))
+            .count();
+        Some(match lines {
+            1 => format!("Pinned {short_id}: 1 line"),
+            _ => format!("Pinned {short_id}: {lines} lines"),
+        })
+    }
+
+    /// The status of the commit that failed to apply to the current content.
+    /// Its lines and older lines are attributed by `git blame` instead.
+    pub fn apply_failed_status(&self) -> Option<String> {
+        let commit_id = self.content.apply_failed_commit_id()?;
+        let short_id = self.git().short_id(commit_id).ok()?;
+        Some(format!("Failed to apply {short_id}, see the messages"))
+    }
+
+    /// The line endings of the file if they're not LF.
+    pub fn line_ending_status(&self) -> Option<String> {
+        if self.content.content_type() != ContentType::File {
+            return None;
+        }
+        match self.content.line_ending() {
+            LineEnding::Lf => None,
+            LineEnding::CrLf => Some("CRLF".to_string()),
+            LineEnding::Mixed => Some("Mixed CRLF and LF".to_string()),
+        }
+    }
+
+    fn minimap(&self) -> Minimap {
+        Minimap::new(self.content.lines_len(), self.view_rows() as usize)
+    }
+
+    /// Move to the first line of the previous or the next row of the minimap.
+    pub fn move_by_minimap_row(&mut self, reverse: bool) {
+        let minimap = self.minimap();
+        let row = minimap.row_from_line_index(self.current_line_index());
+        let row = if reverse {
+            row.saturating_sub(1)
+        } else {
+            cmp::min(row + 1, self.view_rows().saturating_sub(1) as usize)
+        };
+        let line_index = minimap.line_index_range(row).start;
+        if line_index < self.content.lines_len() {
+            self.set_current_line_index(line_index);
+        }
+    }
+
     pub fn set_view_size(&mut self, size: (u16, u16)) {
         self.view_size = size;
         self.scroll_current_line_into_view();
     }
 
-    fn view_end_line_index(&self) -> usize {
-        self.view_start_line_index + self.view_rows() as usize
+    fn view_end_row(&self) -> usize {
+        self.view_start_row + self.view_rows() as usize
+    }
+
+    fn view_row_range(&self) -> Range<usize> {
+        self.view_start_row..self.view_end_row()
+    }
+
+    fn adjust_row_range_into_view(&self, row_range: &Range<usize>) -> Range<usize> {
+        row_range.intersect(self.view_row_range())
+    }
+
+    /// The line indexes of the rows in the view, including folded lines.
+    fn view_line_index_range(&self) -> Range<usize> {
+        if self.content.lines_len() == 0 {
+            return 0..0;
+        }
+        let view_start = self.display_rows.line_index_from_row(self.view_start_row);
+        let view_end = if self.view_end_row() < self.rows_len() {
+            self.display_rows.line_index_from_row(self.view_end_row())
+        } else {
+            self.content.lines_len()
+        };
+        view_start..view_end
+    }
+
+    /// The number of rows, where each fold occupies a row.
+    fn rows_len(&self) -> usize {
+        self.display_rows.len(self.content.lines_len())
     }
 
-    fn view_line_indexes(&self) -> Range<usize> {
-        self.view_start_line_index..self.view_end_line_index()
+    fn current_row(&self) -> usize {
+        self.display_rows
+            .row_from_line_index(self.current_line_index())
     }
 
-    fn adjust_line_index_range_into_view(&self, line_index_range: &Range<usize>) -> Range<usize> {
-        line_index_range.intersect(self.view_line_indexes())
+    fn set_current_row(&mut self, row: usize) {
+        let row = cmp::min(row, self.rows_len().saturating_sub(1));
+        self.set_current_line_index(self.display_rows.line_index_from_row(row));
     }
 
     pub fn rendered_rows(&self) -> u16 {
@@ -99,6 +299,15 @@ impl BlameRenderer {
             .ok_or(anyhow::anyhow!("This line doesn't have a commit"))
     }
 
+    /// The path and the line number of the current line
+    /// at the commit that introduced the line.
+    pub fn current_line_original_position(&self) -> Option<(&Path, usize)> {
+        let line = self.current_line();
+        let line_number = line.original_line_number()?;
+        let path = line.original_path(self.history())?;
+        Some((path, line_number))
+    }
+
     fn set_current_line_index(&mut self, line_index: usize) {
         self.content.set_current_line_index(line_index);
         self.scroll_current_line_into_view();
@@ -119,11 +328,25 @@ impl BlameRenderer {
     }
 
     pub fn move_to_prev_line_by(&mut self, by: usize) {
-        self.set_current_line_index(self.current_line_index().saturating_sub(by));
+        self.set_current_row(self.current_row().saturating_sub(by));
     }
 
     pub fn move_to_next_line_by(&mut self, by: usize) {
-        self.set_current_line_index(self.current_line_index() + by);
+        self.set_current_row(self.current_row() + by);
+    }
+
+    /// Move to the next or the previous line of the current line commit.
+    pub fn move_to_same_commit_line(&mut self, reverse: bool) {
+        if let Some(line_index) = self.content.same_commit_line_index(reverse) {
+            self.set_current_line_index(line_index);
+        }
+    }
+
+    /// Move to the next or the previous hunk.
+    pub fn move_to_diff(&mut self, reverse: bool) {
+        if let Some(line_index) = self.content.diff_line_index(reverse) {
+            self.set_current_line_index(line_index);
+        }
     }
 
     pub fn move_to_prev_page(&mut self) {
@@ -136,8 +359,8 @@ impl BlameRenderer {
         self.move_to_next_line_by(page_size);
     }
 
-    pub fn search(&mut self, search: &str, reverse: bool) {
-        if let Some(line_index) = self.content.search(search, reverse) {
+    pub fn search(&mut self, search: &SearchPattern, reverse: bool) {
+        if let Some(line_index) = self.content.search(search, self.history.commits(), reverse) {
             self.set_current_line_index(line_index);
         }
     }
@@ -145,31 +368,31 @@ impl BlameRenderer {
     fn scroll_current_line_into_view(&mut self) {
         // Content may became smaller. Ensure all view rows are filled.
         let view_rows = self.view_rows() as usize;
-        let max_start_line_index = self.content.lines_len().saturating_sub(view_rows);
-        if self.view_start_line_index > max_start_line_index {
-            self.view_start_line_index = max_start_line_index;
+        let rows_len = self.rows_len();
+        let max_start_row = rows_len.saturating_sub(view_rows);
+        if self.view_start_row > max_start_row {
+            self.view_start_row = max_start_row;
         }
 
         // Scroll up to ensure `MARGIN` lines above the current line are visible.
         const MARGIN: usize = 5;
-        let line_index = self.current_line_index();
-        let above_margin = line_index.saturating_sub(MARGIN);
-        if self.view_start_line_index > above_margin {
-            self.view_start_line_index = above_margin;
+        let row = self.current_row();
+        let above_margin = row.saturating_sub(MARGIN);
+        if self.view_start_row > above_margin {
+            self.view_start_row = above_margin;
         } else {
             // Scroll down to ensure `MARGIN` lines below the current line are visible.
-            let below_margin = self.content.saturate_line_index(line_index + MARGIN);
+            let below_margin = cmp::min(row + MARGIN, rows_len.saturating_sub(1));
             let below_margin_start_index = (below_margin + 1).saturating_sub(view_rows);
-            if self.view_start_line_index < below_margin_start_index {
-                self.view_start_line_index = below_margin_start_index;
+            if self.view_start_row < below_margin_start_index {
+                self.view_start_row = below_margin_start_index;
             }
         }
 
         // If the scroll causes a full refresh (no lines were visible before),
         // center the current line.
         if self.rendered_rows > 0
-            && (self.view_start_line_index as isize - self.rendered_view_start_line_index as isize)
-                .abs()
+            && (self.view_start_row as isize - self.rendered_view_start_row as isize).abs()
                 >= view_rows as isize
         {
             self.scroll_current_line_to_center_of_view();
@@ -178,19 +401,100 @@ impl BlameRenderer {
 
     pub fn scroll_current_line_to_center_of_view(&mut self) {
         let view_rows = self.view_rows() as usize;
-        let content_lines_len = self.content.lines_len();
-        if content_lines_len <= view_rows {
+        let rows_len = self.rows_len();
+        if rows_len <= view_rows {
             return;
         }
         let view_rows_half = view_rows / 2;
-        let line_index = self.current_line_index();
-        if line_index < view_rows_half {
-            self.view_start_line_index = 0;
+        let row = self.current_row();
+        if row < view_rows_half {
+            self.view_start_row = 0;
             return;
         }
-        let view_start_line_index = line_index - view_rows_half;
-        let max_view_start_line_index = content_lines_len - view_rows;
-        self.view_start_line_index = cmp::min(view_start_line_index, max_view_start_line_index);
+        let view_start_row = row - view_rows_half;
+        let max_view_start_row = rows_len - view_rows;
+        self.view_start_row = cmp::min(view_start_row, max_view_start_row);
+    }
+
+    /// Set the columns of the left pane.
+    pub fn set_columns(&mut self, columns: Vec<GutterColumn>) {
+        self.columns = columns;
+        self.invalidate_render();
+    }
+
+    /// The number of columns to widen or narrow the left pane at a time.
+    const LEFT_PANE_RESIZE_STEP: isize = 4;
+
+    /// Widen the left pane, or narrow it if `reverse`.
+    /// See `GutterColumn::resize()`.
+    pub fn resize_left_pane(&mut self, reverse: bool) -> anyhow::Result<()> {
+        let delta = if reverse {
+            -Self::LEFT_PANE_RESIZE_STEP
+        } else {
+            Self::LEFT_PANE_RESIZE_STEP
+        };
+        GutterColumn::resize(&mut self.columns, delta)?;
+        self.invalidate_render();
+        Ok(())
+    }
+
+    pub fn filter(&self) -> &LineFilter {
+        &self.filter
+    }
+
+    /// Set the filter to dim lines that don't match it.
+    /// In the log, commits that don't match it are hidden.
+    pub fn set_filter(&mut self, filter: LineFilter) -> anyhow::Result<()> {
+        if self.content.content_type() == ContentType::Log {
+            self.content.set_log_filter(&filter, &self.history)?;
+            self.update_display_rows();
+        }
+        self.filter = filter;
+        self.invalidate_render();
+        Ok(())
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.invalidate_render();
+    }
+
+    pub fn set_time_format(&mut self, time_format: TimeFormat) {
+        self.time_format = time_format;
+        self.invalidate_render();
+    }
+
+    /// Toggle between the absolute and the relative times of commits.
+    pub fn toggle_time_format(&mut self) {
+        self.set_time_format(self.time_format.toggle());
+    }
+
+    pub fn has_pager(&self) -> bool {
+        self.pager.is_some()
+    }
+
+    pub fn set_pager(&mut self, pager: Option<String>) {
+        self.pager = pager;
+    }
+
+    pub fn set_difftool(&mut self, difftool: Option<String>) {
+        self.difftool = difftool;
+    }
+
+    pub fn set_author_format(&mut self, author_format: AuthorFormat) {
+        self.author_format = author_format;
+        self.invalidate_render();
+    }
+
+    /// Set the time span of the heatmap gradient. See `Config::heat_range()`.
+    pub fn set_heat_range(&mut self, heat_range: Option<TimeDelta>) {
+        self.heat_range = heat_range;
+        self.invalidate_render();
+    }
+
+    /// Switch to the next `AuthorFormat`.
+    pub fn toggle_author_format(&mut self) {
+        self.set_author_format(self.author_format.toggle());
     }
 
     pub fn commit_id(&self) -> Oid {
@@ -201,26 +505,364 @@ impl BlameRenderer {
         self.content.path()
     }
 
+    /// The commit of the current tree.
+    /// This is the start commit if the `commit_id()` is zero.
+    fn tree_commit_id(&self) -> anyhow::Result<Oid> {
+        let commit_id = self.commit_id();
+        if commit_id.is_zero() {
+            return self
+                .history
+                .start_commit_id()
+                .ok_or_else(|| anyhow::anyhow!("The history isn't read"));
+        }
+        Ok(commit_id)
+    }
+
     fn swap_content(&mut self, content: &mut FileContent) {
         std::mem::swap(&mut self.content, content);
+        self.content.take_line_damage();
+        self.selection_anchor = None;
+        self.update_display_rows();
         self.invalidate_render();
         self.scroll_current_line_into_view();
     }
 
-    pub fn read_poll(&mut self) -> anyhow::Result<()> {
-        if self.history_mut().read_poll()? {
-            let current_line_index_before = self.current_line_index();
-            self.content.update_commits(&self.history)?;
-            let current_line_index_after = self.current_line_index();
-            if current_line_index_after > current_line_index_before {
-                // If lines were inserted before the current line, adjust the
-                // scroll position so that the current line stays unchanged on
-                // the view.
-                self.view_start_line_index += current_line_index_after - current_line_index_before;
+    fn update_display_rows(&mut self) {
+        self.display_rows = match self.fold_target {
+            Some(target) if self.content.content_type() == ContentType::File => {
+                DisplayRows::new_folded(
+                    self.content.lines(),
+                    self.content.hunk_ranges(),
+                    self.history.commits(),
+                    target,
+                    &self.expanded_line_numbers,
+                )
             }
+            _ => DisplayRows::default(),
+        };
+    }
+
+    /// Fold lines not changed by the current line commit,
+    /// or unfold if already folded.
+    pub fn toggle_fold(&mut self) -> anyhow::Result<()> {
+        let target = match self.fold_target {
+            Some(_) => None,
+            None => Some(FoldTarget::Commit(self.current_line_commit_id()?)),
+        };
+        self.set_fold_target(target);
+        Ok(())
+    }
+
+    /// Fold lines not kept by the `target`, or unfold if `None`.
+    pub fn set_fold_target(&mut self, target: Option<FoldTarget>) {
+        self.fold_target = target;
+        self.expanded_line_numbers.clear();
+        self.update_display_rows();
+        self.invalidate_render();
+        self.scroll_current_line_into_view();
+    }
+
+    /// Expand the fold at the current line.
+    pub fn expand_fold(&mut self) {
+        if let DisplayRow::Fold(_) = self.display_rows.row(self.current_row()) {
+            self.expanded_line_numbers
+                .insert(self.current_line_number());
+            self.update_display_rows();
             self.invalidate_render();
             self.scroll_current_line_into_view();
         }
+    }
+
+    pub fn has_selection(&self) -> bool {
+        self.selection_anchor.is_some()
+    }
+
+    /// Start selecting lines from the current line, or stop selecting.
+    pub fn toggle_selection(&mut self) {
+        self.selection_anchor = match self.selection_anchor {
+            Some(_) => None,
+            None => Some(self.current_line_index()),
+        };
+        self.invalidate_render();
+    }
+
+    pub fn clear_selection(&mut self) {
+        if self.selection_anchor.take().is_some() {
+            self.invalidate_render();
+        }
+    }
+
+    /// The line indexes of the selected lines.
+    /// If not selecting, the current line.
+    fn selection(&self) -> RangeInclusive<usize> {
+        let current = self.current_line_index();
+        let anchor = self.selection_anchor.unwrap_or(current);
+        cmp::min(anchor, current)..=cmp::max(anchor, current)
+    }
+
+    fn selected_lines(&self) -> impl Iterator<Item = &Line> {
+        self.content.lines()[self.selection()]
+            .iter()
+            .filter(|line| !line.is_deleted())
+    }
+
+    /// The content of the selected lines.
+    pub fn selection_content(&self) -> String {
+        let lines: Vec<&str> = self.selected_lines().map(|line| line.content()).collect();
+        lines.join("\n")
+    }
+
+    /// The first and the last line numbers of the selected lines.
+    fn selection_line_numbers(&self) -> anyhow::Result<RangeInclusive<usize>> {
+        if self.content.content_type() != ContentType::File {
+            anyhow::bail!("Not available in the log");
+        }
+        let mut line_numbers = self.selected_lines().map(|line| line.line_number());
+        let Some(first) = line_numbers.next() else {
+            anyhow::bail!("No lines are selected");
+        };
+        Ok(first..=line_numbers.last().unwrap_or(first))
+    }
+
+    /// The path and the line numbers of the selected lines;
+    /// e.g., `src/main.rs:10` or `src/main.rs:10-12`.
+    pub fn selection_path_line(&self) -> anyhow::Result<String> {
+        let (start, end) = self.selection_line_numbers()?.into_inner();
+        let path = self.path().display();
+        Ok(if end > start {
+            format!("{path}:{start}-{end}")
+        } else {
+            format!("{path}:{start}")
+        })
+    }
+
+    /// The URL of the selected lines on the forge of the `origin` remote.
+    pub fn selection_permalink(&self) -> anyhow::Result<String> {
+        let line_numbers = self.selection_line_numbers()?;
+        let forge = Forge::from_repository(self.git().repository())?;
+        Ok(forge.file_url(self.tree_commit_id()?, self.path(), line_numbers))
+    }
+
+    /// The URL of the current line commit on the forge of the `origin` remote,
+    /// or the URL of the selected lines if selecting.
+    pub fn browse_url(&self) -> anyhow::Result<String> {
+        if self.has_selection() {
+            return self.selection_permalink();
+        }
+        let forge = Forge::from_repository(self.git().repository())?;
+        Ok(forge.commit_url(self.current_line_commit_id()?))
+    }
+
+    /// The distinct commits of the selected lines, the newest first.
+    pub fn selection_commits(&self) -> Vec<&FileCommit> {
+        let mut commits: Vec<&FileCommit> = vec![];
+        for line in self.selected_lines() {
+            if let Some(commit_id) = line.commit_id()
+                && let Ok(commit) = self.history.commit_by_id(commit_id)
+                && !commits.iter().any(|c| c.commit_id() == commit.commit_id())
+            {
+                commits.push(commit);
+            }
+        }
+        commits.sort_by_key(|commit| commit.index());
+        commits
+    }
+
+    /// The diff of the selected lines, from the parent of the oldest commit
+    /// of the selected lines to the current tree.
+    /// In the log, the diff of the file between the selected commits.
+    pub fn selection_diff(&self) -> anyhow::Result<DiffOverlay> {
+        if self.content.content_type() == ContentType::Log {
+            return self.log_selection_diff();
+        }
+        let line_numbers = self.selection_line_numbers()?;
+        let commits = self.selection_commits();
+        let Some(oldest) = commits.last() else {
+            anyhow::bail!("The selected lines don't have commits");
+        };
+        let mut paths = vec![self.path()];
+        let old_path = oldest.old_path_if_rename().unwrap_or(oldest.path());
+        if old_path != self.path() {
+            paths.push(old_path);
+        }
+        let mut overlay = DiffOverlay::new(
+            self.git(),
+            self.tree_commit_id()?,
+            oldest.parent_commit_id(),
+            &paths,
+        )?;
+        overlay.set_view_size(self.view_size);
+        let (start, end) = line_numbers.into_inner();
+        overlay.retain_hunks(start..end + 1);
+        Ok(overlay)
+    }
+
+    /// The diff of the file from the oldest to the newest of the selected
+    /// commits in the log.
+    fn log_selection_diff(&self) -> anyhow::Result<DiffOverlay> {
+        let commits = self.selection_commits();
+        let [newest, .., oldest] = commits.as_slice() else {
+            anyhow::bail!("Select two or more commits to diff");
+        };
+        let mut paths = vec![newest.path()];
+        if oldest.path() != newest.path() {
+            paths.push(oldest.path());
+        }
+        let mut overlay = DiffOverlay::new(
+            self.git(),
+            newest.commit_id(),
+            Some(oldest.commit_id()),
+            &paths,
+        )?;
+        overlay.set_view_size(self.view_size);
+        Ok(overlay)
+    }
+
+    /// The minimum interval of `read_poll()` to update the content, so that
+    /// the commits read in the interval are applied and rendered at once.
+    pub const READ_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    /// Update the content by the commits read since the last update, and
+    /// mark the rows in the view whose lines have changed to repaint.
+    /// This is no-op if called within `READ_POLL_INTERVAL` since the last
+    /// update.
+    pub fn read_poll(&mut self) -> anyhow::Result<()> {
+        if let Some(last_read_poll_time) = self.last_read_poll_time
+            && last_read_poll_time.elapsed() < Self::READ_POLL_INTERVAL
+        {
+            return Ok(());
+        }
+        let commits_len = self.history.commits().len();
+        let read_ahead_len = self.history.read_ahead_len();
+        let time_range = self.history.commits().time_range();
+        let current_commit = self.current_commit_and_metadata_len();
+        if !self.history_mut().read_poll()? && !self.content.is_blame_partial() {
+            return Ok(());
+        }
+        self.last_read_poll_time = Some(Instant::now());
+        if self.history.commits().len() > commits_len
+            || self.history.read_ahead_len() > read_ahead_len
+            || self.content.is_blame_partial()
+        {
+            self.update_content_after_read(time_range)?;
+        }
+        // The current line may have been attributed, or the `CommitMetadata`
+        // of the commit may have been attached.
+        if self.current_commit_and_metadata_len() != current_commit {
+            self.detail = CommitDetail::default();
+            self.is_detail_damaged = true;
+        }
+        Ok(())
+    }
+
+    fn current_commit_and_metadata_len(&self) -> (Option<Oid>, usize) {
+        let commit_id = self
+            .content
+            .lines()
+            .get(self.current_line_index())
+            .and_then(|line| line.commit_id());
+        let metadata_len = commit_id
+            .and_then(|commit_id| self.history.commit_by_id(commit_id).ok())
+            .map_or(0, |commit| commit.metadata().len());
+        (commit_id, metadata_len)
+    }
+
+    fn update_content_after_read(&mut self, time_range: Option<(i64, i64)>) -> anyhow::Result<()> {
+        let rows_len_before = self.rows_len();
+        let current_row_before = self.current_row();
+        self.content
+            .set_visible_line_range(Some(self.view_line_index_range()));
+        self.content.update_commits(&self.history)?;
+        if self.history.is_reading() {
+            let line_range = self.view_line_index_range();
+            if self.history.provider().is_blame() {
+                let commit_ids = self.content.unread_blame_commit_ids(line_range);
+                self.history.set_priority_commit_ids(commit_ids);
+            } else if let Some(line_numbers) = self.content.unattributed_line_numbers(line_range) {
+                let commit_id = self.content.commit_id();
+                self.history
+                    .set_priority_lines(commit_id, self.content.path(), line_numbers);
+            }
+        }
+        self.update_display_rows();
+        let current_row_after = self.current_row();
+        if current_row_after > current_row_before {
+            // If lines were inserted before the current line, adjust the
+            // scroll position so that the current line stays unchanged on
+            // the view.
+            self.view_start_row += current_row_after - current_row_before;
+        }
+        self.scroll_current_line_into_view();
+        match self.content.take_line_damage() {
+            // Folds may change by the new commits.
+            LineDamage::Lines(line_indexes)
+                if self.fold_target.is_none() && self.rows_len() == rows_len_before =>
+            {
+                if self.history.commits().time_range() != time_range {
+                    // The colors of the ages are relative to the time range.
+                    self.damaged_rows.extend(self.view_row_range());
+                } else {
+                    // Rows out of the view are rendered when scrolled into it.
+                    let view_row_range = self.view_row_range();
+                    let rows = line_indexes
+                        .into_iter()
+                        .map(|line_index| self.display_rows.row_from_line_index(line_index))
+                        .filter(|row| view_row_range.contains(row));
+                    self.damaged_rows.extend(rows);
+                }
+                self.is_minimap_damaged = true;
+            }
+            _ => self.invalidate_render(),
+        }
+        Ok(())
+    }
+
+    /// Reload the history if the `HEAD` of the repository, or the file in the
+    /// index or in the working directory, has changed since the history was
+    /// loaded. See `FileHistory::has_changed()`. The current line number is
+    /// kept. Returns `true` if reloaded.
+    pub fn reload_if_changed(&mut self) -> anyhow::Result<bool> {
+        if !self.history.has_changed()? {
+            return Ok(false);
+        }
+        debug!("reload_if_changed: {:?}", self.history.path());
+        let mut history = self.history.to_builder().build();
+        history.read_start_after(&self.history)?;
+        let mut content = history.content(Oid::ZERO_SHA1)?;
+        content.set_current_line_index(self.current_line_number().saturating_sub(1));
+        self.history = history;
+        self.swap_content(&mut content);
+        Ok(true)
+    }
+
+    /// The position of the current line, to jump back to by `jump_to()`.
+    pub fn jump_position(&self) -> JumpPosition {
+        JumpPosition {
+            content_type: self.content.content_type(),
+            commit_id: self.commit_id(),
+            line_number: self.current_line_number(),
+        }
+    }
+
+    pub fn jump_to(&mut self, position: &JumpPosition) -> anyhow::Result<()> {
+        if position.content_type == self.content.content_type()
+            && position.commit_id == self.commit_id()
+        {
+            return self.set_current_line_number(position.line_number);
+        }
+        let mut content = match position.content_type {
+            ContentType::File => self.history.content(position.commit_id)?,
+            ContentType::Log => {
+                let mut content = FileContent::new_log(position.commit_id, self.path());
+                content.set_log_filter(&self.filter, &self.history)?;
+                content
+            }
+        };
+        match position.content_type {
+            ContentType::File => content.set_current_line_number(position.line_number)?,
+            ContentType::Log => content.set_current_line_number_or_next(position.line_number),
+        }
+        self.swap_content(&mut content);
         Ok(())
     }
 
@@ -243,7 +885,7 @@ impl BlameRenderer {
             }
             ContentType::Log => {
                 let commit = self.history.commits().get_by_commit_id(commit_id)?;
-                let diff_parts = commit.diff_parts();
+                let diff_parts = commit.diff_parts()?;
                 if !diff_parts.is_empty() {
                     let first_line_number = diff_parts[0].new.start_line_number();
                     content.set_current_line_number(first_line_number)?;
@@ -254,17 +896,61 @@ impl BlameRenderer {
         Ok(())
     }
 
+    /// Set the commit to a revision such as a commit ID, a branch, or a tag.
+    /// If the file isn't changed in the commit,
+    /// the last commit that changed the file before it is used.
+    pub fn set_commit_id_from_rev(&mut self, rev: &str) -> anyhow::Result<()> {
+        let commit_id = self.git().commit_id_from_rev(rev)?;
+        let commit_id = self.history.commit_at_or_before(commit_id)?.commit_id();
+        self.set_commit_id(commit_id)
+    }
+
+    /// Show the parent tree of the current line commit, and select the lines
+    /// that the commit replaced with the current line, like "View blame prior
+    /// to this change" on GitHub.
+    /// Returns the line numbers of the replaced lines in the parent tree,
+    /// which is empty if the commit added the current line.
+    pub fn set_commit_id_prior_to_current_line(&mut self) -> anyhow::Result<Range<usize>> {
+        if self.content.content_type() == ContentType::Log {
+            anyhow::bail!("Not available in the log");
+        }
+        let commit_id = self.current_line_commit_id()?;
+        let Some(line_number) = self.current_line().original_line_number() else {
+            anyhow::bail!("The line isn't attributed to its commit yet");
+        };
+        let commit = self.history.commits().get_by_commit_id(commit_id)?;
+        let parent_commit_id = self.history.parent_commit(commit.index())?.commit_id();
+        let diff_parts = commit.diff_parts()?;
+        let old = match diff_parts
+            .iter()
+            .find(|part| part.new.line_numbers.contains(&line_number))
+        {
+            Some(part) => part.old.line_numbers.clone(),
+            None => {
+                let line_number = LineNumberMap::new_old_from_new(&diff_parts).map(line_number);
+                line_number..line_number
+            }
+        };
+        self.set_commit_id(parent_commit_id)?;
+        let line_index = self
+            .content
+            .line_index_from_number(old.start)
+            .unwrap_or(usize::MAX);
+        self.set_current_line_index(line_index);
+        if !old.is_empty() {
+            self.selection_anchor = self.content.line_index_from_number(old.end - 1).ok();
+        }
+        self.invalidate_render();
+        Ok(old)
+    }
+
     pub fn set_commit_id_to_older_than_current_line(&mut self) -> anyhow::Result<()> {
         let mut commit_id = self.current_line_commit_id()?;
         debug!("set_commit_id_to_older_than_current_line: {commit_id:?}");
         match self.content.content_type() {
             ContentType::File => {
                 let commit_index = self.history.commits().index_from_commit_id(commit_id)?;
-                let parent_commit_index = commit_index + 1;
-                if parent_commit_index >= self.history.commits().len() {
-                    bail!("No commits before {commit_id}");
-                }
-                let parent_commit = self.history.commit(parent_commit_index);
+                let parent_commit = self.history.parent_commit(commit_index)?;
                 commit_id = parent_commit.commit_id();
             }
             ContentType::Log => {}
@@ -272,167 +958,416 @@ impl BlameRenderer {
         self.set_commit_id(commit_id)
     }
 
+    /// In the log, move to the parent commit of the current line commit,
+    /// or to its child if `reverse`.
+    /// Unlike moving to the next line, this follows the ancestry graph.
+    pub fn move_to_log_parent(&mut self, reverse: bool) -> anyhow::Result<()> {
+        if self.content.content_type() != ContentType::Log {
+            anyhow::bail!("Only available in the log");
+        }
+        let mut commit_id = self.current_line_commit_id()?;
+        // Follow the ancestry across the commits hidden by the filter.
+        loop {
+            let index = self.history.commits().index_from_commit_id(commit_id)?;
+            let target_index = if reverse {
+                (0..index)
+                    .rev()
+                    .find(|i| {
+                        self.history
+                            .parent_commit(*i)
+                            .is_ok_and(|parent| parent.commit_id() == commit_id)
+                    })
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("No newer commits are children of this commit")
+                    })?
+            } else {
+                self.history.parent_commit(index)?.index()
+            };
+            // The log has a line per commit, but not for the hidden commits.
+            if let Ok(line_index) = self
+                .content
+                .lines()
+                .binary_search_by_key(&target_index, Line::line_number)
+            {
+                self.set_current_line_index(line_index);
+                return Ok(());
+            }
+            commit_id = self.history.commit(target_index).commit_id();
+        }
+    }
+
     pub fn set_log_content(&mut self) -> anyhow::Result<()> {
         if self.content.content_type() == ContentType::Log {
             return Ok(());
         }
         let mut content = FileContent::new_log(git2::Oid::ZERO_SHA1, self.path());
-        content.update_commits(&self.history)?;
+        content.set_log_filter(&self.filter, &self.history)?;
         if content.lines_len() == 0 {
             anyhow::bail!("No commits loaded yet")
         }
         if let Some(commit_id) = self.current_line().commit_id() {
             let commit_index = self.history.commits().index_from_commit_id(commit_id)?;
-            content.set_current_line_index(commit_index);
+            content.set_current_line_number_or_next(commit_index);
         }
         self.swap_content(&mut content);
         Ok(())
     }
 
-    pub fn show_current_line_commit(&mut self, current_file_only: bool) -> anyhow::Result<()> {
+    pub fn show_current_line_commit(&mut self) -> anyhow::Result<()> {
         let commit_id = self.current_line_commit_id()?;
-        let mut paths = vec![];
-        if current_file_only {
-            let commit = self.history.commits().get_by_commit_id(commit_id)?;
-            paths.push(commit.path());
-            if let Some(old_path) = commit.old_path_if_rename() {
-                paths.push(old_path);
-            }
+        self.git().show(commit_id, &[], self.pager.as_deref())?;
+        self.invalidate_render();
+        Ok(())
+    }
+
+    /// Show the diff of the current file of the current line commit
+    /// by `git show`, through the pager if configured.
+    pub fn show_current_line_diff(&mut self) -> anyhow::Result<()> {
+        let commit_id = self.current_line_commit_id()?;
+        let commit = self.history.commit_by_id(commit_id)?;
+        let mut paths = vec![commit.path()];
+        if let Some(old_path) = commit.old_path_if_rename() {
+            paths.push(old_path);
         }
-        self.git().show(commit_id, &paths)?;
+        self.git().show(commit_id, &paths, self.pager.as_deref())?;
         self.invalidate_render();
         Ok(())
     }
 
+    /// Open the current file at the current line commit and at its parent
+    /// in the external diff tool.
+    pub fn open_current_line_difftool(&mut self) -> anyhow::Result<()> {
+        let commit_id = self.current_line_commit_id()?;
+        let commit = self.history.commit_by_id(commit_id)?;
+        let mut paths = vec![commit.path()];
+        if let Some(old_path) = commit.old_path_if_rename() {
+            paths.push(old_path);
+        }
+        self.git().difftool(
+            commit.parent_commit_id(),
+            commit_id,
+            &paths,
+            self.difftool.as_deref(),
+        )?;
+        self.invalidate_render();
+        Ok(())
+    }
+
+    /// The diff of the current file of the current line commit,
+    /// scrolled to the hunk of the current line.
+    pub fn current_line_diff(&self) -> anyhow::Result<DiffOverlay> {
+        let commit_id = self.current_line_commit_id()?;
+        let commit = self.history.commit_by_id(commit_id)?;
+        let mut paths = vec![commit.path()];
+        if let Some(old_path) = commit.old_path_if_rename() {
+            paths.push(old_path);
+        }
+        let mut overlay =
+            DiffOverlay::new(self.git(), commit_id, commit.parent_commit_id(), &paths)?;
+        overlay.set_view_size(self.view_size);
+        if let Some(line_number) = self.current_line().original_line_number() {
+            overlay.scroll_to_line_number(line_number);
+        }
+        Ok(overlay)
+    }
+
     pub fn invalidate_render(&mut self) {
         self.rendered_rows = 0;
     }
 
-    pub fn render(&mut self, out: &mut impl Write) -> anyhow::Result<()> {
+    pub fn render(&mut self, out: &mut impl RenderTarget) -> anyhow::Result<()> {
+        let rendered_view_start_row = self.rendered_view_start_row;
+        let rendered_current_row = self.rendered_current_row;
         if self.try_render_by_update(out)? {
+            // Scrolling or clearing lines breaks the minimap and the detail pane.
+            let is_scrolled = rendered_view_start_row != self.rendered_view_start_row
+                || rendered_current_row != self.rendered_current_row;
+            if is_scrolled || self.is_minimap_damaged {
+                self.render_minimap(out)?;
+            }
+            if is_scrolled || self.is_detail_damaged {
+                self.render_detail_pane(out)?;
+            }
+            self.is_minimap_damaged = false;
+            self.is_detail_damaged = false;
+            return Ok(());
+        }
+
+        out.clear_all()?;
+        self.damaged_rows.clear();
+        self.is_minimap_damaged = false;
+        self.is_detail_damaged = false;
+        self.rendered_rows = self.render_row_range_unchecked(out, false, self.view_row_range())?;
+        self.rendered_view_start_row = self.view_start_row;
+        self.rendered_current_row = self.current_row();
+        self.render_minimap(out)?;
+        self.render_detail_pane(out)?;
+        Ok(())
+    }
+
+    /// Write the rows in the view in plain text, without styles.
+    /// The current row is marked by `>`.
+    pub fn render_text(&self, out: &mut impl Write) -> anyhow::Result<()> {
+        let row_range = self.view_start_row..cmp::min(self.view_end_row(), self.rows_len());
+        let current_row = self.current_row();
+        let constraint = self.line_constraint();
+        let lines = self.content.lines();
+        for row in row_range {
+            let text = match self.display_rows.row(row) {
+                DisplayRow::Line(line_index) => {
+                    constraint.plain_text(&lines[line_index], self.history())?
+                }
+                DisplayRow::Fold(fold) => {
+                    format!("\u{2026} {} unchanged lines \u{2026}", fold.len())
+                }
+            };
+            let marker = if row == current_row { '>' } else { ' ' };
+            writeln!(out, "{marker}{text}")?;
+        }
+        Ok(())
+    }
+
+    /// Render the screen of the `width` and `height` to plain text,
+    /// in the same layout as `render()`. Useful for snapshot tests.
+    pub fn render_to_string(&mut self, width: u16, height: u16) -> anyhow::Result<String> {
+        Ok(self.render_to_buffer(width, height)?.to_plain_string())
+    }
+
+    /// Same as `render_to_string()`, with markers around the styled text;
+    /// e.g., `<fg=DarkGrey,bold>text</>`.
+    pub fn render_to_styled_string(&mut self, width: u16, height: u16) -> anyhow::Result<String> {
+        Ok(self.render_to_buffer(width, height)?.to_styled_string())
+    }
+
+    fn render_to_buffer(&mut self, width: u16, height: u16) -> anyhow::Result<TextBuffer> {
+        let view_size = self.view_size;
+        let view_start_row = self.view_start_row;
+        self.set_view_size((width, height));
+        self.invalidate_render();
+        let mut buffer = TextBuffer::new(width, height);
+        let result = self.render(&mut buffer);
+        self.view_size = view_size;
+        self.view_start_row = view_start_row;
+        // The terminal doesn't have what's rendered to the buffer.
+        self.invalidate_render();
+        result?;
+        Ok(buffer)
+    }
+
+    fn line_constraint(&self) -> LineConstraint {
+        let mut constraint = LineConstraint::new(self.content_cols() as usize);
+        constraint.set_time_format(self.time_format);
+        constraint.set_author_format(self.author_format);
+        constraint.set_columns(&self.columns);
+        constraint.set_time_range(self.history.commits().time_range());
+        constraint.set_heat_range(self.heat_range);
+        constraint.set_filter(&self.filter);
+        constraint.set_pinned_commit_id(self.pinned_commit.as_ref().map(|(id, _)| *id));
+        constraint.set_not_committed(self.history.is_not_committed());
+        constraint
+    }
+
+    fn render_minimap(&self, out: &mut impl RenderTarget) -> anyhow::Result<()> {
+        if !self.is_minimap_visible || self.content.lines_len() == 0 {
             return Ok(());
         }
+        let commit_id = self.current_line().commit_id();
+        let cells = self.minimap().cells(
+            self.content.lines(),
+            commit_id,
+            &self.view_line_index_range(),
+        );
+        let left = self.content_cols();
+        for (row, cell) in cells.iter().enumerate() {
+            out.move_to(left, row as u16)?;
+            if cell.is_in_view() {
+                out.set_style(self.theme.minimap_view())?;
+            }
+            if cell.has_current_commit() {
+                out.set_style(self.theme.minimap())?;
+            }
+            out.print(&cell.symbol().to_string())?;
+            out.reset_style()?;
+        }
+        Ok(())
+    }
 
-        queue!(out, terminal::Clear(terminal::ClearType::All))?;
-        self.rendered_rows =
-            self.render_line_index_range_unchecked(out, false, self.view_line_indexes())?;
-        self.rendered_view_start_line_index = self.view_start_line_index;
-        self.rendered_current_line_index = self.current_line_index();
+    fn render_detail_pane(&mut self, out: &mut impl RenderTarget) -> anyhow::Result<()> {
+        let log_lines;
+        let lines = if self.is_log_pane_visible {
+            log_lines = self.log_pane_lines();
+            &log_lines
+        } else if self.is_detail_pane_visible {
+            let commit_id = self.current_line().commit_id();
+            if commit_id != self.detail.commit_id() || self.detail.lines().is_empty() {
+                let metadata = match commit_id {
+                    Some(commit_id) => self
+                        .history
+                        .commits()
+                        .get_by_commit_id(commit_id)?
+                        .metadata(),
+                    None => &[],
+                };
+                self.detail = CommitDetail::new(self.git(), commit_id, metadata)?;
+            }
+            self.detail.lines()
+        } else {
+            return Ok(());
+        };
+        let left = self.content_cols() + self.minimap_cols();
+        // The first column is for the border.
+        let constraint = LineConstraint::new(self.detail_pane_cols().saturating_sub(1) as usize);
+        for row in 0..self.view_rows() {
+            out.move_to(left, row)?;
+            out.clear_until_new_line()?;
+            out.print_styled(self.theme.dimmed(), "\u{2502}")?;
+            if let Some(line) = lines.get(row as usize) {
+                out.print(&constraint.truncate(line, 0))?;
+            }
+        }
         Ok(())
     }
 
-    fn try_render_by_update(&mut self, out: &mut impl Write) -> anyhow::Result<bool> {
+    /// The lines of the log pane; the newest messages that fit in the view.
+    fn log_pane_lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = Logger::recent()
+            .iter()
+            .flat_map(|message| message.lines().map(str::to_string))
+            .collect();
+        if lines.is_empty() {
+            lines.push("No warnings or errors.".to_string());
+        }
+        let skip = lines.len().saturating_sub(self.view_rows() as usize);
+        lines.split_off(skip)
+    }
+
+    fn try_render_by_update(&mut self, out: &mut impl RenderTarget) -> anyhow::Result<bool> {
         if self.rendered_rows == 0 {
             return Ok(false);
         }
 
-        if self.rendered_view_start_line_index != self.view_start_line_index {
-            let view_start_line_index = self.view_start_line_index;
-            let render_range = if view_start_line_index > self.rendered_view_start_line_index {
-                let scroll_up = view_start_line_index - self.rendered_view_start_line_index;
+        if self.rendered_view_start_row != self.view_start_row {
+            let view_start_row = self.view_start_row;
+            let render_range = if view_start_row > self.rendered_view_start_row {
+                let scroll_up = view_start_row - self.rendered_view_start_row;
                 if scroll_up >= self.view_rows() as usize {
                     return Ok(false);
                 }
-                queue!(out, terminal::ScrollUp(scroll_up as u16))?;
-                let view_end_line_index = self.view_end_line_index();
-                view_end_line_index - scroll_up..view_end_line_index
+                out.scroll_up(scroll_up as u16)?;
+                let view_end_row = self.view_end_row();
+                view_end_row - scroll_up..view_end_row
             } else {
-                let scroll_down = self.rendered_view_start_line_index - view_start_line_index;
+                let scroll_down = self.rendered_view_start_row - view_start_row;
                 if scroll_down >= self.view_rows() as usize {
                     return Ok(false);
                 }
-                queue!(out, terminal::ScrollDown(scroll_down as u16))?;
-                view_start_line_index..view_start_line_index + scroll_down
+                out.scroll_down(scroll_down as u16)?;
+                view_start_row..view_start_row + scroll_down
             };
-            self.render_line_index_range_unchecked(out, true, render_range)?;
-            self.rendered_view_start_line_index = self.view_start_line_index;
+            self.render_row_range_unchecked(out, true, render_range)?;
+            self.rendered_view_start_row = self.view_start_row;
+        }
+
+        let current_row = self.current_row();
+        if self.rendered_current_row != current_row {
+            if self.has_selection() {
+                // The selection is extended or shrunk between the rows.
+                let start = cmp::min(self.rendered_current_row, current_row);
+                let end = cmp::max(self.rendered_current_row, current_row) + 1;
+                self.render_row_range(out, true, start..end)?;
+            } else {
+                self.render_row(out, self.rendered_current_row)?;
+                self.render_row(out, current_row)?;
+            }
+            self.rendered_current_row = current_row;
         }
 
-        let current_line_index = self.current_line_index();
-        if self.rendered_current_line_index != current_line_index {
-            self.render_line_index(out, self.rendered_current_line_index)?;
-            self.render_line_index(out, current_line_index)?;
-            self.rendered_current_line_index = current_line_index;
+        if !self.damaged_rows.is_empty() {
+            let mut damaged_rows = std::mem::take(&mut self.damaged_rows);
+            damaged_rows.sort_unstable();
+            damaged_rows.dedup();
+            for row in damaged_rows {
+                self.render_row(out, row)?;
+            }
         }
         Ok(true)
     }
 
-    fn render_line_index(&self, out: &mut impl Write, line_index: usize) -> anyhow::Result<()> {
-        self.render_line_index_range(out, true, line_index..line_index + 1)?;
+    fn render_row(&self, out: &mut impl RenderTarget, row: usize) -> anyhow::Result<()> {
+        self.render_row_range(out, true, row..row + 1)?;
         Ok(())
     }
 
-    fn render_line_index_range(
+    fn render_row_range(
         &self,
-        out: &mut impl Write,
+        out: &mut impl RenderTarget,
         should_clear_lines: bool,
-        line_index_range: Range<usize>,
+        row_range: Range<usize>,
     ) -> anyhow::Result<u16> {
-        let adjusted_range = self.adjust_line_index_range_into_view(&line_index_range);
+        let adjusted_range = self.adjust_row_range_into_view(&row_range);
         if adjusted_range.is_empty() {
             return Ok(0);
         }
-        self.render_line_index_range_unchecked(out, should_clear_lines, adjusted_range)
+        self.render_row_range_unchecked(out, should_clear_lines, adjusted_range)
     }
 
-    fn render_line_index_range_unchecked(
+    /// Render the `row_range`, and returns the row on the screen
+    /// after the last rendered row.
+    fn render_row_range_unchecked(
         &self,
-        out: &mut impl Write,
+        out: &mut impl RenderTarget,
         should_clear_lines: bool,
-        line_index_range: Range<usize>,
+        row_range: Range<usize>,
     ) -> anyhow::Result<u16> {
-        assert!(!line_index_range.is_empty());
-        assert!(line_index_range.start >= self.view_start_line_index);
-        assert!(line_index_range.end <= self.view_end_line_index());
-        let start_row = line_index_range.start - self.view_start_line_index;
-        let lines = self
-            .content
-            .lines()
-            .iter()
-            .take(line_index_range.end)
-            .skip(line_index_range.start);
-        self.render_lines(
-            out,
-            start_row as u16,
-            line_index_range.start,
-            should_clear_lines,
-            lines,
-        )
-    }
-
-    fn render_lines<'a, Iter>(
-        &self,
-        out: &mut impl Write,
-        start_row: u16,
-        start_line_index: usize,
-        should_clear_lines: bool,
-        lines: Iter,
-    ) -> anyhow::Result<u16>
-    where
-        Iter: Iterator<Item = &'a Line>,
-    {
-        let mut row = start_row;
-        let current_line_index = self.current_line_index();
-        let constraint = LineConstraint::new(self.view_cols() as usize);
-        for (line_index, line) in (start_line_index..).zip(lines) {
-            queue!(out, cursor::MoveTo(0, row))?;
+        assert!(!row_range.is_empty());
+        assert!(row_range.start >= self.view_start_row);
+        assert!(row_range.end <= self.view_end_row());
+        let row_range = row_range.start..cmp::min(row_range.end, self.rows_len());
+        let mut screen_row = (row_range.start - self.view_start_row) as u16;
+        let current_row = self.current_row();
+        let constraint = self.line_constraint();
+        let lines = self.content.lines();
+        let selection = self.has_selection().then(|| self.selection());
+        let is_selected = |line_index| {
+            selection
+                .as_ref()
+                .is_some_and(|selection| selection.contains(&line_index))
+        };
+        for row in row_range {
+            out.move_to(0, screen_row)?;
             if should_clear_lines {
-                queue!(out, terminal::Clear(terminal::ClearType::CurrentLine))?;
+                out.clear_line()?;
             }
-            line.render(
-                out,
-                self.history(),
-                line_index == current_line_index,
-                &constraint,
-            )?;
-            row += 1;
+            let is_current_line = row == current_row;
+            match self.display_rows.row(row) {
+                DisplayRow::Line(line_index) => constraint.render(
+                    &lines[line_index],
+                    out,
+                    self.history(),
+                    is_current_line,
+                    is_selected(line_index),
+                    &self.theme,
+                )?,
+                DisplayRow::Fold(fold) => {
+                    let text = format!("\u{2026} {} unchanged lines \u{2026}", fold.len());
+                    let text = constraint.truncate(&text, 0);
+                    let style = if is_current_line {
+                        self.theme.current_line()
+                    } else {
+                        self.theme.dimmed()
+                    };
+                    out.print_styled(style, &text)?;
+                }
+            }
+            screen_row += 1;
         }
-        Ok(row)
+        Ok(screen_row)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::extensions::tests::TempRepository;
 
     use super::*;
 
@@ -464,10 +1399,176 @@ mod tests {
         renderer.set_view_size((10, 20));
         renderer.content.set_lines_len_for_test(100);
         renderer.rendered_rows = 20;
-        renderer.rendered_view_start_line_index = 0;
+        renderer.rendered_view_start_row = 0;
         renderer.content.set_current_line_index(80);
         renderer.scroll_current_line_into_view();
-        assert_eq!(renderer.view_start_line_index, 70);
+        assert_eq!(renderer.view_start_row, 70);
+        Ok(())
+    }
+
+    #[test]
+    fn render_text() -> anyhow::Result<()> {
+        let mut renderer = BlameRenderer::new_for_test()?;
+        renderer.set_columns(vec![]);
+        renderer
+            .content
+            .set_lines_for_test((1..=30).map(|i| format!("line {i}")));
+        renderer.set_view_size((10, 12));
+        renderer.set_current_line_number(20)?;
+        let mut out = vec![];
+        renderer.render_text(&mut out)?;
+        let out = String::from_utf8(out)?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 12);
+        assert_eq!(lines[0], " |line 14");
+        assert_eq!(lines[6], ">|line 20");
+        Ok(())
+    }
+
+    #[test]
+    fn render_to_string() -> anyhow::Result<()> {
+        let mut renderer = BlameRenderer::new_for_test()?;
+        renderer.set_columns(vec![]);
+        renderer
+            .content
+            .set_lines_for_test((1..=30).map(|i| format!("line {i}")));
+        renderer.set_view_size((80, 24));
+        renderer.set_current_line_number(20)?;
+        let view_start_row = renderer.view_start_row;
+        let out = renderer.render_to_string(8, 12)?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 12);
+        assert_eq!(lines[0], "|line 14");
+        assert_eq!(lines[6], "|line 20");
+        assert_eq!(lines[7], "|line 21");
+
+        let out = renderer.render_to_styled_string(8, 12)?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "|line 14");
+        assert_eq!(lines[6], "<fg=Black,bg=Cyan>|</>line 20");
+
+        // The view is restored.
+        assert_eq!(renderer.view_cols(), 80);
+        assert_eq!(renderer.view_start_row, view_start_row);
+        Ok(())
+    }
+
+    #[test]
+    fn selection() -> anyhow::Result<()> {
+        let mut renderer = BlameRenderer::new_for_test()?;
+        renderer.set_view_size((10, 20));
+        renderer
+            .content
+            .set_lines_for_test((1..=10).map(|i| format!("line {i}")));
+        assert!(!renderer.has_selection());
+        renderer.set_current_line_index(4);
+        assert_eq!(renderer.selection_content(), "line 5");
+
+        renderer.toggle_selection();
+        assert!(renderer.has_selection());
+        renderer.move_to_prev_line_by(2);
+        assert_eq!(renderer.selection(), 2..=4);
+        assert_eq!(renderer.selection_content(), "line 3\nline 4\nline 5");
+        assert_eq!(renderer.selection_line_numbers()?, 3..=5);
+        assert_eq!(renderer.selection_path_line()?, ":3-5");
+        renderer.move_to_next_line_by(3);
+        assert_eq!(renderer.selection(), 4..=5);
+
+        renderer.clear_selection();
+        assert!(!renderer.has_selection());
+        assert_eq!(renderer.selection(), 5..=5);
+        Ok(())
+    }
+
+    #[test]
+    fn move_over_folds() -> anyhow::Result<()> {
+        let mut renderer = BlameRenderer::new_for_test()?;
+        renderer.set_view_size((10, 20));
+        renderer.content.set_lines_len_for_test(20);
+        renderer.display_rows = DisplayRows::new(vec![1..2, 5..15]);
+        assert_eq!(renderer.rows_len(), 11);
+        renderer.set_current_line_index(4);
+        renderer.move_to_next_line_by(1);
+        assert_eq!(renderer.current_line_index(), 5);
+        renderer.move_to_next_line_by(1);
+        assert_eq!(renderer.current_line_index(), 15);
+        renderer.move_to_prev_line_by(1);
+        assert_eq!(renderer.current_line_index(), 5);
+        renderer.move_to_next_line_by(100);
+        assert_eq!(renderer.current_line_index(), 19);
+        Ok(())
+    }
+
+    #[test]
+    fn read_poll() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        let content: String = (1..=20).map(|i| format!("{i}\n")).collect();
+        git.add_file_content(path, &content)?;
+        git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_start()?;
+        history.read_join()?;
+        let mut renderer = BlameRenderer::new(history)?;
+        renderer.set_view_size((40, 12));
+        renderer.render(&mut TextBuffer::new(40, 12))?;
+        assert!(renderer.rendered_rows() > 0);
+
+        renderer.read_poll()?;
+        assert_eq!(renderer.history().commits().len(), 1);
+        assert!(renderer.rendered_rows() > 0);
+        assert!(!renderer.damaged_rows.is_empty());
+        assert!(renderer.damaged_rows.iter().all(|row| *row < 12));
+        renderer.render(&mut TextBuffer::new(40, 12))?;
+        assert!(renderer.damaged_rows.is_empty());
+
+        // Calls within the interval don't poll.
+        let last_read_poll_time = renderer.last_read_poll_time;
+        renderer.read_poll()?;
+        assert_eq!(renderer.last_read_poll_time, last_read_poll_time);
+        Ok(())
+    }
+
+    #[test]
+    fn move_to_log_parent() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.repository()
+            .config()?
+            .set_str("user.email", "other@test.com")?;
+        git.add_file_content(path, "2\n")?;
+        let commit_id2 = git.commit(commit_id1, "Modify by other")?;
+        git.repository()
+            .config()?
+            .set_str("user.email", "test@test.com")?;
+        git.add_file_content(path, "3\n")?;
+        let commit_id3 = git.commit(commit_id2, "Modify")?;
+
+        let mut history = FileHistory::new(&git.to_file_path(path));
+        history.read_all()?;
+        let mut renderer = BlameRenderer::new(history)?;
+        renderer.set_log_content()?;
+        renderer.move_to_first_line();
+        assert_eq!(renderer.current_line_commit_id()?, commit_id3);
+        renderer.move_to_log_parent(false)?;
+        assert_eq!(renderer.current_line_commit_id()?, commit_id2);
+        renderer.move_to_log_parent(true)?;
+        assert_eq!(renderer.current_line_commit_id()?, commit_id3);
+
+        // The commits hidden by the filter are skipped.
+        let mut filter = LineFilter::default();
+        filter.set_author(Some("test@"));
+        renderer.set_filter(filter)?;
+        assert_eq!(renderer.current_line_commit_id()?, commit_id3);
+        renderer.move_to_log_parent(false)?;
+        assert_eq!(renderer.current_line_commit_id()?, commit_id1);
+        assert!(renderer.move_to_log_parent(false).is_err());
+        renderer.move_to_log_parent(true)?;
+        assert_eq!(renderer.current_line_commit_id()?, commit_id3);
+        assert!(renderer.move_to_log_parent(true).is_err());
         Ok(())
     }
 
@@ -480,9 +1581,9 @@ mod tests {
     ) -> usize {
         renderer.content.set_lines_len_for_test(lines_len);
         renderer.set_view_size((10, view_rows));
-        renderer.view_start_line_index = start_line_index;
+        renderer.view_start_row = start_line_index;
         renderer.set_current_line_index(current_line_index);
         renderer.scroll_current_line_into_view();
-        renderer.view_start_line_index
+        renderer.view_start_row
     }
 }
@@ -1,36 +1,56 @@
 use std::{
-    io::stdout,
+    fs,
+    io::{self, Write, stdin, stdout},
+    mem,
+    ops::Range,
     path::{Path, PathBuf},
+    sync::Arc,
     time::Duration,
 };
 
+use anyhow::Context;
 use clap::Parser;
 #[cfg(not(any(target_os = "macos", feature = "arboard")))]
 use crossterm::clipboard::CopyToClipboard;
-use crossterm::{cursor, execute, terminal};
+use crossterm::{cursor, execute, style, terminal};
 use git2::Oid;
-use log::debug;
+use git2_time_chrono_ext::Git2TimeChronoExt;
+use log::{debug, info, warn};
 
-use crate::{blame::FileHistory, extensions::TerminalRawModeScope};
+use crate::{
+    blame::{
+        BlameError, BlameOptions, BlameSnapshot, CsvWriter, EngineBenchmark, FileCommit,
+        FileHistory, FileHistoryBuilder, FileStats, GitEngine, GutterColumn, JjProvider,
+        LineFilter, LoadBenchmark, PorcelainWriter, PullRequestMetadataProvider, SearchPattern,
+    },
+    extensions::{
+        FileWatcher, GitError, GitTools, Logger, OrDefault, TerminalRawModeScope, open_url,
+    },
+};
 
 use super::*;
 
-/// Interactive enhanced `git blame` command line tool.
-#[derive(Debug, Default, Parser)]
-#[command(version, about)]
-struct Args {
-    /// Use git to compute the commit diff.
-    #[cfg(feature = "git2")]
-    #[arg(long, default_value_t = false)]
-    git: bool,
-
-    /// Use git2 to compute the commit diff.
-    #[cfg(not(feature = "git2"))]
-    #[arg(long, default_value_t = false)]
-    git2: bool,
-
-    /// Path of the file to annotate the history.
-    path: PathBuf,
+/// What `Cli::run()` does.
+#[derive(Debug, Default)]
+enum Mode {
+    #[default]
+    Interactive,
+    /// Run the commands in the file, or the standard input if `-`.
+    Script(PathBuf),
+    Export(ExportFormat),
+    Log,
+    Stats {
+        is_json: bool,
+    },
+    Who,
+    History {
+        lines: Range<usize>,
+        is_json: bool,
+    },
+    Bench {
+        runs: usize,
+        is_json: bool,
+    },
 }
 
 #[derive(Debug, Default)]
@@ -48,25 +68,110 @@ struct Args {
 /// ```
 pub struct Cli {
     path: PathBuf,
+    mode: Mode,
+    /// The line number to open at.
+    line_number: Option<usize>,
+    options: BlameOptions,
+    /// The engine specified by the options,
+    /// which the per-repository preferences can't override.
+    engine: Option<Engine>,
+    /// The engine selected by `select_engine()`.
+    git_engine: Option<GitEngine>,
+    is_watch: bool,
+    columns: Vec<GutterColumn>,
+    keys_path: Option<PathBuf>,
+    config_path: Option<PathBuf>,
+    log_file: Option<PathBuf>,
+    theme: Option<String>,
+    is_color: bool,
     history: Vec<Oid>,
-    last_search: Option<String>,
+    last_search: Option<SearchPattern>,
+    jump_list: JumpList,
+    macros: Macros,
+    is_replaying: bool,
+    /// Set when `Command::Quit` is handled, such as by `:quit`, to end the
+    /// loop of `run()` or `run_script()`.
+    is_quit: bool,
+    watcher: Option<FileWatcher>,
 }
 
 impl Cli {
     pub fn new_from_args() -> Self {
         let args = Args::parse();
+        match args.subcommand {
+            None => Self::new_from_blame_args(args.blame),
+            Some(Subcommand::Blame(args)) => Self::new_from_blame_args(args),
+            Some(Subcommand::Log(args)) => {
+                Self::new_from_common_args(&args.common, args.location, Mode::Log)
+            }
+            Some(Subcommand::Export(args)) => Self {
+                columns: args.columns,
+                ..Self::new_from_common_args(&args.common, args.location, Mode::Export(args.format))
+            },
+            Some(Subcommand::Stats(args)) => Self::new_from_common_args(
+                &args.common,
+                args.location,
+                Mode::Stats { is_json: args.json },
+            ),
+            Some(Subcommand::Who(args)) => {
+                let location = FileLocation {
+                    path: args.path,
+                    line_number: Some(args.line as usize),
+                    ..Default::default()
+                };
+                Self::new_from_common_args(&args.common, location, Mode::Who)
+            }
+            Some(Subcommand::History(args)) => {
+                let location = FileLocation {
+                    path: args.path,
+                    ..Default::default()
+                };
+                let mode = Mode::History {
+                    lines: args.lines,
+                    is_json: args.json,
+                };
+                Self::new_from_common_args(&args.common, location, mode)
+            }
+            Some(Subcommand::Bench(args)) => Self::new_from_common_args(
+                &args.common,
+                args.location,
+                Mode::Bench {
+                    runs: args.runs as usize,
+                    is_json: args.json,
+                },
+            ),
+        }
+    }
 
-        #[cfg(feature = "git2")]
-        if args.git {
-            crate::blame::FileCommit::use_git();
+    fn new_from_blame_args(args: BlameArgs) -> Self {
+        let mode = match (&args.script, args.export_format()) {
+            (Some(script), _) => Mode::Script(script.clone()),
+            (None, Some(format)) => Mode::Export(format),
+            (None, None) => Mode::Interactive,
+        };
+        let mut location = args.location.unwrap_or_default();
+        if let Some(line) = args.line {
+            location.line_number = Some(line as usize);
         }
-        #[cfg(not(feature = "git2"))]
-        if args.git2 {
-            crate::blame::FileCommit::use_git2();
+        Self {
+            is_watch: args.watch,
+            columns: args.columns,
+            keys_path: args.keys,
+            theme: args.theme,
+            is_color: args.color.is_color(),
+            ..Self::new_from_common_args(&args.common, location, mode)
         }
+    }
 
+    fn new_from_common_args(common: &CommonArgs, location: FileLocation, mode: Mode) -> Self {
         Self {
-            path: args.path,
+            path: location.path,
+            engine: common.engine(),
+            mode,
+            line_number: location.line_number,
+            options: common.options(location.rev.as_deref()),
+            config_path: common.config.clone(),
+            log_file: common.log_file.clone(),
             ..Default::default()
         }
     }
@@ -74,36 +179,136 @@ impl Cli {
     pub fn new(path: &Path) -> Self {
         Self {
             path: path.to_path_buf(),
+            is_color: ColorWhen::Auto.is_color(),
             ..Default::default()
         }
     }
 
+    /// Initialize the logger of the `log` crate,
+    /// to write to the `--log-file` if specified, or to the standard error,
+    /// and to keep recent warnings and errors for the log pane.
+    pub fn init_logger(&self) -> anyhow::Result<()> {
+        Logger::init(self.log_file.as_deref())
+    }
+
     /// Run the `git-iblame` command line interface.
     pub fn run(&mut self) -> anyhow::Result<()> {
-        let mut history = FileHistory::new(&self.path);
+        let mut config = Config::new_with_config(self.config_path.as_deref())?;
+        let (git, _) = GitTools::from_file_or_relative_path(&self.path)?;
+        config.apply_repository(&git)?;
+        self.apply_config(&config);
+        if let Mode::Bench { runs, is_json } = self.mode {
+            return self.bench(&git, runs, is_json, &mut stdout().lock());
+        }
+        let (engine_message, mut git_notice) = match GitTools::check_git_command() {
+            Ok(()) => (self.select_engine(config.engine())?, None),
+            Err(error) => (None, Some(self.fall_back_to_git2(config.engine(), &error))),
+        };
+        if let Some(notice) = self.fall_back_for_options() {
+            git_notice = Some(match git_notice {
+                Some(git_notice) => format!("{git_notice}; {notice}"),
+                None => notice,
+            });
+        }
+        match &self.mode {
+            Mode::Interactive => {}
+            Mode::Script(script_path) => {
+                let script = if script_path == Path::new("-") {
+                    io::read_to_string(stdin())?
+                } else {
+                    fs::read_to_string(script_path)
+                        .with_context(|| format!("Failed to read {}", script_path.display()))?
+                };
+                return self.run_script(&script, &config, &mut stdout().lock());
+            }
+            Mode::Export(format) => return self.print(*format, &config),
+            Mode::Log => return self.log(&mut stdout().lock()),
+            Mode::Stats { is_json } => return self.stats(*is_json, &mut stdout().lock()),
+            Mode::Who => return self.who(&mut stdout().lock()),
+            Mode::History { lines, is_json } => {
+                return self.range_history(lines.clone(), *is_json, &mut stdout().lock());
+            }
+            Mode::Bench { .. } => unreachable!(),
+        }
+        let key_map = CommandKeyMap::new_with_config(self.keys_path.as_deref())?;
+        // Without colors, the theme uses attributes such as reverse instead.
+        style::force_color_output(self.is_color);
+        let theme = if self.is_color {
+            Theme::new_with_config(self.theme.as_deref())?
+        } else {
+            Theme::builtin(Theme::NO_COLOR)?
+        };
+        let mut history = self.history_builder().build();
         history.read_start()?;
 
         let mut renderer = BlameRenderer::new(history)?;
+        self.set_preferences(&mut renderer, &config);
+        renderer.set_theme(theme.clone());
         let size = terminal::size()?;
         renderer.set_view_size((size.0, size.1 - 1));
+        if let Some(line_number) = self.line_number {
+            renderer.set_current_line_number(line_number)?;
+        }
+        if self.is_watch {
+            self.watcher = Some(Self::create_watcher(renderer.history())?);
+        }
 
         let mut ui = CommandUI::new();
+        ui.key_map = key_map;
+        ui.theme = theme;
+        if let Some(message) = engine_message {
+            ui.set_prompt(message);
+        }
         let mut out = stdout();
         let mut terminal_raw_mode = TerminalRawModeScope::new_with_alternate_screen()?;
         loop {
-            let result = renderer.render(&mut out);
+            // Poll in every iteration, not only on timeouts, so that the
+            // loading progresses while keys are repeated.
+            let result = renderer
+                .read_poll()
+                .and_then(|()| renderer.render(&mut out));
             ui.set_result(result);
             let command_rows = renderer.rendered_rows();
+            let status: Vec<String> = [
+                renderer.pinned_status(),
+                renderer.apply_failed_status(),
+                renderer.line_ending_status(),
+                renderer
+                    .history()
+                    .is_not_committed()
+                    .then(|| "Not committed".to_string()),
+                git_notice.clone(),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            ui.status = status.join(", ");
+            if let Some(register) = self.macros.recording_register() {
+                ui.status = format!("Recording @{register} {}", ui.status);
+            }
 
             if renderer.history().is_reading() {
-                ui.timeout = Duration::from_millis(1000);
-                if matches!(ui.prompt, CommandPrompt::None) {
-                    ui.prompt = CommandPrompt::Loading;
+                ui.timeout = BlameRenderer::READ_POLL_INTERVAL;
+                if matches!(
+                    ui.prompt,
+                    CommandPrompt::None | CommandPrompt::Loading { .. }
+                ) {
+                    ui.prompt = CommandPrompt::Loading {
+                        progress: renderer.history().read_progress(),
+                        is_paused: renderer.history().is_read_paused(),
+                    };
                 }
             } else {
-                ui.timeout = Duration::ZERO;
-                if matches!(ui.prompt, CommandPrompt::Loading) {
+                ui.timeout = if self.watcher.is_some() {
+                    Duration::from_millis(1000)
+                } else {
+                    Duration::ZERO
+                };
+                if matches!(ui.prompt, CommandPrompt::Loading { .. }) {
                     ui.prompt = CommandPrompt::None;
+                    if let Some(hint) = Self::read_completed_hint(renderer.history()) {
+                        ui.set_prompt(hint);
+                    }
                 }
             }
             let command = ui.read(command_rows)?;
@@ -112,11 +317,696 @@ impl Cli {
                 Command::Timeout => {}
                 _ => ui.prompt = CommandPrompt::None,
             }
+            self.macros.record(&command);
+            let jump_from = command.is_jump().then(|| renderer.jump_position());
             let result = self.handle_command(command, &mut renderer, &mut ui);
             ui.set_result(result);
+            if let Some(jump_from) = jump_from
+                && renderer.jump_position() != jump_from
+            {
+                self.jump_list.push(jump_from);
+            }
+            if self.is_quit {
+                break;
+            }
         }
 
         terminal_raw_mode.reset()?;
+        // Errors in reading don't matter when quitting.
+        if let Err(error) = renderer.history_mut().read_cancel() {
+            warn!("Failed to cancel reading: {error:#}");
+        }
+        Ok(())
+    }
+
+    /// Apply the preferences to compute the history,
+    /// in addition to the command line options.
+    fn apply_config(&mut self, config: &Config) {
+        for rev in config.ignore_revs() {
+            self.options.add_ignore_rev(rev);
+        }
+        if config.detect_moves() {
+            self.options.set_detect_moves(true);
+        }
+        if config.ignore_cr_at_eol() {
+            self.options.set_ignore_cr_at_eol(true);
+        }
+        if !self.options.has_jobs() {
+            self.options.set_jobs(config.jobs());
+        }
+        if self.options.nice().is_none() {
+            self.options.set_nice(config.nice());
+        }
+    }
+
+    /// Select the engine by the options, or by the `config_engine` if the
+    /// options don't specify it. Returns the message to show if `Engine::Auto`
+    /// chose the engine.
+    fn select_engine(&mut self, config_engine: Option<Engine>) -> anyhow::Result<Option<String>> {
+        let Some(engine) = self.engine.or(config_engine) else {
+            return Ok(None);
+        };
+        if let Some(git_engine) = engine.git_engine() {
+            self.git_engine = Some(git_engine);
+            return Ok(None);
+        }
+        let benchmark = EngineBenchmark::new(
+            &self.path,
+            &self.options,
+            EngineBenchmark::DEFAULT_COMMITS_LEN,
+        )?;
+        let git_engine = benchmark.fastest();
+        self.git_engine = Some(git_engine);
+        let elapsed: Vec<String> = benchmark
+            .elapsed()
+            .iter()
+            .map(|(engine, elapsed)| format!("{} {elapsed:.1?}", engine.name()))
+            .collect();
+        let message = format!(
+            "Engine: {} ({} for {} commits)",
+            git_engine.name(),
+            elapsed.join(", "),
+            benchmark.commits_len()
+        );
+        info!("{message}");
+        Ok(Some(message))
+    }
+
+    /// Select the `git2` engine corresponding to the engine by the options or
+    /// by the `config_engine`, because the `git` command is not available.
+    /// Returns the notice to show.
+    fn fall_back_to_git2(&mut self, config_engine: Option<Engine>, error: &GitError) -> String {
+        let engine = self
+            .engine
+            .or(config_engine)
+            .and_then(Engine::git_engine)
+            .unwrap_or_else(FileCommit::git_engine);
+        let git_engine = if engine.is_blame() {
+            GitEngine::Git2Blame
+        } else {
+            GitEngine::Git2
+        };
+        self.git_engine = Some(git_engine);
+        let notice = format!("{error}, using the {} engine", git_engine.name());
+        warn!("{notice}");
+        notice
+    }
+
+    /// Select the engine to fall back to, if the selected engine doesn't
+    /// support the options. Returns the notice to show.
+    /// See `GitEngine::fallback_engine()`.
+    fn fall_back_for_options(&mut self) -> Option<String> {
+        let engine = self.git_engine?;
+        let fallback = engine.fallback_engine(&self.options)?;
+        self.git_engine = Some(fallback);
+        let notice = format!(
+            "The {} engine can't ignore revisions nor detect moves, using the {} engine",
+            engine.name(),
+            fallback.name()
+        );
+        warn!("{notice}");
+        Some(notice)
+    }
+
+    /// The `FileHistoryBuilder` for the file, with the options and the engine.
+    fn history_builder(&self) -> FileHistoryBuilder {
+        let builder = FileHistoryBuilder::new(&self.path)
+            .options(self.options.clone())
+            .metadata_provider(Arc::new(PullRequestMetadataProvider));
+        let builder = match self.git_engine {
+            Some(engine) => builder.engine(engine),
+            None => builder,
+        };
+        if JjProvider::is_in_workspace(&self.path) {
+            let engine = self.git_engine.unwrap_or_else(FileCommit::git_engine);
+            return builder.provider(Arc::new(JjProvider::new(engine.provider())));
+        }
+        builder
+    }
+
+    fn set_preferences(&self, renderer: &mut BlameRenderer, config: &Config) {
+        if !self.columns.is_empty() {
+            renderer.set_columns(self.columns.clone());
+        } else if !config.columns().is_empty() {
+            renderer.set_columns(config.columns().to_vec());
+        }
+        renderer.set_time_format(config.time_format());
+        renderer.set_author_format(config.author_format());
+        renderer.set_heat_range(config.heat_range());
+        renderer.set_pager(config.pager().map(str::to_string));
+        renderer.set_difftool(config.difftool().map(str::to_string));
+    }
+
+    /// The view size in scripts, fixed for deterministic outputs.
+    const SCRIPT_VIEW_SIZE: (u16, u16) = (80, 24);
+
+    /// Run the commands in the `script`, one per line, and write the outputs
+    /// to the `out`. Empty lines and lines starting with `#` are ignored.
+    /// `print` writes the view in plain text, `screen` writes the whole screen
+    /// as rendered to the terminal, and `quit` stops the script.
+    /// Other lines are the same as the `:` command line.
+    fn run_script(
+        &mut self,
+        script: &str,
+        config: &Config,
+        out: &mut impl Write,
+    ) -> anyhow::Result<()> {
+        let mut history = self.history_builder().build();
+        history.read_start()?;
+        history.read_join()?;
+        let mut renderer = BlameRenderer::new(history)?;
+        renderer.read_poll()?;
+        self.set_preferences(&mut renderer, config);
+        renderer.set_view_size(Self::SCRIPT_VIEW_SIZE);
+        if let Some(line_number) = self.line_number {
+            renderer.set_current_line_number(line_number)?;
+        }
+
+        let mut ui = CommandUI::new();
+        self.is_quit = false;
+        for (i, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let context = || format!("Line {}: {line}", i + 1);
+            let name = line.split(' ').next().unwrap_or(line);
+            let result = match name {
+                "print" => renderer.render_text(out),
+                "screen" => Self::write_screen(&mut renderer, line[name.len()..].trim(), out),
+                _ => match CommandRegistry::find(name).with_context(context)?.command() {
+                    Some(command) if command.needs_terminal() => {
+                        Err(anyhow::anyhow!("Not available in scripts"))
+                    }
+                    Some(Command::Log) if renderer.has_selection() => {
+                        Err(anyhow::anyhow!("Not available in scripts"))
+                    }
+                    _ => self.handle_command_line(line, &mut renderer, &mut ui),
+                },
+            };
+            result.with_context(context)?;
+            if let CommandPrompt::Message { message } = mem::take(&mut ui.prompt) {
+                writeln!(out, "{message}")?;
+            }
+            if self.is_quit {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the screen of `SCRIPT_VIEW_SIZE` in plain text,
+    /// or with the style markers if the `arg` is `styled`.
+    fn write_screen(
+        renderer: &mut BlameRenderer,
+        arg: &str,
+        out: &mut impl Write,
+    ) -> anyhow::Result<()> {
+        let (width, height) = Self::SCRIPT_VIEW_SIZE;
+        let text = match arg {
+            "" => renderer.render_to_string(width, height)?,
+            "styled" => renderer.render_to_styled_string(width, height)?,
+            _ => anyhow::bail!("Unknown argument \"{arg}\""),
+        };
+        out.write_all(text.as_bytes())?;
+        Ok(())
+    }
+
+    /// Print the annotated lines to the standard output,
+    /// in the same columns as the left pane of the interactive UI,
+    /// or in the porcelain format.
+    fn print(&self, format: ExportFormat, config: &Config) -> anyhow::Result<()> {
+        let mut history = self.history_builder().build();
+        history.read_all()?;
+        let content = history.content(Oid::ZERO_SHA1)?;
+        match format {
+            ExportFormat::Plain => {}
+            ExportFormat::Porcelain | ExportFormat::LinePorcelain => {
+                let writer = PorcelainWriter::new(format == ExportFormat::LinePorcelain);
+                return Ok(writer.write(&mut stdout().lock(), &history, &content)?);
+            }
+            ExportFormat::Csv | ExportFormat::Tsv => {
+                let delimiter = if format == ExportFormat::Csv {
+                    ','
+                } else {
+                    '\t'
+                };
+                let writer = CsvWriter::new(delimiter);
+                return Ok(writer.write(&mut stdout().lock(), &history, &content)?);
+            }
+            ExportFormat::Json => {
+                let snapshot = BlameSnapshot::new(&history, &content);
+                let mut out = stdout().lock();
+                writeln!(out, "{}", serde_json::to_string_pretty(&snapshot)?)?;
+                return Ok(());
+            }
+        }
+
+        let mut constraint = LineConstraint::new(usize::MAX);
+        if !self.columns.is_empty() {
+            constraint.set_columns(&self.columns);
+        } else if !config.columns().is_empty() {
+            constraint.set_columns(config.columns());
+        }
+        constraint.set_time_format(config.time_format());
+        constraint.set_author_format(config.author_format());
+        constraint.set_time_range(history.commits().time_range());
+        constraint.set_heat_range(config.heat_range());
+        constraint.set_not_committed(history.is_not_committed());
+        let mut out = stdout().lock();
+        for line in content.lines().iter().filter(|line| !line.is_deleted()) {
+            writeln!(out, "{}", constraint.plain_text(line, &history)?)?;
+        }
+        Ok(())
+    }
+
+    /// Print the commits that changed the file, newest first.
+    fn log(&self, out: &mut impl Write) -> anyhow::Result<()> {
+        let mut history = self.history_builder().build();
+        history.read_all()?;
+        for commit in history.commits().iter() {
+            writeln!(out, "{}", Self::commit_line(commit))?;
+        }
+        Ok(())
+    }
+
+    /// Print the `FileStats` in tables, or in JSON if `is_json`.
+    fn stats(&self, is_json: bool, out: &mut impl Write) -> anyhow::Result<()> {
+        let mut history = self.history_builder().build();
+        history.read_all()?;
+        let content = history.content(Oid::ZERO_SHA1)?;
+        let stats = FileStats::new(&history, &content)?;
+        if is_json {
+            let authors: Vec<serde_json::Value> = stats
+                .authors()
+                .iter()
+                .map(|author| {
+                    serde_json::json!({
+                        "name": author.name,
+                        "email": author.email,
+                        "lines": author.lines,
+                        "percent": stats.percent(author.lines),
+                    })
+                })
+                .collect();
+            let commits: Vec<serde_json::Value> = stats
+                .commits()
+                .iter()
+                .map(|commit| {
+                    serde_json::json!({
+                        "commit": commit.commit_id.to_string(),
+                        "author": commit.author_email,
+                        "time": commit.time.seconds(),
+                        "summary": commit.summary,
+                        "added": commit.added,
+                        "deleted": commit.deleted,
+                    })
+                })
+                .collect();
+            let json = serde_json::json!({
+                "path": history.path(),
+                "lines": stats.lines(),
+                "authors": authors,
+                "commits": commits,
+            });
+            writeln!(out, "{}", serde_json::to_string_pretty(&json)?)?;
+            return Ok(());
+        }
+
+        writeln!(out, "{:>7} {:>6}  AUTHOR", "LINES", "%")?;
+        for author in stats.authors() {
+            writeln!(
+                out,
+                "{:>7} {:>5.1}%  {} <{}>",
+                author.lines,
+                stats.percent(author.lines),
+                author.name,
+                author.email
+            )?;
+        }
+        writeln!(out)?;
+        writeln!(out, "{:>7} {:>7}  COMMIT", "ADDED", "DELETED")?;
+        for (commit, churn) in history.commits().iter().zip(stats.commits()) {
+            writeln!(
+                out,
+                "{:>7} {:>7}  {}",
+                format!("+{}", churn.added),
+                format!("-{}", churn.deleted),
+                Self::commit_line(commit)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Print the commit that last changed the line at the `line_number`,
+    /// in a format similar to `git log`.
+    fn who(&self, out: &mut impl Write) -> anyhow::Result<()> {
+        let mut history = self.history_builder().build();
+        history.read_all()?;
+        let content = history.content(Oid::ZERO_SHA1)?;
+        let line_number = self.line_number.unwrap_or(1);
+        // The deleted lines have the same line numbers as the lines after them.
+        let line_index = content
+            .line_index_from_number_not_deleted(line_number)
+            .ok_or(BlameError::InvalidLineNumber(line_number))?;
+        let Some(commit_id) = content.lines()[line_index].commit_id() else {
+            anyhow::bail!("The line {line_number} isn't committed yet");
+        };
+        let commit = history.commits().get_by_commit_id(commit_id)?;
+        let date = commit.time().to_local_date_time().map_or_else(
+            |_| String::new(),
+            |datetime| datetime.format("%Y-%m-%d %H:%M:%S %z").to_string(),
+        );
+        writeln!(out, "commit {commit_id}")?;
+        writeln!(
+            out,
+            "Author: {} <{}>",
+            commit.author_name(),
+            commit.author_email()
+        )?;
+        writeln!(out, "Date:   {date}")?;
+        writeln!(out, "\n    {}", commit.summary().or_default())?;
+        Ok(())
+    }
+
+    /// Print the commits that changed the range of `lines`,
+    /// and the range of lines in each commit,
+    /// in a table, or in JSON if `is_json`.
+    fn range_history(
+        &self,
+        lines: Range<usize>,
+        is_json: bool,
+        out: &mut impl Write,
+    ) -> anyhow::Result<()> {
+        let mut history = self.history_builder().build();
+        history.read_all()?;
+        let content = history.content(Oid::ZERO_SHA1)?;
+        let lines_len = content.lines().iter().filter(|l| !l.is_deleted()).count();
+        if lines.start > lines_len {
+            anyhow::bail!(
+                "The file has only {lines_len} lines: {}",
+                history.path().display()
+            );
+        }
+        let lines = lines.start..lines.end.min(lines_len + 1);
+        let commits = history.commits_changing_lines(lines)?;
+        if is_json {
+            let commits: Vec<serde_json::Value> = commits
+                .iter()
+                .map(|(commit, lines)| {
+                    serde_json::json!({
+                        "commit": commit.commit_id().to_string(),
+                        "author": commit.author_email(),
+                        "time": commit.time().seconds(),
+                        "summary": commit.summary(),
+                        "path": commit.path(),
+                        "lines": { "start": lines.start, "end": lines.end - 1 },
+                    })
+                })
+                .collect();
+            writeln!(out, "{}", serde_json::to_string_pretty(&commits)?)?;
+            return Ok(());
+        }
+
+        writeln!(out, "{:>11}  COMMIT", "LINES")?;
+        for (commit, lines) in &commits {
+            writeln!(
+                out,
+                "{:>11}  {}",
+                format!("{},{}", lines.start, lines.end - 1),
+                Self::commit_line(commit)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Print the time to load the history by each engine,
+    /// or by the engine specified by the options,
+    /// in a table, or in JSON if `is_json`.
+    fn bench(
+        &self,
+        git: &GitTools,
+        runs: usize,
+        is_json: bool,
+        out: &mut impl Write,
+    ) -> anyhow::Result<()> {
+        let engines = match self.engine.and_then(Engine::git_engine) {
+            Some(engine) => vec![engine],
+            None => LoadBenchmark::ENGINES.to_vec(),
+        };
+        let benchmarks = engines
+            .into_iter()
+            .map(|engine| LoadBenchmark::new(&self.path, &self.options, engine, runs))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let has_commit_graph = git.has_commit_graph();
+        let has_changed_path_filters = git.has_changed_path_filters();
+        if is_json {
+            let engines: Vec<serde_json::Value> = benchmarks
+                .iter()
+                .map(|benchmark| {
+                    serde_json::json!({
+                        "engine": benchmark.engine().name(),
+                        "commits": benchmark.commits_len(),
+                        "lines": benchmark.lines_len(),
+                        "read_ms": benchmark.read().as_secs_f64() * 1000.0,
+                        "apply_ms": benchmark.apply().as_secs_f64() * 1000.0,
+                        "total_ms": benchmark.total().as_secs_f64() * 1000.0,
+                        "apply_commits_per_second": benchmark.apply_throughput(),
+                    })
+                })
+                .collect();
+            let json = serde_json::json!({
+                "path": self.path,
+                "runs": runs,
+                "commit_graph": has_commit_graph,
+                "changed_path_filters": has_changed_path_filters,
+                "engines": engines,
+            });
+            writeln!(out, "{}", serde_json::to_string_pretty(&json)?)?;
+            return Ok(());
+        }
+
+        writeln!(
+            out,
+            "Commit-graph: {}, changed-path filters: {}, the fastest of {runs} runs",
+            if has_commit_graph { "yes" } else { "no" },
+            if has_changed_path_filters {
+                "yes"
+            } else {
+                "no"
+            }
+        )?;
+        writeln!(
+            out,
+            "{:<6} {:>7} {:>7} {:>10} {:>10} {:>10} {:>10}",
+            "ENGINE", "COMMITS", "LINES", "READ", "APPLY", "TOTAL", "COMMITS/S"
+        )?;
+        for benchmark in &benchmarks {
+            writeln!(
+                out,
+                "{:<6} {:>7} {:>7} {:>10} {:>10} {:>10} {:>10.0}",
+                benchmark.engine().name(),
+                benchmark.commits_len(),
+                benchmark.lines_len(),
+                format!("{:.1?}", benchmark.read()),
+                format!("{:.1?}", benchmark.apply()),
+                format!("{:.1?}", benchmark.total()),
+                benchmark.apply_throughput()
+            )?;
+        }
+        if !has_changed_path_filters {
+            writeln!(out, "{}", Self::CHANGED_PATH_FILTERS_HINT)?;
+        }
+        Ok(())
+    }
+
+    const CHANGED_PATH_FILTERS_HINT: &str =
+        "`git commit-graph write --reachable --changed-paths` may speed up reading the commits";
+
+    /// Reading longer than this shows the `CHANGED_PATH_FILTERS_HINT` if the
+    /// repository doesn't have the changed-path filters.
+    const SLOW_READ_DURATION: Duration = Duration::from_secs(5);
+
+    /// The hint to show when the `history` is loaded, if any.
+    fn read_completed_hint(history: &FileHistory) -> Option<String> {
+        let elapsed = history.read_progress().elapsed();
+        (elapsed >= Self::SLOW_READ_DURATION && !history.git().has_changed_path_filters()).then(
+            || {
+                format!(
+                    "Loaded in {:.1?}; {}",
+                    elapsed,
+                    Self::CHANGED_PATH_FILTERS_HINT
+                )
+            },
+        )
+    }
+
+    fn copy(
+        &mut self,
+        target: CopyTarget,
+        renderer: &mut BlameRenderer,
+        ui: &mut CommandUI,
+    ) -> anyhow::Result<()> {
+        let text = match target {
+            CopyTarget::CommitId => renderer.current_line_commit_id()?.to_string(),
+            CopyTarget::ShortId => renderer
+                .history()
+                .git()
+                .short_id(renderer.current_line_commit_id()?)?,
+            CopyTarget::PathLine => renderer.selection_path_line()?,
+            CopyTarget::Content => renderer.selection_content(),
+            CopyTarget::Url => renderer.selection_permalink()?,
+        };
+        Self::copy_to_clipboard(&text)?;
+        let mut message = match target {
+            CopyTarget::Content => "Copied the lines to clipboard".to_string(),
+            _ => format!("Copied {text}"),
+        };
+        if target == CopyTarget::CommitId
+            && let Some((path, line_number)) = renderer.current_line_original_position()
+        {
+            message += &format!(", the line was {}:{line_number}", path.display());
+        }
+        ui.set_prompt(message);
+        renderer.clear_selection();
+        Ok(())
+    }
+
+    fn copy_to_clipboard(text: &str) -> anyhow::Result<()> {
+        #[cfg(any(target_os = "macos", feature = "arboard"))]
+        {
+            let mut clipboard = arboard::Clipboard::new()?;
+            clipboard.set_text(text)?;
+        }
+        #[cfg(not(any(target_os = "macos", feature = "arboard")))]
+        {
+            execute!(stdout(), CopyToClipboard::to_clipboard_from(text))?;
+        }
+        Ok(())
+    }
+
+    /// A line to show a commit; the short ID, the date, the author, and the
+    /// summary.
+    fn commit_line(commit: &FileCommit) -> String {
+        let date = commit.time().to_local_date_time().map_or_else(
+            |_| String::new(),
+            |datetime| datetime.format("%Y-%m-%d").to_string(),
+        );
+        format!(
+            "{:.7} {date} {} {}",
+            commit.commit_id().to_string(),
+            commit.author_email(),
+            commit.summary().or_default()
+        )
+    }
+
+    fn create_watcher(history: &FileHistory) -> anyhow::Result<FileWatcher> {
+        let git = history.git();
+        let mut watcher = FileWatcher::new()?;
+        watcher.watch_file(&git.workdir_path().join(history.path()))?;
+        // Watch `HEAD` and the refs, to detect new commits or checkouts.
+        let repository_path = git.repository_path();
+        watcher.watch(repository_path, false)?;
+        watcher.watch(&repository_path.join("refs"), true)?;
+        Ok(watcher)
+    }
+
+    /// Handle a command line entered after `:`, such as `commit main`.
+    fn handle_command_line(
+        &mut self,
+        line: &str,
+        renderer: &mut BlameRenderer,
+        ui: &mut CommandUI,
+    ) -> anyhow::Result<()> {
+        let (name, arg) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        let named = CommandRegistry::find(name)?;
+        if let Some(command) = named.command() {
+            return self.handle_command(command.clone(), renderer, ui);
+        }
+        let name = named.name();
+        match name {
+            "commit" => {
+                let arg = arg.trim();
+                if arg.is_empty() {
+                    anyhow::bail!("Usage: :commit <commit-id|ref>");
+                }
+                let path_before = renderer.path().to_path_buf();
+                let old_commit_id = renderer.commit_id();
+                renderer.set_commit_id_from_rev(arg)?;
+                if !old_commit_id.is_zero() {
+                    self.history.push(old_commit_id);
+                }
+                if path_before != renderer.path() {
+                    ui.set_prompt(format!("Path changed to {}", renderer.path().display()));
+                }
+            }
+            "after" | "before" => {
+                let arg = arg.trim();
+                let timestamp = if arg.is_empty() {
+                    None
+                } else {
+                    Some(renderer.history().git().timestamp_from_date(arg)?)
+                };
+                let mut filter = renderer.filter().clone();
+                if name == "after" {
+                    filter.set_after(timestamp);
+                } else {
+                    filter.set_before(timestamp);
+                }
+                renderer.set_filter(filter)?;
+            }
+            "filter" => {
+                let mut filter = renderer.filter().clone();
+                for arg in arg.split_whitespace() {
+                    let Some((key, value)) = arg.split_once('=') else {
+                        anyhow::bail!("Usage: :filter author=<EMAIL> after=<DATE> before=<DATE>");
+                    };
+                    let value = (!value.is_empty()).then_some(value);
+                    match key {
+                        "author" => filter.set_author(value),
+                        "after" | "before" => {
+                            let timestamp = match value {
+                                Some(value) => {
+                                    Some(renderer.history().git().timestamp_from_date(value)?)
+                                }
+                                None => None,
+                            };
+                            if key == "after" {
+                                filter.set_after(timestamp);
+                            } else {
+                                filter.set_before(timestamp);
+                            }
+                        }
+                        _ => anyhow::bail!("Unknown filter: {key}"),
+                    }
+                }
+                renderer.set_filter(filter)?;
+            }
+            "nofilter" => renderer.set_filter(LineFilter::default())?,
+            "fold" => {
+                let arg = arg.trim();
+                let target = if arg.is_empty() {
+                    FoldTarget::Commit(renderer.current_line_commit_id()?)
+                } else {
+                    FoldTarget::Newest(arg.parse()?)
+                };
+                renderer.set_fold_target(Some(target));
+            }
+            "nofold" => renderer.set_fold_target(None),
+            "goto" => {
+                let Ok(line_number) = arg.trim().parse() else {
+                    anyhow::bail!("Usage: :goto <LINE>");
+                };
+                self.handle_command(Command::LineNumber(line_number), renderer, ui)?;
+            }
+            "search" => {
+                if arg.is_empty() {
+                    anyhow::bail!("Usage: :search <PATTERN>");
+                }
+                self.handle_command(Command::Search(arg.to_string()), renderer, ui)?;
+            }
+            _ => anyhow::bail!("Unknown command: {name}"),
+        }
         Ok(())
     }
 
@@ -130,14 +1020,33 @@ impl Cli {
         match command {
             Command::PrevLine => renderer.move_to_prev_line_by(1),
             Command::NextLine => renderer.move_to_next_line_by(1),
-            // Command::PrevDiff => renderer.move_to_prev_diff(),
-            // Command::NextDiff => renderer.move_to_next_diff(),
+            Command::PrevSameCommit => renderer.move_to_same_commit_line(/*reverse*/ true),
+            Command::NextSameCommit => renderer.move_to_same_commit_line(/*reverse*/ false),
+            Command::PrevMinimapRow => renderer.move_by_minimap_row(/*reverse*/ true),
+            Command::NextMinimapRow => renderer.move_by_minimap_row(/*reverse*/ false),
+            Command::PrevDiff => renderer.move_to_diff(/*reverse*/ true),
+            Command::NextDiff => renderer.move_to_diff(/*reverse*/ false),
             Command::PrevPage => renderer.move_to_prev_page(),
             Command::NextPage => renderer.move_to_next_page(),
             Command::FirstLine => renderer.move_to_first_line(),
             Command::LastLine => renderer.move_to_last_line(),
             Command::LineNumber(number) => renderer.set_current_line_number(number)?,
+            Command::Repeat(count, command) => match *command {
+                Command::PrevLine => renderer.move_to_prev_line_by(count),
+                Command::NextLine => renderer.move_to_next_line_by(count),
+                command => {
+                    for _ in 0..count {
+                        self.handle_command(command.clone(), renderer, ui)?;
+                    }
+                }
+            },
             Command::Search(search) => {
+                let search = SearchPattern::new(&search);
+                renderer.search(&search, /*reverses*/ false);
+                self.last_search = Some(search);
+            }
+            Command::SearchCommit(query) => {
+                let search = SearchPattern::from_commit_query(&query)?;
                 renderer.search(&search, /*reverses*/ false);
                 self.last_search = Some(search);
             }
@@ -157,6 +1066,20 @@ impl Cli {
                     ui.set_prompt(format!("Path changed to {}", renderer.path().display()));
                 }
             }
+            Command::BlamePrior => {
+                let old_commit_id = renderer.commit_id();
+                let commit_id = renderer.current_line_commit_id()?;
+                let old = renderer.set_commit_id_prior_to_current_line()?;
+                if !old_commit_id.is_zero() {
+                    self.history.push(old_commit_id);
+                }
+                let short_id = renderer.history().git().short_id(commit_id)?;
+                ui.set_prompt(match old.len() {
+                    0 => format!("The line was added by {short_id}"),
+                    1 => format!("The line before {short_id}"),
+                    len => format!("The {len} lines before {short_id}"),
+                });
+            }
             Command::Newer => {
                 if let Some(commit_id) = self.history.pop() {
                     let path_before = renderer.path().to_path_buf();
@@ -166,6 +1089,32 @@ impl Cli {
                     }
                 }
             }
+            Command::Ex(line) => self.handle_command_line(&line, renderer, ui)?,
+            Command::JumpBack => {
+                if let Some(position) = self.jump_list.back(renderer.jump_position()) {
+                    renderer.jump_to(&position)?;
+                }
+            }
+            Command::JumpForward => {
+                if let Some(position) = self.jump_list.forward() {
+                    renderer.jump_to(&position)?;
+                }
+            }
+            Command::Log if renderer.has_selection() => {
+                execute!(
+                    out,
+                    terminal::Clear(terminal::ClearType::All),
+                    cursor::MoveTo(0, 0),
+                )?;
+                renderer.invalidate_render();
+                let mut terminal_raw_mode = TerminalRawModeScope::new(false)?;
+                for commit in renderer.selection_commits() {
+                    println!("{}", Self::commit_line(commit));
+                }
+                println!();
+                terminal_raw_mode.reset()?;
+                CommandUI::wait_for_any_key("Press any key to continue...")?;
+            }
             Command::Log => {
                 let old_commit_id = renderer.commit_id();
                 renderer.set_log_content()?;
@@ -173,26 +1122,81 @@ impl Cli {
                     self.history.push(old_commit_id);
                 }
             }
-            Command::Copy => {
-                if let Ok(commit_id) = renderer.current_line_commit_id() {
-                    #[cfg(any(target_os = "macos", feature = "arboard"))]
-                    {
-                        let mut clipboard = arboard::Clipboard::new()?;
-                        clipboard.set_text(commit_id.to_string())?;
-                    }
-                    #[cfg(not(any(target_os = "macos", feature = "arboard")))]
-                    {
-                        execute!(
-                            out,
-                            CopyToClipboard::to_clipboard_from(commit_id.to_string())
-                        )?;
-                    }
-                    ui.set_prompt("Copied to clipboard".to_string());
+            Command::ToggleTimeFormat => renderer.toggle_time_format(),
+            Command::ToggleAuthorFormat => renderer.toggle_author_format(),
+            Command::ToggleDetailPane => renderer.toggle_detail_pane(),
+            Command::ToggleLogPane => renderer.toggle_log_pane(),
+            Command::ToggleMinimap => renderer.toggle_minimap(),
+            Command::ToggleReadPause => {
+                let history = renderer.history();
+                history.set_read_paused(!history.is_read_paused());
+            }
+            Command::TogglePin => renderer.toggle_pin()?,
+            Command::LogParent => renderer.move_to_log_parent(false)?,
+            Command::LogChild => renderer.move_to_log_parent(true)?,
+            Command::NarrowLeftPane => renderer.resize_left_pane(true)?,
+            Command::WidenLeftPane => renderer.resize_left_pane(false)?,
+            Command::ToggleFold => renderer.toggle_fold()?,
+            Command::ExpandFold => renderer.expand_fold(),
+            Command::Copy if renderer.has_selection() => {
+                self.copy(CopyTarget::Content, renderer, ui)?
+            }
+            Command::Copy => self.copy(CopyTarget::CommitId, renderer, ui)?,
+            Command::CopyPermalink => self.copy(CopyTarget::Url, renderer, ui)?,
+            Command::CopyMenu => {
+                let key = ui.read_char(renderer.rendered_rows(), CopyTarget::prompt())?;
+                if let Some(target) = key.and_then(CopyTarget::from_char) {
+                    self.copy(target, renderer, ui)?;
+                }
+            }
+            Command::Browse => {
+                let url = renderer.browse_url()?;
+                open_url(&url)?;
+                renderer.clear_selection();
+                ui.set_prompt(format!("Opened {url}"));
+            }
+            Command::ToggleSelection => renderer.toggle_selection(),
+            Command::ClearSelection => renderer.clear_selection(),
+            Command::ShowDiff if renderer.has_selection() => {
+                let mut overlay = renderer.selection_diff()?;
+                renderer.invalidate_render();
+                overlay.run(&mut out)?;
+            }
+            Command::ShowDiff if renderer.has_pager() => {
+                let mut terminal_raw_mode = TerminalRawModeScope::new(false)?;
+                renderer.show_current_line_diff()?;
+                terminal_raw_mode.reset()?;
+                CommandUI::wait_for_any_key("Press any key to continue...")?;
+            }
+            Command::ShowDiff | Command::ShowSideBySide => {
+                let mut overlay = renderer.current_line_diff()?;
+                if command == Command::ShowSideBySide {
+                    overlay.toggle_side_by_side();
                 }
+                renderer.invalidate_render();
+                overlay.run(&mut out)?;
             }
-            Command::ShowCommit | Command::ShowDiff => {
+            Command::DiffTool => {
+                execute!(
+                    out,
+                    terminal::Clear(terminal::ClearType::All),
+                    cursor::MoveTo(0, 0),
+                )?;
                 let mut terminal_raw_mode = TerminalRawModeScope::new(false)?;
-                renderer.show_current_line_commit(command == Command::ShowDiff)?;
+                let result = renderer.open_current_line_difftool();
+                terminal_raw_mode.reset()?;
+                renderer.invalidate_render();
+                result?;
+            }
+            Command::ShowCommit => {
+                let mut terminal_raw_mode = TerminalRawModeScope::new(false)?;
+                renderer.show_current_line_commit()?;
+                if let Some((path, line_number)) = renderer.current_line_original_position() {
+                    println!(
+                        "\nThe current line was {}:{line_number} in this commit.",
+                        path.display()
+                    );
+                }
                 terminal_raw_mode.reset()?;
                 CommandUI::wait_for_any_key("Press any key to continue...")?;
             }
@@ -209,7 +1213,42 @@ impl Cli {
                 terminal_raw_mode.reset()?;
                 CommandUI::wait_for_any_key("Press any key to continue...")?;
             }
-            Command::Timeout => renderer.read_poll()?,
+            Command::RecordMacro => {
+                anyhow::ensure!(!self.is_replaying, "Can't record in macros");
+                if let Some((register, len)) = self.macros.stop_recording() {
+                    ui.set_prompt(format!("Recorded {len} commands to @{register}"));
+                } else if let Some(register) =
+                    ui.read_char(renderer.rendered_rows(), "Record to:".to_string())?
+                {
+                    self.macros.start_recording(register)?;
+                }
+            }
+            Command::ReplayMacro => {
+                anyhow::ensure!(!self.is_replaying, "Can't replay in macros");
+                let Some(register) =
+                    ui.read_char(renderer.rendered_rows(), "Replay:".to_string())?
+                else {
+                    return Ok(());
+                };
+                let commands = self.macros.commands(register)?;
+                self.is_replaying = true;
+                let result = commands
+                    .into_iter()
+                    .try_for_each(|command| self.handle_command(command, renderer, ui));
+                self.is_replaying = false;
+                result?;
+            }
+            Command::Timeout => {
+                if let Some(watcher) = self.watcher.as_ref()
+                    && watcher.has_changed()
+                    && renderer.reload_if_changed()?
+                {
+                    // The commits in the stacks may not be in the new history.
+                    self.history.clear();
+                    self.jump_list = JumpList::default();
+                    ui.set_prompt("Reloaded for the changes".to_string());
+                }
+            }
             Command::Repaint => {
                 renderer.invalidate_render();
                 renderer.scroll_current_line_to_center_of_view();
@@ -217,11 +1256,154 @@ impl Cli {
             Command::Resize(columns, rows) => renderer.set_view_size((columns, rows - 1)),
             Command::Debug => {
                 let commit_id = renderer.current_line_commit_id()?;
-                let commit = renderer.history().commits().get_by_commit_id(commit_id)?;
+                let commit = renderer.history().commit_by_id(commit_id)?;
                 debug!("debug_current_line: {commit:?}");
             }
-            Command::Quit => {}
+            Command::Quit => self.is_quit = true,
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::extensions::tests::TempRepository;
+
+    use super::*;
+
+    #[test]
+    fn who() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\nA\n2\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add A")?;
+
+        let mut cli = Cli::new(&git.to_file_path(path));
+        let who = |cli: &Cli| -> anyhow::Result<String> {
+            let mut out = vec![];
+            cli.who(&mut out)?;
+            Ok(String::from_utf8(out)?)
+        };
+        cli.line_number = Some(2);
+        let out = who(&cli)?;
+        assert!(out.starts_with(&format!(
+            "commit {commit_id2}\nAuthor: Test User <test@test.com>\n"
+        )));
+        assert!(out.ends_with("\n    Add A\n"));
+        cli.line_number = Some(3);
+        assert!(who(&cli)?.starts_with(&format!("commit {commit_id1}\n")));
+        cli.line_number = Some(4);
+        assert!(who(&cli).is_err());
+
+        // Not the commit that deleted the line before it.
+        git.add_file_content(path, "1\n2\n")?;
+        git.commit(commit_id2, "Delete A")?;
+        let mut cli = Cli::new(&git.to_file_path(path));
+        cli.line_number = Some(2);
+        assert!(who(&cli)?.starts_with(&format!("commit {commit_id1}\n")));
+        Ok(())
+    }
+
+    #[test]
+    fn range_history() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n3\n")?;
+        let commit_id1 = git.commit(Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "0\n1\n2\n3\n")?;
+        let commit_id2 = git.commit(commit_id1, "Add 0")?;
+        git.add_file_content(path, "0\n1\nB\n3\n")?;
+        git.commit(commit_id2, "Modify 2")?;
+
+        let cli = Cli::new(&git.to_file_path(path));
+        let mut out = vec![];
+        cli.range_history(3..5, false, &mut out)?;
+        let out = String::from_utf8(out)?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("        3,4  "));
+        assert!(lines[1].ends_with(" Modify 2"));
+        assert!(lines[2].starts_with("        2,3  "));
+        assert!(lines[2].ends_with(" Add file"));
+
+        let mut out = vec![];
+        cli.range_history(1..100, true, &mut out)?;
+        let json: serde_json::Value = serde_json::from_slice(&out)?;
+        let json = json.as_array().unwrap();
+        assert_eq!(json.len(), 3);
+        assert_eq!(json[1]["summary"], "Add 0");
+        assert_eq!(json[1]["lines"]["end"], 4);
+        assert_eq!(json[2]["lines"], serde_json::json!({"start": 1, "end": 3}));
+
+        assert!(cli.range_history(5..6, false, &mut vec![]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn quit_command_line() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        git.commit(Oid::ZERO_SHA1, "Add file")?;
+
+        let mut cli = Cli::new(&git.to_file_path(path));
+        let mut history = cli.history_builder().build();
+        history.read_start()?;
+        history.read_join()?;
+        let mut renderer = BlameRenderer::new(history)?;
+        renderer.read_poll()?;
+        let mut ui = CommandUI::new();
+        cli.handle_command(Command::Ex("goto 2".to_string()), &mut renderer, &mut ui)?;
+        assert!(!cli.is_quit);
+        cli.handle_command(Command::Ex("quit".to_string()), &mut renderer, &mut ui)?;
+        assert!(cli.is_quit);
+
+        // An abbreviation of `quit` ends the script.
+        let mut out = vec![];
+        cli.run_script("print\nq\nprint\n", &Config::default(), &mut out)?;
+        let out = String::from_utf8(out)?;
+        assert_eq!(out.lines().filter(|line| line.starts_with('>')).count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn run_script() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n")?;
+        let commit_id1 = git.commit(Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\nA\n2\n")?;
+        git.commit(commit_id1, "Add A")?;
+
+        let mut cli = Cli::new(&git.to_file_path(path));
+        let config = Config::default();
+        let mut out = vec![];
+        cli.run_script(
+            "# Comment\ngoto 2\nprint\n\nolder\nprint\nquit\nprint\n",
+            &config,
+            &mut out,
+        )?;
+        let out = String::from_utf8(out)?;
+        let current_lines: Vec<&str> = out.lines().filter(|line| line.starts_with('>')).collect();
+        assert_eq!(current_lines.len(), 2);
+        assert!(current_lines[0].ends_with("|A"));
+        assert!(current_lines[1].ends_with("|2"));
+
+        let error = cli
+            .run_script("goto 2\nhelp\n", &config, &mut vec![])
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Line 2: help");
+        assert!(cli.run_script("unknown\n", &config, &mut vec![]).is_err());
+
+        let mut out = vec![];
+        cli.run_script("goto 2\nscreen\n", &config, &mut out)?;
+        let out = String::from_utf8(out)?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 24);
+        assert!(lines[1].ends_with("|A"));
+        assert!(cli.run_script("screen x\n", &config, &mut vec![]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,521 @@
+use std::{
+    env,
+    io::{IsTerminal, stdout},
+    ops::Range,
+    path::PathBuf,
+};
+
+use clap::Parser;
+
+use crate::blame::{BlameOptions, GitEngine, GutterColumn};
+
+use super::FileLocation;
+
+/// Interactive enhanced `git blame` command line tool.
+///
+/// Without subcommands, the same as the `blame` subcommand.
+#[derive(Debug, Default, Parser)]
+#[command(
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+pub(crate) struct Args {
+    #[command(subcommand)]
+    pub subcommand: Option<Subcommand>,
+
+    #[command(flatten)]
+    pub blame: BlameArgs,
+}
+
+#[derive(Clone, Debug, clap::Subcommand)]
+pub(crate) enum Subcommand {
+    /// Annotate the history of the file interactively. This is the default.
+    Blame(BlameArgs),
+    /// Print the commits that changed the file, newest first, and exit.
+    Log(LogArgs),
+    /// Print the annotated lines to the standard output, and exit.
+    Export(ExportArgs),
+    /// Print the number of lines of each author,
+    /// and the lines each commit added and deleted, and exit.
+    Stats(StatsArgs),
+    /// Print the commit that last changed the line, and exit.
+    /// Exits with a non-zero status if the line isn't committed yet.
+    Who(WhoArgs),
+    /// Print the commits that changed the range of lines, newest first,
+    /// like `git log -L`, and exit.
+    History(HistoryArgs),
+    /// Measure the time to load the history of the file by each engine,
+    /// and exit.
+    Bench(BenchArgs),
+}
+
+/// The options common to the subcommands, mostly to compute the history.
+#[derive(Clone, Debug, Default, clap::Args)]
+pub(crate) struct CommonArgs {
+    /// Use git to compute the commit diff.
+    #[cfg(feature = "git2")]
+    #[arg(long, default_value_t = false)]
+    git: bool,
+
+    /// Use git2 to compute the commit diff.
+    #[cfg(not(feature = "git2"))]
+    #[arg(long, default_value_t = false)]
+    git2: bool,
+
+    /// The engine to compute the history.
+    #[arg(long, value_enum)]
+    engine: Option<Engine>,
+
+    /// Ignore changes made by the revision when assigning blame.
+    /// Can be specified multiple times.
+    #[arg(long = "ignore-rev", value_name = "REV")]
+    ignore_revs: Vec<String>,
+
+    /// Don't follow renames of the file.
+    #[arg(long, default_value_t = false)]
+    no_follow: bool,
+
+    /// The similarity threshold in percent to detect renames.
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u16).range(0..=100))]
+    rename_threshold: Option<u16>,
+
+    /// Detect blocks of lines moved within the file,
+    /// and attribute them to the commits that originally added them.
+    #[arg(long, default_value_t = false)]
+    detect_moves: bool,
+
+    /// Ignore changes of only the line endings, such as from LF to CRLF,
+    /// when assigning blame.
+    #[arg(long, default_value_t = false)]
+    ignore_cr_at_eol: bool,
+
+    /// Show the blame at the revision, such as a commit ID, a branch, or a tag,
+    /// instead of `HEAD`.
+    #[arg(long, value_name = "REV")]
+    pub rev: Option<String>,
+
+    /// Read only the newest N commits that changed the file.
+    /// Lines older than them are attributed to the oldest commit, marked `^`.
+    #[arg(long, value_name = "N")]
+    max_count: Option<usize>,
+
+    /// Read only the commits more recent than the date.
+    /// Lines older than them are attributed to the oldest commit, marked `^`.
+    #[arg(long, value_name = "DATE")]
+    since: Option<String>,
+
+    /// Show the blame as of the date;
+    /// i.e., at the latest commit before the date.
+    /// Accepts the formats `git` accepts, such as "2025-01-31" or "2 weeks ago".
+    #[arg(long, value_name = "DATE")]
+    at: Option<String>,
+
+    /// The number of threads to compute the diffs of commits.
+    /// The default is the number of CPUs, up to 8.
+    #[arg(long, short = 'j', value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: Option<u16>,
+
+    /// Lower the priority of the threads to compute the diffs of commits,
+    /// and the `git` processes they start, by the niceness, like the `nice`
+    /// command, so that they don't slow other programs. Only on Linux.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(1..=19))]
+    nice: Option<u8>,
+
+    /// The megabytes of the diffs of commits to keep in memory.
+    /// The diffs of older commits are spilled to a temporary file,
+    /// and are read back when they're needed.
+    #[arg(long, value_name = "MB", value_parser = clap::value_parser!(u32).range(1..))]
+    memory_budget: Option<u32>,
+
+    /// The preferences file.
+    /// The default is `config.toml` in the config directory if it exists.
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Write logs to the file. The default level is `debug`,
+    /// and the `RUST_LOG` environment variable can change it.
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+}
+
+impl CommonArgs {
+    /// The engine to compute the history, if the options specify it.
+    pub fn engine(&self) -> Option<Engine> {
+        #[cfg(feature = "git2")]
+        if self.git {
+            return Some(Engine::Git);
+        }
+        #[cfg(not(feature = "git2"))]
+        if self.git2 {
+            return Some(Engine::Git2);
+        }
+        self.engine
+    }
+
+    /// The `BlameOptions`, with the `rev` if the `--rev` option isn't given.
+    pub fn options(&self, rev: Option<&str>) -> BlameOptions {
+        let mut options = BlameOptions::new();
+        for rev in &self.ignore_revs {
+            options.add_ignore_rev(rev);
+        }
+        options.set_follow(!self.no_follow);
+        options.set_rename_threshold(self.rename_threshold);
+        options.set_detect_moves(self.detect_moves);
+        options.set_ignore_cr_at_eol(self.ignore_cr_at_eol);
+        options.set_max_commits(self.max_count);
+        options.set_since(self.since.as_deref());
+        options.set_at(self.at.as_deref());
+        options.set_rev(self.rev.as_deref().or(rev));
+        options.set_jobs(self.jobs.map(usize::from));
+        options.set_nice(self.nice.map(i32::from));
+        options.set_memory_budget(self.memory_budget.map(|mb| mb as usize * 1024 * 1024));
+        options
+    }
+}
+
+#[derive(Clone, Debug, Default, clap::Args)]
+pub(crate) struct BlameArgs {
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    /// The columns of the left pane, separated by commas.
+    /// Each column is one of `number`, `orig`, `blame`, `index`, `sha`, `change`,
+    /// `author`, `date`, `summary`, or `heat`, optionally followed by `:` and the width;
+    /// e.g., `number,sha,author:12,date`.
+    #[arg(long, value_name = "COLUMNS", value_delimiter = ',')]
+    pub columns: Vec<GutterColumn>,
+
+    /// Print the annotated lines to the standard output and exit,
+    /// without the interactive UI. The same as the `export` subcommand.
+    /// This is the default if the standard output isn't a terminal.
+    #[arg(long, default_value_t = false)]
+    pub print: bool,
+
+    /// Print in the format of `git blame --porcelain` and exit.
+    #[arg(long, default_value_t = false, conflicts_with = "line_porcelain")]
+    pub porcelain: bool,
+
+    /// Print in the format of `git blame --line-porcelain` and exit.
+    #[arg(long, default_value_t = false)]
+    pub line_porcelain: bool,
+
+    /// The line number to open at.
+    /// This overrides the line number in the `PATH`.
+    #[arg(long, value_name = "LINE", value_parser = clap::value_parser!(u64).range(1..))]
+    pub line: Option<u64>,
+
+    /// Run the commands in the file without the terminal, one per line,
+    /// such as `older`, `goto 120`, `print`, and `quit`.
+    /// `-` reads the commands from the standard input.
+    #[arg(long, value_name = "FILE")]
+    pub script: Option<PathBuf>,
+
+    /// Watch the file and the repository, and reload when `HEAD` changes.
+    #[arg(long, default_value_t = false)]
+    pub watch: bool,
+
+    /// The key bindings file.
+    /// The default is `keys.toml` in the config directory if it exists.
+    #[arg(long, value_name = "PATH")]
+    pub keys: Option<PathBuf>,
+
+    /// The color theme; `dark`, `light`, `solarized`, or the path to a TOML
+    /// file. The default is `theme.toml` in the config directory if it exists.
+    #[arg(long, value_name = "THEME")]
+    pub theme: Option<String>,
+
+    /// When to use colors.
+    /// `auto` disables colors if the `NO_COLOR` environment variable is set.
+    #[arg(long, value_name = "WHEN", value_enum, default_value_t = ColorWhen::Auto)]
+    pub color: ColorWhen,
+
+    /// Path of the file to annotate the history.
+    /// `path:line` opens at the line,
+    /// and a permalink URL such as `https://github.com/o/r/blob/<commit>/path#L12`
+    /// opens the file at the commit and the line.
+    #[arg(value_name = "PATH", value_parser = FileLocation::parse, required = true)]
+    pub location: Option<FileLocation>,
+}
+
+impl BlameArgs {
+    /// The format to print, if the options or the standard output
+    /// ask for printing instead of the interactive UI.
+    pub fn export_format(&self) -> Option<ExportFormat> {
+        if self.porcelain {
+            Some(ExportFormat::Porcelain)
+        } else if self.line_porcelain {
+            Some(ExportFormat::LinePorcelain)
+        } else if self.print || !stdout().is_terminal() {
+            Some(ExportFormat::Plain)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub(crate) struct LogArgs {
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    /// Path of the file.
+    #[arg(value_name = "PATH", value_parser = FileLocation::parse)]
+    pub location: FileLocation,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub(crate) struct ExportArgs {
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    /// The format to print.
+    #[arg(long, value_enum, default_value_t = ExportFormat::Plain)]
+    pub format: ExportFormat,
+
+    /// The columns for the `plain` format. See the `blame` subcommand.
+    #[arg(long, value_name = "COLUMNS", value_delimiter = ',')]
+    pub columns: Vec<GutterColumn>,
+
+    /// Path of the file.
+    #[arg(value_name = "PATH", value_parser = FileLocation::parse)]
+    pub location: FileLocation,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub(crate) struct StatsArgs {
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    /// Print in JSON instead of tables.
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+
+    /// Path of the file.
+    #[arg(value_name = "PATH", value_parser = FileLocation::parse)]
+    pub location: FileLocation,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub(crate) struct WhoArgs {
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    /// Path of the file.
+    pub path: PathBuf,
+
+    /// The line number.
+    #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+    pub line: u64,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub(crate) struct HistoryArgs {
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    /// Print in JSON instead of a table.
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+
+    /// The range of lines, `START,END` or `START,+COUNT`,
+    /// in the file at `HEAD` or at the `--rev`.
+    #[arg(short = 'L', value_name = "START,END", value_parser = parse_line_range)]
+    pub lines: Range<usize>,
+
+    /// Path of the file.
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub(crate) struct BenchArgs {
+    #[command(flatten)]
+    pub common: CommonArgs,
+
+    /// Print in JSON instead of a table.
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+
+    /// The number of times to load the history by each engine.
+    /// The fastest time is reported.
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u64).range(1..))]
+    pub runs: u64,
+
+    /// Path of the file.
+    #[arg(value_name = "PATH", value_parser = FileLocation::parse)]
+    pub location: FileLocation,
+}
+
+/// Parse `START,END` or `START,+COUNT` of 1-based line numbers, both inclusive,
+/// to a `Range` that excludes the end.
+fn parse_line_range(value: &str) -> Result<Range<usize>, String> {
+    let Some((start, end)) = value.split_once(',') else {
+        return Err(format!("Expected START,END: {value}"));
+    };
+    let parse = |value: &str| {
+        value
+            .trim()
+            .parse::<usize>()
+            .map_err(|error| format!("{error}: {value}"))
+    };
+    let invalid = || format!("Invalid range of lines: {value}");
+    let start = parse(start)?;
+    let end = match end.trim().strip_prefix('+') {
+        Some(count) => match parse(count)? {
+            0 => return Err(format!("The count of lines must be positive: {value}")),
+            count => start.checked_add(count).ok_or_else(invalid)?,
+        },
+        None => parse(end)?.checked_add(1).ok_or_else(invalid)?,
+    };
+    if start == 0 || end <= start {
+        return Err(invalid());
+    }
+    Ok(start..end)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, clap::ValueEnum)]
+pub(crate) enum Engine {
+    /// Compute the commit diffs by `git`.
+    Git,
+    /// Compute the commit diffs by `git2`.
+    Git2,
+    /// Attribute lines by `git blame`.
+    /// Deleted lines are not shown in this engine.
+    Blame,
+    /// Attribute lines by the blame of `git2`, the visible lines first.
+    /// Deleted lines are not shown in this engine.
+    Git2Blame,
+    /// Read a few commits by `git` and by `git2`, and use the faster one.
+    Auto,
+}
+
+impl Engine {
+    /// The `GitEngine`, or `None` for `Engine::Auto`.
+    pub fn git_engine(self) -> Option<GitEngine> {
+        match self {
+            Engine::Git => Some(GitEngine::Git),
+            Engine::Git2 => Some(GitEngine::Git2),
+            Engine::Blame => Some(GitEngine::GitBlame),
+            Engine::Git2Blame => Some(GitEngine::Git2Blame),
+            Engine::Auto => None,
+        }
+    }
+}
+
+/// The formats of the `export` subcommand.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub(crate) enum ExportFormat {
+    /// The same columns as the left pane of the interactive UI.
+    #[default]
+    Plain,
+    /// The format of `git blame --porcelain`.
+    Porcelain,
+    /// The format of `git blame --line-porcelain`.
+    LinePorcelain,
+    /// Comma-separated values of the line number, the commit ID, the author,
+    /// the email, the date, and the content, with a header row.
+    Csv,
+    /// The same as `csv`, separated by tabs.
+    Tsv,
+    /// The lines and the commits they're attributed to, in JSON.
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub(crate) enum ColorWhen {
+    /// Use colors unless `NO_COLOR` is set, or the output isn't a terminal.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorWhen {
+    pub fn is_color(&self) -> bool {
+        match self {
+            ColorWhen::Auto => {
+                env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                    && stdout().is_terminal()
+            }
+            ColorWhen::Always => true,
+            ColorWhen::Never => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subcommand() -> anyhow::Result<()> {
+        let args = Args::try_parse_from(["git-iblame", "src/main.rs"])?;
+        assert!(args.subcommand.is_none());
+        assert!(args.blame.location.is_some());
+        let args = Args::try_parse_from(["git-iblame", "blame", "--watch", "src/main.rs"])?;
+        assert!(matches!(
+            args.subcommand,
+            Some(Subcommand::Blame(BlameArgs { watch: true, .. }))
+        ));
+        let args = Args::try_parse_from(["git-iblame", "log", "--no-follow", "src/main.rs"])?;
+        assert!(matches!(args.subcommand, Some(Subcommand::Log(_))));
+        let args = Args::try_parse_from(["git-iblame", "export", "--format", "porcelain", "a"])?;
+        assert!(matches!(
+            args.subcommand,
+            Some(Subcommand::Export(ExportArgs {
+                format: ExportFormat::Porcelain,
+                ..
+            }))
+        ));
+        let args = Args::try_parse_from(["git-iblame", "stats", "--json", "src/main.rs"])?;
+        assert!(matches!(
+            args.subcommand,
+            Some(Subcommand::Stats(StatsArgs { json: true, .. }))
+        ));
+        let args =
+            Args::try_parse_from(["git-iblame", "who", "--rev", "main", "src/main.rs", "3"])?;
+        let Some(Subcommand::Who(who)) = args.subcommand else {
+            anyhow::bail!("Not who: {:?}", args.subcommand);
+        };
+        assert_eq!(who.line, 3);
+        assert_eq!(who.common.rev.as_deref(), Some("main"));
+
+        let args = Args::try_parse_from(["git-iblame", "history", "-L", "3,5", "src/main.rs"])?;
+        assert!(matches!(
+            args.subcommand,
+            Some(Subcommand::History(HistoryArgs {
+                lines: Range { start: 3, end: 6 },
+                json: false,
+                ..
+            }))
+        ));
+
+        let args = Args::try_parse_from(["git-iblame", "bench", "--engine", "git", "a"])?;
+        let Some(Subcommand::Bench(bench)) = args.subcommand else {
+            anyhow::bail!("Not bench: {:?}", args.subcommand);
+        };
+        assert_eq!(bench.runs, 3);
+        assert_eq!(bench.common.engine(), Some(Engine::Git));
+
+        assert!(Args::try_parse_from(["git-iblame"]).is_err());
+        assert!(Args::try_parse_from(["git-iblame", "bench", "--runs", "0", "a"]).is_err());
+        assert!(Args::try_parse_from(["git-iblame", "who", "src/main.rs", "0"]).is_err());
+        assert!(Args::try_parse_from(["git-iblame", "--watch", "log", "src/main.rs"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_line_range() {
+        assert_eq!(super::parse_line_range("3,5"), Ok(3..6));
+        assert_eq!(super::parse_line_range("3, 3"), Ok(3..4));
+        assert_eq!(super::parse_line_range("3,+2"), Ok(3..5));
+        assert!(super::parse_line_range("3").is_err());
+        assert!(super::parse_line_range("0,2").is_err());
+        assert!(super::parse_line_range("5,3").is_err());
+        assert!(super::parse_line_range("a,3").is_err());
+        assert!(super::parse_line_range("3,+0").is_err());
+        assert!(super::parse_line_range(&format!("3,+{}", usize::MAX)).is_err());
+        assert!(super::parse_line_range(&format!("3,{}", usize::MAX)).is_err());
+    }
+}
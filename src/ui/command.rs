@@ -2,20 +2,67 @@
 pub enum Command {
     PrevLine,
     NextLine,
+    PrevSameCommit,
+    NextSameCommit,
+    /// Move to the first line of a hunk.
+    PrevDiff,
+    NextDiff,
+    /// Move by a row of the minimap.
+    PrevMinimapRow,
+    NextMinimapRow,
     PrevPage,
     NextPage,
     FirstLine,
     LastLine,
     Older,
     Newer,
+    /// Like `Older`, but select the lines the current line replaced.
+    BlamePrior,
+    /// Move to the parent or the child commit in the log.
+    LogParent,
+    LogChild,
+    JumpBack,
+    JumpForward,
     LineNumber(usize),
+    /// Repeat the command by the count prefix, such as `12j`.
+    Repeat(usize, Box<Command>),
     Search(String),
+    SearchCommit(String),
+    /// A command line entered after `:`, like `ex` commands of `vi`.
+    Ex(String),
     SearchPrev,
     SearchNext,
     Copy,
+    CopyPermalink,
+    /// Choose what to copy by the next key.
+    CopyMenu,
+    Browse,
+    ToggleSelection,
+    ClearSelection,
+    TogglePin,
     ShowCommit,
     ShowDiff,
+    ShowSideBySide,
+    /// Open the current line commit and its parent in the external diff tool.
+    DiffTool,
     Log,
+    ToggleTimeFormat,
+    ToggleAuthorFormat,
+    ToggleDetailPane,
+    /// Toggle the pane to show recent warnings and errors.
+    ToggleLogPane,
+    ToggleMinimap,
+    /// Pause or resume loading the history.
+    ToggleReadPause,
+    NarrowLeftPane,
+    WidenLeftPane,
+    ToggleFold,
+    ExpandFold,
+    /// Start recording commands to the register by the next key,
+    /// or stop recording.
+    RecordMacro,
+    /// Replay the commands in the register by the next key.
+    ReplayMacro,
     Repaint,
     Resize(u16, u16),
     Help,
@@ -23,3 +70,92 @@ pub enum Command {
     Timeout,
     Debug,
 }
+
+impl Command {
+    /// Whether the command may move the current line far,
+    /// so that `Command::JumpBack` should go back to the position before it.
+    pub fn is_jump(&self) -> bool {
+        if let Command::Repeat(_, command) = self {
+            return command.is_jump();
+        }
+        matches!(
+            self,
+            Command::PrevSameCommit
+                | Command::NextSameCommit
+                | Command::PrevMinimapRow
+                | Command::NextMinimapRow
+                | Command::FirstLine
+                | Command::LastLine
+                | Command::Older
+                | Command::Newer
+                | Command::BlamePrior
+                | Command::LogParent
+                | Command::LogChild
+                | Command::LineNumber(_)
+                | Command::Search(_)
+                | Command::SearchCommit(_)
+                | Command::SearchPrev
+                | Command::SearchNext
+                | Command::Log
+                | Command::Ex(_)
+                | Command::ReplayMacro
+        )
+    }
+
+    /// Whether the command can be repeated by a count prefix.
+    pub fn is_repeatable(&self) -> bool {
+        matches!(
+            self,
+            Command::PrevLine
+                | Command::NextLine
+                | Command::PrevSameCommit
+                | Command::NextSameCommit
+                | Command::PrevDiff
+                | Command::NextDiff
+                | Command::PrevMinimapRow
+                | Command::NextMinimapRow
+                | Command::PrevPage
+                | Command::NextPage
+                | Command::Older
+                | Command::Newer
+                | Command::LogParent
+                | Command::LogChild
+                | Command::JumpBack
+                | Command::JumpForward
+                | Command::SearchPrev
+                | Command::SearchNext
+                | Command::NarrowLeftPane
+                | Command::WidenLeftPane
+        )
+    }
+
+    /// Whether the command interacts with the terminal or other applications,
+    /// which isn't available in scripts.
+    pub fn needs_terminal(&self) -> bool {
+        matches!(
+            self,
+            Command::Copy
+                | Command::CopyPermalink
+                | Command::CopyMenu
+                | Command::Browse
+                | Command::RecordMacro
+                | Command::ReplayMacro
+                | Command::ShowCommit
+                | Command::ShowDiff
+                | Command::ShowSideBySide
+                | Command::DiffTool
+                | Command::Help
+        )
+    }
+
+    /// The command for the `count` prefix, such as `12j` or `5G`.
+    pub fn with_count(self, count: usize) -> Command {
+        match self {
+            Command::FirstLine | Command::LastLine => Command::LineNumber(count),
+            command if count > 1 && command.is_repeatable() => {
+                Command::Repeat(count, Box::new(command))
+            }
+            command => command,
+        }
+    }
+}
@@ -1,8 +1,10 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fs, path::Path};
 
+use anyhow::Context;
 use crossterm::event::{KeyCode, KeyModifiers};
 
 use super::*;
+use crate::extensions::config_dir;
 
 #[derive(Debug, Default)]
 pub struct CommandKeyMap {
@@ -16,6 +18,135 @@ impl CommandKeyMap {
         }
     }
 
+    /// The file name of the key bindings in the `config_dir()`.
+    pub const CONFIG_FILE_NAME: &str = "keys.toml";
+
+    /// Create the default key map, overridden by the key bindings file at the
+    /// `path`. If the `path` is `None`, the `CONFIG_FILE_NAME` in the
+    /// `config_dir()` is used if it exists.
+    pub fn new_with_config(path: Option<&Path>) -> anyhow::Result<Self> {
+        let mut key_map = Self::new();
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match config_dir().map(|dir| dir.join(Self::CONFIG_FILE_NAME)) {
+                Some(path) if path.exists() => path,
+                _ => return Ok(key_map),
+            },
+        };
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        key_map
+            .apply_config(&content)
+            .with_context(|| format!("Invalid key bindings in {}", path.display()))?;
+        Ok(key_map)
+    }
+
+    /// Apply the key bindings in the TOML `content`.
+    /// The `keys` table maps keys to the names in the `CommandRegistry`,
+    /// or `none` to remove the default binding;
+    /// e.g.,
+    /// ```toml
+    /// [keys]
+    /// "x" = "older"
+    /// "ctrl+o" = "none"
+    /// ```
+    fn apply_config(&mut self, content: &str) -> anyhow::Result<()> {
+        let table: toml::Table = content.parse()?;
+        let Some(keys) = table.get("keys") else {
+            return Ok(());
+        };
+        let Some(keys) = keys.as_table() else {
+            anyhow::bail!("\"keys\" must be a table");
+        };
+        let mut overrides: HashMap<(KeyCode, KeyModifiers), Option<Command>> = HashMap::new();
+        for (key_str, value) in keys {
+            let key = Self::key_from_str(key_str)?;
+            if Self::is_reserved_key(key) {
+                anyhow::bail!("The key \"{key_str}\" is reserved for the command line");
+            }
+            let Some(name) = value.as_str() else {
+                anyhow::bail!("The command for \"{key_str}\" must be a string");
+            };
+            let command = if name == "none" {
+                None
+            } else {
+                let named = CommandRegistry::find(name)?;
+                let Some(command) = named.command() else {
+                    anyhow::bail!("The command \"{name}\" takes arguments and can't be bound");
+                };
+                Some(command.clone())
+            };
+            if overrides.insert(key, command).is_some() {
+                anyhow::bail!("Duplicate key found: \"{key_str}\"");
+            }
+        }
+        for (key, command) in overrides {
+            match command {
+                Some(command) => self.map.insert(key, command),
+                None => self.map.remove(&key),
+            };
+        }
+        Ok(())
+    }
+
+    /// Whether the key starts the command line in `CommandUI`,
+    /// such as `/` or digits.
+    fn is_reserved_key((key, modifiers): (KeyCode, KeyModifiers)) -> bool {
+        matches!(key, KeyCode::Char(ch) if ch == '/' || ch == '?' || ch == ':' || ch.is_ascii_digit())
+            && (modifiers - KeyModifiers::SHIFT).is_empty()
+    }
+
+    /// Parse a key such as "x", "X", "ctrl+o", "alt+up", or "enter".
+    fn key_from_str(s: &str) -> anyhow::Result<(KeyCode, KeyModifiers)> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        while let Some((modifier, key)) = rest.split_once('+')
+            && !key.is_empty()
+        {
+            modifiers |= match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => anyhow::bail!("Unknown modifier \"{modifier}\" in \"{s}\""),
+            };
+            rest = key;
+        }
+        let mut chars = rest.chars();
+        let mut key = match (chars.next(), chars.next()) {
+            (Some(ch), None) => KeyCode::Char(ch),
+            _ => match rest.to_ascii_lowercase().as_str() {
+                "enter" => KeyCode::Enter,
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                "esc" => KeyCode::Esc,
+                "space" => KeyCode::Char(' '),
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "insert" => KeyCode::Insert,
+                "delete" => KeyCode::Delete,
+                name => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n) => KeyCode::F(n),
+                    None => anyhow::bail!("Unknown key \"{rest}\" in \"{s}\""),
+                },
+            },
+        };
+        // Terminals report upper case letters with `Shift`.
+        if let KeyCode::Char(ch) = key {
+            if ch.is_ascii_uppercase() {
+                modifiers |= KeyModifiers::SHIFT;
+            } else if modifiers.contains(KeyModifiers::SHIFT) && ch.is_ascii_lowercase() {
+                key = KeyCode::Char(ch.to_ascii_uppercase());
+            }
+        }
+        Ok((key, modifiers))
+    }
+
     pub fn get(&self, key_code: KeyCode, modifiers: KeyModifiers) -> Option<&Command> {
         self.map.get(&(key_code, modifiers))
     }
@@ -27,12 +158,34 @@ impl CommandKeyMap {
                 println!("\n        {heading}\n");
                 continue;
             }
-            let key_str = match command {
-                Command::LineNumber(_) => "[number] + Enter".to_string(),
-                _ => key_str_from_command.get(command).unwrap().clone(),
+            let mut keys: Vec<String> = match command {
+                Command::LineNumber(_) => vec!["[number] + Enter".to_string()],
+                _ => key_str_from_command
+                    .get(command)
+                    .cloned()
+                    .into_iter()
+                    .collect(),
             };
+            if let Some(name) = CommandRegistry::name_from_command(command) {
+                keys.push(format!(":{name}"));
+            }
+            let key_str = keys.join(", ");
             println!("  {key_str:<20} {help}");
         }
+
+        println!("\n        COMMAND LINE\n");
+        for named in CommandRegistry::commands() {
+            if named.command().is_some() {
+                continue;
+            }
+            let usage = format!(":{} {}", named.name(), named.usage());
+            println!("  {usage:<20} {}", named.help());
+        }
+        println!("  {:<20} Complete the command name or the argument.", "Tab");
+        println!(
+            "  {:<20} Recall the previous or the next command line.",
+            "Up, Down"
+        );
     }
 
     fn key_str_from_command(&self) -> HashMap<Command, String> {
@@ -106,23 +259,49 @@ impl CommandKeyMap {
             ((KeyCode::Char('q'), KeyModifiers::NONE), Command::Quit),
 
             ((KeyCode::Char('c'), KeyModifiers::NONE), Command::Copy),
+            ((KeyCode::Char('C'), KeyModifiers::SHIFT), Command::CopyPermalink),
+            ((KeyCode::Char('y'), KeyModifiers::NONE), Command::CopyMenu),
             ((KeyCode::Char('d'), KeyModifiers::NONE), Command::ShowDiff),
+            ((KeyCode::Char('D'), KeyModifiers::SHIFT), Command::ShowSideBySide),
+            ((KeyCode::Char('e'), KeyModifiers::NONE), Command::DiffTool),
+            ((KeyCode::Char('i'), KeyModifiers::NONE), Command::ToggleDetailPane),
+            ((KeyCode::Char('L'), KeyModifiers::SHIFT), Command::ToggleLogPane),
             ((KeyCode::Char('l'), KeyModifiers::NONE), Command::Log),
+            ((KeyCode::Char('M'), KeyModifiers::SHIFT), Command::ToggleMinimap),
+            ((KeyCode::Char('W'), KeyModifiers::SHIFT), Command::ToggleReadPause),
+            ((KeyCode::Char('<'), KeyModifiers::NONE), Command::NarrowLeftPane),
+            ((KeyCode::Char('>'), KeyModifiers::NONE), Command::WidenLeftPane),
+            ((KeyCode::Char('o'), KeyModifiers::NONE), Command::ExpandFold),
+            ((KeyCode::Char('O'), KeyModifiers::SHIFT), Command::Browse),
             ((KeyCode::Enter, KeyModifiers::CONTROL), Command::ShowDiff),
             ((KeyCode::Char('s'), KeyModifiers::NONE), Command::ShowCommit),
+            ((KeyCode::Char('t'), KeyModifiers::NONE), Command::ToggleTimeFormat),
+            ((KeyCode::Char('a'), KeyModifiers::NONE), Command::ToggleAuthorFormat),
+            ((KeyCode::Char('Z'), KeyModifiers::SHIFT), Command::ToggleFold),
+            ((KeyCode::Char('V'), KeyModifiers::SHIFT), Command::ToggleSelection),
+            ((KeyCode::Char('P'), KeyModifiers::SHIFT), Command::TogglePin),
+            ((KeyCode::Esc, KeyModifiers::NONE), Command::ClearSelection),
 
             ((KeyCode::Enter, KeyModifiers::NONE), Command::Older),
             ((KeyCode::Right, KeyModifiers::NONE), Command::Older),
             ((KeyCode::Backspace, KeyModifiers::NONE), Command::Newer),
             ((KeyCode::Left, KeyModifiers::NONE), Command::Newer),
+            ((KeyCode::Char('p'), KeyModifiers::NONE), Command::BlamePrior),
+            ((KeyCode::Char('o'), KeyModifiers::CONTROL), Command::JumpBack),
+            // `Ctrl-I` is the same as `Tab` in terminals.
+            ((KeyCode::Tab, KeyModifiers::NONE), Command::JumpForward),
 
             // `vi`, `emacs`, or `less`-like key bindings.
             ((KeyCode::Char('b'), KeyModifiers::NONE), Command::PrevPage),
             ((KeyCode::Char('b'), KeyModifiers::CONTROL), Command::PrevPage),
             ((KeyCode::Char('f'), KeyModifiers::NONE), Command::NextPage),
             ((KeyCode::Char('f'), KeyModifiers::CONTROL), Command::NextPage),
+            ((KeyCode::Char('g'), KeyModifiers::NONE), Command::FirstLine),
+            ((KeyCode::Char('G'), KeyModifiers::SHIFT), Command::LastLine),
             ((KeyCode::Char('j'), KeyModifiers::NONE), Command::NextLine),
             ((KeyCode::Char('k'), KeyModifiers::NONE), Command::PrevLine),
+            ((KeyCode::Char('J'), KeyModifiers::SHIFT), Command::LogParent),
+            ((KeyCode::Char('K'), KeyModifiers::SHIFT), Command::LogChild),
             ((KeyCode::Char('l'), KeyModifiers::CONTROL), Command::Repaint),
             ((KeyCode::Char('n'), KeyModifiers::CONTROL), Command::NextLine),
             ((KeyCode::Char('p'), KeyModifiers::CONTROL), Command::PrevLine),
@@ -131,14 +310,24 @@ impl CommandKeyMap {
 
             ((KeyCode::Up, KeyModifiers::NONE), Command::PrevLine),
             ((KeyCode::Down, KeyModifiers::NONE), Command::NextLine),
+            ((KeyCode::Up, KeyModifiers::SHIFT), Command::PrevDiff),
+            ((KeyCode::Down, KeyModifiers::SHIFT), Command::NextDiff),
             ((KeyCode::PageUp, KeyModifiers::NONE), Command::PrevPage),
             ((KeyCode::PageDown, KeyModifiers::NONE), Command::NextPage),
             ((KeyCode::Home, KeyModifiers::NONE), Command::FirstLine),
             ((KeyCode::End, KeyModifiers::NONE), Command::LastLine),
 
+            ((KeyCode::Char('['), KeyModifiers::NONE), Command::PrevSameCommit),
+            ((KeyCode::Char(']'), KeyModifiers::NONE), Command::NextSameCommit),
+            ((KeyCode::Char('{'), KeyModifiers::NONE), Command::PrevMinimapRow),
+            ((KeyCode::Char('}'), KeyModifiers::NONE), Command::NextMinimapRow),
+
             ((KeyCode::Char('N'), KeyModifiers::SHIFT), Command::SearchPrev),
             ((KeyCode::Char('n'), KeyModifiers::NONE), Command::SearchNext),
 
+            ((KeyCode::Char('Q'), KeyModifiers::SHIFT), Command::RecordMacro),
+            ((KeyCode::Char('@'), KeyModifiers::NONE), Command::ReplayMacro),
+
             ((KeyCode::Char('z'), KeyModifiers::NONE), Command::Debug),
         ]
     }
@@ -148,30 +337,65 @@ impl CommandKeyMap {
         &[
             ("Show this help.", Command::Help),
             ("Quit the program.", Command::Quit),
+            ("Pause or resume loading the history.", Command::ToggleReadPause),
 
             ("#COMMITS", Command::ShowCommit),
             ("Show the current line commit.", Command::ShowCommit),
-            ("Show the current file of the current line commit.", Command::ShowDiff),
-            ("Show the list of commits.", Command::Log),
-            ("Copy the current line commit ID to clipboard.", Command::Copy),
+            ("Show the diff of the current file of the current line commit, of the selected lines, or between the selected commits in the log.", Command::ShowDiff),
+            ("Show the current file at the current line commit and at its parent side by side.", Command::ShowSideBySide),
+            ("Open the current file at the current line commit and at its parent in the external diff tool.", Command::DiffTool),
+            ("Show the list of commits, or the commits of the selected lines.", Command::Log),
+            ("Toggle the pane to show the current line commit details.", Command::ToggleDetailPane),
+            ("Toggle the pane to show recent warnings and errors.", Command::ToggleLogPane),
+            ("Toggle the minimap of the hunks and the current line commit in the whole file.", Command::ToggleMinimap),
+            ("Narrow the left pane.", Command::NarrowLeftPane),
+            ("Widen the left pane.", Command::WidenLeftPane),
+            ("Copy the current line commit ID, or the selected lines, to clipboard.", Command::Copy),
+            ("Copy the URL of the current or the selected lines on GitHub, GitLab, or Gitea.", Command::CopyPermalink),
+            ("Open the current line commit, or the selected lines, in the browser.", Command::Browse),
+            ("Copy the commit ID, the short ID, the path:line, the line, or the URL by the next key.", Command::CopyMenu),
+            ("Toggle absolute and relative commit times.", Command::ToggleTimeFormat),
+            ("Switch the authors between the email, the name, the initials, and both.", Command::ToggleAuthorFormat),
+            ("Fold lines not changed by the current line commit, or unfold.", Command::ToggleFold),
+            ("Expand the folded lines at the current line.", Command::ExpandFold),
+            ("Pin the current line commit to highlight its lines, or unpin.", Command::TogglePin),
+
+            ("#SELECTING LINES", Command::ToggleSelection),
+            ("Start selecting lines, or stop selecting.", Command::ToggleSelection),
+            ("Stop selecting lines.", Command::ClearSelection),
 
             ("#TRAVERSING TREES", Command::Older),
             ("Show the parent tree of the current line commit.", Command::Older),
             ("Back to the last tree.", Command::Newer),
+            ("Show the parent tree of the current line commit, and select the lines the current line replaced.", Command::BlamePrior),
 
             ("#MOVING", Command::NextLine),
-            ("Move to the next diff.", Command::NextLine),
-            ("Move to the previous diff.", Command::PrevLine),
+            ("Move to the next line.", Command::NextLine),
+            ("Move to the previous line.", Command::PrevLine),
+            ("Move to the first line of the next hunk.", Command::NextDiff),
+            ("Move to the first line of the current or the previous hunk.", Command::PrevDiff),
+            ("Move to the next line of the current line commit.", Command::NextSameCommit),
+            ("Move to the previous line of the current line commit.", Command::PrevSameCommit),
+            ("In the log, move to the parent commit.", Command::LogParent),
+            ("In the log, move to the child commit.", Command::LogChild),
+            ("Move to the next row of the minimap.", Command::NextMinimapRow),
+            ("Move to the previous row of the minimap.", Command::PrevMinimapRow),
             ("Move to the next page.", Command::NextPage),
             ("Move to the previous page.", Command::PrevPage),
             ("Move to the first line.", Command::FirstLine),
             ("Move to the last line.", Command::LastLine),
-            ("Move to the line number.", Command::LineNumber(0)),
+            ("Move to the line number. A number before moving keys repeats them; e.g., 12j.", Command::LineNumber(0)),
+            ("Go back to the position before the last jump.", Command::JumpBack),
+            ("Go forward to the position after going back.", Command::JumpForward),
             ("Repaint the screen.", Command::Repaint),
 
             ("#SEARCHING", Command::SearchNext),
             ("Repeat previous search.", Command::SearchNext),
             ("Repeat previous search in reverse direction.", Command::SearchPrev),
+
+            ("#MACROS", Command::RecordMacro),
+            ("Record the commands to the register by the next key, or stop recording.", Command::RecordMacro),
+            ("Replay the commands in the register by the next key. @@ replays the last one.", Command::ReplayMacro),
         ]
     }
 }
@@ -189,6 +413,72 @@ mod tests {
         }
     }
 
+    #[test]
+    fn key_from_str() -> anyhow::Result<()> {
+        let target = CommandKeyMap::key_from_str;
+        assert_eq!(target("x")?, (KeyCode::Char('x'), KeyModifiers::NONE));
+        assert_eq!(target("X")?, (KeyCode::Char('X'), KeyModifiers::SHIFT));
+        assert_eq!(
+            target("shift+x")?,
+            (KeyCode::Char('X'), KeyModifiers::SHIFT)
+        );
+        assert_eq!(
+            target("ctrl+o")?,
+            (KeyCode::Char('o'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(target("Alt+Up")?, (KeyCode::Up, KeyModifiers::ALT));
+        assert_eq!(target("+")?, (KeyCode::Char('+'), KeyModifiers::NONE));
+        assert_eq!(
+            target("ctrl++")?,
+            (KeyCode::Char('+'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(target("space")?, (KeyCode::Char(' '), KeyModifiers::NONE));
+        assert_eq!(target("f5")?, (KeyCode::F(5), KeyModifiers::NONE));
+        assert!(target("hyper+x").is_err());
+        assert!(target("unknown").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn apply_config() -> anyhow::Result<()> {
+        let mut key_map = CommandKeyMap::new();
+        key_map.apply_config(
+            r#"
+            [keys]
+            "x" = "older"
+            "ctrl+o" = "none"
+            "Enter" = "log"
+            "#,
+        )?;
+        assert_eq!(
+            key_map.get(KeyCode::Char('x'), KeyModifiers::NONE),
+            Some(&Command::Older)
+        );
+        assert_eq!(key_map.get(KeyCode::Char('o'), KeyModifiers::CONTROL), None);
+        assert_eq!(
+            key_map.get(KeyCode::Enter, KeyModifiers::NONE),
+            Some(&Command::Log)
+        );
+        // Other keys are not changed.
+        assert_eq!(
+            key_map.get(KeyCode::Right, KeyModifiers::NONE),
+            Some(&Command::Older)
+        );
+
+        let mut key_map = CommandKeyMap::new();
+        assert!(key_map.apply_config("[keys]\nx = \"unknown\"").is_err());
+        assert!(key_map.apply_config("[keys]\nx = \"commit\"").is_err());
+        assert!(key_map.apply_config("[keys]\n\"/\" = \"log\"").is_err());
+        assert!(
+            key_map
+                .apply_config("[keys]\nX = \"log\"\n\"shift+x\" = \"log\"")
+                .is_err()
+        );
+        assert!(key_map.apply_config("keys = 1").is_err());
+        assert!(key_map.apply_config("").is_ok());
+        Ok(())
+    }
+
     #[test]
     fn key_str_from_keys() {
         assert_eq!(
@@ -5,11 +5,18 @@ use std::{
 
 use crossterm::{cursor, queue, style, terminal};
 
+use crate::blame::ReadProgress;
+
+use super::Theme;
+
 #[derive(Debug, Default)]
 pub enum CommandPrompt {
     #[default]
     None,
-    Loading,
+    Loading {
+        progress: ReadProgress,
+        is_paused: bool,
+    },
     Message {
         message: String,
     },
@@ -19,7 +26,14 @@ pub enum CommandPrompt {
 }
 
 impl CommandPrompt {
-    pub fn show(&self, row: u16, buffer: &str) -> anyhow::Result<()> {
+    pub fn show(
+        &self,
+        row: u16,
+        buffer: &str,
+        hint: &str,
+        status: &str,
+        theme: &Theme,
+    ) -> anyhow::Result<()> {
         let mut out = stdout();
         queue!(
             out,
@@ -29,9 +43,17 @@ impl CommandPrompt {
         let mut suppress_help = false;
         match self {
             CommandPrompt::None => {}
-            CommandPrompt::Loading => {
-                let icon = Self::loading_indicator()?;
-                queue!(out, style::Print(icon.to_string()),)?;
+            CommandPrompt::Loading {
+                progress,
+                is_paused,
+            } => {
+                let icon = if *is_paused {
+                    "Paused".to_string()
+                } else {
+                    Self::loading_indicator()?.to_string()
+                };
+                let bar = Self::progress_bar(progress.ratio());
+                queue!(out, style::Print(format!("{icon} {bar} {progress} ")))?;
             }
             CommandPrompt::Message { message } => {
                 queue!(out, style::Print(message.to_string()),)?;
@@ -41,24 +63,42 @@ impl CommandPrompt {
                 let error_message = error.to_string();
                 queue!(
                     out,
-                    style::SetColors(style::Colors::new(style::Color::White, style::Color::Red)),
+                    style::SetStyle(theme.error()),
                     style::Print(error_message),
-                    style::ResetColor
+                    style::ResetColor,
+                    style::SetAttribute(style::Attribute::Reset),
                 )?;
                 suppress_help = true;
             }
         }
-        if buffer.starts_with('/') {
+        if buffer.starts_with(['/', '?', ':']) {
             queue!(out, style::Print(buffer))?;
+            if !hint.is_empty() {
+                queue!(
+                    out,
+                    cursor::SavePosition,
+                    style::SetStyle(theme.hint()),
+                    style::Print(hint),
+                    style::ResetColor,
+                    style::SetAttribute(style::Attribute::Reset),
+                    cursor::RestorePosition,
+                )?;
+            }
         } else {
             queue!(out, style::Print(format!(":{buffer}")))?;
             if !suppress_help && buffer.is_empty() {
+                let help = if status.is_empty() {
+                    "h(elp), q(uit), Right=parent, s(how), d(iff)"
+                } else {
+                    status
+                };
                 queue!(
                     out,
                     cursor::SavePosition,
-                    style::SetForegroundColor(style::Color::DarkGrey),
-                    style::Print("h(elp), q(uit), Right=parent, s(how), d(iff)"),
+                    style::SetStyle(theme.hint()),
+                    style::Print(help),
                     style::ResetColor,
+                    style::SetAttribute(style::Attribute::Reset),
                     cursor::RestorePosition,
                 )?;
             }
@@ -68,6 +108,18 @@ impl CommandPrompt {
     }
 
     const ICON_CYCLE: &str = r"-\|/";
+    const PROGRESS_BAR_WIDTH: usize = 10;
+
+    /// A bar of the `ratio` in the range of 0 to 1; e.g., "[###       ]".
+    fn progress_bar(ratio: f64) -> String {
+        let filled = (ratio * Self::PROGRESS_BAR_WIDTH as f64) as usize;
+        let filled = filled.min(Self::PROGRESS_BAR_WIDTH);
+        format!(
+            "[{}{}]",
+            "#".repeat(filled),
+            " ".repeat(Self::PROGRESS_BAR_WIDTH - filled)
+        )
+    }
 
     fn loading_indicator() -> anyhow::Result<char> {
         let now = SystemTime::now();
@@ -77,3 +129,15 @@ impl CommandPrompt {
         Ok(icon)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_bar() {
+        assert_eq!(CommandPrompt::progress_bar(0.0), "[          ]");
+        assert_eq!(CommandPrompt::progress_bar(0.35), "[###       ]");
+        assert_eq!(CommandPrompt::progress_bar(1.0), "[##########]");
+    }
+}
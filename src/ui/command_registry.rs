@@ -0,0 +1,261 @@
+use super::*;
+
+/// A command that can be run by its name from the `:` command line.
+#[derive(Debug)]
+pub(crate) struct NamedCommand {
+    name: &'static str,
+    /// The `Command` to run. `None` if the command takes arguments,
+    /// which `Cli` handles by the `name`.
+    command: Option<Command>,
+    /// The arguments, shown in the help.
+    usage: &'static str,
+    /// The help for commands without a `Command`.
+    /// Commands with a `Command` use the help of the `CommandKeyMap`.
+    help: &'static str,
+    /// The keywords to complete for the arguments, such as `author=`.
+    keywords: &'static [&'static str],
+}
+
+impl NamedCommand {
+    const fn new(name: &'static str, command: Command) -> Self {
+        Self {
+            name,
+            command: Some(command),
+            usage: "",
+            help: "",
+            keywords: &[],
+        }
+    }
+
+    const fn with_args(name: &'static str, usage: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            command: None,
+            usage,
+            help,
+            keywords: &[],
+        }
+    }
+
+    const fn with_keywords(mut self, keywords: &'static [&'static str]) -> Self {
+        self.keywords = keywords;
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn command(&self) -> Option<&Command> {
+        self.command.as_ref()
+    }
+
+    pub fn usage(&self) -> &'static str {
+        self.usage
+    }
+
+    pub fn help(&self) -> &'static str {
+        self.help
+    }
+}
+
+/// The list of `NamedCommand`, to run them from the `:` command line,
+/// to complete their names, and to show them in the help.
+#[derive(Debug)]
+pub(crate) struct CommandRegistry;
+
+impl CommandRegistry {
+    #[rustfmt::skip]
+    const COMMANDS: &[NamedCommand] = &[
+        NamedCommand::new("help", Command::Help),
+        NamedCommand::new("quit", Command::Quit),
+
+        NamedCommand::new("show", Command::ShowCommit),
+        NamedCommand::new("diff", Command::ShowDiff),
+        NamedCommand::new("sidebyside", Command::ShowSideBySide),
+        NamedCommand::new("difftool", Command::DiffTool),
+        NamedCommand::new("log", Command::Log),
+        NamedCommand::new("detail", Command::ToggleDetailPane),
+        NamedCommand::new("messages", Command::ToggleLogPane),
+        NamedCommand::new("minimap", Command::ToggleMinimap),
+        NamedCommand::new("pause", Command::ToggleReadPause),
+        NamedCommand::new("narrow", Command::NarrowLeftPane),
+        NamedCommand::new("widen", Command::WidenLeftPane),
+        NamedCommand::new("copy", Command::Copy),
+        NamedCommand::new("permalink", Command::CopyPermalink),
+        NamedCommand::new("yank", Command::CopyMenu),
+        NamedCommand::new("browse", Command::Browse),
+        NamedCommand::new("select", Command::ToggleSelection),
+        NamedCommand::new("pin", Command::TogglePin),
+        NamedCommand::new("time", Command::ToggleTimeFormat),
+        NamedCommand::new("author", Command::ToggleAuthorFormat),
+        NamedCommand::new("older", Command::Older),
+        NamedCommand::new("newer", Command::Newer),
+        NamedCommand::new("prior", Command::BlamePrior),
+        NamedCommand::new("prev", Command::PrevLine),
+        NamedCommand::new("next", Command::NextLine),
+        NamedCommand::new("prevpage", Command::PrevPage),
+        NamedCommand::new("nextpage", Command::NextPage),
+        NamedCommand::new("prevsame", Command::PrevSameCommit),
+        NamedCommand::new("nextsame", Command::NextSameCommit),
+        NamedCommand::new("prevdiff", Command::PrevDiff),
+        NamedCommand::new("nextdiff", Command::NextDiff),
+        NamedCommand::new("parent", Command::LogParent),
+        NamedCommand::new("child", Command::LogChild),
+        NamedCommand::new("prevmap", Command::PrevMinimapRow),
+        NamedCommand::new("nextmap", Command::NextMinimapRow),
+        NamedCommand::new("first", Command::FirstLine),
+        NamedCommand::new("last", Command::LastLine),
+        NamedCommand::new("back", Command::JumpBack),
+        NamedCommand::new("forward", Command::JumpForward),
+        NamedCommand::new("searchprev", Command::SearchPrev),
+        NamedCommand::new("searchnext", Command::SearchNext),
+        NamedCommand::new("togglefold", Command::ToggleFold),
+        NamedCommand::new("expand", Command::ExpandFold),
+        NamedCommand::new("repaint", Command::Repaint),
+        NamedCommand::new("record", Command::RecordMacro),
+        NamedCommand::new("replay", Command::ReplayMacro),
+
+        NamedCommand::with_args("goto", "<LINE>", "Move to the line number."),
+        NamedCommand::with_args("search", "<PATTERN>", "Search the pattern, like `/`."),
+        NamedCommand::with_args("commit", "<REV>", "Show the tree at the commit ID or the ref."),
+        NamedCommand::with_args("after", "[DATE]", "Dim lines of commits before the date."),
+        NamedCommand::with_args("before", "[DATE]", "Dim lines of commits at or after the date."),
+        NamedCommand::with_args("filter", "<KEY=VALUE>...", "Dim lines of commits not matching the author, after, or before.")
+            .with_keywords(&["author=", "after=", "before="]),
+        NamedCommand::with_args("nofilter", "", "Remove the filters."),
+        NamedCommand::with_args("fold", "[N]", "Fold lines not changed by the current line commit, or the newest N commits."),
+        NamedCommand::with_args("nofold", "", "Unfold all lines."),
+    ];
+
+    pub fn commands() -> &'static [NamedCommand] {
+        Self::COMMANDS
+    }
+
+    /// Find the command by the `name`, or by its unique prefix.
+    pub fn find(name: &str) -> anyhow::Result<&'static NamedCommand> {
+        if let Some(command) = Self::COMMANDS.iter().find(|command| command.name == name) {
+            return Ok(command);
+        }
+        let candidates: Vec<&NamedCommand> = Self::COMMANDS
+            .iter()
+            .filter(|command| !name.is_empty() && command.name.starts_with(name))
+            .collect();
+        match candidates.as_slice() {
+            [command] => Ok(command),
+            [] => anyhow::bail!("Unknown command: {name}"),
+            _ => {
+                let names: Vec<&str> = candidates.iter().map(|command| command.name).collect();
+                anyhow::bail!("Ambiguous command: {name} ({})", names.join(", "))
+            }
+        }
+    }
+
+    /// The name of the `command`, if it can be run by its name.
+    pub fn name_from_command(command: &Command) -> Option<&'static str> {
+        Self::COMMANDS
+            .iter()
+            .find(|named| named.command.as_ref() == Some(command))
+            .map(|named| named.name)
+    }
+
+    /// The candidates to complete the last word of the `line`.
+    /// Each candidate is the whole line after the completion.
+    pub fn complete(line: &str) -> Vec<String> {
+        let Some((head, word)) = line.rsplit_once(' ') else {
+            return Self::COMMANDS
+                .iter()
+                .filter(|command| command.name.starts_with(line))
+                .map(|command| {
+                    let separator = if command.command.is_some() { "" } else { " " };
+                    format!("{}{separator}", command.name)
+                })
+                .collect();
+        };
+        let name = head.split(' ').next().unwrap_or(head);
+        let Some(command) = Self::COMMANDS.iter().find(|command| command.name == name) else {
+            return vec![];
+        };
+        command
+            .keywords
+            .iter()
+            .filter(|keyword| keyword.starts_with(word))
+            .map(|keyword| format!("{head} {keyword}"))
+            .collect()
+    }
+
+    /// The longest common prefix of the `candidates`.
+    pub fn common_prefix(candidates: &[String]) -> &str {
+        let Some((first, rest)) = candidates.split_first() else {
+            return "";
+        };
+        let mut prefix = first.as_str();
+        for candidate in rest {
+            let len = prefix
+                .char_indices()
+                .zip(candidate.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(prefix.len().min(candidate.len()), |((i, _), _)| i);
+            prefix = &prefix[..len];
+        }
+        prefix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find() -> anyhow::Result<()> {
+        assert_eq!(
+            CommandRegistry::find("older")?.command(),
+            Some(&Command::Older)
+        );
+        assert_eq!(CommandRegistry::find("ol")?.name(), "older");
+        assert_eq!(CommandRegistry::find("commit")?.command(), None);
+        assert!(CommandRegistry::find("f").is_err());
+        assert!(CommandRegistry::find("unknown").is_err());
+        assert!(CommandRegistry::find("").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn complete() {
+        assert_eq!(CommandRegistry::complete("ol"), ["older"]);
+        assert_eq!(CommandRegistry::complete("comm"), ["commit "]);
+        assert_eq!(CommandRegistry::complete("fo"), ["forward", "fold "]);
+        assert_eq!(CommandRegistry::complete("filter au"), ["filter author="]);
+        assert_eq!(
+            CommandRegistry::complete("filter author=a "),
+            [
+                "filter author=a author=",
+                "filter author=a after=",
+                "filter author=a before="
+            ]
+        );
+        assert!(CommandRegistry::complete("commit ma").is_empty());
+        assert!(CommandRegistry::complete("x").is_empty());
+    }
+
+    #[test]
+    fn common_prefix() {
+        let target = |candidates: &[&str]| {
+            let candidates: Vec<String> = candidates.iter().map(|s| s.to_string()).collect();
+            CommandRegistry::common_prefix(&candidates).to_string()
+        };
+        assert_eq!(target(&[]), "");
+        assert_eq!(target(&["older"]), "older");
+        assert_eq!(target(&["filter author=", "filter after="]), "filter a");
+        assert_eq!(target(&["forward", "fold "]), "fo");
+    }
+
+    #[test]
+    fn name_from_command() {
+        assert_eq!(
+            CommandRegistry::name_from_command(&Command::Older),
+            Some("older")
+        );
+        assert_eq!(CommandRegistry::name_from_command(&Command::Debug), None);
+    }
+}
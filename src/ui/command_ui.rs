@@ -14,6 +14,16 @@ pub struct CommandUI {
     pub buffer: String,
     pub key_map: CommandKeyMap,
     pub timeout: Duration,
+    pub theme: Theme,
+    /// The status shown instead of the help when idle,
+    /// such as the pinned commit.
+    pub status: String,
+    /// The candidates of the last completion, shown after the `buffer`.
+    hint: String,
+    /// The command lines entered after `:`, the oldest first.
+    history: Vec<String>,
+    /// The index in the `history` shown in the `buffer`.
+    history_index: Option<usize>,
 }
 
 impl CommandUI {
@@ -26,7 +36,8 @@ impl CommandUI {
 
     pub fn read(&mut self, row: u16) -> anyhow::Result<Command> {
         loop {
-            self.prompt.show(row, &self.buffer)?;
+            self.prompt
+                .show(row, &self.buffer, &self.hint, &self.status, &self.theme)?;
             if !self.timeout.is_zero() && !event::poll(self.timeout)? {
                 return Ok(Command::Timeout);
             }
@@ -42,12 +53,35 @@ impl CommandUI {
         }
     }
 
+    /// Show the `prompt`, and read a character key.
+    /// Returns `None` if other keys are pressed.
+    pub fn read_char(&mut self, row: u16, prompt: String) -> anyhow::Result<Option<char>> {
+        self.set_prompt(prompt);
+        self.buffer.clear();
+        self.prompt
+            .show(row, &self.buffer, &self.hint, &self.status, &self.theme)?;
+        self.prompt = CommandPrompt::None;
+        loop {
+            if let event::Event::Key(event) = event::read()?
+                && !event.is_release()
+            {
+                return Ok(match event.code {
+                    event::KeyCode::Char(ch) => Some(ch),
+                    _ => None,
+                });
+            }
+        }
+    }
+
     fn handle_key(&mut self, event: event::KeyEvent) -> Option<Command> {
         if event.is_release() {
             return None;
         }
 
         if !self.buffer.is_empty() {
+            if let Some(command) = self.handle_count_key(event) {
+                return command;
+            }
             if let Some(command) = self.handle_buffer_key(event) {
                 return Some(command);
             }
@@ -62,14 +96,35 @@ impl CommandUI {
         None
     }
 
+    /// Handle a key after a count prefix, such as `j` in `12j`.
+    /// Returns `None` if the `buffer` isn't a count prefix,
+    /// or if the key edits the count.
+    fn handle_count_key(&mut self, event: event::KeyEvent) -> Option<Option<Command>> {
+        let count = self.buffer.parse::<usize>().ok()?;
+        match event.code {
+            event::KeyCode::Char(ch) if ch.is_ascii_digit() => return None,
+            event::KeyCode::Enter | event::KeyCode::Backspace | event::KeyCode::Esc => return None,
+            _ => {}
+        }
+        self.buffer.clear();
+        let command = self.key_map.get(event.code, event.modifiers)?;
+        Some(Some(command.clone().with_count(count)))
+    }
+
     fn handle_buffer_key(&mut self, event: event::KeyEvent) -> Option<Command> {
         assert!(!event.is_release());
 
+        self.hint.clear();
         match event.code {
             event::KeyCode::Char(ch)
-                if (!self.buffer.is_empty() || ch == '/' || ch.is_ascii_digit()) =>
+                if (!self.buffer.is_empty()
+                    || ch == '/'
+                    || ch == '?'
+                    || ch == ':'
+                    || ch.is_ascii_digit()) =>
             {
                 self.buffer.push(ch);
+                self.history_index = None;
             }
             event::KeyCode::Enter => {
                 if let Ok(number) = self.buffer.parse() {
@@ -81,7 +136,21 @@ impl CommandUI {
                     self.buffer.clear();
                     return Some(Command::Search(search));
                 }
+                if let Some(query) = self.buffer.strip_prefix('?') {
+                    let query = query.to_string();
+                    self.buffer.clear();
+                    return Some(Command::SearchCommit(query));
+                }
+                if let Some(line) = self.buffer.strip_prefix(':') {
+                    let line = line.to_string();
+                    self.buffer.clear();
+                    self.add_history(&line);
+                    return Some(Command::Ex(line));
+                }
             }
+            event::KeyCode::Tab if self.buffer.starts_with(':') => self.complete(),
+            event::KeyCode::Up if self.buffer.starts_with(':') => self.move_history(true),
+            event::KeyCode::Down if self.buffer.starts_with(':') => self.move_history(false),
             event::KeyCode::Backspace => {
                 self.buffer.pop();
             }
@@ -91,6 +160,50 @@ impl CommandUI {
         None
     }
 
+    /// Complete the command line in the `buffer` by the `CommandRegistry`.
+    /// If there are multiple candidates, complete their common prefix
+    /// and show them in the `hint`.
+    fn complete(&mut self) {
+        let Some(line) = self.buffer.strip_prefix(':') else {
+            return;
+        };
+        let candidates = CommandRegistry::complete(line);
+        let prefix = CommandRegistry::common_prefix(&candidates);
+        if prefix.len() > line.len() {
+            self.buffer = format!(":{prefix}");
+        }
+        if candidates.len() > 1 {
+            let words: Vec<&str> = candidates
+                .iter()
+                .filter_map(|candidate| candidate.trim_end().rsplit(' ').next())
+                .collect();
+            self.hint = format!("  {}", words.join(" "));
+        }
+    }
+
+    fn add_history(&mut self, line: &str) {
+        self.history_index = None;
+        if line.is_empty() || self.history.last().is_some_and(|last| last == line) {
+            return;
+        }
+        self.history.push(line.to_string());
+    }
+
+    /// Show the previous or the next command line in the `history`.
+    fn move_history(&mut self, is_prev: bool) {
+        let index = match (self.history_index, is_prev) {
+            (None, true) => self.history.len().checked_sub(1),
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) => Some(index + 1).filter(|i| *i < self.history.len()),
+        };
+        self.history_index = index;
+        self.buffer = match index {
+            Some(index) => format!(":{}", self.history[index]),
+            None => ":".to_string(),
+        };
+    }
+
     pub fn set_error(&mut self, error: anyhow::Error) {
         self.prompt = CommandPrompt::Err { error };
     }
@@ -120,3 +233,98 @@ impl CommandUI {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send_keys(ui: &mut CommandUI, keys: &[event::KeyCode]) -> Option<Command> {
+        let mut command = None;
+        for key in keys {
+            command = ui.handle_key(event::KeyEvent::new(*key, event::KeyModifiers::NONE));
+        }
+        command
+    }
+
+    fn send_str(ui: &mut CommandUI, s: &str) {
+        for ch in s.chars() {
+            send_keys(ui, &[event::KeyCode::Char(ch)]);
+        }
+    }
+
+    #[test]
+    fn count() {
+        let mut ui = CommandUI::new();
+        send_str(&mut ui, "12");
+        let command = send_keys(&mut ui, &[event::KeyCode::Char('j')]);
+        assert_eq!(
+            command,
+            Some(Command::Repeat(12, Box::new(Command::NextLine)))
+        );
+        assert!(ui.buffer.is_empty());
+
+        send_str(&mut ui, "5");
+        let command = send_keys(&mut ui, &[event::KeyCode::Down]);
+        assert_eq!(
+            command,
+            Some(Command::Repeat(5, Box::new(Command::NextLine)))
+        );
+
+        send_str(&mut ui, "7");
+        let command = send_keys(&mut ui, &[event::KeyCode::End]);
+        assert_eq!(command, Some(Command::LineNumber(7)));
+
+        send_str(&mut ui, "12");
+        let command = send_keys(&mut ui, &[event::KeyCode::Enter]);
+        assert_eq!(command, Some(Command::LineNumber(12)));
+
+        // Commands that can't be repeated ignore the count.
+        send_str(&mut ui, "3");
+        let command = send_keys(&mut ui, &[event::KeyCode::Char('s')]);
+        assert_eq!(command, Some(Command::ShowCommit));
+
+        // Keys without commands cancel the count.
+        send_str(&mut ui, "3");
+        let command = send_keys(&mut ui, &[event::KeyCode::Char('x')]);
+        assert_eq!(command, None);
+        assert!(ui.buffer.is_empty());
+    }
+
+    #[test]
+    fn complete() {
+        let mut ui = CommandUI::new();
+        send_str(&mut ui, ":comm");
+        send_keys(&mut ui, &[event::KeyCode::Tab]);
+        assert_eq!(ui.buffer, ":commit ");
+
+        ui.buffer.clear();
+        send_str(&mut ui, ":fo");
+        send_keys(&mut ui, &[event::KeyCode::Tab]);
+        assert_eq!(ui.buffer, ":fo");
+        assert_eq!(ui.hint, "  forward fold");
+        send_str(&mut ui, "l");
+        assert!(ui.hint.is_empty());
+        send_keys(&mut ui, &[event::KeyCode::Tab]);
+        assert_eq!(ui.buffer, ":fold ");
+    }
+
+    #[test]
+    fn history() {
+        let mut ui = CommandUI::new();
+        send_str(&mut ui, ":older");
+        let command = send_keys(&mut ui, &[event::KeyCode::Enter]);
+        assert_eq!(command, Some(Command::Ex("older".to_string())));
+        send_str(&mut ui, ":log");
+        send_keys(&mut ui, &[event::KeyCode::Enter]);
+
+        send_str(&mut ui, ":");
+        send_keys(&mut ui, &[event::KeyCode::Up]);
+        assert_eq!(ui.buffer, ":log");
+        send_keys(&mut ui, &[event::KeyCode::Up, event::KeyCode::Up]);
+        assert_eq!(ui.buffer, ":older");
+        send_keys(&mut ui, &[event::KeyCode::Down]);
+        assert_eq!(ui.buffer, ":log");
+        send_keys(&mut ui, &[event::KeyCode::Down]);
+        assert_eq!(ui.buffer, ":");
+    }
+}
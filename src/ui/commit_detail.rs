@@ -0,0 +1,164 @@
+use git2_time_chrono_ext::Git2TimeChronoExt;
+
+use crate::{blame::CommitMetadata, extensions::GitTools};
+
+/// The lines to show the details of a commit, such as the full message,
+/// the author and the committer, the refs, the `CommitMetadata`,
+/// and the changed files.
+#[derive(Debug, Default)]
+pub(crate) struct CommitDetail {
+    commit_id: Option<git2::Oid>,
+    lines: Vec<String>,
+}
+
+impl CommitDetail {
+    pub fn new(
+        git: &GitTools,
+        commit_id: Option<git2::Oid>,
+        metadata: &[CommitMetadata],
+    ) -> anyhow::Result<Self> {
+        let lines = match commit_id {
+            Some(commit_id) => Self::read_lines(git, commit_id, metadata)?,
+            None => vec!["...".to_string()],
+        };
+        Ok(Self { commit_id, lines })
+    }
+
+    pub fn commit_id(&self) -> Option<git2::Oid> {
+        self.commit_id
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    fn read_lines(
+        git: &GitTools,
+        commit_id: git2::Oid,
+        metadata: &[CommitMetadata],
+    ) -> anyhow::Result<Vec<String>> {
+        let repository = git.repository();
+        let commit = repository.find_commit(commit_id)?;
+        let mut lines = vec![format!("commit {commit_id}")];
+        let refs = Self::refs(repository, commit_id)?;
+        if !refs.is_empty() {
+            lines.push(format!("Refs:   {}", refs.join(", ")));
+        }
+        lines.push(format!("Author: {}", Self::signature_str(&commit.author())));
+        lines.push(format!(
+            "Date:   {}",
+            Self::time_str(commit.author().when())
+        ));
+        let committer = commit.committer();
+        if committer.name_bytes() != commit.author().name_bytes()
+            || committer.email_bytes() != commit.author().email_bytes()
+        {
+            lines.push(format!("Commit: {}", Self::signature_str(&committer)));
+        }
+        if committer.when() != commit.author().when() {
+            lines.push(format!("        {}", Self::time_str(committer.when())));
+        }
+        for item in metadata {
+            let name = format!("{}:", item.name());
+            lines.push(match item.url() {
+                Some(url) => format!("{name:<8}{} {url}", item.value()),
+                None => format!("{name:<8}{}", item.value()),
+            });
+        }
+
+        lines.push(String::new());
+        let message = String::from_utf8_lossy(commit.message_bytes());
+        lines.extend(message.trim_end().lines().map(|line| format!("    {line}")));
+
+        lines.push(String::new());
+        let tree = commit.tree()?;
+        let parent_tree = match commit.parents().next() {
+            Some(parent) => Some(parent.tree()?),
+            None => None,
+        };
+        let diff = repository.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+        for delta in diff.deltas() {
+            let path = delta
+                .new_file()
+                .path()
+                .or_else(|| delta.old_file().path())
+                .map_or_else(String::new, |path| path.display().to_string());
+            lines.push(format!("{} {path}", Self::status_char(delta.status())));
+        }
+        Ok(lines)
+    }
+
+    /// The short names of the references that point to the `commit_id`.
+    fn refs(repository: &git2::Repository, commit_id: git2::Oid) -> anyhow::Result<Vec<String>> {
+        let mut refs = vec![];
+        for reference in repository.references()? {
+            let reference = reference?;
+            if let Ok(commit) = reference.peel_to_commit()
+                && commit.id() == commit_id
+                && let Ok(name) = reference.shorthand()
+            {
+                refs.push(name.to_string());
+            }
+        }
+        Ok(refs)
+    }
+
+    fn signature_str(signature: &git2::Signature) -> String {
+        format!(
+            "{} <{}>",
+            String::from_utf8_lossy(signature.name_bytes()),
+            String::from_utf8_lossy(signature.email_bytes())
+        )
+    }
+
+    fn time_str(time: git2::Time) -> String {
+        time.to_local_date_time().map_or_else(
+            |e| format!("Invalid date/time: {e}"),
+            |datetime| datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
+        )
+    }
+
+    /// The status letter, same as `git diff --name-status`.
+    fn status_char(status: git2::Delta) -> char {
+        match status {
+            git2::Delta::Added => 'A',
+            git2::Delta::Deleted => 'D',
+            git2::Delta::Modified => 'M',
+            git2::Delta::Renamed => 'R',
+            git2::Delta::Copied => 'C',
+            git2::Delta::Typechange => 'T',
+            _ => '?',
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::extensions::tests::TempRepository;
+
+    #[test]
+    fn lines() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file\n\nThe body.")?;
+        let metadata = [
+            CommitMetadata::new("PR", "#12"),
+            CommitMetadata::new("Ticket", "ABC-3").with_url("https://example.com/ABC-3"),
+        ];
+        let detail = CommitDetail::new(&git.git, Some(commit_id1), &metadata)?;
+        assert_eq!(detail.commit_id(), Some(commit_id1));
+        let lines = detail.lines();
+        assert_eq!(lines[0], format!("commit {commit_id1}"));
+        assert!(lines.iter().any(|line| line.starts_with("Refs:")));
+        assert!(lines.contains(&"PR:     #12".to_string()));
+        assert!(lines.contains(&"Ticket: ABC-3 https://example.com/ABC-3".to_string()));
+        assert!(lines.contains(&"    Add file".to_string()));
+        assert!(lines.contains(&"    The body.".to_string()));
+        assert_eq!(lines.last().unwrap(), "A test.txt");
+        Ok(())
+    }
+}
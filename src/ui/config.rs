@@ -0,0 +1,407 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use chrono::TimeDelta;
+
+use crate::{
+    blame::GutterColumn,
+    extensions::{GitTools, config_dir},
+};
+
+use super::{AuthorFormat, Engine, TimeFormat};
+
+/// The user preferences in the `CONFIG_FILE_NAME`,
+/// overridden by the per-repository preferences.
+#[derive(Debug, Default)]
+pub(crate) struct Config {
+    time_format: TimeFormat,
+    author_format: AuthorFormat,
+    columns: Vec<GutterColumn>,
+    pager: Option<String>,
+    difftool: Option<String>,
+    engine: Option<Engine>,
+    ignore_revs: Vec<String>,
+    detect_moves: bool,
+    ignore_cr_at_eol: bool,
+    jobs: Option<usize>,
+    nice: Option<i32>,
+    heat_range: Option<TimeDelta>,
+}
+
+impl Config {
+    /// The file name of the preferences in the `config_dir()`.
+    pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+    /// Create from the file at the `path`. If the `path` is `None`, the
+    /// `CONFIG_FILE_NAME` in the `config_dir()` is used if it exists.
+    pub fn new_with_config(path: Option<&Path>) -> anyhow::Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match config_dir().map(|dir| dir.join(Self::CONFIG_FILE_NAME)) {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_toml(&content).with_context(|| format!("Invalid config in {}", path.display()))
+    }
+
+    /// The keys of the preferences.
+    const KEYS: &[&str] = &[
+        "date",
+        "author",
+        "columns",
+        "pager",
+        "difftool",
+        "engine",
+        "ignore-revs",
+        "detect-moves",
+        "ignore-cr-at-eol",
+        "jobs",
+        "nice",
+        "heat-range",
+    ];
+
+    /// The per-repository preferences file name in the `.git` directory.
+    pub const REPOSITORY_FILE_NAME: &str = "iblame.toml";
+
+    /// Create from the TOML `content`; e.g.,
+    /// ```toml
+    /// date = "relative"
+    /// author = "name"
+    /// columns = "number,blame:40"
+    /// pager = "delta"
+    /// difftool = "meld"
+    /// engine = "git2"
+    /// ignore-revs = ["0123abc"]
+    /// detect-moves = true
+    /// ignore-cr-at-eol = true
+    /// jobs = 2
+    /// nice = 10
+    /// heat-range = "2y"
+    /// ```
+    pub fn from_toml(content: &str) -> anyhow::Result<Self> {
+        let mut config = Self::default();
+        config.apply_toml(content)?;
+        Ok(config)
+    }
+
+    /// Override by the per-repository preferences of the repository of the
+    /// `git`; the `[iblame]` section of the git config, such as
+    /// `iblame.engine`, and then the `REPOSITORY_FILE_NAME` in the `.git`
+    /// directory if it exists.
+    pub fn apply_repository(&mut self, git: &GitTools) -> anyhow::Result<()> {
+        let git_config = git.repository().config()?;
+        let mut table = toml::Table::new();
+        let mut entries = git_config.entries(Some(r"^iblame\."))?;
+        while let Some(entry) = entries.next() {
+            let entry = entry?;
+            let (Ok(name), Ok(value)) = (entry.name(), entry.value()) else {
+                continue;
+            };
+            let key = name.strip_prefix("iblame.").unwrap_or(name);
+            // Git lowercases the names, and they may be in camelCase, such
+            // as `iblame.ignoreRevs` for `ignore-revs`.
+            let key = Self::KEYS
+                .iter()
+                .find(|known| {
+                    known
+                        .replace('-', "")
+                        .eq_ignore_ascii_case(&key.replace('-', ""))
+                })
+                .copied()
+                .unwrap_or(key);
+            let value = match key {
+                // Multiple values are allowed.
+                "ignore-revs" => {
+                    let revs = table
+                        .entry(key)
+                        .or_insert_with(|| toml::Value::Array(vec![]));
+                    if let toml::Value::Array(revs) = revs {
+                        revs.push(value.into());
+                    }
+                    continue;
+                }
+                "detect-moves" | "ignore-cr-at-eol" => git2::Config::parse_bool(value)
+                    .with_context(|| format!("Invalid {name} in the git config"))?
+                    .into(),
+                "jobs" | "nice" => git2::Config::parse_i64(value)
+                    .with_context(|| format!("Invalid {name} in the git config"))?
+                    .into(),
+                _ => value.into(),
+            };
+            table.insert(key.to_string(), value);
+        }
+        self.apply_table(&table)
+            .context("Invalid [iblame] section in the git config")?;
+
+        let path = git.repository_path().join(Self::REPOSITORY_FILE_NAME);
+        if path.exists() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            self.apply_toml(&content)
+                .with_context(|| format!("Invalid config in {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    fn apply_toml(&mut self, content: &str) -> anyhow::Result<()> {
+        let table: toml::Table = content.parse()?;
+        self.apply_table(&table)
+    }
+
+    fn apply_table(&mut self, table: &toml::Table) -> anyhow::Result<()> {
+        for (key, value) in table {
+            match key.as_str() {
+                "date" => {
+                    self.time_format = match value.as_str() {
+                        Some("absolute") => TimeFormat::Absolute,
+                        Some("relative") => TimeFormat::Relative,
+                        _ => anyhow::bail!("\"date\" must be \"absolute\" or \"relative\""),
+                    }
+                }
+                "author" => {
+                    self.author_format = match value.as_str() {
+                        Some("email") => AuthorFormat::Email,
+                        Some("name") => AuthorFormat::Name,
+                        Some("initials") => AuthorFormat::Initials,
+                        Some("both") => AuthorFormat::NameEmail,
+                        _ => anyhow::bail!(
+                            "\"author\" must be \"email\", \"name\", \"initials\", or \"both\""
+                        ),
+                    }
+                }
+                "columns" => {
+                    let Some(columns) = value.as_str() else {
+                        anyhow::bail!("\"columns\" must be a string");
+                    };
+                    self.columns = columns
+                        .split(',')
+                        .map(str::parse)
+                        .collect::<anyhow::Result<_>>()?;
+                }
+                "pager" => {
+                    let Some(pager) = value.as_str() else {
+                        anyhow::bail!("\"pager\" must be a string");
+                    };
+                    self.pager = Some(pager.to_string()).filter(|pager| !pager.trim().is_empty());
+                }
+                "difftool" => {
+                    let Some(difftool) = value.as_str() else {
+                        anyhow::bail!("\"difftool\" must be a string");
+                    };
+                    self.difftool = Some(difftool.to_string());
+                }
+                "engine" => {
+                    let Some(engine) = value.as_str() else {
+                        anyhow::bail!("\"engine\" must be a string");
+                    };
+                    self.engine = Some(
+                        clap::ValueEnum::from_str(engine, true)
+                            .map_err(|_| anyhow::anyhow!("Unknown engine \"{engine}\""))?,
+                    );
+                }
+                "ignore-revs" => {
+                    let Some(revs) = value.as_array() else {
+                        anyhow::bail!("\"ignore-revs\" must be an array");
+                    };
+                    for rev in revs {
+                        let Some(rev) = rev.as_str() else {
+                            anyhow::bail!("\"ignore-revs\" must be an array of strings");
+                        };
+                        self.ignore_revs.push(rev.to_string());
+                    }
+                }
+                "detect-moves" => {
+                    let Some(detect_moves) = value.as_bool() else {
+                        anyhow::bail!("\"detect-moves\" must be a boolean");
+                    };
+                    self.detect_moves = detect_moves;
+                }
+                "ignore-cr-at-eol" => {
+                    let Some(ignore_cr_at_eol) = value.as_bool() else {
+                        anyhow::bail!("\"ignore-cr-at-eol\" must be a boolean");
+                    };
+                    self.ignore_cr_at_eol = ignore_cr_at_eol;
+                }
+                "jobs" => {
+                    let jobs = value
+                        .as_integer()
+                        .and_then(|jobs| usize::try_from(jobs).ok());
+                    let Some(jobs) = jobs.filter(|jobs| *jobs > 0) else {
+                        anyhow::bail!("\"jobs\" must be a positive integer");
+                    };
+                    self.jobs = Some(jobs);
+                }
+                "nice" => {
+                    let Some(nice) = value.as_integer().filter(|nice| (1..=19).contains(nice))
+                    else {
+                        anyhow::bail!("\"nice\" must be an integer from 1 to 19");
+                    };
+                    self.nice = Some(nice as i32);
+                }
+                "heat-range" => {
+                    let Some(heat_range) = value.as_str() else {
+                        anyhow::bail!("\"heat-range\" must be a string");
+                    };
+                    self.heat_range = Some(
+                        TimeFormat::relative_time_from_str(heat_range)
+                            .context("Invalid \"heat-range\"")?,
+                    );
+                }
+                _ => anyhow::bail!("Unknown key \"{key}\""),
+            }
+        }
+        Ok(())
+    }
+
+    /// The initial `TimeFormat`, which the `t` key toggles.
+    pub fn time_format(&self) -> TimeFormat {
+        self.time_format
+    }
+
+    /// The initial `AuthorFormat`, which the `a` key switches.
+    pub fn author_format(&self) -> AuthorFormat {
+        self.author_format
+    }
+
+    /// The columns of the left pane, or empty for the default.
+    /// The `--columns` option overrides this.
+    pub fn columns(&self) -> &[GutterColumn] {
+        &self.columns
+    }
+
+    /// The command line to page the commits and the diffs,
+    /// such as `delta` or `less -R`, instead of the pager of `git`.
+    pub fn pager(&self) -> Option<&str> {
+        self.pager.as_deref()
+    }
+
+    /// The tool of `git difftool`, such as `meld` or `vimdiff`.
+    /// The `diff.tool` config of `git` is used if `None`.
+    pub fn difftool(&self) -> Option<&str> {
+        self.difftool.as_deref()
+    }
+
+    /// The engine if the options don't specify it.
+    pub fn engine(&self) -> Option<Engine> {
+        self.engine
+    }
+
+    /// The revisions to ignore, in addition to the `--ignore-rev` options.
+    pub fn ignore_revs(&self) -> &[String] {
+        &self.ignore_revs
+    }
+
+    /// Whether to detect moved lines, like the `--detect-moves` option.
+    pub fn detect_moves(&self) -> bool {
+        self.detect_moves
+    }
+
+    /// Whether to ignore changes of only the line endings,
+    /// like the `--ignore-cr-at-eol` option.
+    pub fn ignore_cr_at_eol(&self) -> bool {
+        self.ignore_cr_at_eol
+    }
+
+    /// The number of threads if the `--jobs` option isn't given.
+    pub fn jobs(&self) -> Option<usize> {
+        self.jobs
+    }
+
+    /// The niceness of the threads if the `--nice` option isn't given.
+    pub fn nice(&self) -> Option<i32> {
+        self.nice
+    }
+
+    /// The time span of the heatmap gradient back from the newest commit.
+    /// Older commits are the coldest. `None` to span the whole history.
+    pub fn heat_range(&self) -> Option<TimeDelta> {
+        self.heat_range
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extensions::tests::TempRepository;
+
+    use super::*;
+
+    #[test]
+    fn from_toml() -> anyhow::Result<()> {
+        assert_eq!(Config::from_toml("")?.time_format(), TimeFormat::Absolute);
+        let config = Config::from_toml("date = \"relative\"")?;
+        assert_eq!(config.time_format(), TimeFormat::Relative);
+        assert!(Config::from_toml("date = \"now\"").is_err());
+        let config = Config::from_toml("author = \"initials\"")?;
+        assert_eq!(config.author_format(), AuthorFormat::Initials);
+        assert!(Config::from_toml("author = 1").is_err());
+        let config = Config::from_toml("columns = \"number,blame:40\"")?;
+        assert_eq!(config.columns()[1].width(), 40);
+        assert!(Config::from_toml("columns = \"number,x\"").is_err());
+        assert_eq!(Config::from_toml("")?.pager(), None);
+        let config = Config::from_toml("pager = \"less -R\"")?;
+        assert_eq!(config.pager(), Some("less -R"));
+        assert_eq!(Config::from_toml("pager = \" \"")?.pager(), None);
+        assert!(Config::from_toml("pager = true").is_err());
+        let config = Config::from_toml("difftool = \"meld\"")?;
+        assert_eq!(config.difftool(), Some("meld"));
+        let config = Config::from_toml("engine = \"Git2\"\nignore-revs = [\"a\", \"b\"]")?;
+        assert_eq!(config.engine(), Some(Engine::Git2));
+        assert_eq!(config.ignore_revs(), ["a", "b"]);
+        assert!(Config::from_toml("engine = \"x\"").is_err());
+        assert!(Config::from_toml("ignore-revs = \"a\"").is_err());
+        assert!(Config::from_toml("detect-moves = \"yes\"").is_err());
+        assert!(Config::from_toml("ignore-cr-at-eol = true")?.ignore_cr_at_eol());
+        assert!(Config::from_toml("ignore-cr-at-eol = 1").is_err());
+        let config = Config::from_toml("jobs = 2\nnice = 10")?;
+        assert_eq!(config.jobs(), Some(2));
+        assert_eq!(config.nice(), Some(10));
+        assert!(Config::from_toml("jobs = 0").is_err());
+        assert!(Config::from_toml("nice = 20").is_err());
+        let config = Config::from_toml("heat-range = \"2y\"")?;
+        assert_eq!(config.heat_range(), Some(TimeDelta::days(730)));
+        assert!(Config::from_toml("heat-range = \"2\"").is_err());
+        assert!(Config::from_toml("unknown = 1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn apply_repository() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let mut git_config = git
+            .git
+            .repository()
+            .config()?
+            .open_level(git2::ConfigLevel::Local)?;
+        git_config.set_str("iblame.engine", "blame")?;
+        git_config.set_multivar("iblame.ignore-revs", "^$", "a")?;
+        git_config.set_multivar("iblame.ignore-revs", "^$", "b")?;
+        git_config.set_str("iblame.detect-moves", "yes")?;
+        git_config.set_str("iblame.date", "relative")?;
+        git_config.set_str("iblame.nice", "5")?;
+        git_config.set_str("iblame.ignoreCrAtEol", "true")?;
+        git_config.set_str("iblame.heatRange", "6w")?;
+        fs::write(
+            git.git.repository_path().join(Config::REPOSITORY_FILE_NAME),
+            "date = \"absolute\"\nauthor = \"name\"\n",
+        )?;
+
+        let mut config = Config::from_toml("author = \"initials\"\ncolumns = \"sha\"")?;
+        config.apply_repository(&git.git)?;
+        assert_eq!(config.engine(), Some(Engine::Blame));
+        assert_eq!(config.ignore_revs(), ["a", "b"]);
+        assert!(config.detect_moves());
+        assert_eq!(config.time_format(), TimeFormat::Absolute);
+        assert_eq!(config.author_format(), AuthorFormat::Name);
+        assert_eq!(config.columns().len(), 1);
+        assert_eq!(config.nice(), Some(5));
+        assert!(config.ignore_cr_at_eol());
+        assert_eq!(config.heat_range(), Some(TimeDelta::weeks(6)));
+
+        git_config.set_str("iblame.detect-moves", "maybe")?;
+        assert!(Config::default().apply_repository(&git.git).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+/// The things to copy to the clipboard.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum CopyTarget {
+    /// The full commit ID of the current line.
+    CommitId,
+    /// The abbreviated commit ID of the current line.
+    ShortId,
+    /// The path and the line number; e.g., `src/main.rs:10`.
+    PathLine,
+    /// The content of the lines.
+    Content,
+    /// The URL of the lines on the forge.
+    Url,
+}
+
+impl CopyTarget {
+    const KEYS: &[(char, CopyTarget, &str)] = &[
+        ('c', CopyTarget::CommitId, "commit ID"),
+        ('s', CopyTarget::ShortId, "short ID"),
+        ('p', CopyTarget::PathLine, "path:line"),
+        ('l', CopyTarget::Content, "line"),
+        ('u', CopyTarget::Url, "URL"),
+    ];
+
+    pub fn from_char(ch: char) -> Option<CopyTarget> {
+        Self::KEYS
+            .iter()
+            .find(|(key, _, _)| *key == ch)
+            .map(|(_, target, _)| *target)
+    }
+
+    /// The prompt to choose a target by its key.
+    pub fn prompt() -> String {
+        let choices: Vec<String> = Self::KEYS
+            .iter()
+            .map(|(key, _, name)| format!("{key}={name}"))
+            .collect();
+        format!("Copy: {}", choices.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_char() {
+        assert_eq!(CopyTarget::from_char('s'), Some(CopyTarget::ShortId));
+        assert_eq!(CopyTarget::from_char('x'), None);
+        assert!(CopyTarget::prompt().starts_with("Copy: c=commit ID, s=short ID"));
+    }
+}
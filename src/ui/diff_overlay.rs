@@ -0,0 +1,665 @@
+use std::{cmp, io::Write, ops::Range, path::Path};
+
+use crossterm::{
+    cursor,
+    event::{self, KeyCode, KeyModifiers},
+    queue, style, terminal,
+};
+
+use crate::extensions::GitTools;
+
+use super::LineConstraint;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum DiffLineKind {
+    Header,
+    Hunk,
+    Context,
+    Added,
+    Deleted,
+}
+
+impl DiffLineKind {
+    fn color(&self) -> Option<style::Color> {
+        match self {
+            DiffLineKind::Header => Some(style::Color::Yellow),
+            DiffLineKind::Hunk => Some(style::Color::Cyan),
+            DiffLineKind::Context => None,
+            DiffLineKind::Added => Some(style::Color::Green),
+            DiffLineKind::Deleted => Some(style::Color::Red),
+        }
+    }
+}
+
+/// A line of one side in the side-by-side view.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct DiffSide {
+    kind: DiffLineKind,
+    line_number: usize,
+    content: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum DiffRow {
+    /// A line of the unified diff.
+    Line(DiffLineKind, String),
+    /// The old and the new lines in the side-by-side view.
+    Pair(Option<DiffSide>, Option<DiffSide>),
+}
+
+/// The rows of a diff view, and its hunks.
+#[derive(Debug, Default)]
+struct DiffView {
+    rows: Vec<DiffRow>,
+    /// The row indexes of the hunks,
+    /// and the line numbers of the hunks in the new file.
+    hunks: Vec<(usize, Range<usize>)>,
+}
+
+impl DiffView {
+    fn new_unified(diff: &git2::Diff) -> anyhow::Result<Self> {
+        let mut view = Self::default();
+        diff.print(git2::DiffFormat::Patch, |_, hunk, line| {
+            let content = String::from_utf8_lossy(line.content());
+            let kind = match line.origin() {
+                'F' => DiffLineKind::Header,
+                'H' => {
+                    if let Some(hunk) = hunk {
+                        let start = hunk.new_start() as usize;
+                        view.hunks
+                            .push((view.rows.len(), start..start + hunk.new_lines() as usize));
+                    }
+                    DiffLineKind::Hunk
+                }
+                '+' => DiffLineKind::Added,
+                '-' => DiffLineKind::Deleted,
+                ' ' => DiffLineKind::Context,
+                // "\ No newline at end of file" and similar lines.
+                _ => {
+                    view.rows.push(DiffRow::Line(
+                        DiffLineKind::Context,
+                        content.trim_end().to_string(),
+                    ));
+                    return true;
+                }
+            };
+            match kind {
+                DiffLineKind::Header | DiffLineKind::Hunk => view.rows.extend(
+                    content
+                        .lines()
+                        .map(|line| DiffRow::Line(kind, line.to_string())),
+                ),
+                _ => view.rows.push(DiffRow::Line(
+                    kind,
+                    format!(
+                        "{}{}",
+                        line.origin(),
+                        content.trim_end_matches(['\n', '\r'])
+                    ),
+                )),
+            }
+            true
+        })?;
+        Ok(view)
+    }
+
+    /// Create the side-by-side view from a diff with the full context.
+    /// The deleted and the added lines of each change are aligned side by side,
+    /// and each change is a hunk.
+    fn new_side_by_side(diff: &git2::Diff) -> anyhow::Result<Self> {
+        let mut view = Self::default();
+        let mut deleted = vec![];
+        let mut added = vec![];
+        let mut next_new_line_number = 1;
+        diff.print(git2::DiffFormat::Patch, |_, _, line| {
+            let side = |kind, line_number: Option<u32>| DiffSide {
+                kind,
+                line_number: line_number.unwrap_or(0) as usize,
+                content: String::from_utf8_lossy(line.content())
+                    .trim_end_matches(['\n', '\r'])
+                    .to_string(),
+            };
+            match line.origin() {
+                '-' => deleted.push(side(DiffLineKind::Deleted, line.old_lineno())),
+                '+' => added.push(side(DiffLineKind::Added, line.new_lineno())),
+                ' ' => {
+                    view.flush_change(&mut deleted, &mut added, next_new_line_number);
+                    let new = side(DiffLineKind::Context, line.new_lineno());
+                    next_new_line_number = new.line_number + 1;
+                    let old = side(DiffLineKind::Context, line.old_lineno());
+                    view.rows.push(DiffRow::Pair(Some(old), Some(new)));
+                }
+                _ => {}
+            }
+            true
+        })?;
+        view.flush_change(&mut deleted, &mut added, next_new_line_number);
+        Ok(view)
+    }
+
+    fn flush_change(
+        &mut self,
+        deleted: &mut Vec<DiffSide>,
+        added: &mut Vec<DiffSide>,
+        next_new_line_number: usize,
+    ) {
+        if deleted.is_empty() && added.is_empty() {
+            return;
+        }
+        let start = added
+            .first()
+            .map_or(next_new_line_number, |side| side.line_number);
+        self.hunks
+            .push((self.rows.len(), start..start + added.len()));
+        let len = cmp::max(deleted.len(), added.len());
+        let mut deleted = deleted.drain(..);
+        let mut added = added.drain(..);
+        for _ in 0..len {
+            self.rows.push(DiffRow::Pair(deleted.next(), added.next()));
+        }
+    }
+
+    /// Keep only the hunks that overlap the `line_numbers` of the new file,
+    /// and the header before the first hunk.
+    fn retain_hunks(&mut self, line_numbers: &Range<usize>) {
+        let Some(first_row) = self.hunks.first().map(|(row, _)| *row) else {
+            return;
+        };
+        let mut rows = self.rows[..first_row].to_vec();
+        let mut hunks = vec![];
+        for (i, (row, hunk_line_numbers)) in self.hunks.iter().enumerate() {
+            // Hunks of only deleted lines are empty at the line before them.
+            let hunk_end = cmp::max(hunk_line_numbers.end, hunk_line_numbers.start + 1);
+            if hunk_line_numbers.start >= line_numbers.end || hunk_end <= line_numbers.start {
+                continue;
+            }
+            let end_row = self
+                .hunks
+                .get(i + 1)
+                .map_or(self.rows.len(), |(row, _)| *row);
+            hunks.push((rows.len(), hunk_line_numbers.clone()));
+            rows.extend_from_slice(&self.rows[*row..end_row]);
+        }
+        self.rows = rows;
+        self.hunks = hunks;
+    }
+
+    /// The hunk that contains the `line_number` of the new file.
+    /// If no hunks contain it, the last hunk before it.
+    fn hunk_from_line_number(&self, line_number: usize) -> Option<&(usize, Range<usize>)> {
+        self.hunks
+            .iter()
+            .take_while(|(_, line_numbers)| line_numbers.start <= line_number)
+            .last()
+            .or(self.hunks.first())
+    }
+}
+
+/// A scrollable overlay to show the diff of a commit
+/// without leaving the alternate screen.
+///
+/// It has two views;
+/// the unified diff, and the side-by-side view of the whole file
+/// at the parent commit and at the commit.
+#[derive(Debug)]
+pub(crate) struct DiffOverlay {
+    unified: DiffView,
+    side_by_side: DiffView,
+    is_side_by_side: bool,
+    view_start_row: usize,
+    view_size: (u16, u16),
+}
+
+impl DiffOverlay {
+    /// The number of context lines to include the whole file in a hunk.
+    const FULL_CONTEXT_LINES: u32 = u32::MAX >> 2;
+    /// The number of rows to show before a hunk in the side-by-side view.
+    const SIDE_BY_SIDE_HUNK_MARGIN: usize = 3;
+
+    /// Create the diff of the `paths` between the `parent_commit_id` and the
+    /// `commit_id`. If the `parent_commit_id` is `None`, the diff is against
+    /// the empty tree.
+    pub fn new(
+        git: &GitTools,
+        commit_id: git2::Oid,
+        parent_commit_id: Option<git2::Oid>,
+        paths: &[&Path],
+    ) -> anyhow::Result<Self> {
+        let repository = git.repository();
+        let tree = repository.find_commit(commit_id)?.tree()?;
+        let parent_tree = match parent_commit_id {
+            Some(parent_commit_id) => Some(repository.find_commit(parent_commit_id)?.tree()?),
+            None => None,
+        };
+        let diff = |context_lines: Option<u32>| -> anyhow::Result<git2::Diff> {
+            let mut diff_options = git2::DiffOptions::new();
+            for path in paths {
+                diff_options.pathspec(path);
+            }
+            if let Some(context_lines) = context_lines {
+                diff_options.context_lines(context_lines);
+            }
+            let mut diff = repository.diff_tree_to_tree(
+                parent_tree.as_ref(),
+                Some(&tree),
+                Some(&mut diff_options),
+            )?;
+            if paths.len() > 1 {
+                diff.find_similar(None)?;
+            }
+            Ok(diff)
+        };
+
+        let mut unified = DiffView::new_unified(&diff(None)?)?;
+        if unified.rows.is_empty() {
+            unified.rows.push(DiffRow::Line(
+                DiffLineKind::Context,
+                "No changes.".to_string(),
+            ));
+        }
+        let side_by_side = DiffView::new_side_by_side(&diff(Some(Self::FULL_CONTEXT_LINES))?)?;
+        Ok(Self {
+            unified,
+            side_by_side,
+            is_side_by_side: false,
+            view_start_row: 0,
+            view_size: (0, 0),
+        })
+    }
+
+    /// Show only the hunks of the unified diff
+    /// that overlap the `line_numbers` of the new file.
+    pub fn retain_hunks(&mut self, line_numbers: Range<usize>) {
+        self.unified.retain_hunks(&line_numbers);
+        if self.unified.hunks.is_empty() {
+            self.unified.rows = vec![DiffRow::Line(
+                DiffLineKind::Context,
+                "No changes in the lines.".to_string(),
+            )];
+        }
+        self.view_start_row = 0;
+    }
+
+    fn view(&self) -> &DiffView {
+        if self.is_side_by_side {
+            &self.side_by_side
+        } else {
+            &self.unified
+        }
+    }
+
+    /// Toggle the unified diff and the side-by-side view,
+    /// keeping the current hunk in the view.
+    pub fn toggle_side_by_side(&mut self) {
+        let line_numbers = self
+            .view()
+            .hunks
+            .iter()
+            .take_while(|(row, _)| *row <= self.hunk_anchor_row())
+            .last()
+            .map(|(_, line_numbers)| line_numbers.clone());
+        self.is_side_by_side = !self.is_side_by_side;
+        self.view_start_row = 0;
+        if let Some(line_numbers) = line_numbers {
+            let row = self
+                .view()
+                .hunks
+                .iter()
+                .find(|(_, hunk_line_numbers)| {
+                    hunk_line_numbers.end >= line_numbers.start
+                        && hunk_line_numbers.start <= line_numbers.end
+                })
+                .map(|(row, _)| *row);
+            match row {
+                Some(row) => self.scroll_to_hunk_row(row),
+                None => self.scroll_to_line_number(line_numbers.start),
+            }
+        }
+    }
+
+    pub fn set_view_size(&mut self, size: (u16, u16)) {
+        self.view_size = size;
+        self.view_start_row = cmp::min(self.view_start_row, self.max_start());
+    }
+
+    fn view_rows(&self) -> usize {
+        // The last row is for the status.
+        self.view_size.1.saturating_sub(1) as usize
+    }
+
+    fn max_start(&self) -> usize {
+        self.view().rows.len().saturating_sub(self.view_rows())
+    }
+
+    fn scroll_to(&mut self, row: usize) {
+        self.view_start_row = cmp::min(row, self.max_start());
+    }
+
+    fn scroll_by(&mut self, delta: isize) {
+        self.scroll_to(self.view_start_row.saturating_add_signed(delta));
+    }
+
+    /// Scroll to the hunk at the `row`. The side-by-side view has no hunk
+    /// headers, so a few lines before the hunk are also shown.
+    fn scroll_to_hunk_row(&mut self, row: usize) {
+        if self.is_side_by_side {
+            self.scroll_to(row.saturating_sub(Self::SIDE_BY_SIDE_HUNK_MARGIN));
+        } else {
+            self.scroll_to(row);
+        }
+    }
+
+    /// Scroll to the hunk that contains the `line_number` of the new file.
+    /// If no hunks contain it, scroll to the last hunk before it.
+    pub fn scroll_to_line_number(&mut self, line_number: usize) {
+        if let Some((row, _)) = self.view().hunk_from_line_number(line_number) {
+            self.scroll_to_hunk_row(*row);
+        }
+    }
+
+    /// The row where `scroll_to_hunk_row()` puts hunks.
+    fn hunk_anchor_row(&self) -> usize {
+        if self.is_side_by_side {
+            self.view_start_row + Self::SIDE_BY_SIDE_HUNK_MARGIN
+        } else {
+            self.view_start_row
+        }
+    }
+
+    fn scroll_to_hunk(&mut self, reverse: bool) {
+        let current = self.hunk_anchor_row();
+        let hunks = &self.view().hunks;
+        let row = if reverse {
+            hunks.iter().rev().find(|(row, _)| *row < current)
+        } else {
+            hunks.iter().find(|(row, _)| *row > current)
+        };
+        if let Some((row, _)) = row {
+            self.scroll_to_hunk_row(*row);
+        }
+    }
+
+    /// Show the overlay and handle the keys until it's closed.
+    pub fn run(&mut self, out: &mut impl Write) -> anyhow::Result<()> {
+        loop {
+            self.render(out)?;
+            out.flush()?;
+            match event::read()? {
+                event::Event::Key(event)
+                    if !event.is_release() && !self.handle_key(event.code, event.modifiers) =>
+                {
+                    return Ok(());
+                }
+                event::Event::Resize(columns, rows) => self.set_view_size((columns, rows)),
+                _ => {}
+            }
+        }
+    }
+
+    /// Handle a key. Returns `false` if the overlay should be closed.
+    fn handle_key(&mut self, key: KeyCode, modifiers: KeyModifiers) -> bool {
+        let page = self.view_rows().max(1) as isize;
+        match (key, modifiers) {
+            (KeyCode::Char('q'), _) | (KeyCode::Esc, _) => return false,
+            (KeyCode::Up, _) | (KeyCode::Char('k'), KeyModifiers::NONE) => self.scroll_by(-1),
+            (KeyCode::Down, _) | (KeyCode::Char('j'), KeyModifiers::NONE) | (KeyCode::Enter, _) => {
+                self.scroll_by(1)
+            }
+            (KeyCode::PageUp, _) | (KeyCode::Char('b'), _) => self.scroll_by(-page),
+            (KeyCode::PageDown, _) | (KeyCode::Char('f'), _) | (KeyCode::Char(' '), _) => {
+                self.scroll_by(page)
+            }
+            (KeyCode::Home, _) | (KeyCode::Char('g'), _) => self.scroll_to(0),
+            (KeyCode::End, _) | (KeyCode::Char('G'), _) => self.scroll_to(self.max_start()),
+            (KeyCode::Char('N'), _) => self.scroll_to_hunk(true),
+            (KeyCode::Char('n'), _) => self.scroll_to_hunk(false),
+            (KeyCode::Char('v'), _) => self.toggle_side_by_side(),
+            _ => {}
+        }
+        true
+    }
+
+    fn render(&self, out: &mut impl Write) -> anyhow::Result<()> {
+        queue!(out, terminal::Clear(terminal::ClearType::All))?;
+        let cols = self.view_size.0;
+        let rows = &self.view().rows;
+        let end = cmp::min(self.view_start_row + self.view_rows(), rows.len());
+        // The left side, the border, and the right side.
+        let left_cols = cols.saturating_sub(1) / 2;
+        let left = LineConstraint::new(left_cols as usize);
+        let right = LineConstraint::new(cols.saturating_sub(left_cols + 1) as usize);
+        let full = LineConstraint::new(cols as usize);
+        for (row, diff_row) in rows[self.view_start_row..end].iter().enumerate() {
+            let row = row as u16;
+            match diff_row {
+                DiffRow::Line(kind, line) => {
+                    queue!(out, cursor::MoveTo(0, row))?;
+                    Self::render_str(out, *kind, &full.truncate(line, 0))?;
+                }
+                DiffRow::Pair(old, new) => {
+                    queue!(out, cursor::MoveTo(0, row))?;
+                    Self::render_side(out, old.as_ref(), &left)?;
+                    queue!(
+                        out,
+                        cursor::MoveTo(left_cols, row),
+                        style::SetForegroundColor(style::Color::DarkGrey),
+                        style::Print('\u{2502}'),
+                        style::ResetColor,
+                    )?;
+                    Self::render_side(out, new.as_ref(), &right)?;
+                }
+            }
+        }
+        let status = format!(
+            "Lines {}-{}/{}, n/N: next/previous hunk, v: {}, q: close",
+            cmp::min(self.view_start_row + 1, end),
+            end,
+            rows.len(),
+            if self.is_side_by_side {
+                "unified"
+            } else {
+                "side-by-side"
+            }
+        );
+        queue!(
+            out,
+            cursor::MoveTo(0, self.view_size.1.saturating_sub(1)),
+            style::SetAttribute(style::Attribute::Reverse),
+            style::Print(full.truncate(&status, 0)),
+            style::SetAttribute(style::Attribute::Reset),
+        )?;
+        Ok(())
+    }
+
+    fn render_side(
+        out: &mut impl Write,
+        side: Option<&DiffSide>,
+        constraint: &LineConstraint,
+    ) -> anyhow::Result<()> {
+        if let Some(side) = side {
+            let line = format!("{:>4} {}", side.line_number, side.content);
+            Self::render_str(out, side.kind, &constraint.truncate(&line, 0))?;
+        }
+        Ok(())
+    }
+
+    fn render_str(out: &mut impl Write, kind: DiffLineKind, str: &str) -> anyhow::Result<()> {
+        if let Some(color) = kind.color() {
+            queue!(out, style::SetForegroundColor(color))?;
+        }
+        queue!(out, style::Print(str), style::ResetColor)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extensions::tests::TempRepository;
+
+    fn line_strs(view: &DiffView) -> Vec<&str> {
+        view.rows
+            .iter()
+            .map(|row| match row {
+                DiffRow::Line(_, line) => line.as_str(),
+                DiffRow::Pair(..) => panic!("Unexpected pair"),
+            })
+            .collect()
+    }
+
+    fn pair_strs(view: &DiffView) -> Vec<(&str, &str)> {
+        fn side_str(side: &Option<DiffSide>) -> &str {
+            side.as_ref().map_or("", |side| side.content.as_str())
+        }
+        view.rows
+            .iter()
+            .map(|row| match row {
+                DiffRow::Pair(old, new) => (side_str(old), side_str(new)),
+                DiffRow::Line(..) => panic!("Unexpected line"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unified() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n3\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2a\n3\n")?;
+        let commit_id2 = git.commit(commit_id1, "Modify file")?;
+
+        let overlay = DiffOverlay::new(&git.git, commit_id2, Some(commit_id1), &[path])?;
+        let lines = line_strs(&overlay.unified);
+        assert!(lines[0].starts_with("diff --git a/test.txt b/test.txt"));
+        let hunk_index = lines
+            .iter()
+            .position(|line| line.starts_with("@@"))
+            .unwrap();
+        assert_eq!(
+            lines[hunk_index..],
+            ["@@ -1,3 +1,3 @@", " 1", "-2", "+2a", " 3"]
+        );
+        assert_eq!(overlay.unified.hunks, [(hunk_index, 1..4)]);
+        assert_eq!(
+            overlay.unified.rows[hunk_index + 3],
+            DiffRow::Line(DiffLineKind::Added, "+2a".to_string())
+        );
+
+        let overlay = DiffOverlay::new(&git.git, commit_id1, None, &[path])?;
+        assert_eq!(line_strs(&overlay.unified).last(), Some(&"+3"));
+        Ok(())
+    }
+
+    #[test]
+    fn retain_hunks() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        let content: String = (1..=20).map(|i| format!("{i}\n")).collect();
+        git.add_file_content(path, &content)?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        let content = content.replace("\n2\n", "\n2a\n").replace("18\n", "18a\n");
+        git.add_file_content(path, &content)?;
+        let commit_id2 = git.commit(commit_id1, "Modify file")?;
+
+        let mut overlay = DiffOverlay::new(&git.git, commit_id2, Some(commit_id1), &[path])?;
+        assert_eq!(overlay.unified.hunks.len(), 2);
+        overlay.retain_hunks(17..19);
+        let lines = line_strs(&overlay.unified);
+        assert!(lines[0].starts_with("diff --git"));
+        assert_eq!(overlay.unified.hunks.len(), 1);
+        let (hunk_row, _) = overlay.unified.hunks[0];
+        assert_eq!(lines[hunk_row], "@@ -15,6 +15,6 @@");
+        assert!(!lines.contains(&"+2a"));
+        assert!(lines.contains(&"+18a"));
+
+        let mut overlay = DiffOverlay::new(&git.git, commit_id2, Some(commit_id1), &[path])?;
+        overlay.retain_hunks(8..10);
+        assert_eq!(line_strs(&overlay.unified), ["No changes in the lines."]);
+        Ok(())
+    }
+
+    #[test]
+    fn side_by_side() -> anyhow::Result<()> {
+        let git = TempRepository::new()?;
+        let path = Path::new("test.txt");
+        git.add_file_content(path, "1\n2\n3\n4\n5\n6\n7\n8\n9\n")?;
+        let commit_id1 = git.commit(git2::Oid::ZERO_SHA1, "Add file")?;
+        git.add_file_content(path, "1\n2a\n2b\n3\n4\n5\n6\n7\n9\n")?;
+        let commit_id2 = git.commit(commit_id1, "Modify file")?;
+
+        let overlay = DiffOverlay::new(&git.git, commit_id2, Some(commit_id1), &[path])?;
+        let view = &overlay.side_by_side;
+        assert_eq!(
+            pair_strs(view),
+            [
+                ("1", "1"),
+                ("2", "2a"),
+                ("", "2b"),
+                ("3", "3"),
+                ("4", "4"),
+                ("5", "5"),
+                ("6", "6"),
+                ("7", "7"),
+                ("8", ""),
+                ("9", "9"),
+            ]
+        );
+        assert_eq!(view.hunks, [(1, 2..4), (8, 9..9)]);
+        let DiffRow::Pair(Some(old), Some(new)) = &view.rows[9] else {
+            panic!();
+        };
+        assert_eq!((old.line_number, new.line_number), (9, 9));
+
+        let overlay = DiffOverlay::new(&git.git, commit_id1, None, &[path])?;
+        assert_eq!(overlay.side_by_side.rows.len(), 9);
+        assert_eq!(overlay.side_by_side.hunks, [(0, 1..10)]);
+        Ok(())
+    }
+
+    #[test]
+    fn scroll() {
+        let mut overlay = DiffOverlay {
+            unified: DiffView {
+                rows: vec![DiffRow::Line(DiffLineKind::Context, String::new()); 100],
+                hunks: vec![(10, 1..5), (50, 20..30), (80, 60..62)],
+            },
+            side_by_side: DiffView {
+                rows: vec![DiffRow::Pair(None, None); 100],
+                hunks: vec![(3, 2..4), (40, 25..27), (70, 61..62)],
+            },
+            is_side_by_side: false,
+            view_start_row: 0,
+            view_size: (80, 11),
+        };
+        overlay.scroll_to_line_number(25);
+        assert_eq!(overlay.view_start_row, 50);
+        overlay.scroll_to_line_number(40);
+        assert_eq!(overlay.view_start_row, 50);
+        overlay.scroll_to_line_number(70);
+        assert_eq!(overlay.view_start_row, 80);
+        overlay.scroll_to_line_number(0);
+        assert_eq!(overlay.view_start_row, 10);
+
+        assert!(overlay.handle_key(KeyCode::Char('n'), KeyModifiers::NONE));
+        assert_eq!(overlay.view_start_row, 50);
+        assert!(overlay.handle_key(KeyCode::Char('v'), KeyModifiers::NONE));
+        assert_eq!(overlay.view_start_row, 37);
+        assert!(overlay.handle_key(KeyCode::Char('n'), KeyModifiers::NONE));
+        assert_eq!(overlay.view_start_row, 67);
+        assert!(overlay.handle_key(KeyCode::Char('N'), KeyModifiers::SHIFT));
+        assert_eq!(overlay.view_start_row, 37);
+        assert!(overlay.handle_key(KeyCode::Char('v'), KeyModifiers::NONE));
+        assert_eq!(overlay.view_start_row, 50);
+
+        assert!(overlay.handle_key(KeyCode::Char('N'), KeyModifiers::SHIFT));
+        assert_eq!(overlay.view_start_row, 10);
+        assert!(overlay.handle_key(KeyCode::End, KeyModifiers::NONE));
+        assert_eq!(overlay.view_start_row, 90);
+        assert!(overlay.handle_key(KeyCode::Char('j'), KeyModifiers::NONE));
+        assert_eq!(overlay.view_start_row, 90);
+        assert!(overlay.handle_key(KeyCode::PageUp, KeyModifiers::NONE));
+        assert_eq!(overlay.view_start_row, 80);
+        assert!(!overlay.handle_key(KeyCode::Char('q'), KeyModifiers::NONE));
+    }
+}
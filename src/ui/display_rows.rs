@@ -0,0 +1,200 @@
+use std::{collections::HashSet, ops::Range};
+
+use crate::blame::{FileCommits, Line};
+
+/// What lines to keep when folding.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum FoldTarget {
+    /// Keep lines of the commit.
+    Commit(git2::Oid),
+    /// Keep lines of the newest N commits.
+    Newest(usize),
+}
+
+impl FoldTarget {
+    fn is_kept(&self, line: &Line, commits: &FileCommits) -> bool {
+        let Some(commit_id) = line.commit_id() else {
+            return false;
+        };
+        match self {
+            FoldTarget::Commit(target) => commit_id == *target,
+            FoldTarget::Newest(count) => commits
+                .get_by_commit_id(commit_id)
+                .is_ok_and(|commit| commit.index() < *count),
+        }
+    }
+}
+
+/// A row on the screen.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum DisplayRow {
+    /// A line at the line index.
+    Line(usize),
+    /// Folded lines at the line index range.
+    Fold(Range<usize>),
+}
+
+/// Maps the rows on the screen and the line indexes,
+/// where each fold occupies a row.
+#[derive(Debug, Default)]
+pub(crate) struct DisplayRows {
+    /// Sorted and non-overlapping line index ranges of the folds.
+    folds: Vec<Range<usize>>,
+}
+
+impl DisplayRows {
+    /// The number of lines to keep around the kept lines.
+    const CONTEXT_LINES: usize = 3;
+    /// The minimum number of lines to fold.
+    const MIN_FOLD_LEN: usize = 2;
+
+    #[cfg(test)]
+    pub fn new(folds: Vec<Range<usize>>) -> Self {
+        assert!(folds.windows(2).all(|w| w[0].end <= w[1].start));
+        Self { folds }
+    }
+
+    /// Fold the runs of `lines` not kept by the `target`,
+    /// except the `CONTEXT_LINES` around kept lines,
+    /// and the runs containing the `expanded_line_numbers`.
+    /// The `hunks` are the line index ranges of the same commits,
+    /// so that the `target` is checked once for each hunk.
+    pub fn new_folded(
+        lines: &[Line],
+        hunks: impl IntoIterator<Item = Range<usize>>,
+        commits: &FileCommits,
+        target: FoldTarget,
+        expanded_line_numbers: &HashSet<usize>,
+    ) -> Self {
+        let mut is_visible = vec![false; lines.len()];
+        for hunk in hunks {
+            if hunk.end > lines.len() {
+                break;
+            }
+            if target.is_kept(&lines[hunk.start], commits) {
+                let start = hunk.start.saturating_sub(Self::CONTEXT_LINES);
+                let end = (hunk.end + Self::CONTEXT_LINES).min(lines.len());
+                is_visible[start..end].fill(true);
+            }
+        }
+        let mut folds = vec![];
+        let mut line_index = 0;
+        while line_index < lines.len() {
+            if is_visible[line_index] {
+                line_index += 1;
+                continue;
+            }
+            let start = line_index;
+            while line_index < lines.len() && !is_visible[line_index] {
+                line_index += 1;
+            }
+            let fold = start..line_index;
+            if fold.len() >= Self::MIN_FOLD_LEN
+                && !lines[fold.clone()]
+                    .iter()
+                    .any(|line| expanded_line_numbers.contains(&line.line_number()))
+            {
+                folds.push(fold);
+            }
+        }
+        Self { folds }
+    }
+
+    /// The number of rows for the `lines_len` lines.
+    pub fn len(&self, lines_len: usize) -> usize {
+        let folded: usize = self.folds.iter().map(|fold| fold.len() - 1).sum();
+        lines_len - folded
+    }
+
+    /// The row of the `line_index`.
+    /// If the line is folded, the row of the fold.
+    pub fn row_from_line_index(&self, line_index: usize) -> usize {
+        let mut folded = 0;
+        for fold in &self.folds {
+            if line_index < fold.start {
+                break;
+            }
+            if line_index < fold.end {
+                return fold.start - folded;
+            }
+            folded += fold.len() - 1;
+        }
+        line_index - folded
+    }
+
+    pub fn row(&self, row: usize) -> DisplayRow {
+        let mut folded = 0;
+        for fold in &self.folds {
+            let fold_row = fold.start - folded;
+            if row < fold_row {
+                break;
+            }
+            if row == fold_row {
+                return DisplayRow::Fold(fold.clone());
+            }
+            folded += fold.len() - 1;
+        }
+        DisplayRow::Line(row + folded)
+    }
+
+    /// The first line index of the `row`.
+    pub fn line_index_from_row(&self, row: usize) -> usize {
+        match self.row(row) {
+            DisplayRow::Line(line_index) => line_index,
+            DisplayRow::Fold(fold) => fold.start,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows() {
+        let rows = DisplayRows::new(vec![2..5, 7..9]);
+        assert_eq!(rows.len(10), 7);
+        let all: Vec<DisplayRow> = (0..7).map(|row| rows.row(row)).collect();
+        assert_eq!(
+            all,
+            [
+                DisplayRow::Line(0),
+                DisplayRow::Line(1),
+                DisplayRow::Fold(2..5),
+                DisplayRow::Line(5),
+                DisplayRow::Line(6),
+                DisplayRow::Fold(7..9),
+                DisplayRow::Line(9),
+            ]
+        );
+        let rows_from_line_indexes: Vec<usize> =
+            (0..10).map(|i| rows.row_from_line_index(i)).collect();
+        assert_eq!(rows_from_line_indexes, [0, 1, 2, 2, 2, 3, 4, 5, 5, 6]);
+        assert_eq!(rows.line_index_from_row(2), 2);
+        assert_eq!(rows.line_index_from_row(3), 5);
+    }
+
+    #[test]
+    fn new_folded() {
+        let commit_id = git2::Oid::from_bytes(&[1; 20]).unwrap();
+        let mut lines: Vec<Line> = (1..=20).map(|i| Line::new(i, String::new())).collect();
+        lines[9].set_commit_id(commit_id);
+        let commits = FileCommits::new();
+        let target = FoldTarget::Commit(commit_id);
+        let hunks = || [0..9, 9..10, 10..20];
+        let rows = DisplayRows::new_folded(&lines, hunks(), &commits, target, &HashSet::new());
+        assert_eq!(rows.folds, [0..6, 13..20]);
+
+        // The fold containing the expanded line number is not folded.
+        let expanded = HashSet::from([15]);
+        let rows = DisplayRows::new_folded(&lines, hunks(), &commits, target, &expanded);
+        assert_eq!(rows.folds.len(), 1);
+        assert_eq!(rows.folds[0], 0..6);
+
+        // Too short to fold.
+        lines[2].set_commit_id(commit_id);
+        let hunks = [0..2, 2..3, 3..9, 9..10, 10..12];
+        let rows = DisplayRows::new_folded(&lines[..12], hunks, &commits, target, &HashSet::new());
+        assert!(rows.folds.is_empty());
+    }
+}
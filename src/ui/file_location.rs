@@ -0,0 +1,117 @@
+use std::path::{Path, PathBuf};
+
+/// The file, the revision, and the line to open, parsed from the command line
+/// argument; a path, `path:line`, or a permalink URL such as
+/// `https://github.com/owner/repo/blob/<commit>/path#L123`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct FileLocation {
+    pub path: PathBuf,
+    pub rev: Option<String>,
+    pub line_number: Option<usize>,
+}
+
+impl FileLocation {
+    /// The markers in the URLs of the forges, before `<commit>/<path>`.
+    const URL_MARKERS: &[&str] = &["/-/blob/", "/blob/", "/src/commit/", "/src/branch/"];
+
+    pub fn parse(arg: &str) -> anyhow::Result<Self> {
+        if arg.starts_with("https://") || arg.starts_with("http://") {
+            return Self::parse_url(arg);
+        }
+        // An existing path wins, for file names that contain `:`.
+        if !Path::new(arg).exists()
+            && let Some((path, line)) = arg.rsplit_once(':')
+            && let Ok(line_number) = line.parse::<usize>()
+        {
+            anyhow::ensure!(line_number > 0, "Line numbers start from 1: {arg}");
+            return Ok(Self {
+                path: PathBuf::from(path),
+                line_number: Some(line_number),
+                ..Default::default()
+            });
+        }
+        Ok(Self {
+            path: PathBuf::from(arg),
+            ..Default::default()
+        })
+    }
+
+    fn parse_url(url: &str) -> anyhow::Result<Self> {
+        let (url, fragment) = url.split_once('#').unwrap_or((url, ""));
+        let url = url.split_once('?').map_or(url, |(url, _)| url);
+        let Some((rev, path)) = Self::URL_MARKERS
+            .iter()
+            .find_map(|marker| url.split_once(marker))
+            .and_then(|(_, rev_path)| rev_path.split_once('/'))
+        else {
+            anyhow::bail!("Not a URL of a file: {url}");
+        };
+        // `#L123`, `#L123-L130`, or `#L123-130`; the first line is used.
+        let line_number = fragment
+            .strip_prefix('L')
+            .and_then(|lines| {
+                let end = lines
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(lines.len());
+                lines[..end].parse::<usize>().ok()
+            })
+            .filter(|line_number| *line_number > 0);
+        Ok(Self {
+            path: PathBuf::from(path),
+            rev: Some(rev.to_string()),
+            line_number,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() -> anyhow::Result<()> {
+        assert_eq!(
+            FileLocation::parse("src/nonexistent.rs")?,
+            FileLocation {
+                path: PathBuf::from("src/nonexistent.rs"),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            FileLocation::parse("src/nonexistent.rs:123")?,
+            FileLocation {
+                path: PathBuf::from("src/nonexistent.rs"),
+                line_number: Some(123),
+                ..Default::default()
+            }
+        );
+        assert!(FileLocation::parse("src/nonexistent.rs:0").is_err());
+        assert_eq!(
+            FileLocation::parse("src/nonexistent.rs:x")?.path,
+            Path::new("src/nonexistent.rs:x")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_url() -> anyhow::Result<()> {
+        let expected = FileLocation {
+            path: PathBuf::from("src/main.rs"),
+            rev: Some("0123abc".to_string()),
+            line_number: Some(12),
+        };
+        for url in [
+            "https://github.com/owner/repo/blob/0123abc/src/main.rs#L12",
+            "https://github.com/owner/repo/blob/0123abc/src/main.rs?plain=1#L12-L20",
+            "https://gitlab.com/owner/repo/-/blob/0123abc/src/main.rs#L12-20",
+            "https://codeberg.org/owner/repo/src/commit/0123abc/src/main.rs#L12",
+        ] {
+            assert_eq!(FileLocation::parse(url)?, expected, "{url}");
+        }
+        let location = FileLocation::parse("https://github.com/owner/repo/blob/main/README.md")?;
+        assert_eq!(location.rev.as_deref(), Some("main"));
+        assert_eq!(location.line_number, None);
+        assert!(FileLocation::parse("https://github.com/owner/repo").is_err());
+        Ok(())
+    }
+}
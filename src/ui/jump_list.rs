@@ -0,0 +1,110 @@
+use crate::blame::ContentType;
+
+/// A position of the current line, to jump back to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct JumpPosition {
+    pub content_type: ContentType,
+    pub commit_id: git2::Oid,
+    pub line_number: usize,
+}
+
+/// The list of positions before large jumps,
+/// such as searches or traversing the history,
+/// to go back and forward like `Ctrl-O` and `Ctrl-I` of `vi`.
+#[derive(Debug, Default)]
+pub(crate) struct JumpList {
+    positions: Vec<JumpPosition>,
+    /// The index of the current position while going back and forward.
+    /// It's the same as the length when not going back.
+    index: usize,
+}
+
+impl JumpList {
+    /// The maximum number of positions to keep.
+    const MAX_LEN: usize = 100;
+
+    /// Add the `position` before a jump.
+    /// Positions after the current position are discarded.
+    pub fn push(&mut self, position: JumpPosition) {
+        self.positions.truncate(self.index);
+        if self.positions.last() != Some(&position) {
+            self.positions.push(position);
+        }
+        if self.positions.len() > Self::MAX_LEN {
+            self.positions.remove(0);
+        }
+        self.index = self.positions.len();
+    }
+
+    /// The position to go back to from the `current` position.
+    pub fn back(&mut self, current: JumpPosition) -> Option<JumpPosition> {
+        if self.index == self.positions.len() {
+            // Save the `current` position to go forward to.
+            if self.positions.last() == Some(&current) {
+                self.index -= 1;
+            } else {
+                self.positions.push(current);
+            }
+        }
+        if self.index == 0 {
+            return None;
+        }
+        self.index -= 1;
+        Some(self.positions[self.index])
+    }
+
+    /// The position to go forward to, after going back.
+    pub fn forward(&mut self) -> Option<JumpPosition> {
+        if self.index + 1 >= self.positions.len() {
+            return None;
+        }
+        self.index += 1;
+        Some(self.positions[self.index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(line_number: usize) -> JumpPosition {
+        JumpPosition {
+            content_type: ContentType::File,
+            commit_id: git2::Oid::ZERO_SHA1,
+            line_number,
+        }
+    }
+
+    #[test]
+    fn back_forward() {
+        let mut list = JumpList::default();
+        assert_eq!(list.back(position(1)), None);
+        assert_eq!(list.forward(), None);
+
+        let mut list = JumpList::default();
+        list.push(position(1));
+        list.push(position(2));
+        assert_eq!(list.back(position(3)), Some(position(2)));
+        assert_eq!(list.back(position(2)), Some(position(1)));
+        assert_eq!(list.back(position(1)), None);
+        assert_eq!(list.forward(), Some(position(2)));
+        assert_eq!(list.forward(), Some(position(3)));
+        assert_eq!(list.forward(), None);
+
+        // A jump after going back discards the positions after it.
+        assert_eq!(list.back(position(3)), Some(position(2)));
+        list.push(position(2));
+        assert_eq!(list.forward(), None);
+        assert_eq!(list.back(position(4)), Some(position(2)));
+        assert_eq!(list.back(position(2)), Some(position(1)));
+    }
+
+    #[test]
+    fn back_from_last() {
+        let mut list = JumpList::default();
+        list.push(position(1));
+        list.push(position(2));
+        assert_eq!(list.back(position(2)), Some(position(1)));
+        assert_eq!(list.forward(), Some(position(2)));
+    }
+}
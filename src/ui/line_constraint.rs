@@ -0,0 +1,658 @@
+use std::{borrow::Cow, cmp};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use crossterm::style;
+use git2_time_chrono_ext::Git2TimeChronoExt;
+use unicode_width_utils::UnicodeWidth;
+
+use crate::{
+    blame::{FileCommit, FileHistory, GutterColumn, GutterColumnKind, Line, LineFilter},
+    extensions::OrDefault,
+};
+
+use super::{RenderTarget, Theme};
+
+/// How to show the date and time of commits.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TimeFormat {
+    /// The absolute date and time; e.g., "2025-01-31 12:34".
+    #[default]
+    Absolute,
+    /// The relative time from now; e.g., "3y", "2w", or "5d".
+    Relative,
+}
+
+impl TimeFormat {
+    pub fn toggle(self) -> Self {
+        match self {
+            TimeFormat::Absolute => TimeFormat::Relative,
+            TimeFormat::Relative => TimeFormat::Absolute,
+        }
+    }
+
+    /// Format the `age` in the largest unit; e.g., "3y", "2w", or "5d".
+    pub fn relative_time_str(age: TimeDelta) -> String {
+        let days = age.num_days();
+        if days >= 365 {
+            format!("{}y", days / 365)
+        } else if days >= 7 {
+            format!("{}w", days / 7)
+        } else if days >= 1 {
+            format!("{days}d")
+        } else if age.num_hours() >= 1 {
+            format!("{}h", age.num_hours())
+        } else {
+            format!("{}m", cmp::max(age.num_minutes(), 0))
+        }
+    }
+
+    /// Parse the format of `relative_time_str()`; e.g., "3y", "2w", or "5d".
+    pub fn relative_time_from_str(value: &str) -> anyhow::Result<TimeDelta> {
+        let value = value.trim();
+        let (number, unit) =
+            value.split_at(value.len() - value.chars().last().map_or(0, char::len_utf8));
+        let Ok(number) = number.parse::<i64>() else {
+            anyhow::bail!("\"{value}\" must be a number followed by y, w, d, h, or m");
+        };
+        Ok(match unit {
+            "y" => TimeDelta::days(number * 365),
+            "w" => TimeDelta::weeks(number),
+            "d" => TimeDelta::days(number),
+            "h" => TimeDelta::hours(number),
+            "m" => TimeDelta::minutes(number),
+            _ => anyhow::bail!("\"{value}\" must be a number followed by y, w, d, h, or m"),
+        })
+    }
+}
+
+/// How to show the authors of commits.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum AuthorFormat {
+    /// The email; e.g., "alice@example.com".
+    #[default]
+    Email,
+    /// The name; e.g., "Alice Smith".
+    Name,
+    /// The initials of the name; e.g., "AS".
+    Initials,
+    /// The name and the email; e.g., "Alice Smith <alice@example.com>".
+    NameEmail,
+}
+
+impl AuthorFormat {
+    pub fn toggle(self) -> Self {
+        match self {
+            AuthorFormat::Email => AuthorFormat::Name,
+            AuthorFormat::Name => AuthorFormat::Initials,
+            AuthorFormat::Initials => AuthorFormat::NameEmail,
+            AuthorFormat::NameEmail => AuthorFormat::Email,
+        }
+    }
+
+    /// Format the author of the `name` and the `email`.
+    /// The email is used if the `name` is empty.
+    pub fn format(self, name: &str, email: &str) -> String {
+        if name.is_empty() {
+            return email.to_string();
+        }
+        match self {
+            AuthorFormat::Email => email.to_string(),
+            AuthorFormat::Name => name.to_string(),
+            AuthorFormat::Initials => name
+                .split_whitespace()
+                .filter_map(|word| word.chars().next())
+                .flat_map(char::to_uppercase)
+                .collect(),
+            AuthorFormat::NameEmail => format!("{name} <{email}>"),
+        }
+    }
+}
+
+pub(crate) struct LineConstraint {
+    max_columns: usize,
+    uw: UnicodeWidth,
+    time_format: TimeFormat,
+    author_format: AuthorFormat,
+    now: DateTime<Utc>,
+    columns: Vec<GutterColumn>,
+    time_range: Option<(i64, i64)>,
+    heat_range: Option<TimeDelta>,
+    filter: LineFilter,
+    pinned_commit_id: Option<git2::Oid>,
+    is_not_committed: bool,
+}
+
+impl LineConstraint {
+    const TAB_SIZE: u8 = 4;
+
+    pub(crate) fn new(max_columns: usize) -> Self {
+        let mut uw = UnicodeWidth::new();
+        uw.set_tab_size(Self::TAB_SIZE);
+        uw.set_expand_tab(true);
+        Self {
+            max_columns,
+            uw,
+            time_format: TimeFormat::default(),
+            author_format: AuthorFormat::default(),
+            now: Utc::now(),
+            columns: GutterColumn::default_columns(),
+            time_range: None,
+            heat_range: None,
+            filter: LineFilter::default(),
+            pinned_commit_id: None,
+            is_not_committed: false,
+        }
+    }
+
+    pub(crate) fn set_columns(&mut self, columns: &[GutterColumn]) {
+        self.columns = columns.to_vec();
+    }
+
+    /// Set the oldest and the newest commit times in seconds,
+    /// to compute the colors of `GutterColumnKind::Heat`.
+    /// See `FileCommits::time_range()`.
+    pub(crate) fn set_time_range(&mut self, time_range: Option<(i64, i64)>) {
+        self.time_range = time_range;
+    }
+
+    /// Set the time span of the heatmap gradient back from the newest commit.
+    /// See `Config::heat_range()`.
+    pub(crate) fn set_heat_range(&mut self, heat_range: Option<TimeDelta>) {
+        self.heat_range = heat_range;
+    }
+
+    /// The color of the `commit` in the gradient of the `theme`, from cold
+    /// (the oldest commit, or the start of the `heat_range`) to hot (the
+    /// newest commit).
+    fn heat_color(&self, commit: &FileCommit, theme: &Theme) -> Option<style::Color> {
+        let time_range = self
+            .time_range
+            .map(|(oldest, newest)| match self.heat_range {
+                Some(heat_range) => (cmp::max(oldest, newest - heat_range.num_seconds()), newest),
+                None => (oldest, newest),
+            });
+        let ratio = match time_range {
+            Some((oldest, newest)) if newest > oldest => {
+                (commit.time().seconds() - oldest) as f64 / (newest - oldest) as f64
+            }
+            _ => 1.0,
+        };
+        theme.heat_color(ratio)
+    }
+
+    /// Set the filter to dim lines that don't match it.
+    pub(crate) fn set_filter(&mut self, filter: &LineFilter) {
+        self.filter = filter.clone();
+    }
+
+    /// Set the commit to highlight its lines.
+    pub(crate) fn set_pinned_commit_id(&mut self, commit_id: Option<git2::Oid>) {
+        self.pinned_commit_id = commit_id;
+    }
+
+    /// Show that lines without commits are not committed yet.
+    /// See `FileHistory::is_not_committed()`.
+    pub(crate) fn set_not_committed(&mut self, is_not_committed: bool) {
+        self.is_not_committed = is_not_committed;
+    }
+
+    pub(crate) fn set_time_format(&mut self, time_format: TimeFormat) {
+        self.time_format = time_format;
+    }
+
+    pub(crate) fn set_author_format(&mut self, author_format: AuthorFormat) {
+        self.author_format = author_format;
+    }
+
+    pub(crate) fn truncate<'a>(&self, input: &'a str, margin: usize) -> Cow<'a, str> {
+        let max_columns = self.max_columns.saturating_sub(margin);
+        self.uw.truncate(input, max_columns)
+    }
+}
+
+/// How to highlight a line, in the order of the priority.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum LineHighlight {
+    Current,
+    Selected,
+    /// A line of the pinned commit.
+    Pinned,
+    None,
+}
+
+impl LineConstraint {
+    pub(crate) fn render(
+        &self,
+        line: &Line,
+        out: &mut impl RenderTarget,
+        history: &FileHistory,
+        is_current_line: bool,
+        is_selected: bool,
+        theme: &Theme,
+    ) -> anyhow::Result<()> {
+        let commit = line
+            .commit_id()
+            .map(|commit_id| history.commit_by_id(commit_id))
+            .transpose()?;
+        let is_dimmed = !is_current_line && !self.filter.is_match(commit);
+        let highlight = if is_current_line {
+            LineHighlight::Current
+        } else if is_selected {
+            LineHighlight::Selected
+        } else if self.pinned_commit_id.is_some()
+            && line.commit_id() == self.pinned_commit_id
+            && !line.is_deleted()
+        {
+            LineHighlight::Pinned
+        } else {
+            LineHighlight::None
+        };
+        let should_reset = self.queue_style(line, out, commit, highlight, is_dimmed, theme)?;
+        let mut left_pane_len = 0;
+        for (text, color) in self.left_pane_segments(line, commit, Some(theme)) {
+            left_pane_len += text.chars().count();
+            match color {
+                Some(color) => {
+                    out.set_foreground_color(color)?;
+                    out.print(&text)?;
+                    // Restore the style of the line.
+                    out.reset_style()?;
+                    self.queue_style(line, out, commit, highlight, is_dimmed, theme)?;
+                }
+                None => out.print(&text)?,
+            }
+        }
+
+        if should_reset {
+            out.reset_style()?;
+        }
+
+        if line.is_deleted() {
+            out.print_styled(theme.deleted(), "##deleted##")?;
+        } else {
+            let content = self.truncate(line.content(), left_pane_len);
+            if is_dimmed {
+                out.print_styled(theme.dimmed(), &content)?;
+            } else {
+                out.print(&content)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the style of the line, and returns `true` if it needs to be reset.
+    fn queue_style(
+        &self,
+        line: &Line,
+        out: &mut impl RenderTarget,
+        commit: Option<&FileCommit>,
+        highlight: LineHighlight,
+        is_dimmed: bool,
+        theme: &Theme,
+    ) -> anyhow::Result<bool> {
+        let mut should_reset = false;
+        if highlight == LineHighlight::Current {
+            out.set_style(theme.current_line())?;
+            should_reset = true;
+        } else if highlight == LineHighlight::Selected {
+            out.set_style(theme.selected_line())?;
+            should_reset = true;
+        } else if highlight == LineHighlight::Pinned {
+            out.set_style(theme.pinned_line())?;
+            should_reset = true;
+        } else if let Some(commit) = commit
+            && commit.is_apply_failed()
+        {
+            out.set_style(theme.error_line())?;
+            should_reset = true;
+        } else if is_dimmed {
+            out.set_style(theme.dimmed())?;
+            should_reset = true;
+        } else if line.is_boundary() {
+            out.set_style(theme.boundary())?;
+            should_reset = true;
+        }
+
+        if line.is_last_line_in_hunk() {
+            out.set_attribute(style::Attribute::Underlined)?;
+            should_reset = true;
+        }
+        Ok(should_reset)
+    }
+
+    /// The left pane and the content in plain text, without styles.
+    pub(crate) fn plain_text(&self, line: &Line, history: &FileHistory) -> anyhow::Result<String> {
+        let commit = line
+            .commit_id()
+            .map(|commit_id| history.commit_by_id(commit_id))
+            .transpose()?;
+        let mut text = self.left_pane(line, commit);
+        if line.is_deleted() {
+            text += "##deleted##";
+        } else {
+            text += line.content();
+        }
+        Ok(text)
+    }
+
+    /// The left pane, which consists of the `LineConstraint::columns`.
+    fn left_pane(&self, line: &Line, commit: Option<&FileCommit>) -> String {
+        self.left_pane_segments(line, commit, None)
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect()
+    }
+
+    /// The segments of the left pane, and their colors by the `theme` if they
+    /// have colors different from the line.
+    fn left_pane_segments(
+        &self,
+        line: &Line,
+        commit: Option<&FileCommit>,
+        theme: Option<&Theme>,
+    ) -> Vec<(String, Option<style::Color>)> {
+        let mut segments = vec![];
+        let mut text = String::new();
+        let mut last_kind: Option<GutterColumnKind> = None;
+        for column in &self.columns {
+            match last_kind {
+                None => {}
+                Some(GutterColumnKind::LineNumber) => text.push(':'),
+                Some(_) => text.push(' '),
+            }
+            let column_str = self.column_str(line, column.kind(), commit);
+            let width = column.width();
+            match column.kind() {
+                // Don't truncate line numbers.
+                GutterColumnKind::LineNumber | GutterColumnKind::OriginalLineNumber => {
+                    text += &format!("{column_str:>width$}")
+                }
+                GutterColumnKind::Heat => {
+                    segments.push((std::mem::take(&mut text), None));
+                    let color = commit
+                        .zip(theme)
+                        .and_then(|(commit, theme)| self.heat_color(commit, theme));
+                    let column_str = column_str.repeat(width);
+                    segments.push((format!("{column_str:width$.width$}"), color));
+                }
+                GutterColumnKind::Author => {
+                    let color = commit
+                        .zip(theme)
+                        .and_then(|(commit, theme)| theme.author_color(commit.author_email()));
+                    let column_str = format!("{column_str:width$.width$}");
+                    if color.is_some() {
+                        segments.push((std::mem::take(&mut text), None));
+                        segments.push((column_str, color));
+                    } else {
+                        text += &column_str;
+                    }
+                }
+                _ => text += &format!("{column_str:width$.width$}"),
+            }
+            last_kind = Some(column.kind());
+        }
+        text.push('|');
+        segments.push((text, None));
+        segments
+    }
+
+    fn column_str(
+        &self,
+        line: &Line,
+        kind: GutterColumnKind,
+        commit: Option<&FileCommit>,
+    ) -> Cow<'static, str> {
+        match kind {
+            GutterColumnKind::LineNumber => {
+                return if line.is_deleted() {
+                    "".into()
+                } else {
+                    line.line_number().to_string().into()
+                };
+            }
+            GutterColumnKind::OriginalLineNumber => {
+                return line
+                    .original_line_number()
+                    .map_or_else(|| "".into(), |line_number| line_number.to_string().into());
+            }
+            GutterColumnKind::Blame => return self.blame_column_str(line, commit),
+            // A full block, colored by `LineConstraint::heat_color()`.
+            GutterColumnKind::Heat => {
+                return if commit.is_some() { "\u{2588}" } else { " " }.into();
+            }
+            _ => {}
+        }
+        let Some(commit) = commit else {
+            return "...".into();
+        };
+        if line.index_in_hunk() > 0 {
+            return "".into();
+        }
+        match kind {
+            GutterColumnKind::CommitIndex => {
+                format!("{}{}", Self::prefix(line), commit.index()).into()
+            }
+            GutterColumnKind::ShortId => commit.commit_id().to_string().into(),
+            GutterColumnKind::ChangeId => {
+                commit.change_id().map(Self::short_change_id).or_default()
+            }
+            GutterColumnKind::Author => self
+                .author_format
+                .format(commit.author_name(), commit.author_email())
+                .into(),
+            GutterColumnKind::Date => self.time_str(commit).into(),
+            GutterColumnKind::Summary => commit.summary().or_default(),
+            GutterColumnKind::LineNumber
+            | GutterColumnKind::OriginalLineNumber
+            | GutterColumnKind::Blame
+            | GutterColumnKind::Heat => unreachable!(),
+        }
+    }
+
+    /// The `GutterColumnKind::Blame` column.
+    fn blame_column_str(&self, line: &Line, commit: Option<&FileCommit>) -> Cow<'static, str> {
+        let Some(commit) = commit else {
+            return if self.is_not_committed {
+                "Not committed".into()
+            } else {
+                "...".into()
+            };
+        };
+        let author = || {
+            self.author_format
+                .format(commit.author_name(), commit.author_email())
+        };
+        match line.index_in_hunk() {
+            0 => {
+                let datetime = self.time_str(commit);
+                if line.is_log() {
+                    format!("{} {}", datetime, author())
+                } else {
+                    format!("{}{} {}", Self::prefix(line), commit.index(), datetime)
+                }
+                .into()
+            }
+            1 => commit.summary().map(|s| format!("  {s}")).or_default(),
+            2 => format!("  {}", author()).into(),
+            3 => match commit.change_id() {
+                Some(change_id) => {
+                    format!(
+                        "  {} {}",
+                        Self::short_change_id(change_id),
+                        commit.commit_id()
+                    )
+                }
+                None => format!("  {}", commit.commit_id()),
+            }
+            .into(),
+            _ => "".into(),
+        }
+    }
+
+    /// The first 8 characters of the `change_id`, as `jj log` shows.
+    /// The `??` of divergent changes is kept.
+    fn short_change_id(change_id: &str) -> String {
+        let (id, suffix) = match change_id.strip_suffix("??") {
+            Some(id) => (id, "??"),
+            None => (change_id, ""),
+        };
+        let id: String = id.chars().take(8).collect();
+        id + suffix
+    }
+
+    /// The prefix of the commit index.
+    fn prefix(line: &Line) -> char {
+        // Mark moved lines with `>`, and boundary commits with `^` as
+        // `git blame` does.
+        if line.is_moved() {
+            '>'
+        } else if line.is_boundary() {
+            '^'
+        } else {
+            '#'
+        }
+    }
+
+    fn time_str(&self, commit: &FileCommit) -> String {
+        match self.time_format {
+            TimeFormat::Absolute => commit.time().to_local_date_time().map_or_else(
+                |e| format!("Invalid date/time: {e}"),
+                |datetime| datetime.format("%Y-%m-%d %H:%M").to_string(),
+            ),
+            TimeFormat::Relative => {
+                let age = self.now.timestamp() - commit.time().seconds();
+                TimeFormat::relative_time_str(TimeDelta::seconds(age))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn relative_time_str() {
+        let target = |age: TimeDelta| TimeFormat::relative_time_str(age);
+        assert_eq!(target(TimeDelta::days(365 * 3 + 10)), "3y");
+        assert_eq!(target(TimeDelta::days(15)), "2w");
+        assert_eq!(target(TimeDelta::days(5)), "5d");
+        assert_eq!(target(TimeDelta::hours(3)), "3h");
+        assert_eq!(target(TimeDelta::minutes(42)), "42m");
+        assert_eq!(target(TimeDelta::seconds(-5)), "0m");
+    }
+
+    #[test]
+    fn author_format() {
+        let target = |format: AuthorFormat| format.format("alice smith", "alice@example.com");
+        assert_eq!(target(AuthorFormat::Email), "alice@example.com");
+        assert_eq!(target(AuthorFormat::Name), "alice smith");
+        assert_eq!(target(AuthorFormat::Initials), "AS");
+        assert_eq!(
+            target(AuthorFormat::NameEmail),
+            "alice smith <alice@example.com>"
+        );
+        assert_eq!(AuthorFormat::Name.format("", "bob@x"), "bob@x");
+    }
+
+    #[test]
+    fn left_pane() -> anyhow::Result<()> {
+        let mut constraint = LineConstraint::new(80);
+        let line = Line::new(12, String::new());
+        assert_eq!(
+            constraint.left_pane(&line, None),
+            format!("  12:{:25}|", "...")
+        );
+        let deleted = Line::new_deleted(12, git2::Oid::ZERO_SHA1);
+        assert_eq!(
+            constraint.left_pane(&deleted, None),
+            format!("    :{:25}|", "...")
+        );
+
+        constraint.set_columns(&["number:2".parse()?, "sha".parse()?, "author:4".parse()?]);
+        assert_eq!(constraint.left_pane(&line, None), "12:...     ... |");
+        let line = Line::new(12345, String::new());
+        assert_eq!(constraint.left_pane(&line, None), "12345:...     ... |");
+
+        constraint.set_columns(&["heat:2".parse()?, "number".parse()?]);
+        assert_eq!(constraint.left_pane(&line, None), "   12345|");
+
+        constraint.set_columns(&["number".parse()?, "orig".parse()?]);
+        let mut line = Line::new(12, String::new());
+        assert_eq!(constraint.left_pane(&line, None), "  12:    |");
+        line.set_original_line_number(7);
+        assert_eq!(constraint.left_pane(&line, None), "  12:   7|");
+
+        let line = Line::new(3, "content".to_string());
+        let history = FileHistory::new_for_test();
+        assert_eq!(constraint.plain_text(&line, &history)?, "   3:    |content");
+        Ok(())
+    }
+
+    #[test]
+    fn heat_color() {
+        let mut constraint = LineConstraint::new(80);
+        constraint.set_time_range(Some((100, 200)));
+        let mut commit = FileCommit::new(git2::Oid::ZERO_SHA1, Path::new(""));
+        commit.set_time_for_test(git2::Time::new(200, 0));
+        assert_eq!(
+            constraint.heat_color(&commit, &Theme::default()),
+            Some(style::Color::Rgb {
+                r: 255,
+                g: 64,
+                b: 0
+            })
+        );
+        commit.set_time_for_test(git2::Time::new(100, 0));
+        let cold = Some(style::Color::Rgb {
+            r: 64,
+            g: 96,
+            b: 255,
+        });
+        assert_eq!(constraint.heat_color(&commit, &Theme::default()), cold);
+
+        // Older than the `heat_range` are the coldest.
+        constraint.set_time_range(Some((0, 200)));
+        constraint.set_heat_range(Some(TimeDelta::seconds(100)));
+        assert_eq!(constraint.heat_color(&commit, &Theme::default()), cold);
+        commit.set_time_for_test(git2::Time::new(50, 0));
+        assert_eq!(constraint.heat_color(&commit, &Theme::default()), cold);
+    }
+
+    #[test]
+    fn truncate() {
+        let c = LineConstraint::new(5);
+        assert_eq!(c.truncate("abc", 0), "abc");
+        assert_eq!(c.truncate("abc", 3), "ab");
+        assert_eq!(c.truncate("abc", 5), "");
+    }
+
+    #[test]
+    fn truncate_tab() {
+        let c = LineConstraint::new(5);
+        assert_eq!(c.truncate("\t", 1), "    ");
+        assert_eq!(c.truncate("\t", 2), "");
+
+        assert_eq!(c.truncate("a\t", 0), "a   ");
+        assert_eq!(c.truncate("a\t", 1), "a   ");
+        assert_eq!(c.truncate("a\t", 2), "a");
+
+        assert_eq!(c.truncate("123\t", 0), "123 ");
+        let c = LineConstraint::new(10);
+        assert_eq!(c.truncate("1234\t", 0), "1234    ");
+    }
+
+    #[test]
+    fn truncate_wide() {
+        let c = LineConstraint::new(11);
+        assert_eq!(c.truncate("あいうえお", 0), "あいうえお");
+        assert_eq!(c.truncate("あいうえお", 1), "あいうえお");
+        assert_eq!(c.truncate("あいうえお", 2), "あいうえ");
+        assert_eq!(c.truncate("あいうえお", 3), "あいうえ");
+        assert_eq!(c.truncate("あいうえお", 9), "あ");
+        assert_eq!(c.truncate("あいうえお", 10), "");
+        assert_eq!(c.truncate("あいうえお", 11), "");
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use super::Command;
+
+/// The commands recorded to registers, to replay them like `q` and `@` of `vi`.
+/// The registers are kept only in the session.
+#[derive(Debug, Default)]
+pub(crate) struct Macros {
+    registers: HashMap<char, Vec<Command>>,
+    /// The register and the commands while recording.
+    recording: Option<(char, Vec<Command>)>,
+    /// The register replayed last, for `@@`.
+    last_replayed: Option<char>,
+}
+
+impl Macros {
+    /// The register that means the register replayed last.
+    const LAST_REGISTER: char = '@';
+
+    /// Whether the `register` can record commands.
+    pub fn is_register(register: char) -> bool {
+        register.is_ascii_alphanumeric()
+    }
+
+    pub fn recording_register(&self) -> Option<char> {
+        self.recording.as_ref().map(|(register, _)| *register)
+    }
+
+    /// Start recording commands to the `register`.
+    pub fn start_recording(&mut self, register: char) -> anyhow::Result<()> {
+        anyhow::ensure!(Self::is_register(register), "Invalid register: {register}");
+        self.recording = Some((register, vec![]));
+        Ok(())
+    }
+
+    /// Stop recording, and save the recorded commands to the register.
+    /// Returns the register and the number of the commands.
+    pub fn stop_recording(&mut self) -> Option<(char, usize)> {
+        let (register, commands) = self.recording.take()?;
+        let len = commands.len();
+        self.registers.insert(register, commands);
+        Some((register, len))
+    }
+
+    /// Record the `command` if recording.
+    /// Commands that control macros or the terminal are not recorded.
+    pub fn record(&mut self, command: &Command) {
+        let Some((_, commands)) = self.recording.as_mut() else {
+            return;
+        };
+        if matches!(
+            command,
+            Command::RecordMacro
+                | Command::ReplayMacro
+                | Command::Resize(_, _)
+                | Command::Repaint
+                | Command::Help
+                | Command::Quit
+                | Command::Timeout
+                | Command::Debug
+        ) {
+            return;
+        }
+        commands.push(command.clone());
+    }
+
+    /// The commands to replay for the `register`.
+    /// `@` is the register replayed last.
+    pub fn commands(&mut self, register: char) -> anyhow::Result<Vec<Command>> {
+        let register = if register == Self::LAST_REGISTER {
+            self.last_replayed
+                .ok_or_else(|| anyhow::anyhow!("No previously replayed register"))?
+        } else {
+            register
+        };
+        let Some(commands) = self.registers.get(&register) else {
+            anyhow::bail!("Register {register} is empty");
+        };
+        self.last_replayed = Some(register);
+        Ok(commands.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record() -> anyhow::Result<()> {
+        let mut macros = Macros::default();
+        macros.record(&Command::Older);
+        assert!(macros.commands('a').is_err());
+
+        macros.start_recording('a')?;
+        assert_eq!(macros.recording_register(), Some('a'));
+        macros.record(&Command::Older);
+        macros.record(&Command::Timeout);
+        macros.record(&Command::Search("TODO".to_string()));
+        macros.record(&Command::Copy);
+        assert_eq!(macros.stop_recording(), Some(('a', 3)));
+        assert_eq!(macros.recording_register(), None);
+        assert_eq!(macros.stop_recording(), None);
+        macros.record(&Command::Newer);
+
+        let expected = [
+            Command::Older,
+            Command::Search("TODO".to_string()),
+            Command::Copy,
+        ];
+        assert!(macros.commands('@').is_err());
+        assert_eq!(macros.commands('a')?, expected);
+        assert_eq!(macros.commands('@')?, expected);
+        assert!(macros.commands('b').is_err());
+        assert!(macros.start_recording('!').is_err());
+        Ok(())
+    }
+}
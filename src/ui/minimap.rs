@@ -0,0 +1,144 @@
+use std::ops::Range;
+
+use crate::blame::Line;
+
+/// A cell of the minimap, which summarizes a range of lines.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct MinimapCell {
+    /// The number of the boundaries of hunks in the lines.
+    boundaries: usize,
+    /// Whether the lines contain lines of the current line commit.
+    has_current_commit: bool,
+    /// Whether the lines are in the view.
+    is_in_view: bool,
+}
+
+impl MinimapCell {
+    /// The character to render the cell.
+    pub fn symbol(&self) -> char {
+        if self.has_current_commit {
+            return '\u{2588}';
+        }
+        match self.boundaries {
+            0 => ' ',
+            1 => '\u{2591}',
+            2 => '\u{2592}',
+            _ => '\u{2593}',
+        }
+    }
+
+    pub fn has_current_commit(&self) -> bool {
+        self.has_current_commit
+    }
+
+    pub fn is_in_view(&self) -> bool {
+        self.is_in_view
+    }
+}
+
+/// A narrow column that shows where the hunks and the lines of the current
+/// line commit are in the whole file.
+/// Each row of the minimap summarizes a range of lines.
+#[derive(Debug)]
+pub(crate) struct Minimap {
+    lines_len: usize,
+    rows: usize,
+}
+
+impl Minimap {
+    pub fn new(lines_len: usize, rows: usize) -> Self {
+        Self { lines_len, rows }
+    }
+
+    /// The line indexes of the minimap `row`.
+    pub fn line_index_range(&self, row: usize) -> Range<usize> {
+        if self.lines_len <= self.rows {
+            return row.min(self.lines_len)..(row + 1).min(self.lines_len);
+        }
+        row * self.lines_len / self.rows..(row + 1) * self.lines_len / self.rows
+    }
+
+    /// The minimap row that contains the `line_index`.
+    pub fn row_from_line_index(&self, line_index: usize) -> usize {
+        if self.lines_len <= self.rows {
+            return line_index;
+        }
+        // The inverse of `line_index_range`; the largest `row` where
+        // `row * lines_len / rows <= line_index`.
+        ((line_index + 1) * self.rows - 1) / self.lines_len
+    }
+
+    /// Compute the cells of the `lines`, where `commit_id` is the current line
+    /// commit, and `view` is the line indexes in the view.
+    pub fn cells(
+        &self,
+        lines: &[Line],
+        commit_id: Option<git2::Oid>,
+        view: &Range<usize>,
+    ) -> Vec<MinimapCell> {
+        assert_eq!(lines.len(), self.lines_len);
+        (0..self.rows)
+            .map(|row| {
+                let range = self.line_index_range(row);
+                let mut cell = MinimapCell {
+                    is_in_view: range.start < view.end && view.start < range.end,
+                    ..Default::default()
+                };
+                for line_index in range {
+                    let line = &lines[line_index];
+                    if commit_id.is_some() && line.commit_id() == commit_id {
+                        cell.has_current_commit = true;
+                    }
+                    if line_index > 0 && lines[line_index - 1].commit_id() != line.commit_id() {
+                        cell.boundaries += 1;
+                    }
+                }
+                cell
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_index_range() {
+        let minimap = Minimap::new(10, 4);
+        let ranges: Vec<Range<usize>> = (0..4).map(|row| minimap.line_index_range(row)).collect();
+        assert_eq!(ranges, [0..2, 2..5, 5..7, 7..10]);
+        let rows: Vec<usize> = (0..10).map(|i| minimap.row_from_line_index(i)).collect();
+        assert_eq!(rows, [0, 0, 1, 1, 1, 2, 2, 3, 3, 3]);
+
+        // Fewer lines than rows.
+        let minimap = Minimap::new(2, 4);
+        assert_eq!(minimap.line_index_range(1), 1..2);
+        assert!(minimap.line_index_range(3).is_empty());
+        assert_eq!(minimap.row_from_line_index(1), 1);
+    }
+
+    #[test]
+    fn cells() {
+        let commit_id1 = git2::Oid::from_bytes(&[1; 20]).unwrap();
+        let commit_id2 = git2::Oid::from_bytes(&[2; 20]).unwrap();
+        let mut lines: Vec<Line> = (1..=8).map(|i| Line::new(i, String::new())).collect();
+        for (i, line) in lines.iter_mut().enumerate() {
+            line.set_commit_id(if i == 2 || i == 6 {
+                commit_id2
+            } else {
+                commit_id1
+            });
+        }
+        let minimap = Minimap::new(8, 4);
+        let cells = minimap.cells(&lines, Some(commit_id2), &(0..4));
+        let symbols: Vec<char> = cells.iter().map(|cell| cell.symbol()).collect();
+        assert_eq!(symbols, [' ', '\u{2588}', ' ', '\u{2588}']);
+        let in_view: Vec<bool> = cells.iter().map(|cell| cell.is_in_view()).collect();
+        assert_eq!(in_view, [true, true, false, false]);
+
+        let cells = minimap.cells(&lines, None, &(0..4));
+        let boundaries: Vec<usize> = cells.iter().map(|cell| cell.boundaries).collect();
+        assert_eq!(boundaries, [0, 2, 0, 2]);
+    }
+}
@@ -4,6 +4,9 @@ pub(crate) use blame_renderer::*;
 mod cli;
 pub use cli::*;
 
+mod cli_args;
+pub(crate) use cli_args::*;
+
 mod command;
 pub(crate) use command::*;
 
@@ -13,5 +16,47 @@ pub(crate) use command_ui::*;
 mod command_key_map;
 pub(crate) use command_key_map::*;
 
+mod command_registry;
+pub(crate) use command_registry::*;
+
 mod command_prompt;
 pub(crate) use command_prompt::*;
+
+mod config;
+pub(crate) use config::*;
+
+mod commit_detail;
+pub(crate) use commit_detail::*;
+
+mod copy_target;
+pub(crate) use copy_target::*;
+
+mod file_location;
+pub(crate) use file_location::*;
+
+mod display_rows;
+pub(crate) use display_rows::*;
+
+mod diff_overlay;
+pub(crate) use diff_overlay::*;
+
+mod minimap;
+pub(crate) use minimap::*;
+
+mod line_constraint;
+pub(crate) use line_constraint::*;
+
+mod jump_list;
+pub(crate) use jump_list::*;
+
+mod macros;
+pub(crate) use macros::*;
+
+mod render_target;
+pub(crate) use render_target::*;
+
+mod text_buffer;
+pub(crate) use text_buffer::*;
+
+mod theme;
+pub(crate) use theme::*;
@@ -0,0 +1,136 @@
+use std::io::Write;
+
+use crossterm::{
+    cursor, queue,
+    style::{self, Attribute, Color, ContentStyle},
+    terminal,
+};
+
+/// The screen to render to, such as a terminal.
+///
+/// The renderers use this instead of writing the terminal sequences directly,
+/// so that they can render to other targets. Any `Write` is a target, which
+/// writes the sequences by `crossterm`.
+pub(crate) trait RenderTarget {
+    /// Move the cursor to the 0-based `column` and `row`.
+    fn move_to(&mut self, column: u16, row: u16) -> anyhow::Result<()>;
+
+    fn clear_all(&mut self) -> anyhow::Result<()>;
+
+    /// Clear the row of the cursor.
+    fn clear_line(&mut self) -> anyhow::Result<()>;
+
+    /// Clear from the cursor to the end of the row.
+    fn clear_until_new_line(&mut self) -> anyhow::Result<()>;
+
+    /// Scroll the screen up by the `rows`, leaving empty rows at the bottom.
+    fn scroll_up(&mut self, rows: u16) -> anyhow::Result<()>;
+
+    /// Scroll the screen down by the `rows`, leaving empty rows at the top.
+    fn scroll_down(&mut self, rows: u16) -> anyhow::Result<()>;
+
+    fn set_style(&mut self, style: ContentStyle) -> anyhow::Result<()>;
+
+    fn set_foreground_color(&mut self, color: Color) -> anyhow::Result<()>;
+
+    fn set_attribute(&mut self, attribute: Attribute) -> anyhow::Result<()>;
+
+    /// Reset the colors and the attributes.
+    fn reset_style(&mut self) -> anyhow::Result<()>;
+
+    /// Print the `text` at the cursor, and move the cursor after it.
+    fn print(&mut self, text: &str) -> anyhow::Result<()>;
+
+    /// Print the `text` in the `style`, and reset the style.
+    fn print_styled(&mut self, style: ContentStyle, text: &str) -> anyhow::Result<()> {
+        self.set_style(style)?;
+        self.print(text)?;
+        self.reset_style()
+    }
+}
+
+impl<W: Write> RenderTarget for W {
+    fn move_to(&mut self, column: u16, row: u16) -> anyhow::Result<()> {
+        queue!(self, cursor::MoveTo(column, row))?;
+        Ok(())
+    }
+
+    fn clear_all(&mut self) -> anyhow::Result<()> {
+        queue!(self, terminal::Clear(terminal::ClearType::All))?;
+        Ok(())
+    }
+
+    fn clear_line(&mut self) -> anyhow::Result<()> {
+        queue!(self, terminal::Clear(terminal::ClearType::CurrentLine))?;
+        Ok(())
+    }
+
+    fn clear_until_new_line(&mut self) -> anyhow::Result<()> {
+        queue!(self, terminal::Clear(terminal::ClearType::UntilNewLine))?;
+        Ok(())
+    }
+
+    fn scroll_up(&mut self, rows: u16) -> anyhow::Result<()> {
+        queue!(self, terminal::ScrollUp(rows))?;
+        Ok(())
+    }
+
+    fn scroll_down(&mut self, rows: u16) -> anyhow::Result<()> {
+        queue!(self, terminal::ScrollDown(rows))?;
+        Ok(())
+    }
+
+    fn set_style(&mut self, style: ContentStyle) -> anyhow::Result<()> {
+        queue!(self, style::SetStyle(style))?;
+        Ok(())
+    }
+
+    fn set_foreground_color(&mut self, color: Color) -> anyhow::Result<()> {
+        queue!(self, style::SetForegroundColor(color))?;
+        Ok(())
+    }
+
+    fn set_attribute(&mut self, attribute: Attribute) -> anyhow::Result<()> {
+        queue!(self, style::SetAttribute(attribute))?;
+        Ok(())
+    }
+
+    fn reset_style(&mut self) -> anyhow::Result<()> {
+        queue!(
+            self,
+            style::ResetColor,
+            style::SetAttribute(Attribute::Reset)
+        )?;
+        Ok(())
+    }
+
+    fn print(&mut self, text: &str) -> anyhow::Result<()> {
+        queue!(self, style::Print(text))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Stylize;
+
+    use super::*;
+
+    #[test]
+    fn write() -> anyhow::Result<()> {
+        let mut out = Vec::<u8>::new();
+        out.move_to(1, 2)?;
+        out.print_styled(ContentStyle::new().red(), "a")?;
+        let mut expected = Vec::<u8>::new();
+        queue!(
+            expected,
+            cursor::MoveTo(1, 2),
+            style::SetStyle(ContentStyle::new().red()),
+            style::Print("a"),
+            style::ResetColor,
+            style::SetAttribute(Attribute::Reset),
+        )?;
+        assert_eq!(out, expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+use std::{cmp, ops::RangeFrom};
+
+use crossterm::style::{Attribute, Color, ContentStyle};
+
+use super::RenderTarget;
+
+/// A `RenderTarget` that renders to a grid of characters in memory,
+/// to get the rendered screen as text.
+///
+/// Each `char` occupies a column; the width of wide characters is not
+/// considered. Text beyond the `width` is dropped instead of wrapping.
+#[derive(Debug)]
+pub(crate) struct TextBuffer {
+    width: u16,
+    rows: Vec<Vec<(char, ContentStyle)>>,
+    cursor: (u16, u16),
+    style: ContentStyle,
+}
+
+impl TextBuffer {
+    pub(crate) fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            rows: (0..height).map(|_| Self::empty_row(width)).collect(),
+            cursor: (0, 0),
+            style: ContentStyle::default(),
+        }
+    }
+
+    fn empty_row(width: u16) -> Vec<(char, ContentStyle)> {
+        vec![(' ', ContentStyle::default()); width as usize]
+    }
+
+    /// The rows in plain text, without trailing spaces.
+    pub(crate) fn to_plain_string(&self) -> String {
+        self.to_string_with(false)
+    }
+
+    /// The rows in text, with markers around the styled text;
+    /// e.g., `<fg=DarkGrey,bold>text</>`.
+    pub(crate) fn to_styled_string(&self) -> String {
+        self.to_string_with(true)
+    }
+
+    fn to_string_with(&self, has_markers: bool) -> String {
+        let mut text = String::new();
+        for row in &self.rows {
+            // Trailing spaces are not rendered, unless they're styled.
+            let len = row
+                .iter()
+                .rposition(|(ch, style)| {
+                    *ch != ' ' || (has_markers && *style != ContentStyle::default())
+                })
+                .map_or(0, |index| index + 1);
+            let mut current_style = ContentStyle::default();
+            for (ch, style) in &row[..len] {
+                if has_markers && *style != current_style {
+                    if current_style != ContentStyle::default() {
+                        text.push_str("</>");
+                    }
+                    if *style != ContentStyle::default() {
+                        text.push_str(&Self::style_marker(style));
+                    }
+                    current_style = *style;
+                }
+                text.push(*ch);
+            }
+            if current_style != ContentStyle::default() {
+                text.push_str("</>");
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    fn style_marker(style: &ContentStyle) -> String {
+        let mut values = Vec::new();
+        if let Some(color) = style.foreground_color {
+            values.push(format!("fg={}", Self::color_name(color)));
+        }
+        if let Some(color) = style.background_color {
+            values.push(format!("bg={}", Self::color_name(color)));
+        }
+        for attribute in Attribute::iterator() {
+            if attribute != Attribute::Reset && style.attributes.has(attribute) {
+                values.push(format!("{attribute:?}").to_lowercase());
+            }
+        }
+        format!("<{}>", values.join(","))
+    }
+
+    fn color_name(color: Color) -> String {
+        match color {
+            Color::Rgb { r, g, b } => format!("#{r:02x}{g:02x}{b:02x}"),
+            _ => format!("{color:?}"),
+        }
+    }
+
+    fn clear_columns(&mut self, columns: RangeFrom<usize>) {
+        let row = self.cursor.1 as usize;
+        if let Some(row) = self.rows.get_mut(row) {
+            row[columns].fill((' ', ContentStyle::default()));
+        }
+    }
+}
+
+impl RenderTarget for TextBuffer {
+    fn move_to(&mut self, column: u16, row: u16) -> anyhow::Result<()> {
+        self.cursor = (column, row);
+        Ok(())
+    }
+
+    fn clear_all(&mut self) -> anyhow::Result<()> {
+        let height = self.rows.len() as u16;
+        *self = Self::new(self.width, height);
+        Ok(())
+    }
+
+    fn clear_line(&mut self) -> anyhow::Result<()> {
+        self.clear_columns(0..);
+        Ok(())
+    }
+
+    fn clear_until_new_line(&mut self) -> anyhow::Result<()> {
+        let column = cmp::min(self.cursor.0, self.width);
+        self.clear_columns(column as usize..);
+        Ok(())
+    }
+
+    fn scroll_up(&mut self, rows: u16) -> anyhow::Result<()> {
+        let rows = cmp::min(rows as usize, self.rows.len());
+        self.rows.drain(..rows);
+        self.rows
+            .extend((0..rows).map(|_| Self::empty_row(self.width)));
+        Ok(())
+    }
+
+    fn scroll_down(&mut self, rows: u16) -> anyhow::Result<()> {
+        let rows = cmp::min(rows as usize, self.rows.len());
+        self.rows.truncate(self.rows.len() - rows);
+        self.rows
+            .splice(0..0, (0..rows).map(|_| Self::empty_row(self.width)));
+        Ok(())
+    }
+
+    fn set_style(&mut self, style: ContentStyle) -> anyhow::Result<()> {
+        if style.foreground_color.is_some() {
+            self.style.foreground_color = style.foreground_color;
+        }
+        if style.background_color.is_some() {
+            self.style.background_color = style.background_color;
+        }
+        self.style.attributes.extend(style.attributes);
+        Ok(())
+    }
+
+    fn set_foreground_color(&mut self, color: Color) -> anyhow::Result<()> {
+        self.style.foreground_color = Some(color);
+        Ok(())
+    }
+
+    fn set_attribute(&mut self, attribute: Attribute) -> anyhow::Result<()> {
+        if attribute == Attribute::Reset {
+            self.style.attributes = Default::default();
+        } else {
+            self.style.attributes.set(attribute);
+        }
+        Ok(())
+    }
+
+    fn reset_style(&mut self) -> anyhow::Result<()> {
+        self.style = ContentStyle::default();
+        Ok(())
+    }
+
+    fn print(&mut self, text: &str) -> anyhow::Result<()> {
+        let (column, row) = self.cursor;
+        if let Some(row) = self.rows.get_mut(row as usize) {
+            for (cell, ch) in row.iter_mut().skip(column as usize).zip(text.chars()) {
+                *cell = (ch, self.style);
+            }
+        }
+        self.cursor.0 = column.saturating_add(text.chars().count() as u16);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Stylize;
+
+    use super::*;
+
+    #[test]
+    fn print() -> anyhow::Result<()> {
+        let mut buffer = TextBuffer::new(5, 3);
+        buffer.move_to(1, 0)?;
+        buffer.print("abcdefg")?;
+        buffer.move_to(0, 2)?;
+        buffer.print("x")?;
+        buffer.print("y")?;
+        assert_eq!(buffer.to_plain_string(), " abcd\n\nxy\n");
+
+        buffer.move_to(2, 0)?;
+        buffer.clear_until_new_line()?;
+        assert_eq!(buffer.to_plain_string(), " a\n\nxy\n");
+        buffer.scroll_down(1)?;
+        assert_eq!(buffer.to_plain_string(), "\n a\n\n");
+        buffer.scroll_up(2)?;
+        assert_eq!(buffer.to_plain_string(), "\n\n\n");
+        Ok(())
+    }
+
+    #[test]
+    fn to_styled_string() -> anyhow::Result<()> {
+        let mut buffer = TextBuffer::new(10, 1);
+        buffer.print("a")?;
+        buffer.print_styled(ContentStyle::new().dark_grey().bold(), "bc")?;
+        buffer.set_foreground_color(Color::Rgb { r: 1, g: 2, b: 255 })?;
+        buffer.print("d ")?;
+        buffer.reset_style()?;
+        buffer.print("e")?;
+        assert_eq!(buffer.to_plain_string(), "abcd e\n");
+        assert_eq!(
+            buffer.to_styled_string(),
+            "a<fg=DarkGrey,bold>bc</><fg=#0102ff>d </>e\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,470 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use crossterm::style::{Attribute, Color, ContentStyle};
+
+use crate::extensions::config_dir;
+
+/// The styles to render lines and the prompt.
+///
+/// Themes are written in TOML, with an optional `base` theme to override;
+/// e.g.,
+/// ```toml
+/// base = "light"
+/// current-line = { fg = "black", bg = "yellow" }
+/// selected-line = { attributes = ["bold"] }
+/// dimmed = "dark_grey"
+/// heat-hot = "#ff0000"
+/// authors = ["red", "green", "blue"]
+/// ```
+/// A style is a table of `fg`, `bg`, and `attributes`, or a string of `fg`.
+/// Colors are the names of `crossterm::style::Color` such as `dark_grey`,
+/// or `#rrggbb`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    current_line: ContentStyle,
+    selected_line: ContentStyle,
+    /// Lines of the pinned commit.
+    pinned_line: ContentStyle,
+    /// Lines whose commits failed to apply.
+    error_line: ContentStyle,
+    /// Lines not matching the filter, folds, and borders.
+    dimmed: ContentStyle,
+    boundary: ContentStyle,
+    deleted: ContentStyle,
+    /// The heatmap gradient from the oldest commit to the newest commit.
+    /// `None` to not color the heatmap.
+    heat_cold: Option<(u8, u8, u8)>,
+    heat_hot: Option<(u8, u8, u8)>,
+    /// The colors of the author column, chosen by the author email.
+    /// Empty to not color authors.
+    authors: Vec<Color>,
+    /// Errors in the prompt.
+    error: ContentStyle,
+    /// Hints in the prompt.
+    hint: ContentStyle,
+    /// The lines of the current line commit in the minimap.
+    minimap: ContentStyle,
+    /// The lines in the view in the minimap.
+    minimap_view: ContentStyle,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::builtin("dark").unwrap()
+    }
+}
+
+impl Theme {
+    /// The file name of the theme in the `config_dir()`.
+    pub const CONFIG_FILE_NAME: &str = "theme.toml";
+
+    /// The theme when colors are disabled.
+    pub const NO_COLOR: &str = "monochrome";
+
+    const BUILTINS: &[(&str, &str)] = &[
+        (
+            "dark",
+            r##"
+current-line = { fg = "black", bg = "cyan" }
+selected-line = { fg = "white", bg = "dark_blue" }
+pinned-line = { fg = "white", bg = "dark_magenta" }
+error-line = { fg = "red", bg = "black" }
+dimmed = "dark_grey"
+boundary = "dark_yellow"
+deleted = "dark_grey"
+heat-cold = "#4060ff"
+heat-hot = "#ff4000"
+authors = []
+error = { fg = "white", bg = "red" }
+hint = "dark_grey"
+minimap = "cyan"
+minimap-view = { bg = "dark_grey" }
+"##,
+        ),
+        (
+            "light",
+            r##"
+current-line = { fg = "white", bg = "dark_blue" }
+selected-line = { fg = "black", bg = "cyan" }
+pinned-line = { fg = "black", bg = "magenta" }
+error-line = { fg = "dark_red", bg = "white" }
+dimmed = "grey"
+boundary = "dark_magenta"
+deleted = "grey"
+heat-cold = "#2040c0"
+heat-hot = "#c02000"
+authors = []
+error = { fg = "white", bg = "dark_red" }
+hint = "grey"
+minimap = "dark_blue"
+minimap-view = { bg = "grey" }
+"##,
+        ),
+        (
+            "solarized",
+            r##"
+current-line = { fg = "#002b36", bg = "#2aa198" }
+selected-line = { fg = "#fdf6e3", bg = "#268bd2" }
+pinned-line = { fg = "#fdf6e3", bg = "#d33682" }
+error-line = { fg = "#dc322f", bg = "#002b36" }
+dimmed = "#586e75"
+boundary = "#b58900"
+deleted = "#586e75"
+heat-cold = "#268bd2"
+heat-hot = "#cb4b16"
+authors = ["#b58900", "#cb4b16", "#dc322f", "#d33682", "#6c71c4", "#268bd2", "#2aa198", "#859900"]
+error = { fg = "#fdf6e3", bg = "#dc322f" }
+hint = "#586e75"
+minimap = "#2aa198"
+minimap-view = { bg = "#073642" }
+"##,
+        ),
+        (
+            "monochrome",
+            r##"
+current-line = { attributes = ["reverse"] }
+selected-line = { attributes = ["bold"] }
+pinned-line = { attributes = ["italic"] }
+dimmed = { attributes = ["dim"] }
+error = { attributes = ["reverse"] }
+hint = { attributes = ["dim"] }
+minimap-view = { attributes = ["reverse"] }
+"##,
+        ),
+    ];
+
+    /// The builtin theme of the `name`.
+    pub fn builtin(name: &str) -> anyhow::Result<Self> {
+        let Some((_, content)) = Self::BUILTINS.iter().find(|(n, _)| *n == name) else {
+            let names: Vec<&str> = Self::BUILTINS.iter().map(|(n, _)| *n).collect();
+            anyhow::bail!(
+                "Unknown theme \"{name}\", possible values are: {}",
+                names.join(", ")
+            );
+        };
+        let mut theme = Self {
+            current_line: ContentStyle::new(),
+            selected_line: ContentStyle::new(),
+            pinned_line: ContentStyle::new(),
+            error_line: ContentStyle::new(),
+            dimmed: ContentStyle::new(),
+            boundary: ContentStyle::new(),
+            deleted: ContentStyle::new(),
+            heat_cold: None,
+            heat_hot: None,
+            authors: vec![],
+            error: ContentStyle::new(),
+            hint: ContentStyle::new(),
+            minimap: ContentStyle::new(),
+            minimap_view: ContentStyle::new(),
+        };
+        theme.apply(&content.parse()?)?;
+        Ok(theme)
+    }
+
+    /// Create from the `theme`, which is the name of a builtin theme, or the
+    /// path to a TOML file. If it's `None`, the `CONFIG_FILE_NAME` in the
+    /// `config_dir()` is used if it exists.
+    pub fn new_with_config(theme: Option<&str>) -> anyhow::Result<Self> {
+        let path = match theme {
+            Some(name) if Self::BUILTINS.iter().any(|(n, _)| *n == name) => {
+                return Self::builtin(name);
+            }
+            Some(path) => Path::new(path).to_path_buf(),
+            None => match config_dir().map(|dir| dir.join(Self::CONFIG_FILE_NAME)) {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_toml(&content).with_context(|| format!("Invalid theme in {}", path.display()))
+    }
+
+    /// Create from the TOML `content`, based on its `base` theme,
+    /// or the default theme if it doesn't have `base`.
+    pub fn from_toml(content: &str) -> anyhow::Result<Self> {
+        let table: toml::Table = content.parse()?;
+        let mut theme = match table.get("base") {
+            Some(base) => match base.as_str() {
+                Some(name) => Self::builtin(name)?,
+                None => anyhow::bail!("The value of \"base\" must be a string"),
+            },
+            None => Self::default(),
+        };
+        theme.apply(&table)?;
+        Ok(theme)
+    }
+
+    const ATTRIBUTES: &[(&str, Attribute)] = &[
+        ("bold", Attribute::Bold),
+        ("dim", Attribute::Dim),
+        ("italic", Attribute::Italic),
+        ("underlined", Attribute::Underlined),
+        ("reverse", Attribute::Reverse),
+    ];
+
+    /// Override the styles by the keys of the `table`.
+    fn apply(&mut self, table: &toml::Table) -> anyhow::Result<()> {
+        for (key, value) in table {
+            let result = match key.as_str() {
+                "base" => continue,
+                "current-line" => Self::apply_style(&mut self.current_line, value),
+                "selected-line" => Self::apply_style(&mut self.selected_line, value),
+                "pinned-line" => Self::apply_style(&mut self.pinned_line, value),
+                "error-line" => Self::apply_style(&mut self.error_line, value),
+                "dimmed" => Self::apply_style(&mut self.dimmed, value),
+                "boundary" => Self::apply_style(&mut self.boundary, value),
+                "deleted" => Self::apply_style(&mut self.deleted, value),
+                "heat-cold" => Self::rgb_from_value(value).map(|c| self.heat_cold = Some(c)),
+                "heat-hot" => Self::rgb_from_value(value).map(|c| self.heat_hot = Some(c)),
+                "authors" => match value.as_array() {
+                    Some(array) => array
+                        .iter()
+                        .map(Self::color_from_value)
+                        .collect::<anyhow::Result<_>>()
+                        .map(|authors| self.authors = authors),
+                    None => Err(anyhow::anyhow!("Must be an array of colors")),
+                },
+                "error" => Self::apply_style(&mut self.error, value),
+                "hint" => Self::apply_style(&mut self.hint, value),
+                "minimap" => Self::apply_style(&mut self.minimap, value),
+                "minimap-view" => Self::apply_style(&mut self.minimap_view, value),
+                _ => anyhow::bail!("Unknown key \"{key}\""),
+            };
+            result.with_context(|| format!("Invalid value of \"{key}\""))?;
+        }
+        Ok(())
+    }
+
+    /// Override the `style` by the `fg`, `bg`, and `attributes` keys of the
+    /// `value`, or by the foreground color if the `value` is a string.
+    fn apply_style(style: &mut ContentStyle, value: &toml::Value) -> anyhow::Result<()> {
+        if value.is_str() {
+            style.foreground_color = Some(Self::color_from_value(value)?);
+            return Ok(());
+        }
+        let Some(table) = value.as_table() else {
+            anyhow::bail!("Must be a color or a table of \"fg\", \"bg\", and \"attributes\"");
+        };
+        for (key, value) in table {
+            match key.as_str() {
+                "fg" => style.foreground_color = Some(Self::color_from_value(value)?),
+                "bg" => style.background_color = Some(Self::color_from_value(value)?),
+                "attributes" => {
+                    let Some(names) = value.as_array() else {
+                        anyhow::bail!("The attributes must be an array");
+                    };
+                    style.attributes = Default::default();
+                    for name in names {
+                        style.attributes.set(Self::attribute_from_value(name)?);
+                    }
+                }
+                _ => anyhow::bail!("Unknown key \"{key}\""),
+            }
+        }
+        Ok(())
+    }
+
+    fn attribute_from_value(value: &toml::Value) -> anyhow::Result<Attribute> {
+        let name = value.as_str().unwrap_or_default();
+        let Some((_, attribute)) = Self::ATTRIBUTES.iter().find(|(n, _)| *n == name) else {
+            let names: Vec<&str> = Self::ATTRIBUTES.iter().map(|(n, _)| *n).collect();
+            anyhow::bail!(
+                "Unknown attribute {value}, possible values are: {}",
+                names.join(", ")
+            );
+        };
+        Ok(*attribute)
+    }
+
+    fn color_from_value(value: &toml::Value) -> anyhow::Result<Color> {
+        let Some(value) = value.as_str() else {
+            anyhow::bail!("Colors must be strings");
+        };
+        Self::color_from_str(value)
+    }
+
+    fn rgb_from_value(value: &toml::Value) -> anyhow::Result<(u8, u8, u8)> {
+        match Self::color_from_value(value)? {
+            Color::Rgb { r, g, b } => Ok((r, g, b)),
+            _ => anyhow::bail!("Must be \"#rrggbb\""),
+        }
+    }
+
+    /// Parse a color name such as `dark_grey`, or `#rrggbb`.
+    fn color_from_str(value: &str) -> anyhow::Result<Color> {
+        if let Some(hex) = value.strip_prefix('#') {
+            let rgb = match u32::from_str_radix(hex, 16) {
+                Ok(rgb) if hex.len() == 6 && hex.is_ascii() => rgb,
+                _ => anyhow::bail!("Invalid color \"{value}\""),
+            };
+            return Ok(Color::Rgb {
+                r: (rgb >> 16) as u8,
+                g: (rgb >> 8) as u8,
+                b: rgb as u8,
+            });
+        }
+        Color::try_from(value).map_err(|_| anyhow::anyhow!("Unknown color \"{value}\""))
+    }
+
+    pub fn current_line(&self) -> ContentStyle {
+        self.current_line
+    }
+
+    pub fn selected_line(&self) -> ContentStyle {
+        self.selected_line
+    }
+
+    pub fn pinned_line(&self) -> ContentStyle {
+        self.pinned_line
+    }
+
+    pub fn error_line(&self) -> ContentStyle {
+        self.error_line
+    }
+
+    pub fn dimmed(&self) -> ContentStyle {
+        self.dimmed
+    }
+
+    pub fn boundary(&self) -> ContentStyle {
+        self.boundary
+    }
+
+    pub fn deleted(&self) -> ContentStyle {
+        self.deleted
+    }
+
+    pub fn error(&self) -> ContentStyle {
+        self.error
+    }
+
+    pub fn hint(&self) -> ContentStyle {
+        self.hint
+    }
+
+    pub fn minimap(&self) -> ContentStyle {
+        self.minimap
+    }
+
+    pub fn minimap_view(&self) -> ContentStyle {
+        self.minimap_view
+    }
+
+    /// The color at the `ratio` in the heatmap gradient,
+    /// where 0 is the coldest and 1 is the hottest.
+    /// `None` if the theme doesn't color the heatmap.
+    pub fn heat_color(&self, ratio: f64) -> Option<Color> {
+        let (cold, hot) = self.heat_cold.zip(self.heat_hot)?;
+        let ratio = ratio.clamp(0.0, 1.0);
+        let interpolate =
+            |cold: u8, hot: u8| (cold as f64 + (hot as f64 - cold as f64) * ratio).round() as u8;
+        Some(Color::Rgb {
+            r: interpolate(cold.0, hot.0),
+            g: interpolate(cold.1, hot.1),
+            b: interpolate(cold.2, hot.2),
+        })
+    }
+
+    /// The color of the author, or `None` if the theme doesn't color authors.
+    /// The same author always has the same color.
+    pub fn author_color(&self, author_email: &str) -> Option<Color> {
+        if self.authors.is_empty() {
+            return None;
+        }
+        // FNV-1a, to be stable across runs.
+        let hash = author_email.bytes().fold(0x811c9dc5_u32, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x01000193)
+        });
+        Some(self.authors[hash as usize % self.authors.len()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin() -> anyhow::Result<()> {
+        for (name, _) in Theme::BUILTINS {
+            Theme::builtin(name)?;
+        }
+        assert!(Theme::builtin("unknown").is_err());
+        let theme = Theme::builtin("dark")?;
+        assert_eq!(theme.current_line().foreground_color, Some(Color::Black));
+        assert_eq!(theme.current_line().background_color, Some(Color::Cyan));
+        assert_eq!(theme.author_color("a@example.com"), None);
+
+        let theme = Theme::builtin(Theme::NO_COLOR)?;
+        assert_eq!(theme.current_line().foreground_color, None);
+        assert!(theme.current_line().attributes.has(Attribute::Reverse));
+        assert_eq!(theme.heat_color(1.0), None);
+        Ok(())
+    }
+
+    #[test]
+    fn from_toml() -> anyhow::Result<()> {
+        let theme = Theme::from_toml(
+            r##"
+base = "solarized"
+current-line = { bg = "yellow" }
+selected-line = { attributes = ["bold", "underlined"] }
+dimmed = "#102030"
+"##,
+        )?;
+        let solarized = Theme::builtin("solarized")?;
+        assert_eq!(
+            theme.current_line().foreground_color,
+            solarized.current_line().foreground_color
+        );
+        assert_eq!(theme.current_line().background_color, Some(Color::Yellow));
+        assert!(theme.selected_line().attributes.has(Attribute::Bold));
+        assert!(theme.selected_line().attributes.has(Attribute::Underlined));
+        assert_eq!(
+            theme.dimmed().foreground_color,
+            Some(Color::Rgb {
+                r: 0x10,
+                g: 0x20,
+                b: 0x30
+            })
+        );
+        assert_eq!(theme.boundary(), solarized.boundary());
+        assert!(theme.author_color("a@example.com").is_some());
+        assert_eq!(
+            theme.author_color("a@example.com"),
+            theme.author_color("a@example.com")
+        );
+
+        assert!(Theme::from_toml("unknown = \"red\"").is_err());
+        assert!(Theme::from_toml("dimmed = \"unknown\"").is_err());
+        assert!(Theme::from_toml("heat-hot = \"red\"").is_err());
+        assert!(Theme::from_toml("hint = \"#12345\"").is_err());
+        assert!(Theme::from_toml("base = \"unknown\"").is_err());
+        assert!(Theme::from_toml("hint = { attributes = [\"blink\"] }").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn heat_color() {
+        let theme = Theme::builtin("dark").unwrap();
+        assert_eq!(
+            theme.heat_color(1.0),
+            Some(Color::Rgb {
+                r: 255,
+                g: 64,
+                b: 0
+            })
+        );
+        assert_eq!(
+            theme.heat_color(0.0),
+            Some(Color::Rgb {
+                r: 64,
+                g: 96,
+                b: 255
+            })
+        );
+        assert_eq!(theme.heat_color(2.0), theme.heat_color(1.0));
+    }
+}